@@ -1,21 +1,28 @@
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Child;
-use tokio::sync::{mpsc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdout};
+use tokio::sync::{mpsc, oneshot, Mutex};
 
 use crate::backend::app_server::{
-    build_codex_command_with_bin, check_cli_installation, CliAdapter, CliSpawnConfig,
-    WorkspaceSession,
+    build_codex_command_with_bin, check_cli_installation, cli_line_reader, emit_connected,
+    spawn_callback_pruner, CliAdapter, CliSpawnConfig, WorkspaceSession, CALLBACK_PRUNE_INTERVAL,
 };
+use crate::backend::errors::AppServerError;
+use crate::backend::event_recorder::{EventRecorder, RecordingEventSink};
 use crate::backend::events::{AppServerEvent, EventSink};
 use crate::shared::process_core::kill_child_process_tree;
+use crate::shared::version_core::ensure_min_cli_version;
 use crate::types::WorkspaceEntry;
 
+/// Prefix of the `thread/resume` error for a thread id that is in neither
+/// the session's store nor the store file on disk.
+pub(crate) const THREAD_NOT_IN_SESSION: &str = "thread not found in this session";
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub(crate) struct ThreadMetadata {
     #[serde(alias = "claude_session_id")]
@@ -24,6 +31,216 @@ pub(crate) struct ThreadMetadata {
     pub(crate) created_at: u64,
     pub(crate) updated_at: u64,
     pub(crate) archived: bool,
+    #[serde(default)]
+    pub(crate) usage: ThreadUsage,
+    /// Model set with `thread/model/set`; used when `turn/start` names none.
+    #[serde(default)]
+    pub(crate) model: Option<String>,
+    /// Model the CLI reported running on the thread's first turn, kept as a
+    /// record only: unlike `model` it is never applied to later turns.
+    #[serde(default)]
+    pub(crate) reported_model: Option<String>,
+    /// Input of the thread's first `turn/start`, kept for auto-titling.
+    #[serde(default)]
+    pub(crate) first_prompt: Option<String>,
+    /// Set on a duplicate whose `cli_session_id` still belongs to the source
+    /// thread; cleared once a turn reports a session of its own.
+    #[serde(default)]
+    pub(crate) fork_pending: bool,
+}
+
+/// Longest auto-generated thread title, in characters.
+const THREAD_TITLE_MAX_CHARS: usize = 60;
+
+impl ThreadMetadata {
+    /// Title derived from the first prompt: its first non-empty line with
+    /// whitespace collapsed, truncated to `THREAD_TITLE_MAX_CHARS`.
+    pub(crate) fn auto_title(&self) -> Option<String> {
+        let line = self
+            .first_prompt
+            .as_deref()?
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())?;
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.chars().count() <= THREAD_TITLE_MAX_CHARS {
+            return Some(line);
+        }
+        let truncated: String = line.chars().take(THREAD_TITLE_MAX_CHARS - 1).collect();
+        Some(format!("{}…", truncated.trim_end()))
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TurnUsage {
+    pub(crate) turn_id: String,
+    pub(crate) input_tokens: u64,
+    pub(crate) output_tokens: u64,
+    pub(crate) cost_usd: Option<f64>,
+    pub(crate) completed_at: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ThreadUsage {
+    pub(crate) input_tokens: u64,
+    pub(crate) output_tokens: u64,
+    pub(crate) cost_usd: f64,
+    pub(crate) turns: Vec<TurnUsage>,
+}
+
+impl ThreadUsage {
+    pub(crate) fn record(&mut self, turn: TurnUsage) {
+        self.input_tokens = self.input_tokens.saturating_add(turn.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_add(turn.output_tokens);
+        self.cost_usd += turn.cost_usd.unwrap_or(0.0);
+        self.turns.push(turn);
+    }
+}
+
+/// `turn/completed` and `turn/error` both finish a turn; after either one the
+/// read loop must not send its fallback `turn/completed`.
+pub(crate) fn is_turn_ending_event(event: &Value) -> bool {
+    matches!(
+        event.get("method").and_then(|m| m.as_str()),
+        Some("turn/completed" | "turn/error")
+    )
+}
+
+/// Method the UI renders streamed reasoning from, as the app-server emits it.
+pub(crate) const REASONING_DELTA_METHOD: &str = "item/reasoning/textDelta";
+
+/// Other names profiles have used for a reasoning delta.
+const REASONING_DELTA_ALIASES: &[&str] = &[
+    "item/reasoning/delta",
+    "item/thinking/delta",
+    "item/thought/delta",
+];
+
+pub(crate) fn reasoning_delta_event(
+    thread_id: &str,
+    turn_id: &str,
+    item_id: &str,
+    delta: &str,
+) -> Value {
+    json!({
+        "method": REASONING_DELTA_METHOD,
+        "params": {
+            "threadId": thread_id,
+            "turnId": turn_id,
+            "itemId": item_id,
+            "delta": delta
+        }
+    })
+}
+
+/// Rewrites a reasoning delta sent under an alias method, or with its text in
+/// `text`/`content` rather than `delta`, into `reasoning_delta_event`'s shape
+/// so the UI has a single reasoning path. Other events pass through.
+pub(crate) fn normalize_reasoning_event(event: Value) -> Value {
+    let is_reasoning = event
+        .get("method")
+        .and_then(Value::as_str)
+        .is_some_and(|method| {
+            method == REASONING_DELTA_METHOD || REASONING_DELTA_ALIASES.contains(&method)
+        });
+    let Some(params) = event.get("params").filter(|_| is_reasoning) else {
+        return event;
+    };
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| params.get(*key).and_then(Value::as_str))
+            .unwrap_or_default()
+    };
+    reasoning_delta_event(
+        field(&["threadId", "thread_id"]),
+        field(&["turnId", "turn_id"]),
+        field(&["itemId", "item_id"]),
+        field(&["delta", "text", "content"]),
+    )
+}
+
+/// Reads the normalized `usage`/`costUsd` fields profiles attach to `turn/completed`.
+pub(crate) fn extract_turn_usage(event: &Value) -> Option<TurnUsage> {
+    if event.get("method").and_then(|m| m.as_str()) != Some("turn/completed") {
+        return None;
+    }
+    let params = event.get("params")?;
+    let usage = params.get("usage");
+    let tokens = |key: &str| {
+        usage
+            .and_then(|usage| usage.get(key))
+            .and_then(|value| value.as_u64())
+            .unwrap_or(0)
+    };
+    Some(TurnUsage {
+        turn_id: params
+            .get("turnId")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        input_tokens: tokens("inputTokens"),
+        output_tokens: tokens("outputTokens"),
+        cost_usd: params.get("costUsd").and_then(|v| v.as_f64()),
+        completed_at: now_epoch(),
+    })
+}
+
+/// Reads the model a profile reports on `turn/started`, if any.
+pub(crate) fn extract_turn_model(event: &Value) -> Option<&str> {
+    if event.get("method").and_then(|m| m.as_str()) != Some("turn/started") {
+        return None;
+    }
+    event
+        .get("params")?
+        .get("model")?
+        .as_str()
+        .filter(|model| !model.is_empty())
+}
+
+/// Longest reset window an automatic rate-limit retry will wait out.
+const MAX_RATE_LIMIT_RETRY_DELAY: Duration = Duration::from_secs(15 * 60);
+
+/// For a rate-limited `turn/error` that says when the limit resets, how long
+/// to wait before retrying. The reset is read as `retryAfterMs` or `resetsAt`
+/// (unix seconds) from the params, their `usage`, or the error itself.
+pub(crate) fn rate_limit_retry_delay(event: &Value) -> Option<Duration> {
+    if event.get("method").and_then(|m| m.as_str()) != Some("turn/error") {
+        return None;
+    }
+    let params = event.get("params")?;
+    let error = params.get("error");
+    let rate_limited = error.is_some_and(|error| {
+        [
+            error.as_str(),
+            error.get("type").and_then(Value::as_str),
+            error.get("code").and_then(Value::as_str),
+            error.get("message").and_then(Value::as_str),
+        ]
+        .into_iter()
+        .flatten()
+        .map(str::to_ascii_lowercase)
+        .any(|text| {
+            text.contains("rate_limit")
+                || text.contains("rate limit")
+                || text.contains("too many requests")
+        })
+    });
+    if !rate_limited {
+        return None;
+    }
+    let delay = [Some(params), params.get("usage"), error]
+        .into_iter()
+        .flatten()
+        .find_map(|source| {
+            if let Some(ms) = source.get("retryAfterMs").and_then(Value::as_u64) {
+                return Some(Duration::from_millis(ms));
+            }
+            let resets_at = source.get("resetsAt").and_then(Value::as_u64)?;
+            Some(Duration::from_secs(resets_at.saturating_sub(now_epoch())))
+        })?;
+    (delay <= MAX_RATE_LIMIT_RETRY_DELAY).then_some(delay)
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
@@ -33,19 +250,66 @@ pub(crate) struct ThreadStore {
 
 impl ThreadStore {
     pub(crate) fn load(path: &PathBuf) -> Self {
-        std::fs::read_to_string(path)
-            .ok()
-            .and_then(|content| serde_json::from_str(&content).ok())
-            .unwrap_or_default()
+        Self::try_load(path).unwrap_or_default()
+    }
+
+    /// Like `load`, but reports an unreadable or unparseable file instead of
+    /// quietly starting from an empty store. A missing file is not an error.
+    pub(crate) fn try_load(path: &PathBuf) -> Result<Self, String> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(format!("Failed to read {}: {err}", path.display())),
+        };
+        serde_json::from_str(&content)
+            .map_err(|err| format!("Failed to parse {}: {err}", path.display()))
+    }
+
+    /// Unarchived threads as `thread/list` reports them.
+    pub(crate) fn list_entries(&self) -> Vec<Value> {
+        self.threads
+            .iter()
+            .filter(|(_, meta)| !meta.archived)
+            .map(|(id, meta)| {
+                json!({
+                    "id": id,
+                    "name": meta.name,
+                    "createdAt": meta.created_at,
+                    "updatedAt": meta.updated_at,
+                    "archived": meta.archived,
+                    "firstPrompt": meta.first_prompt,
+                    "title": meta.auto_title(),
+                    "preview": meta.first_prompt,
+                })
+            })
+            .collect()
     }
 
+    /// Writes the store to a temp file beside `path` and renames it over the
+    /// old file, so a crash mid-save leaves either the old or the new store.
     pub(crate) fn save(&self, path: &PathBuf) -> Result<(), String> {
+        let temp_path = self.write_temp(path)?;
+        std::fs::rename(&temp_path, path).map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            format!("Failed to write thread store: {e}")
+        })
+    }
+
+    /// First half of `save`: the fully written and synced temp file.
+    fn write_temp(&self, path: &Path) -> Result<PathBuf, String> {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create thread store directory: {e}"))?;
         }
         let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
-        std::fs::write(path, json).map_err(|e| format!("Failed to write thread store: {e}"))
+        let temp_path = path.with_extension("json.tmp");
+        let write = || -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&temp_path)?;
+            std::io::Write::write_all(&mut file, json.as_bytes())?;
+            file.sync_all()
+        };
+        write().map_err(|e| format!("Failed to write thread store: {e}"))?;
+        Ok(temp_path)
     }
 }
 
@@ -56,15 +320,163 @@ pub(crate) fn now_epoch() -> u64 {
         .unwrap_or(0)
 }
 
-pub(crate) fn thread_store_path(workspace_id: &str) -> PathBuf {
-    let data_dir = dirs_next::data_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("agent-monitor")
-        .join("adapter-threads");
-    data_dir.join(format!("{workspace_id}.json"))
+/// `<path>.bak`, where an unparseable thread store is copied before it is
+/// replaced.
+fn thread_store_backup_path(path: &Path) -> PathBuf {
+    let mut backup_name = path.as_os_str().to_os_string();
+    backup_name.push(".bak");
+    PathBuf::from(backup_name)
+}
+
+/// Checks the thread store at `path` and rewrites it if it cannot be parsed.
+/// The original file is copied to `<path>.bak` first; thread entries that
+/// still deserialize are kept and the rest are dropped.
+pub(crate) fn repair_thread_store(path: &PathBuf) -> Result<Value, String> {
+    let path_display = path.display().to_string();
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(json!({ "status": "missing", "path": path_display }));
+        }
+        Err(err) => return Err(format!("Failed to read {path_display}: {err}")),
+    };
+    if let Ok(store) = serde_json::from_str::<ThreadStore>(&content) {
+        return Ok(json!({
+            "status": "ok",
+            "path": path_display,
+            "threadCount": store.threads.len(),
+        }));
+    }
+
+    let backup_path = thread_store_backup_path(path);
+    std::fs::write(&backup_path, &content)
+        .map_err(|err| format!("Failed to back up {path_display}: {err}"))?;
+
+    let mut store = ThreadStore::default();
+    let mut dropped = 0usize;
+    let entries = serde_json::from_str::<Value>(&content)
+        .ok()
+        .and_then(|value| value.get("threads").and_then(Value::as_object).cloned());
+    if let Some(entries) = entries.as_ref() {
+        for (id, entry) in entries {
+            match serde_json::from_value::<ThreadMetadata>(entry.clone()) {
+                Ok(meta) => {
+                    store.threads.insert(id.clone(), meta);
+                }
+                Err(_) => dropped += 1,
+            }
+        }
+    }
+    store.save(path)?;
+
+    let status = if store.threads.is_empty() {
+        "reset"
+    } else {
+        "recovered"
+    };
+    Ok(json!({
+        "status": status,
+        "path": path_display,
+        "backupPath": backup_path.display().to_string(),
+        "threadCount": store.threads.len(),
+        "droppedCount": dropped,
+    }))
+}
+
+/// Directory holding the per-workspace thread stores: the configured
+/// `threadStoreDir` when set, otherwise the default under the data dir.
+pub(crate) fn thread_store_dir(custom_dir: Option<&Path>) -> PathBuf {
+    match custom_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => resolve_thread_store_dir(dirs_next::data_dir()).0,
+    }
+}
+
+pub(crate) fn thread_store_path(custom_dir: Option<&Path>, workspace_id: &str) -> PathBuf {
+    thread_store_dir(custom_dir).join(format!("{workspace_id}.json"))
+}
+
+/// Without a user data dir the store goes to the temp dir rather than the
+/// working directory, which is often the workspace repo. The second value is
+/// a warning to surface when that fallback is used.
+fn resolve_thread_store_dir(data_dir: Option<PathBuf>) -> (PathBuf, Option<String>) {
+    let (base, warning) = match data_dir {
+        Some(data_dir) => (data_dir, None),
+        None => {
+            let temp_dir = std::env::temp_dir();
+            let warning = format!(
+                "No user data directory is available, so thread history is kept in {} and may not survive a restart.",
+                temp_dir.display()
+            );
+            (temp_dir, Some(warning))
+        }
+    };
+    (base.join("agent-monitor").join("adapter-threads"), warning)
+}
+
+fn resolve_thread_store_path(
+    data_dir: Option<PathBuf>,
+    workspace_id: &str,
+) -> (PathBuf, Option<String>) {
+    let (dir, warning) = resolve_thread_store_dir(data_dir);
+    (dir.join(format!("{workspace_id}.json")), warning)
+}
+
+/// Fails unless `dir` exists (or can be created) and accepts a new file.
+pub(crate) fn ensure_dir_writable(dir: &Path) -> Result<(), String> {
+    let not_writable = |err: std::io::Error| format!("{} is not writable: {err}", dir.display());
+    std::fs::create_dir_all(dir).map_err(not_writable)?;
+    let probe = dir.join(format!(".write-test-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&probe, b"").map_err(not_writable)?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Copies the thread stores in `from` into `to`, leaving the originals as a
+/// fallback. Stores already present in `to` are kept. Returns how many files
+/// were copied.
+pub(crate) fn migrate_thread_stores(from: &Path, to: &Path) -> Result<usize, String> {
+    ensure_dir_writable(to)?;
+    let entries = match std::fs::read_dir(from) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(format!("Failed to read {}: {err}", from.display())),
+    };
+    let mut copied = 0;
+    for entry in entries.flatten() {
+        let source = entry.path();
+        if !source.is_file() || source.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(name) = source.file_name() else {
+            continue;
+        };
+        let target = to.join(name);
+        if target.exists() {
+            continue;
+        }
+        std::fs::copy(&source, &target).map_err(|err| {
+            format!(
+                "Failed to copy {} to {}: {err}",
+                source.display(),
+                target.display()
+            )
+        })?;
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+/// Context size a compaction run reports, in tokens.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct CompactionTokens {
+    pub(crate) before: Option<u64>,
+    pub(crate) after: Option<u64>,
 }
 
 pub(crate) trait CliProfile: Send + Sync + 'static {
+    /// A command that leaves the prompt out of its arguments pipes stdin;
+    /// the prompt is then written there after spawn.
     fn build_turn_command(
         &self,
         config: &CliSpawnConfig,
@@ -81,6 +493,44 @@ pub(crate) trait CliProfile: Send + Sync + 'static {
     fn model_list(&self) -> Value;
 
     fn provider_name(&self) -> &str;
+
+    /// `app/list` response in the app-server's `{ data, nextCursor }` shape.
+    fn app_list(&self) -> Value {
+        json!({ "result": { "data": [], "nextCursor": null } })
+    }
+
+    /// `mcpServerStatus/list` response in the same `{ data, nextCursor }`
+    /// shape, for the servers the CLI is configured with in `cwd`.
+    fn mcp_server_list(&self, _config: &CliSpawnConfig, _cwd: &str) -> Value {
+        json!({ "result": { "data": [], "nextCursor": null } })
+    }
+
+    /// `skills/list` response. CLIs without skills report `unsupported` so
+    /// the UI can tell that apart from a CLI with none installed.
+    fn skills_list(&self, _config: &CliSpawnConfig, _cwd: &str) -> Value {
+        json!({
+            "result": {
+                "skills": [],
+                "unsupported": true,
+                "message": format!("{} has no skills.", self.provider_name()),
+            }
+        })
+    }
+
+    /// Called once a turn's output has been read to the end, however the turn
+    /// ended, so per-turn parse state can be dropped.
+    fn finish_turn(&self, _turn_id: &str) {}
+
+    /// Prompt that makes the CLI compact a resumed session's context, for
+    /// CLIs that can do it non-interactively.
+    fn compact_prompt(&self) -> Option<&str> {
+        None
+    }
+
+    /// Token counts reported by one line of a compaction run's output.
+    fn parse_compaction_line(&self, _line: &str) -> Option<CompactionTokens> {
+        None
+    }
 }
 
 pub(crate) struct GenericAdapterSession<P: CliProfile> {
@@ -91,10 +541,67 @@ pub(crate) struct GenericAdapterSession<P: CliProfile> {
     thread_store_path: PathBuf,
     thread_store: Arc<Mutex<ThreadStore>>,
     active_child: Arc<Mutex<Option<Child>>>,
+    /// Held while a turn may still schedule a rate-limit retry; dropping it
+    /// cancels a retry that is waiting for the limit to reset.
+    retry_cancel: Arc<Mutex<Option<oneshot::Sender<()>>>>,
     event_emitter: Arc<dyn Fn(AppServerEvent) + Send + Sync>,
     background_callbacks: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>>,
 }
 
+/// Spawns the CLI for one turn attempt and takes its stdout for the reader.
+fn spawn_turn_process<P: CliProfile>(
+    profile: &P,
+    config: &CliSpawnConfig,
+    session_id: Option<&str>,
+    prompt: &str,
+    cwd: &str,
+    params: &Value,
+) -> Result<(Child, ChildStdout), String> {
+    let mut command = profile.build_turn_command(config, session_id, prompt, cwd, params)?;
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to spawn CLI: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let prompt = prompt.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = stdin.write_all(prompt.as_bytes()).await {
+                eprintln!("adapter: failed to write prompt to CLI stdin: {e}");
+            }
+            // Dropping stdin closes it so the CLI sees the end of the prompt.
+        });
+    }
+    let stdout = child.stdout.take().ok_or("Failed to capture CLI stdout")?;
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(_)) = lines.next_line().await {}
+        });
+    }
+    Ok((child, stdout))
+}
+
+/// Routes a turn event to the background collector waiting on the thread,
+/// or to the UI when there is none.
+async fn dispatch_turn_event(
+    bg_callbacks: &Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>,
+    emitter: &Arc<dyn Fn(AppServerEvent) + Send + Sync>,
+    workspace_id: &str,
+    thread_id: &str,
+    event: Value,
+) {
+    {
+        let callbacks = bg_callbacks.lock().await;
+        if let Some(tx) = callbacks.get(thread_id) {
+            let _ = tx.send(event);
+            return;
+        }
+    }
+    (emitter)(AppServerEvent {
+        workspace_id: workspace_id.to_string(),
+        message: event,
+    });
+}
+
 impl<P: CliProfile> GenericAdapterSession<P> {
     pub(crate) fn new(
         profile: P,
@@ -103,8 +610,38 @@ impl<P: CliProfile> GenericAdapterSession<P> {
         event_emitter: Arc<dyn Fn(AppServerEvent) + Send + Sync>,
         background_callbacks: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>>,
     ) -> Self {
-        let store_path = thread_store_path(&entry.id);
-        let store = ThreadStore::load(&store_path);
+        let emit_warning = |message: String| {
+            (event_emitter)(AppServerEvent {
+                workspace_id: entry.id.clone(),
+                message: json!({
+                    "method": "codex/threadStoreWarning",
+                    "params": { "workspaceId": entry.id, "message": message }
+                }),
+            });
+        };
+        let (store_path, fallback_warning) = match config.thread_store_dir.as_deref() {
+            Some(dir) => (dir.join(format!("{}.json", entry.id)), None),
+            None => resolve_thread_store_path(dirs_next::data_dir(), &entry.id),
+        };
+        if let Some(message) = fallback_warning {
+            emit_warning(message);
+        }
+        // The next save replaces the file, so keep the unreadable copy where
+        // `thread/store/repair` would have put it.
+        let store = ThreadStore::try_load(&store_path).unwrap_or_else(|message| {
+            let backup_path = thread_store_backup_path(&store_path);
+            match std::fs::copy(&store_path, &backup_path) {
+                Ok(_) => emit_warning(format!(
+                    "{message}. The original was copied to {}.",
+                    backup_path.display()
+                )),
+                Err(err) => emit_warning(format!(
+                    "{message}. Failed to back it up to {}: {err}",
+                    backup_path.display()
+                )),
+            }
+            ThreadStore::default()
+        });
         Self {
             profile: Arc::new(profile),
             workspace_id: entry.id.clone(),
@@ -113,11 +650,19 @@ impl<P: CliProfile> GenericAdapterSession<P> {
             thread_store_path: store_path,
             thread_store: Arc::new(Mutex::new(store)),
             active_child: Arc::new(Mutex::new(None)),
+            retry_cancel: Arc::new(Mutex::new(None)),
             event_emitter,
             background_callbacks,
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn with_thread_store_path(mut self, path: PathBuf) -> Self {
+        self.thread_store = Arc::new(Mutex::new(ThreadStore::load(&path)));
+        self.thread_store_path = path;
+        self
+    }
+
     async fn handle_thread_start(&self) -> Result<Value, String> {
         let thread_id = uuid::Uuid::new_v4().to_string();
         let now = now_epoch();
@@ -127,6 +672,11 @@ impl<P: CliProfile> GenericAdapterSession<P> {
             created_at: now,
             updated_at: now,
             archived: false,
+            usage: ThreadUsage::default(),
+            model: None,
+            reported_model: None,
+            first_prompt: None,
+            fork_pending: false,
         };
         {
             let mut store = self.thread_store.lock().await;
@@ -146,34 +696,50 @@ impl<P: CliProfile> GenericAdapterSession<P> {
             .get("threadId")
             .and_then(|v| v.as_str())
             .ok_or("missing threadId")?;
-        let store = self.thread_store.lock().await;
+        let mut store = self.thread_store.lock().await;
         if !store.threads.contains_key(thread_id) {
-            return Err("thread not found".to_string());
+            // Another session for this workspace (e.g. the daemon) may have
+            // written the thread since this one loaded, so re-read the file.
+            if let Ok(on_disk) = ThreadStore::try_load(&self.thread_store_path) {
+                for (id, meta) in on_disk.threads {
+                    store.threads.entry(id).or_insert(meta);
+                }
+            }
+        }
+        let meta = store
+            .threads
+            .get(thread_id)
+            .ok_or_else(|| format!("{THREAD_NOT_IN_SESSION}: {thread_id}"))?;
+        // Without a CLI session id the next turn cannot pass a resume flag and
+        // will start a fresh conversation, so make that visible to the caller.
+        let resumable = meta.cli_session_id.is_some();
+        if !resumable {
+            (self.event_emitter)(AppServerEvent {
+                workspace_id: self.workspace_id.clone(),
+                message: json!({
+                    "method": "codex/resumeWarning",
+                    "params": {
+                        "workspaceId": self.workspace_id,
+                        "threadId": thread_id,
+                        "message": format!(
+                            "No stored {} session for this thread; the next message starts a new conversation.",
+                            self.profile.provider_name()
+                        ),
+                    }
+                }),
+            });
         }
         Ok(json!({
             "result": {
                 "threadId": thread_id,
-                "thread": { "id": thread_id }
+                "thread": { "id": thread_id },
+                "resumable": resumable
             }
         }))
     }
 
     async fn handle_thread_list(&self) -> Result<Value, String> {
-        let store = self.thread_store.lock().await;
-        let threads: Vec<Value> = store
-            .threads
-            .iter()
-            .filter(|(_, meta)| !meta.archived)
-            .map(|(id, meta)| {
-                json!({
-                    "id": id,
-                    "name": meta.name,
-                    "createdAt": meta.created_at,
-                    "updatedAt": meta.updated_at,
-                    "archived": meta.archived,
-                })
-            })
-            .collect();
+        let threads = self.thread_store.lock().await.list_entries();
         Ok(json!({
             "result": {
                 "threads": threads,
@@ -214,6 +780,48 @@ impl<P: CliProfile> GenericAdapterSession<P> {
         Ok(json!({ "result": {} }))
     }
 
+    async fn handle_thread_model_set(&self, params: &Value) -> Result<Value, String> {
+        let thread_id = params
+            .get("threadId")
+            .and_then(|v| v.as_str())
+            .ok_or("missing threadId")?;
+        let model = params
+            .get("model")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .map(str::to_string);
+        let mut store = self.thread_store.lock().await;
+        let meta = store.threads.get_mut(thread_id).ok_or("thread not found")?;
+        meta.model = model;
+        meta.updated_at = now_epoch();
+        store.save(&self.thread_store_path)?;
+        Ok(json!({ "result": {} }))
+    }
+
+    /// Fills in the thread's stored model when the turn doesn't name one.
+    async fn turn_params_with_thread_model(&self, thread_id: &str, params: &Value) -> Value {
+        let mut params = params.clone();
+        let has_model = params
+            .get("model")
+            .and_then(|v| v.as_str())
+            .is_some_and(|model| !model.trim().is_empty());
+        if has_model {
+            return params;
+        }
+        let stored = {
+            let store = self.thread_store.lock().await;
+            store
+                .threads
+                .get(thread_id)
+                .and_then(|meta| meta.model.clone())
+        };
+        if let (Some(model), Some(object)) = (stored, params.as_object_mut()) {
+            object.insert("model".to_string(), json!(model));
+        }
+        params
+    }
+
     async fn handle_thread_fork(&self, params: &Value) -> Result<Value, String> {
         let source_id = params
             .get("threadId")
@@ -225,14 +833,66 @@ impl<P: CliProfile> GenericAdapterSession<P> {
             .get(source_id)
             .cloned()
             .ok_or("thread not found")?;
+        let requested_name = params
+            .get("name")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string);
         let new_id = uuid::Uuid::new_v4().to_string();
         let now = now_epoch();
         let meta = ThreadMetadata {
             cli_session_id: None,
-            name: source.name.map(|n| format!("{n} (fork)")),
+            name: requested_name.or_else(|| source.name.map(|n| format!("{n} (fork)"))),
+            created_at: now,
+            updated_at: now,
+            archived: false,
+            usage: ThreadUsage::default(),
+            model: source.model,
+            reported_model: source.reported_model,
+            first_prompt: source.first_prompt,
+            fork_pending: false,
+        };
+        store.threads.insert(new_id.clone(), meta);
+        store.save(&self.thread_store_path)?;
+        Ok(json!({
+            "result": {
+                "threadId": new_id,
+                "thread": { "id": new_id }
+            }
+        }))
+    }
+
+    /// Unlike `thread/fork`, which starts the new thread with no CLI session, a
+    /// duplicate keeps the source's CLI session id so the next turn resumes the
+    /// same conversation context. It is marked `fork_pending`, so that turn (or
+    /// a compaction) asks the CLI to fork the session (`forkSession` in the
+    /// turn params) rather than append to the source's.
+    async fn handle_thread_duplicate(&self, params: &Value) -> Result<Value, String> {
+        let source_id = params
+            .get("threadId")
+            .and_then(|v| v.as_str())
+            .ok_or("missing threadId")?;
+        let mut store = self.thread_store.lock().await;
+        let source = store
+            .threads
+            .get(source_id)
+            .cloned()
+            .ok_or("thread not found")?;
+        let new_id = uuid::Uuid::new_v4().to_string();
+        let now = now_epoch();
+        let fork_pending = source.cli_session_id.is_some();
+        let meta = ThreadMetadata {
+            cli_session_id: source.cli_session_id,
+            name: source.name.map(|n| format!("{n} (copy)")),
             created_at: now,
             updated_at: now,
             archived: false,
+            usage: ThreadUsage::default(),
+            model: source.model,
+            reported_model: source.reported_model,
+            first_prompt: source.first_prompt,
+            fork_pending,
         };
         store.threads.insert(new_id.clone(), meta);
         store.save(&self.thread_store_path)?;
@@ -244,6 +904,135 @@ impl<P: CliProfile> GenericAdapterSession<P> {
         }))
     }
 
+    async fn handle_thread_store_repair(&self) -> Result<Value, String> {
+        let mut store = self.thread_store.lock().await;
+        let report = repair_thread_store(&self.thread_store_path)?;
+        *store = ThreadStore::load(&self.thread_store_path);
+        Ok(json!({ "result": report }))
+    }
+
+    async fn handle_thread_usage_read(&self, params: &Value) -> Result<Value, String> {
+        let thread_id = params
+            .get("threadId")
+            .and_then(|v| v.as_str())
+            .ok_or("missing threadId")?;
+        let store = self.thread_store.lock().await;
+        let meta = store.threads.get(thread_id).ok_or("thread not found")?;
+        Ok(json!({
+            "result": {
+                "threadId": thread_id,
+                "usage": meta.usage
+            }
+        }))
+    }
+
+    /// Runs the profile's compact prompt against the thread's CLI session and
+    /// emits `thread/compacted` with the context size before and after.
+    async fn handle_thread_compact(&self, params: &Value) -> Result<Value, String> {
+        let thread_id = params
+            .get("threadId")
+            .and_then(|v| v.as_str())
+            .ok_or("missing threadId")?
+            .to_string();
+        let Some(prompt) = self.profile.compact_prompt() else {
+            return Ok(json!({
+                "result": {
+                    "threadId": thread_id,
+                    "compacted": false,
+                    "unsupported": true,
+                    "message": format!(
+                        "{} can't compact threads.",
+                        self.profile.provider_name()
+                    ),
+                }
+            }));
+        };
+        let (session_id, fork_session, last_input_tokens) = {
+            let store = self.thread_store.lock().await;
+            let meta = store.threads.get(&thread_id).ok_or("thread not found")?;
+            (
+                meta.cli_session_id.clone(),
+                meta.fork_pending,
+                meta.usage.turns.last().map(|turn| turn.input_tokens),
+            )
+        };
+        let Some(session_id) = session_id else {
+            return Ok(json!({
+                "result": {
+                    "threadId": thread_id,
+                    "compacted": false,
+                    "message": "The thread has no conversation to compact yet.",
+                }
+            }));
+        };
+        // Checked and filled under one lock so a turn can't start in between.
+        // Held like a turn's child so `turn/interrupt` can stop it.
+        let (compaction_pid, stdout) = {
+            let mut guard = self.active_child.lock().await;
+            if guard.is_some() {
+                return Err("Wait for the running turn to finish before compacting.".to_string());
+            }
+            let (child, stdout) = spawn_turn_process(
+                self.profile.as_ref(),
+                &self.config,
+                Some(session_id.as_str()),
+                prompt,
+                &self.cwd,
+                &json!({ "threadId": thread_id, "forkSession": fork_session }),
+            )?;
+            let pid = child.id();
+            *guard = Some(child);
+            (pid, stdout)
+        };
+        let mut tokens = CompactionTokens::default();
+        let mut lines = cli_line_reader(stdout, self.config.read_buffer_capacity());
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(sid) = self.profile.extract_session_id(&line) {
+                let mut store = self.thread_store.lock().await;
+                if let Some(meta) = store.threads.get_mut(&thread_id) {
+                    meta.cli_session_id = Some(sid);
+                    meta.fork_pending = false;
+                    meta.updated_at = now_epoch();
+                    if let Err(e) = store.save(&self.thread_store_path) {
+                        eprintln!("adapter: failed to persist session id: {e}");
+                    }
+                }
+            }
+            if let Some(reported) = self.profile.parse_compaction_line(&line) {
+                tokens.before = reported.before.or(tokens.before);
+                tokens.after = reported.after.or(tokens.after);
+            }
+        }
+        let compaction_child = take_child_if(&mut *self.active_child.lock().await, compaction_pid);
+        let status = match compaction_child {
+            Some(mut child) => child.wait().await.ok(),
+            // Interrupted or replaced by a new turn while compacting.
+            None => return Err("Compaction was interrupted.".to_string()),
+        };
+        if status.is_some_and(|status| !status.success()) {
+            return Err(format!(
+                "{} failed to compact the thread.",
+                self.profile.provider_name()
+            ));
+        }
+
+        let tokens_before = tokens.before.or(last_input_tokens);
+        let summary = json!({
+            "threadId": thread_id,
+            "compacted": true,
+            "tokensBefore": tokens_before,
+            "tokensAfter": tokens.after,
+            "tokensSaved": tokens_before
+                .zip(tokens.after)
+                .map(|(before, after)| before.saturating_sub(after)),
+        });
+        (self.event_emitter)(AppServerEvent {
+            workspace_id: self.workspace_id.clone(),
+            message: json!({ "method": "thread/compacted", "params": summary }),
+        });
+        Ok(json!({ "result": summary }))
+    }
+
     async fn handle_turn_start(&self, params: &Value) -> Result<Value, String> {
         let thread_id = params
             .get("threadId")
@@ -255,43 +1044,49 @@ impl<P: CliProfile> GenericAdapterSession<P> {
             .and_then(|v| v.as_str())
             .ok_or("missing input")?
             .to_string();
+        let cwd = resolve_turn_cwd(&self.cwd, params.get("cwd").and_then(|v| v.as_str()))?;
         let turn_id = uuid::Uuid::new_v4().to_string();
 
-        let session_id = {
-            let store = self.thread_store.lock().await;
-            store
-                .threads
-                .get(&thread_id)
-                .and_then(|meta| meta.cli_session_id.clone())
+        let (session_id, fork_session) = {
+            let mut store = self.thread_store.lock().await;
+            let meta = store.threads.get_mut(&thread_id);
+            let session_id = meta.as_ref().and_then(|meta| meta.cli_session_id.clone());
+            let fork_session = meta.as_ref().is_some_and(|meta| meta.fork_pending);
+            if let Some(meta) = meta.filter(|meta| meta.first_prompt.is_none()) {
+                meta.first_prompt = Some(prompt.clone());
+                if let Err(e) = store.save(&self.thread_store_path) {
+                    eprintln!("adapter: failed to persist first prompt: {e}");
+                }
+            }
+            (session_id, fork_session)
         };
 
-        {
+        self.retry_cancel.lock().await.take();
+        let mut params = self.turn_params_with_thread_model(&thread_id, params).await;
+        if let (true, Some(object)) = (fork_session, params.as_object_mut()) {
+            object.insert("forkSession".to_string(), json!(true));
+        }
+
+        // Replaced under one lock so a compaction can't slip in between.
+        let (child_pid, stdout) = {
             let mut guard = self.active_child.lock().await;
             if let Some(mut prev) = guard.take() {
                 kill_child_process_tree(&mut prev).await;
             }
-        }
-
-        let mut command = self.profile.build_turn_command(
-            &self.config,
-            session_id.as_deref(),
-            &prompt,
-            &self.cwd,
-            params,
-        )?;
-        let mut child = command
-            .spawn()
-            .map_err(|e| format!("Failed to spawn CLI: {e}"))?;
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or("Failed to capture CLI stdout")?;
-        let stderr = child.stderr.take();
-
-        {
-            let mut guard = self.active_child.lock().await;
-            *guard = Some(child);
-        }
+            let (child, stdout) = spawn_turn_process(
+                self.profile.as_ref(),
+                &self.config,
+                session_id.as_deref(),
+                &prompt,
+                &cwd,
+                &params,
+            )?;
+            let pid = child.id();
+            *guard = Some(child);
+            (pid, stdout)
+        };
+        let (retry_cancel_tx, mut retry_cancel_rx) = oneshot::channel();
+        *self.retry_cancel.lock().await = Some(retry_cancel_tx);
 
         let profile = self.profile.clone();
         let emitter = self.event_emitter.clone();
@@ -302,83 +1097,154 @@ impl<P: CliProfile> GenericAdapterSession<P> {
         let bg_callbacks = self.background_callbacks.clone();
         let thread_id_bg = thread_id.clone();
         let turn_id_bg = turn_id.clone();
+        let read_buffer_capacity = self.config.read_buffer_capacity();
+        let config = self.config.clone();
 
         tokio::spawn(async move {
-            let mut lines = BufReader::new(stdout).lines();
-            let mut got_result = false;
-
-            while let Ok(Some(line)) = lines.next_line().await {
-                if let Some(sid) = profile.extract_session_id(&line) {
-                    let mut s = store.lock().await;
-                    if let Some(meta) = s.threads.get_mut(&thread_id_bg) {
-                        meta.cli_session_id = Some(sid);
-                        meta.updated_at = now_epoch();
-                        if let Err(e) = s.save(&store_path) {
-                            eprintln!("adapter: failed to persist session id: {e}");
+            let mut stdout = Some(stdout);
+            let mut child_pid = child_pid;
+            let mut retried = false;
+
+            while let Some(attempt_stdout) = stdout.take() {
+                let mut lines = cli_line_reader(attempt_stdout, read_buffer_capacity);
+                let mut got_result = false;
+                // A rate-limit error held back while its retry is pending.
+                let mut rate_limited: Option<(Duration, Value)> = None;
+
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(sid) = profile.extract_session_id(&line) {
+                        let mut s = store.lock().await;
+                        if let Some(meta) = s.threads.get_mut(&thread_id_bg) {
+                            meta.cli_session_id = Some(sid);
+                            meta.fork_pending = false;
+                            meta.updated_at = now_epoch();
+                            if let Err(e) = s.save(&store_path) {
+                                eprintln!("adapter: failed to persist session id: {e}");
+                            }
                         }
                     }
-                }
 
-                if let Some(event) =
-                    profile.parse_stream_line(&line, &thread_id_bg, &turn_id_bg)
-                {
-                    if event.get("method").and_then(|m| m.as_str()) == Some("turn/completed") {
-                        got_result = true;
-                    }
-                    let mut sent_to_background = false;
+                    if let Some(event) = profile
+                        .parse_stream_line(&line, &thread_id_bg, &turn_id_bg)
+                        .map(normalize_reasoning_event)
                     {
-                        let callbacks = bg_callbacks.lock().await;
-                        if let Some(tx) = callbacks.get(&thread_id_bg) {
-                            let _ = tx.send(event.clone());
-                            sent_to_background = true;
+                        if config.retry_rate_limited_turns && !retried && rate_limited.is_none() {
+                            if let Some(delay) = rate_limit_retry_delay(&event) {
+                                got_result = true;
+                                rate_limited = Some((delay, event));
+                                continue;
+                            }
                         }
-                    }
-                    if !sent_to_background {
-                        (emitter)(AppServerEvent {
-                            workspace_id: ws_id.clone(),
-                            message: event,
-                        });
+                        if rate_limited.is_some() && is_turn_ending_event(&event) {
+                            continue;
+                        }
+                        if is_turn_ending_event(&event) {
+                            got_result = true;
+                        }
+                        if let Some(model) = extract_turn_model(&event) {
+                            let mut s = store.lock().await;
+                            if let Some(meta) = s.threads.get_mut(&thread_id_bg) {
+                                if meta.reported_model.is_none() {
+                                    meta.reported_model = Some(model.to_string());
+                                    meta.updated_at = now_epoch();
+                                    if let Err(e) = s.save(&store_path) {
+                                        eprintln!("adapter: failed to persist thread model: {e}");
+                                    }
+                                }
+                            }
+                        }
+                        if let Some(turn_usage) = extract_turn_usage(&event) {
+                            let mut s = store.lock().await;
+                            if let Some(meta) = s.threads.get_mut(&thread_id_bg) {
+                                meta.usage.record(turn_usage);
+                                meta.updated_at = now_epoch();
+                                if let Err(e) = s.save(&store_path) {
+                                    eprintln!("adapter: failed to persist thread usage: {e}");
+                                }
+                            }
+                        }
+                        dispatch_turn_event(&bg_callbacks, &emitter, &ws_id, &thread_id_bg, event)
+                            .await;
                     }
                 }
-            }
 
-            if !got_result {
-                let fallback_event = json!({
-                    "method": "turn/completed",
-                    "params": {
-                        "threadId": thread_id_bg,
-                        "turnId": turn_id_bg
-                    }
-                });
-                let mut sent_to_background = false;
-                {
-                    let callbacks = bg_callbacks.lock().await;
-                    if let Some(tx) = callbacks.get(&thread_id_bg) {
-                        let _ = tx.send(fallback_event.clone());
-                        sent_to_background = true;
-                    }
+                let finished = take_child_if(&mut *active_child.lock().await, child_pid);
+                if let Some(mut child) = finished {
+                    let _ = child.wait().await;
                 }
-                if !sent_to_background {
-                    (emitter)(AppServerEvent {
-                        workspace_id: ws_id,
-                        message: fallback_event,
+
+                if let Some((delay, error_event)) = rate_limited {
+                    retried = true;
+                    let retrying_event = json!({
+                        "method": "turn/retrying",
+                        "params": {
+                            "threadId": thread_id_bg,
+                            "turnId": turn_id_bg,
+                            "delayMs": delay.as_millis() as u64,
+                            "attempt": 1
+                        }
                     });
+                    dispatch_turn_event(
+                        &bg_callbacks,
+                        &emitter,
+                        &ws_id,
+                        &thread_id_bg,
+                        retrying_event,
+                    )
+                    .await;
+                    let canceled = tokio::select! {
+                        _ = tokio::time::sleep(delay) => false,
+                        _ = &mut retry_cancel_rx => true,
+                    };
+                    if !canceled {
+                        match spawn_turn_process(
+                            profile.as_ref(),
+                            &config,
+                            session_id.as_deref(),
+                            &prompt,
+                            &cwd,
+                            &params,
+                        ) {
+                            Ok((child, retry_stdout)) => {
+                                child_pid = child.id();
+                                *active_child.lock().await = Some(child);
+                                stdout = Some(retry_stdout);
+                                continue;
+                            }
+                            Err(e) => eprintln!("adapter: failed to retry rate-limited turn: {e}"),
+                        }
+                    }
+                    dispatch_turn_event(
+                        &bg_callbacks,
+                        &emitter,
+                        &ws_id,
+                        &thread_id_bg,
+                        error_event,
+                    )
+                    .await;
                 }
-            }
 
-            let mut guard = active_child.lock().await;
-            if let Some(mut child) = guard.take() {
-                let _ = child.wait().await;
+                if !got_result {
+                    let fallback_event = json!({
+                        "method": "turn/completed",
+                        "params": {
+                            "threadId": thread_id_bg,
+                            "turnId": turn_id_bg
+                        }
+                    });
+                    dispatch_turn_event(
+                        &bg_callbacks,
+                        &emitter,
+                        &ws_id,
+                        &thread_id_bg,
+                        fallback_event,
+                    )
+                    .await;
+                }
             }
+            profile.finish_turn(&turn_id_bg);
         });
 
-        if let Some(stderr) = stderr {
-            tokio::spawn(async move {
-                let mut lines = BufReader::new(stderr).lines();
-                while let Ok(Some(_)) = lines.next_line().await {}
-            });
-        }
-
         Ok(json!({
             "result": {
                 "turn": { "id": turn_id },
@@ -405,12 +1271,17 @@ impl<P: CliProfile> CliAdapter for GenericAdapterSession<P> {
             "thread/start" => self.handle_thread_start().await,
             "thread/resume" => self.handle_thread_resume(&params).await,
             "thread/fork" => self.handle_thread_fork(&params).await,
+            "thread/duplicate" => self.handle_thread_duplicate(&params).await,
             "thread/list" => self.handle_thread_list().await,
             "thread/archive" => self.handle_thread_archive(&params).await,
-            "thread/compact/start" => Ok(json!({ "result": {} })),
+            "thread/compact/start" => self.handle_thread_compact(&params).await,
             "thread/name/set" => self.handle_thread_name_set(&params).await,
+            "thread/model/set" => self.handle_thread_model_set(&params).await,
+            "thread/usage/read" => self.handle_thread_usage_read(&params).await,
+            "thread/store/repair" => self.handle_thread_store_repair().await,
             "turn/start" => self.handle_turn_start(&params).await,
             "turn/interrupt" => {
+                self.retry_cancel.lock().await.take();
                 let mut child_guard = self.active_child.lock().await;
                 if let Some(mut child) = child_guard.take() {
                     kill_child_process_tree(&mut child).await;
@@ -421,9 +1292,9 @@ impl<P: CliProfile> CliAdapter for GenericAdapterSession<P> {
             "account/read" => Ok(json!({ "result": { "provider": provider } })),
             "account/rateLimits/read" => Ok(json!({ "result": Value::Null })),
             "collaborationMode/list" => Ok(json!({ "result": { "modes": [] } })),
-            "skills/list" => Ok(json!({ "result": { "skills": [] } })),
-            "app/list" => Ok(json!({ "result": { "apps": [] } })),
-            "mcpServerStatus/list" => Ok(json!({ "result": { "servers": [] } })),
+            "skills/list" => Ok(self.profile.skills_list(&self.config, &self.cwd)),
+            "app/list" => Ok(self.profile.app_list()),
+            "mcpServerStatus/list" => Ok(self.profile.mcp_server_list(&self.config, &self.cwd)),
             _ => Err(format!("unsupported method: {method}")),
         }
     }
@@ -442,6 +1313,10 @@ impl<P: CliProfile> CliAdapter for GenericAdapterSession<P> {
             kill_child_process_tree(&mut child).await;
         }
     }
+
+    fn cli_type(&self) -> &str {
+        &self.config.cli_type
+    }
 }
 
 pub(crate) async fn spawn_adapter_session<P: CliProfile, E: EventSink>(
@@ -450,34 +1325,81 @@ pub(crate) async fn spawn_adapter_session<P: CliProfile, E: EventSink>(
     entry: WorkspaceEntry,
     config: CliSpawnConfig,
     event_sink: E,
-) -> Result<Arc<WorkspaceSession>, String> {
-    let _ = check_cli_installation(config.cli_bin.clone(), cli_name).await?;
+) -> Result<Arc<WorkspaceSession>, AppServerError> {
+    let spawn_started = Instant::now();
+    let version = check_cli_installation(config.cli_bin.clone(), cli_name).await?;
+    if !config.skip_min_version_check {
+        ensure_min_cli_version(&config.cli_type, cli_name, version.as_deref())
+            .map_err(AppServerError::SpawnFailed)?;
+    }
 
+    let event_recorder = Arc::new(EventRecorder::default());
+    let event_sink = RecordingEventSink::new(event_sink, Arc::clone(&event_recorder));
     let event_sink_clone = event_sink.clone();
     let emitter: Arc<dyn Fn(AppServerEvent) + Send + Sync> = Arc::new(move |event| {
         event_sink_clone.emit_app_server_event(event);
     });
 
     let shared_callbacks = Arc::new(Mutex::new(HashMap::new()));
+    spawn_callback_pruner(&shared_callbacks, CALLBACK_PRUNE_INTERVAL);
     let adapter =
         GenericAdapterSession::new(profile, &entry, config, emitter, shared_callbacks.clone());
     let session = Arc::new(WorkspaceSession::new_with_adapter(
         entry.clone(),
         Box::new(adapter),
         shared_callbacks,
+        event_recorder,
     ));
+    session.record_spawn_duration(spawn_started.elapsed());
+    session.record_initialize_duration(Duration::ZERO);
 
-    event_sink.emit_app_server_event(AppServerEvent {
-        workspace_id: entry.id.clone(),
-        message: json!({
-            "method": "codex/connected",
-            "params": { "workspaceId": entry.id }
-        }),
-    });
+    emit_connected(&event_sink, &session);
 
     Ok(session)
 }
 
+/// Takes the child out of `slot` only if it is still the process `pid`; an
+/// interrupt, a new turn or a compaction may have replaced it since.
+fn take_child_if(slot: &mut Option<Child>, pid: Option<u32>) -> Option<Child> {
+    if pid.is_some() && slot.as_ref().and_then(Child::id) == pid {
+        slot.take()
+    } else {
+        None
+    }
+}
+
+/// Resolves a turn's optional `cwd` against the workspace root. Relative
+/// paths are taken from the root, and the result must be a directory inside
+/// it; without one the turn runs in the root itself. Symlinks are only
+/// resolved for that check: the result is `root` as given, joined with the
+/// directory's path below it.
+pub(crate) fn resolve_turn_cwd(root: &str, requested: Option<&str>) -> Result<String, String> {
+    let Some(requested) = requested.map(str::trim).filter(|cwd| !cwd.is_empty()) else {
+        return Ok(root.to_string());
+    };
+    let root_path = Path::new(root)
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve workspace path {root}: {e}"))?;
+    let resolved = root_path
+        .join(requested)
+        .canonicalize()
+        .map_err(|e| format!("Turn directory {requested} is not accessible: {e}"))?;
+    if !resolved.starts_with(&root_path) {
+        return Err(format!(
+            "Turn directory {requested} is outside the workspace {root}"
+        ));
+    }
+    if !resolved.is_dir() {
+        return Err(format!("Turn directory {requested} is not a directory"));
+    }
+    match resolved.strip_prefix(&root_path) {
+        Ok(suffix) if !suffix.as_os_str().is_empty() => {
+            Ok(Path::new(root).join(suffix).to_string_lossy().to_string())
+        }
+        _ => Ok(root.to_string()),
+    }
+}
+
 // Shared command builder helper used by profiles
 pub(crate) fn build_adapter_command(
     config: &CliSpawnConfig,
@@ -500,9 +1422,197 @@ pub(crate) fn build_adapter_command(
     Ok(command)
 }
 
+/// Checks that `events` are one well-formed turn as an adapter emits it:
+/// `turn/started` first, every event on that thread and turn, no item started
+/// or streamed after it completed, every started item completed before
+/// `turn/completed`, and nothing after the turn ends.
+#[cfg(test)]
+pub(crate) fn check_turn_sequence(events: &[Value]) -> Result<(), String> {
+    use std::collections::HashSet;
+
+    let method_of = |event: &Value| {
+        event
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or("")
+            .to_string()
+    };
+    let first = events.first().ok_or("no events")?;
+    if method_of(first) != "turn/started" {
+        return Err(format!(
+            "first event is {}, not turn/started",
+            method_of(first)
+        ));
+    }
+    let thread_id = first["params"]["threadId"]
+        .as_str()
+        .ok_or("turn/started has no threadId")?;
+    let turn_id = first["params"]["turnId"]
+        .as_str()
+        .ok_or("turn/started has no turnId")?;
+
+    let mut open_items: Vec<String> = Vec::new();
+    let mut finished_items = HashSet::new();
+    let mut ended = false;
+    for (index, event) in events.iter().enumerate().skip(1) {
+        let method = method_of(event);
+        let params = &event["params"];
+        if ended {
+            return Err(format!(
+                "event {index} ({method}) arrives after the turn ended"
+            ));
+        }
+        if method == "turn/started" {
+            return Err(format!("event {index} starts a second turn"));
+        }
+        if params["threadId"].as_str() != Some(thread_id) {
+            return Err(format!(
+                "event {index} ({method}) is for thread {}, not {thread_id}",
+                params["threadId"]
+            ));
+        }
+        if let Some(other) = params.get("turnId").and_then(Value::as_str) {
+            if other != turn_id {
+                return Err(format!(
+                    "event {index} ({method}) is for turn {other}, not {turn_id}"
+                ));
+            }
+        }
+        let item_id = params["item"]["id"].as_str().map(str::to_string);
+        match method.as_str() {
+            "item/started" => {
+                let id =
+                    item_id.ok_or_else(|| format!("event {index} starts an item without an id"))?;
+                if finished_items.contains(&id) || open_items.contains(&id) {
+                    return Err(format!("event {index} starts item {id} again"));
+                }
+                open_items.push(id);
+            }
+            "item/completed" => {
+                let id = item_id
+                    .ok_or_else(|| format!("event {index} completes an item without an id"))?;
+                if !finished_items.insert(id.clone()) {
+                    return Err(format!("event {index} completes item {id} again"));
+                }
+                open_items.retain(|open| *open != id);
+            }
+            "turn/completed" if !open_items.is_empty() => {
+                return Err(format!(
+                    "turn completed with items still open: {}",
+                    open_items.join(", ")
+                ));
+            }
+            _ => {
+                if let Some(id) = params.get("itemId").and_then(Value::as_str) {
+                    if finished_items.contains(id) {
+                        return Err(format!(
+                            "event {index} ({method}) streams into item {id} after it completed"
+                        ));
+                    }
+                }
+            }
+        }
+        ended = is_turn_ending_event(event);
+    }
+    if ended {
+        Ok(())
+    } else {
+        Err("turn never completed".to_string())
+    }
+}
+
+/// Panics with the offending sequence when `check_turn_sequence` rejects it.
+#[cfg(test)]
+pub(crate) fn assert_valid_turn_sequence(events: &[Value]) {
+    if let Err(problem) = check_turn_sequence(events) {
+        let methods: Vec<String> = events
+            .iter()
+            .map(|event| event["method"].as_str().unwrap_or("?").to_string())
+            .collect();
+        panic!("invalid turn sequence: {problem}\nmethods: {methods:?}");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::test_support::test_workspace_entry;
+
+    fn turn_event(method: &str, extra: Value) -> Value {
+        let mut params = json!({ "threadId": "t1", "turnId": "turn1" });
+        if let (Some(params), Some(extra)) = (params.as_object_mut(), extra.as_object()) {
+            params.extend(extra.clone());
+        }
+        json!({ "method": method, "params": params })
+    }
+
+    #[test]
+    fn check_turn_sequence_accepts_a_full_turn_and_rejects_broken_ones() {
+        let started = turn_event("turn/started", json!({}));
+        let tool_started = turn_event("item/started", json!({ "item": { "id": "tool-1" } }));
+        let tool_delta = turn_event("item/toolInput/delta", json!({ "itemId": "tool-1" }));
+        let tool_completed = turn_event("item/completed", json!({ "item": { "id": "tool-1" } }));
+        let completed = turn_event("turn/completed", json!({}));
+
+        check_turn_sequence(&[
+            started.clone(),
+            tool_started.clone(),
+            tool_delta.clone(),
+            tool_completed.clone(),
+            completed.clone(),
+        ])
+        .expect("well-formed turn");
+
+        let broken: Vec<(Vec<Value>, &str)> = vec![
+            (vec![], "no events"),
+            (
+                vec![tool_started.clone(), completed.clone()],
+                "not turn/started",
+            ),
+            (vec![started.clone()], "never completed"),
+            (
+                vec![started.clone(), tool_started.clone(), completed.clone()],
+                "items still open: tool-1",
+            ),
+            (
+                vec![
+                    started.clone(),
+                    tool_started.clone(),
+                    tool_completed.clone(),
+                    tool_delta.clone(),
+                    completed.clone(),
+                ],
+                "after it completed",
+            ),
+            (
+                vec![started.clone(), completed.clone(), tool_delta.clone()],
+                "after the turn ended",
+            ),
+            (
+                vec![
+                    started.clone(),
+                    turn_event(
+                        "item/completed",
+                        json!({ "turnId": "turn2", "item": { "id": "x" } }),
+                    ),
+                    completed.clone(),
+                ],
+                "not turn1",
+            ),
+            (
+                vec![
+                    started.clone(),
+                    json!({ "method": "item/agentMessage/delta", "params": { "threadId": "t2" } }),
+                    completed.clone(),
+                ],
+                "not t1",
+            ),
+        ];
+        for (events, expected) in broken {
+            let err = check_turn_sequence(&events).expect_err(expected);
+            assert!(err.contains(expected), "{err} should mention {expected}");
+        }
+    }
 
     #[test]
     fn thread_store_roundtrip() {
@@ -522,6 +1632,11 @@ mod tests {
                 created_at: 1000,
                 updated_at: 2000,
                 archived: false,
+                usage: ThreadUsage::default(),
+                model: None,
+                reported_model: None,
+                first_prompt: None,
+                fork_pending: false,
             },
         );
         store.save(&path).unwrap();
@@ -536,6 +1651,43 @@ mod tests {
         let _ = std::fs::remove_dir_all(temp_dir);
     }
 
+    #[test]
+    fn thread_store_crash_before_rename_keeps_previous_file() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        let path = temp_dir.join("threads.json");
+        let mut store = ThreadStore::default();
+        store.threads.insert(
+            "t1".to_string(),
+            ThreadMetadata {
+                cli_session_id: None,
+                name: Some("Before".to_string()),
+                created_at: 1000,
+                updated_at: 1000,
+                archived: false,
+                usage: ThreadUsage::default(),
+                model: None,
+                reported_model: None,
+                first_prompt: None,
+                fork_pending: false,
+            },
+        );
+        store.save(&path).unwrap();
+
+        store.threads.get_mut("t1").unwrap().name = Some("After".to_string());
+        let temp_path = store.write_temp(&path).unwrap();
+        assert!(temp_path.exists());
+        let loaded = ThreadStore::try_load(&path).unwrap();
+        assert_eq!(loaded.threads["t1"].name.as_deref(), Some("Before"));
+
+        store.save(&path).unwrap();
+        let loaded = ThreadStore::try_load(&path).unwrap();
+        assert_eq!(loaded.threads["t1"].name.as_deref(), Some("After"));
+        assert!(!temp_path.exists());
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
     #[test]
     fn thread_store_load_missing_file_returns_default() {
         let path = PathBuf::from("/tmp/nonexistent-adapter-test.json");
@@ -543,6 +1695,805 @@ mod tests {
         assert!(store.threads.is_empty());
     }
 
+    #[test]
+    fn thread_store_try_load_reports_corrupt_file() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("threads.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        assert!(ThreadStore::try_load(&path).is_err());
+        assert!(ThreadStore::load(&path).threads.is_empty());
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn corrupt_thread_store_is_backed_up_before_the_next_save() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let entry = test_workspace_entry("corrupt-ws", &temp_dir.to_string_lossy());
+        let path = temp_dir.join("corrupt-ws.json");
+        std::fs::write(&path, "{\"threads\": {\"t1\": ").unwrap();
+        let warnings = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = warnings.clone();
+        let config = CliSpawnConfig {
+            thread_store_dir: Some(temp_dir.clone()),
+            ..CliSpawnConfig::default()
+        };
+        let adapter = GenericAdapterSession::new(
+            ScriptProfile,
+            &entry,
+            config,
+            Arc::new(move |event: AppServerEvent| {
+                sink.lock().unwrap().push(event.message);
+            }),
+            Arc::new(Mutex::new(HashMap::new())),
+        );
+
+        adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+
+        assert_eq!(ThreadStore::try_load(&path).unwrap().threads.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.join("corrupt-ws.json.bak")).unwrap(),
+            "{\"threads\": {\"t1\": "
+        );
+        let warnings = warnings.lock().unwrap();
+        assert_eq!(warnings[0]["method"], "codex/threadStoreWarning");
+        assert!(warnings[0]["params"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("corrupt-ws.json.bak"));
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn repair_thread_store_resets_unparseable_file_with_backup() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("threads.json");
+        std::fs::write(&path, "{\"threads\": {\"t1\": ").unwrap();
+
+        let report = repair_thread_store(&path).unwrap();
+        assert_eq!(report["status"], "reset");
+        assert_eq!(report["threadCount"], 0);
+        let backup = temp_dir.join("threads.json.bak");
+        assert_eq!(
+            std::fs::read_to_string(&backup).unwrap(),
+            "{\"threads\": {\"t1\": "
+        );
+        assert!(ThreadStore::try_load(&path).unwrap().threads.is_empty());
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn repair_thread_store_recovers_valid_entries() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("threads.json");
+        let content = json!({
+            "threads": {
+                "good": {
+                    "cli_session_id": "s1",
+                    "name": "Keep me",
+                    "created_at": 1,
+                    "updated_at": 2,
+                    "archived": false
+                },
+                "bad": { "name": 42 }
+            }
+        });
+        std::fs::write(&path, content.to_string()).unwrap();
+
+        let report = repair_thread_store(&path).unwrap();
+        assert_eq!(report["status"], "recovered");
+        assert_eq!(report["threadCount"], 1);
+        assert_eq!(report["droppedCount"], 1);
+        assert!(temp_dir.join("threads.json.bak").exists());
+        let repaired = ThreadStore::try_load(&path).unwrap();
+        assert_eq!(repaired.threads["good"].name.as_deref(), Some("Keep me"));
+        assert!(!repaired.threads.contains_key("bad"));
+
+        let second = repair_thread_store(&path).unwrap();
+        assert_eq!(second["status"], "ok");
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn thread_usage_sums_completed_turns() {
+        let mut usage = ThreadUsage::default();
+        for (turn_id, input, output, cost) in [("turn1", 100, 20, 0.25), ("turn2", 50, 30, 0.5)] {
+            let event = json!({
+                "method": "turn/completed",
+                "params": {
+                    "threadId": "t1",
+                    "turnId": turn_id,
+                    "costUsd": cost,
+                    "usage": { "inputTokens": input, "outputTokens": output }
+                }
+            });
+            usage.record(extract_turn_usage(&event).unwrap());
+        }
+        assert_eq!(usage.input_tokens, 150);
+        assert_eq!(usage.output_tokens, 50);
+        assert!((usage.cost_usd - 0.75).abs() < f64::EPSILON);
+        assert_eq!(usage.turns.len(), 2);
+        assert_eq!(usage.turns[1].turn_id, "turn2");
+    }
+
+    #[test]
+    fn extract_turn_usage_defaults_missing_fields() {
+        let event = json!({
+            "method": "turn/completed",
+            "params": { "threadId": "t1", "turnId": "turn1" }
+        });
+        let turn = extract_turn_usage(&event).unwrap();
+        assert_eq!(turn.input_tokens, 0);
+        assert_eq!(turn.output_tokens, 0);
+        assert!(turn.cost_usd.is_none());
+
+        let other = json!({ "method": "turn/started", "params": {} });
+        assert!(extract_turn_usage(&other).is_none());
+    }
+
+    #[test]
+    fn extract_turn_model_reads_turn_started_only() {
+        let started = json!({
+            "method": "turn/started",
+            "params": { "threadId": "t1", "turnId": "turn1", "model": "claude-4" }
+        });
+        assert_eq!(extract_turn_model(&started), Some("claude-4"));
+
+        let unnamed = json!({ "method": "turn/started", "params": { "model": "" } });
+        assert!(extract_turn_model(&unnamed).is_none());
+        let other = json!({ "method": "turn/completed", "params": { "model": "claude-4" } });
+        assert!(extract_turn_model(&other).is_none());
+    }
+
+    #[test]
+    fn rate_limit_retry_delay_reads_reset_from_params_usage_or_error() {
+        let from_params = json!({
+            "method": "turn/error",
+            "params": { "error": "rate_limit_error", "retryAfterMs": 1500 }
+        });
+        assert_eq!(
+            rate_limit_retry_delay(&from_params),
+            Some(Duration::from_millis(1500))
+        );
+
+        let from_usage = json!({
+            "method": "turn/error",
+            "params": {
+                "error": { "message": "Too Many Requests" },
+                "usage": { "resetsAt": now_epoch() + 60 }
+            }
+        });
+        let delay = rate_limit_retry_delay(&from_usage).unwrap();
+        assert!(delay <= Duration::from_secs(60) && delay >= Duration::from_secs(58));
+
+        let from_error = json!({
+            "method": "turn/error",
+            "params": { "error": { "type": "rate_limit", "retryAfterMs": 10 } }
+        });
+        assert_eq!(
+            rate_limit_retry_delay(&from_error),
+            Some(Duration::from_millis(10))
+        );
+
+        let no_reset = json!({ "method": "turn/error", "params": { "error": "rate limit" } });
+        assert!(rate_limit_retry_delay(&no_reset).is_none());
+        let other_error = json!({
+            "method": "turn/error",
+            "params": { "error": "boom", "retryAfterMs": 10 }
+        });
+        assert!(rate_limit_retry_delay(&other_error).is_none());
+        let too_long = json!({
+            "method": "turn/error",
+            "params": { "error": "rate_limit", "retryAfterMs": 60 * 60 * 1000 }
+        });
+        assert!(rate_limit_retry_delay(&too_long).is_none());
+    }
+
+    /// Runs `cli_bin` as a shell script and passes its stdout lines through
+    /// as events.
+    struct ScriptProfile;
+
+    impl CliProfile for ScriptProfile {
+        fn build_turn_command(
+            &self,
+            config: &CliSpawnConfig,
+            _session_id: Option<&str>,
+            _prompt: &str,
+            cwd: &str,
+            _params: &Value,
+        ) -> Result<tokio::process::Command, String> {
+            let mut command = tokio::process::Command::new("sh");
+            command
+                .arg(config.cli_bin.as_deref().ok_or("missing cli_bin")?)
+                .current_dir(cwd)
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped());
+            Ok(command)
+        }
+
+        fn parse_stream_line(&self, line: &str, _thread_id: &str, _turn_id: &str) -> Option<Value> {
+            serde_json::from_str(line).ok()
+        }
+
+        fn extract_session_id(&self, _line: &str) -> Option<String> {
+            None
+        }
+
+        fn model_list(&self) -> Value {
+            json!({ "result": { "data": [] } })
+        }
+
+        fn provider_name(&self) -> &str {
+            "script"
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_refuses_cli_older_than_minimum_unless_overridden() {
+        use crate::backend::events::{TerminalExit, TerminalOutput};
+        use std::os::unix::fs::PermissionsExt;
+
+        struct NoopSink;
+
+        impl EventSink for NoopSink {
+            fn emit_app_server_event(&self, _event: AppServerEvent) {}
+            fn emit_terminal_output(&self, _event: TerminalOutput) {}
+            fn emit_terminal_exit(&self, _event: TerminalExit) {}
+        }
+
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let entry = test_workspace_entry("version-ws", &temp_dir.to_string_lossy());
+        let spawn = |version: &str, skip_min_version_check: bool| {
+            let script = temp_dir.join(format!("claude-{version}"));
+            std::fs::write(
+                &script,
+                format!("#!/bin/sh\necho '{version} (Claude Code)'\n"),
+            )
+            .unwrap();
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+            let config = CliSpawnConfig {
+                cli_type: "claude".to_string(),
+                cli_bin: Some(script.to_string_lossy().to_string()),
+                skip_min_version_check,
+                thread_store_dir: Some(temp_dir.clone()),
+                ..CliSpawnConfig::default()
+            };
+            spawn_adapter_session(ScriptProfile, "Claude", entry.clone(), config, NoopSink)
+        };
+
+        let error = spawn("0.2.9", false)
+            .await
+            .err()
+            .expect("old CLI is refused");
+        assert_eq!(error.code(), "spawn_failed");
+        assert!(error.message().contains("too old"), "{error}");
+        assert!(spawn("0.2.9", true).await.is_ok());
+        assert!(spawn("1.0.33", false).await.is_ok());
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn first_prompt_is_captured_once() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let script = temp_dir.join("silent.sh");
+        std::fs::write(&script, "exit 0\n").unwrap();
+        let entry = test_workspace_entry("title-ws", &temp_dir.to_string_lossy());
+        let config = CliSpawnConfig {
+            cli_bin: Some(script.to_string_lossy().to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let adapter = GenericAdapterSession::new(
+            ScriptProfile,
+            &entry,
+            config,
+            Arc::new(|_| {}),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("threads.json"));
+
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let thread_id = started["result"]["threadId"].as_str().unwrap();
+        for input in ["Refactor the parser", "Now add tests"] {
+            adapter
+                .send_request(
+                    "turn/start",
+                    json!({ "threadId": thread_id, "input": input }),
+                )
+                .await
+                .unwrap();
+        }
+
+        let listed = adapter
+            .send_request("thread/list", json!({}))
+            .await
+            .unwrap();
+        let thread = &listed["result"]["threads"][0];
+        assert_eq!(thread["firstPrompt"], "Refactor the parser");
+        assert_eq!(thread["title"], "Refactor the parser");
+        assert!(thread["name"].is_null());
+        let stored = ThreadStore::load(&temp_dir.join("threads.json"));
+        assert_eq!(
+            stored.threads[thread_id].first_prompt.as_deref(),
+            Some("Refactor the parser")
+        );
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rate_limited_turn_is_retried_once() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let runs_path = temp_dir.join("runs");
+        let script = temp_dir.join("rate-limited.sh");
+        std::fs::write(
+            &script,
+            format!(
+                "echo run >> '{runs}'\n\
+                 echo '{{\"method\":\"turn/error\",\"params\":{{\"error\":\"rate_limit_error\",\"retryAfterMs\":50}}}}'\n",
+                runs = runs_path.display()
+            ),
+        )
+        .unwrap();
+
+        let entry = test_workspace_entry("retry-ws", &temp_dir.to_string_lossy());
+        let config = CliSpawnConfig {
+            cli_bin: Some(script.to_string_lossy().to_string()),
+            retry_rate_limited_turns: true,
+            ..CliSpawnConfig::default()
+        };
+        let events = Arc::new(std::sync::Mutex::new(Vec::<Value>::new()));
+        let sink = events.clone();
+        let adapter = GenericAdapterSession::new(
+            ScriptProfile,
+            &entry,
+            config,
+            Arc::new(move |event: AppServerEvent| sink.lock().unwrap().push(event.message)),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("threads.json"));
+
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let thread_id = started["result"]["threadId"].as_str().unwrap();
+        adapter
+            .send_request(
+                "turn/start",
+                json!({ "threadId": thread_id, "input": "hi" }),
+            )
+            .await
+            .unwrap();
+
+        let count = |method: &str| {
+            events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| event["method"] == method)
+                .count()
+        };
+        for _ in 0..100 {
+            if count("turn/error") > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // Let a second, unwanted retry show itself before counting.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let runs = std::fs::read_to_string(&runs_path).unwrap();
+        assert_eq!(runs.lines().count(), 2);
+        assert_eq!(count("turn/retrying"), 1);
+        assert_eq!(count("turn/error"), 1);
+        let retrying = events
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|event| event["method"] == "turn/retrying")
+            .cloned()
+            .unwrap();
+        assert_eq!(retrying["params"]["delayMs"], 50);
+        assert_eq!(retrying["params"]["threadId"], thread_id);
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn reported_model_is_recorded_without_pinning_the_thread() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let script = temp_dir.join("reports-model.sh");
+        std::fs::write(
+            &script,
+            "echo '{\"method\":\"turn/started\",\"params\":{\"model\":\"claude-4\"}}'\n\
+             echo '{\"method\":\"turn/completed\",\"params\":{}}'\n",
+        )
+        .unwrap();
+
+        let entry = test_workspace_entry("model-ws", &temp_dir.to_string_lossy());
+        let config = CliSpawnConfig {
+            cli_bin: Some(script.to_string_lossy().to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let events = Arc::new(std::sync::Mutex::new(Vec::<Value>::new()));
+        let sink = events.clone();
+        let store_path = temp_dir.join("threads.json");
+        let adapter = GenericAdapterSession::new(
+            ScriptProfile,
+            &entry,
+            config,
+            Arc::new(move |event: AppServerEvent| sink.lock().unwrap().push(event.message)),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(store_path.clone());
+
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let thread_id = started["result"]["threadId"].as_str().unwrap();
+        adapter
+            .send_request(
+                "turn/start",
+                json!({ "threadId": thread_id, "input": "hi" }),
+            )
+            .await
+            .unwrap();
+        for _ in 0..100 {
+            let completed = events
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|event| event["method"] == "turn/completed");
+            if completed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let store = ThreadStore::load(&store_path);
+        let meta = &store.threads[thread_id];
+        assert_eq!(meta.reported_model.as_deref(), Some("claude-4"));
+        assert!(meta.model.is_none());
+        let next_turn = adapter
+            .turn_params_with_thread_model(thread_id, &json!({ "threadId": thread_id }))
+            .await;
+        assert!(next_turn.get("model").is_none());
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn resolve_turn_cwd_accepts_subdirectories_and_rejects_escapes() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        let root = temp_dir.join("repo");
+        std::fs::create_dir_all(root.join("packages/app")).unwrap();
+        std::fs::create_dir_all(temp_dir.join("outside")).unwrap();
+        std::fs::write(root.join("README.md"), "readme").unwrap();
+        let root_str = root.to_string_lossy().to_string();
+        let canonical_root = root.canonicalize().unwrap();
+
+        assert_eq!(resolve_turn_cwd(&root_str, None).unwrap(), root_str);
+        assert_eq!(resolve_turn_cwd(&root_str, Some("  ")).unwrap(), root_str);
+        assert_eq!(resolve_turn_cwd(&root_str, Some(".")).unwrap(), root_str);
+        let subdir = root.join("packages").join("app");
+        assert_eq!(
+            resolve_turn_cwd(&root_str, Some("packages/app")).unwrap(),
+            subdir.to_string_lossy()
+        );
+        assert_eq!(
+            resolve_turn_cwd(
+                &root_str,
+                Some(&canonical_root.join("packages/app").to_string_lossy())
+            )
+            .unwrap(),
+            subdir.to_string_lossy()
+        );
+
+        let escape = resolve_turn_cwd(&root_str, Some("../outside")).unwrap_err();
+        assert!(escape.contains("outside the workspace"), "{escape}");
+        let absolute_escape =
+            resolve_turn_cwd(&root_str, Some(&temp_dir.join("outside").to_string_lossy()))
+                .unwrap_err();
+        assert!(
+            absolute_escape.contains("outside the workspace"),
+            "{absolute_escape}"
+        );
+        assert!(resolve_turn_cwd(&root_str, Some("packages/missing")).is_err());
+        assert!(resolve_turn_cwd(&root_str, Some("README.md"))
+            .unwrap_err()
+            .contains("not a directory"));
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn take_child_if_leaves_a_replacement_process_alone() {
+        let spawn = || {
+            tokio::process::Command::new("sleep")
+                .arg("5")
+                .kill_on_drop(true)
+                .spawn()
+                .unwrap()
+        };
+        let (replaced, replacement) = (spawn(), spawn());
+        let replaced_pid = replaced.id();
+        let replacement_pid = replacement.id();
+        drop(replaced);
+        let mut slot = Some(replacement);
+
+        assert!(take_child_if(&mut slot, replaced_pid).is_none());
+        assert!(take_child_if(&mut slot, None).is_none());
+        assert_eq!(slot.as_ref().and_then(Child::id), replacement_pid);
+        let mut taken = take_child_if(&mut slot, replacement_pid).expect("own child");
+        assert!(slot.is_none());
+        kill_child_process_tree(&mut taken).await;
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_turn_cwd_keeps_a_symlinked_workspace_root() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        let target = temp_dir.join("repo");
+        std::fs::create_dir_all(target.join("packages/app")).unwrap();
+        std::fs::create_dir_all(temp_dir.join("outside")).unwrap();
+        let root = temp_dir.join("linked-repo");
+        std::os::unix::fs::symlink(&target, &root).unwrap();
+        let root_str = root.to_string_lossy().to_string();
+
+        assert_eq!(resolve_turn_cwd(&root_str, Some(".")).unwrap(), root_str);
+        assert_eq!(
+            resolve_turn_cwd(&root_str, Some("packages/app")).unwrap(),
+            root.join("packages").join("app").to_string_lossy()
+        );
+        let escape = resolve_turn_cwd(&root_str, Some("../outside")).unwrap_err();
+        assert!(escape.contains("outside the workspace"), "{escape}");
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn turn_runs_in_requested_subdirectory() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        let root = temp_dir.join("repo");
+        std::fs::create_dir_all(root.join("packages/app")).unwrap();
+        std::fs::create_dir_all(temp_dir.join("outside")).unwrap();
+        let pwd_path = temp_dir.join("pwd");
+        let script = temp_dir.join("pwd.sh");
+        std::fs::write(&script, format!("pwd -P > '{}'\n", pwd_path.display())).unwrap();
+        let entry = test_workspace_entry("cwd-ws", &root.to_string_lossy());
+        let config = CliSpawnConfig {
+            cli_bin: Some(script.to_string_lossy().to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let adapter = GenericAdapterSession::new(
+            ScriptProfile,
+            &entry,
+            config,
+            Arc::new(|_| {}),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("threads.json"));
+
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let thread_id = started["result"]["threadId"].as_str().unwrap();
+        let error = adapter
+            .send_request(
+                "turn/start",
+                json!({ "threadId": thread_id, "input": "hi", "cwd": "../outside" }),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.contains("outside the workspace"), "{error}");
+        assert!(!pwd_path.exists());
+
+        adapter
+            .send_request(
+                "turn/start",
+                json!({ "threadId": thread_id, "input": "hi", "cwd": "packages/app" }),
+            )
+            .await
+            .unwrap();
+        let mut pwd = String::new();
+        for _ in 0..100 {
+            pwd = std::fs::read_to_string(&pwd_path).unwrap_or_default();
+            if !pwd.trim().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            Path::new(pwd.trim()),
+            root.join("packages/app").canonicalize().unwrap()
+        );
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn auto_title_uses_first_line_of_first_prompt() {
+        let mut meta = ThreadMetadata {
+            cli_session_id: None,
+            name: None,
+            created_at: 0,
+            updated_at: 0,
+            archived: false,
+            usage: ThreadUsage::default(),
+            model: None,
+            reported_model: None,
+            first_prompt: None,
+            fork_pending: false,
+        };
+        assert!(meta.auto_title().is_none());
+
+        meta.first_prompt = Some("\n  Fix   the\tbuild  \nthen run tests".to_string());
+        assert_eq!(meta.auto_title().as_deref(), Some("Fix the build"));
+
+        meta.first_prompt = Some("word ".repeat(40));
+        let title = meta.auto_title().unwrap();
+        assert_eq!(title.chars().count(), THREAD_TITLE_MAX_CHARS);
+        assert!(title.ends_with("word…"));
+    }
+
+    #[test]
+    fn thread_store_without_data_dir_stays_out_of_cwd() {
+        let (path, warning) = resolve_thread_store_path(None, "ws-1");
+        let cwd = std::env::current_dir().unwrap();
+        assert!(path.is_absolute());
+        assert!(!path.starts_with(&cwd));
+        assert!(path.starts_with(std::env::temp_dir()));
+        assert!(path.ends_with("agent-monitor/adapter-threads/ws-1.json"));
+        assert!(warning.is_some_and(|message| message.contains("No user data directory")));
+
+        let (path, warning) = resolve_thread_store_path(Some(PathBuf::from("/data")), "ws-1");
+        assert_eq!(
+            path,
+            PathBuf::from("/data/agent-monitor/adapter-threads/ws-1.json")
+        );
+        assert!(warning.is_none());
+    }
+
+    #[test]
+    fn migrate_thread_stores_copies_existing_files() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        let from = temp_dir.join("old");
+        let to = temp_dir.join("new").join("threads");
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::write(from.join("ws-1.json"), r#"{"threads":{}}"#).unwrap();
+        std::fs::write(from.join("ws-2.json"), r#"{"threads":{"t1":null}}"#).unwrap();
+        std::fs::write(from.join("notes.txt"), "not a store").unwrap();
+
+        assert_eq!(migrate_thread_stores(&from, &to).unwrap(), 2);
+        assert_eq!(
+            std::fs::read_to_string(to.join("ws-2.json")).unwrap(),
+            r#"{"threads":{"t1":null}}"#
+        );
+        assert!(!to.join("notes.txt").exists());
+        assert!(from.join("ws-1.json").exists());
+
+        // Stores already at the target are not overwritten.
+        std::fs::write(to.join("ws-1.json"), "newer").unwrap();
+        assert_eq!(migrate_thread_stores(&from, &to).unwrap(), 0);
+        assert_eq!(
+            std::fs::read_to_string(to.join("ws-1.json")).unwrap(),
+            "newer"
+        );
+
+        let missing = temp_dir.join("missing");
+        assert_eq!(migrate_thread_stores(&missing, &to).unwrap(), 0);
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn migrate_thread_stores_rejects_unwritable_target() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("adapter-base-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        std::fs::write(temp_dir.join("ws-1.json"), "{}").unwrap();
+        // A directory below a regular file can never be created.
+        let blocker = temp_dir.join("blocker");
+        std::fs::write(&blocker, "").unwrap();
+        let target = blocker.join("threads");
+
+        let err = migrate_thread_stores(&temp_dir, &target).unwrap_err();
+        assert!(err.contains("is not writable"), "{err}");
+        assert!(ensure_dir_writable(&target).is_err());
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn thread_store_path_prefers_configured_dir() {
+        let dir = PathBuf::from("/shared/threads");
+        assert_eq!(
+            thread_store_path(Some(&dir), "ws-1"),
+            PathBuf::from("/shared/threads/ws-1.json")
+        );
+        assert!(thread_store_path(None, "ws-1").ends_with("adapter-threads/ws-1.json"));
+    }
+
+    #[test]
+    fn normalize_reasoning_event_rewrites_aliases_to_canonical_shape() {
+        let canonical = reasoning_delta_event("t1", "turn1", "r1", "thinking...");
+        for event in [
+            json!({
+                "method": "item/reasoning/delta",
+                "params": {
+                    "threadId": "t1",
+                    "turnId": "turn1",
+                    "itemId": "r1",
+                    "delta": "thinking..."
+                }
+            }),
+            json!({
+                "method": "item/thought/delta",
+                "params": {
+                    "thread_id": "t1",
+                    "turn_id": "turn1",
+                    "item_id": "r1",
+                    "text": "thinking..."
+                }
+            }),
+            json!({
+                "method": "item/reasoning/textDelta",
+                "params": {
+                    "threadId": "t1",
+                    "turnId": "turn1",
+                    "itemId": "r1",
+                    "content": "thinking..."
+                }
+            }),
+        ] {
+            assert_eq!(normalize_reasoning_event(event), canonical);
+        }
+        let message = json!({
+            "method": "item/agentMessage/delta",
+            "params": { "threadId": "t1", "text": "hi" }
+        });
+        assert_eq!(normalize_reasoning_event(message.clone()), message);
+    }
+
     #[test]
     fn now_epoch_returns_nonzero() {
         assert!(now_epoch() > 0);
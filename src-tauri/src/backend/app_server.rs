@@ -1,31 +1,125 @@
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, Lines};
 use tokio::process::{Child, ChildStdin, Command};
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio::time::timeout;
 
-use crate::backend::events::{AppServerEvent, EventSink};
-use crate::shared::process_core::{kill_child_process_tree, tokio_command};
+use crate::backend::adapter_base::CliProfile;
+use crate::backend::approval_rules::{approval_rules_path, ApprovalRuleStore};
+use crate::backend::claude_adapter::ClaudeProfile;
+use crate::backend::cursor_adapter::CursorProfile;
+use crate::backend::errors::AppServerError;
+use crate::backend::event_recorder::{ActiveTurns, EventRecorder, RecordingEventSink};
+use crate::backend::events::{tag_unsupported_method, AppServerEvent, EventSink};
+use crate::backend::gemini_adapter::GeminiProfile;
+use crate::shared::process_core::{
+    kill_child_process_tree, tokio_command, CommandRunner, SystemCommandRunner,
+};
 use crate::codex::args::parse_codex_args;
-use crate::types::WorkspaceEntry;
+use crate::codex::home::ensure_cli_home_dir;
+use crate::types::{SessionInfo, WorkspaceEntry};
 
 #[cfg(target_os = "windows")]
 use crate::shared::process_core::{build_cmd_c_command, resolve_windows_executable};
 
-#[derive(Clone, Debug)]
+const DEFAULT_INITIALIZE_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_INITIALIZE_ATTEMPTS: u32 = 3;
+const DEFAULT_INITIALIZE_BACKOFF: Duration = Duration::from_millis(500);
+pub(crate) const DEFAULT_READ_BUFFER_CAPACITY: usize = 64 * 1024;
+pub(crate) const CALLBACK_PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_CLIENT_NAME: &str = "Agent Monitor";
+/// `clientInfo.name` app-server peers identify this client by; only the
+/// display title follows the configured client name.
+const CLIENT_ID: &str = "codex_monitor";
+const STARTUP_OUTPUT_LINES: usize = 20;
+const PARSE_ERROR_UNHEALTHY_THRESHOLD: usize = 20;
+const PARSE_ERROR_WINDOW: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Default)]
 pub(crate) struct CliSpawnConfig {
     pub cli_type: String,
     pub cli_bin: Option<String>,
     pub cli_args: Option<String>,
     pub cli_home: Option<PathBuf>,
+    /// Claude profile dir, exported as `CLAUDE_CONFIG_DIR`. Only set when a
+    /// workspace configures one so Claude otherwise keeps its own default.
+    pub claude_home: Option<PathBuf>,
+    /// Cursor profile dir, exported as `CURSOR_CONFIG_DIR`.
+    pub cursor_home: Option<PathBuf>,
+    /// How many windows to wait for the `initialize` response before the
+    /// spawn is abandoned. `initialize` itself is only sent once.
+    pub initialize_attempts: Option<u32>,
+    /// Length of the first window.
+    pub initialize_timeout_ms: Option<u64>,
+    /// Extra wait added to each later window; doubled every window.
+    pub initialize_backoff_ms: Option<u64>,
+    /// Capacity of the stdout line reader. A line longer than this is still
+    /// read whole; the reader just refills more often while the line grows.
+    pub read_buffer_capacity: Option<usize>,
+    /// Reported as `clientInfo.title` in `initialize`; defaults to
+    /// `DEFAULT_CLIENT_NAME` when unset.
+    pub client_name: Option<String>,
+    /// Workspace sandbox strictness (`readOnly`, `workspaceWrite` or `full`)
+    /// for CLIs that take it at spawn. Unset keeps the CLI's own default.
+    pub sandbox_policy: Option<String>,
+    /// Cursor `--mode` for turns that don't pick one. Checked against
+    /// `CURSOR_MODES` before spawn; unset or `agent` passes no flag.
+    pub cursor_default_mode: Option<String>,
+    /// Leave out Gemini's `--sandbox` even when the policy asks for one
+    /// (the `geminiUseSandbox` setting turned off).
+    pub gemini_sandbox_disabled: bool,
+    /// Retry an adapter turn once after a rate-limit error that says when
+    /// the limit resets.
+    pub retry_rate_limited_turns: bool,
+    /// Where adapter sessions keep thread stores instead of the default
+    /// directory under the user data dir.
+    pub thread_store_dir: Option<PathBuf>,
+    /// Standing instruction for every turn; each adapter injects it the way
+    /// its CLI allows.
+    pub system_prompt: Option<String>,
+    /// Spawn even when the CLI reports a version below the supported minimum.
+    pub skip_min_version_check: bool,
+    /// Regexes for stderr lines to keep out of `codex/stderr`.
+    pub stderr_deny_patterns: Vec<String>,
+    /// Regexes for stderr lines that are emitted even when a deny pattern
+    /// matches them.
+    pub stderr_allow_patterns: Vec<String>,
+    /// How long an approval request may wait for the user before it is
+    /// answered with `approval_timeout_decision`. Unset waits forever.
+    pub approval_timeout_ms: Option<u64>,
+    /// Decision sent for timed-out approvals; unset declines.
+    pub approval_timeout_decision: Option<String>,
+}
+
+impl CliSpawnConfig {
+    pub(crate) fn system_prompt(&self) -> Option<&str> {
+        self.system_prompt
+            .as_deref()
+            .map(str::trim)
+            .filter(|prompt| !prompt.is_empty())
+    }
+
+    pub(crate) fn read_buffer_capacity(&self) -> usize {
+        self.read_buffer_capacity
+            .filter(|capacity| *capacity > 0)
+            .unwrap_or(DEFAULT_READ_BUFFER_CAPACITY)
+    }
+}
+
+pub(crate) fn cli_line_reader<R: AsyncRead + Unpin>(
+    reader: R,
+    capacity: usize,
+) -> Lines<BufReader<R>> {
+    BufReader::with_capacity(capacity, reader).lines()
 }
 
 #[async_trait::async_trait]
@@ -34,6 +128,7 @@ pub(crate) trait CliAdapter: Send + Sync {
     async fn send_notification(&self, method: &str, params: Option<Value>) -> Result<(), String>;
     async fn send_response(&self, id: Value, result: Value) -> Result<(), String>;
     async fn kill(&self);
+    fn cli_type(&self) -> &str;
 }
 
 struct AppServerTransport {
@@ -52,6 +147,54 @@ pub(crate) struct WorkspaceSession {
     pub(crate) entry: WorkspaceEntry,
     pub(crate) background_thread_callbacks: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>>,
     transport: SessionTransport,
+    started_at: u64,
+    /// Collaboration mode selected per thread, applied to turns that don't
+    /// pass one explicitly. Seeded from the workspace settings, which keep it
+    /// across restarts.
+    collaboration_modes: Mutex<HashMap<String, Value>>,
+    /// Model selected per thread, applied to turns that don't pass one.
+    thread_models: Mutex<HashMap<String, String>>,
+    event_recorder: Arc<EventRecorder>,
+    /// Time from spawn start until the session was constructed.
+    spawn_duration_ms: AtomicU64,
+    /// Time the `initialize` handshake took. Adapter sessions have no
+    /// handshake and report 0.
+    initialize_duration_ms: OnceLock<u64>,
+    pending_approvals: Mutex<PendingApprovals>,
+    /// The workspace's remembered approval rules, loaded on the first
+    /// approval request and dropped whenever the rules change.
+    approval_rules: Mutex<Option<ApprovalRuleStore>>,
+}
+
+/// Approval request ids (as JSON text) forwarded to the UI and not answered
+/// yet, plus those the session already answered itself after a timeout,
+/// mapped to their thread until that thread's turn ends.
+#[derive(Default)]
+struct PendingApprovals {
+    pending: HashSet<String>,
+    timed_out: HashMap<String, Option<String>>,
+}
+
+/// Whether `message` ends its turn. Mirrors `ActiveTurns::observe`.
+fn is_turn_end(message: &Value) -> bool {
+    let params = message.get("params").unwrap_or(&Value::Null);
+    match message.get("method").and_then(Value::as_str) {
+        Some("turn/completed" | "turn/interrupted") => true,
+        Some("turn/error") => params.get("willRetry").and_then(Value::as_bool) != Some(true),
+        _ => false,
+    }
+}
+
+/// Mirrors `isApprovalRequestMethod` in `src/utils/appServerEvents.ts`.
+fn is_approval_request_method(method: &str) -> bool {
+    method.ends_with("requestApproval")
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
 }
 
 impl WorkspaceSession {
@@ -72,17 +215,74 @@ impl WorkspaceSession {
         }
     }
 
-    pub(crate) async fn send_request(&self, method: &str, params: Value) -> Result<Value, String> {
+    /// Fails once the workspace directory is gone, e.g. a worktree deleted
+    /// while its session was still connected.
+    pub(crate) fn ensure_workspace_dir(&self) -> Result<(), AppServerError> {
+        if Path::new(&self.entry.path).is_dir() {
+            return Ok(());
+        }
+        Err(AppServerError::WorkspaceMissing(format!(
+            "workspace directory missing: {}",
+            self.entry.path
+        )))
+    }
+
+    pub(crate) async fn send_request(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, AppServerError> {
+        if method != "turn/start" {
+            return self.dispatch_request(method, params).await;
+        }
+        self.ensure_workspace_dir()?;
+        let thread_id = params
+            .get("threadId")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let response = self.dispatch_request(method, params).await?;
+        let turn_id = response
+            .get("result")
+            .and_then(|result| result.get("turn"))
+            .and_then(|turn| turn.get("id"))
+            .and_then(Value::as_str);
+        if let (Some(thread_id), Some(turn_id)) = (thread_id, turn_id) {
+            // Background turns report to their collector, not the recorder,
+            // so their end would never be seen.
+            let background = self
+                .background_thread_callbacks
+                .lock()
+                .await
+                .contains_key(&thread_id);
+            if !background {
+                self.active_turns().start(&thread_id, turn_id);
+            }
+        }
+        Ok(response)
+    }
+
+    async fn dispatch_request(&self, method: &str, params: Value) -> Result<Value, AppServerError> {
         match &self.transport {
             SessionTransport::AppServer(t) => {
                 let id = t.next_id.fetch_add(1, Ordering::SeqCst);
                 let (tx, rx) = oneshot::channel();
                 t.pending.lock().await.insert(id, tx);
                 self.write_message(json!({ "id": id, "method": method, "params": params }))
-                    .await?;
-                rx.await.map_err(|_| "request canceled".to_string())
+                    .await
+                    .map_err(AppServerError::Io)?;
+                let response = rx
+                    .await
+                    .map_err(|_| AppServerError::Protocol("request canceled".to_string()))?;
+                if let Some(message) = rpc_error_message(&response) {
+                    eprintln!("app-server: `{method}` failed: {}", response["error"]);
+                    return Err(AppServerError::Protocol(message));
+                }
+                Ok(response)
             }
-            SessionTransport::Adapter(adapter) => adapter.send_request(method, params).await,
+            SessionTransport::Adapter(adapter) => adapter
+                .send_request(method, params)
+                .await
+                .map_err(AppServerError::Protocol),
         }
     }
 
@@ -105,6 +305,20 @@ impl WorkspaceSession {
     }
 
     pub(crate) async fn send_response(&self, id: Value, result: Value) -> Result<(), String> {
+        {
+            let mut approvals = self.pending_approvals.lock().await;
+            let key = id.to_string();
+            if approvals.timed_out.remove(&key).is_some() {
+                return Err(format!(
+                    "Approval request {key} timed out and was already answered"
+                ));
+            }
+            approvals.pending.remove(&key);
+        }
+        self.write_response(id, result).await
+    }
+
+    async fn write_response(&self, id: Value, result: Value) -> Result<(), String> {
         match &self.transport {
             SessionTransport::AppServer(_) => {
                 self.write_message(json!({ "id": id, "result": result }))
@@ -114,6 +328,72 @@ impl WorkspaceSession {
         }
     }
 
+    /// Drops the timed-out approvals of a thread, and any without a thread,
+    /// once a turn ends there, so the set doesn't grow for the session's life.
+    pub(crate) async fn forget_timed_out_approvals(&self, message: &Value) {
+        if !is_turn_end(message) {
+            return;
+        }
+        let thread_id = extract_thread_id(message);
+        self.pending_approvals
+            .lock()
+            .await
+            .timed_out
+            .retain(|_, approval_thread| {
+                approval_thread.is_some() && *approval_thread != thread_id
+            });
+    }
+
+    async fn is_alive(&self) -> bool {
+        match &self.transport {
+            SessionTransport::AppServer(t) => {
+                let mut child = t.child.lock().await;
+                matches!(child.try_wait(), Ok(None))
+            }
+            SessionTransport::Adapter(_) => true,
+        }
+    }
+
+    pub(crate) fn is_adapter(&self) -> bool {
+        matches!(self.transport, SessionTransport::Adapter(_))
+    }
+
+    pub(crate) fn cli_type(&self) -> &str {
+        match &self.transport {
+            SessionTransport::AppServer(_) => "codex",
+            SessionTransport::Adapter(adapter) => adapter.cli_type(),
+        }
+    }
+
+    pub(crate) async fn info(&self) -> SessionInfo {
+        let pid = match &self.transport {
+            SessionTransport::AppServer(t) => t.child.lock().await.id(),
+            SessionTransport::Adapter(_) => None,
+        };
+        SessionInfo {
+            workspace_id: self.entry.id.clone(),
+            cli_type: self.cli_type().to_string(),
+            alive: self.is_alive().await,
+            pid,
+            started_at: self.started_at,
+            spawn_duration_ms: self.spawn_duration_ms.load(Ordering::Relaxed),
+            initialize_duration_ms: self.initialize_duration_ms.get().copied(),
+            recording_path: self
+                .event_recorder
+                .recording_path()
+                .map(|path| path.to_string_lossy().to_string()),
+        }
+    }
+
+    pub(crate) fn record_spawn_duration(&self, duration: Duration) {
+        self.spawn_duration_ms
+            .store(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_initialize_duration(&self, duration: Duration) {
+        let _ = self.initialize_duration_ms.set(duration.as_millis() as u64);
+    }
+
     pub(crate) async fn kill(&self) {
         match &self.transport {
             SessionTransport::AppServer(t) => {
@@ -130,13 +410,165 @@ impl WorkspaceSession {
         entry: WorkspaceEntry,
         adapter: Box<dyn CliAdapter>,
         callbacks: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>>,
+        event_recorder: Arc<EventRecorder>,
     ) -> Self {
+        let collaboration_modes = entry.settings.thread_collaboration_modes.clone();
         Self {
             entry,
             background_thread_callbacks: callbacks,
             transport: SessionTransport::Adapter(adapter),
+            started_at: now_ms(),
+            collaboration_modes: Mutex::new(collaboration_modes),
+            thread_models: Mutex::new(HashMap::new()),
+            event_recorder,
+            spawn_duration_ms: AtomicU64::new(0),
+            initialize_duration_ms: OnceLock::new(),
+            pending_approvals: Mutex::new(PendingApprovals::default()),
+            approval_rules: Mutex::new(None),
         }
     }
+
+    /// Recorder fed with every event this session emits.
+    pub(crate) fn event_recorder(&self) -> &EventRecorder {
+        &self.event_recorder
+    }
+
+    /// Accepts an approval request whose command matches one of the
+    /// workspace's remembered rules, so it never reaches the UI. Returns
+    /// whether the request was answered.
+    async fn auto_approve(&self, request: &Value) -> bool {
+        let (Some(id), Some(params)) = (request.get("id"), request.get("params")) else {
+            return false;
+        };
+        {
+            let mut rules = self.approval_rules.lock().await;
+            let store = rules.get_or_insert_with(|| {
+                ApprovalRuleStore::load(&approval_rules_path(&self.entry.id))
+            });
+            if !store.matches_request(params) {
+                return false;
+            }
+        }
+        let response = json!({ "id": id, "result": { "decision": "accept" } });
+        match self.write_message(response).await {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("app-server: failed to auto-approve request {id}: {err}");
+                false
+            }
+        }
+    }
+
+    /// Makes the next approval request reload the remembered rules.
+    pub(crate) async fn invalidate_approval_rules(&self) {
+        *self.approval_rules.lock().await = None;
+    }
+
+    /// Starts the clock on an approval request forwarded to the UI. If no
+    /// `send_response` answers it within `wait`, the session sends `decision`
+    /// itself and emits `approval/timedOut`.
+    pub(crate) async fn track_approval_request<E: EventSink>(
+        self: &Arc<Self>,
+        request: &Value,
+        wait: Duration,
+        decision: &str,
+        event_sink: E,
+    ) {
+        let Some(id) = request.get("id").cloned() else {
+            return;
+        };
+        let key = id.to_string();
+        self.pending_approvals
+            .lock()
+            .await
+            .pending
+            .insert(key.clone());
+        let session: Weak<Self> = Arc::downgrade(self);
+        let decision = decision.to_string();
+        let method = request.get("method").cloned().unwrap_or(Value::Null);
+        let thread_id = extract_thread_id(request);
+        let approval_thread = thread_id.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(wait).await;
+            let Some(session) = session.upgrade() else {
+                return;
+            };
+            {
+                let mut approvals = session.pending_approvals.lock().await;
+                if !approvals.pending.remove(&key) {
+                    return;
+                }
+                approvals.timed_out.insert(key, approval_thread);
+            }
+            let result = json!({ "decision": decision });
+            if let Err(err) = session.write_response(id.clone(), result).await {
+                eprintln!("app-server: failed to answer timed-out approval {id}: {err}");
+            }
+            event_sink.emit_app_server_event(AppServerEvent {
+                workspace_id: session.entry.id.clone(),
+                message: json!({
+                    "method": "approval/timedOut",
+                    "params": {
+                        "requestId": id,
+                        "threadId": thread_id,
+                        "method": method,
+                        "decision": decision,
+                        "timeoutMs": wait.as_millis() as u64,
+                    }
+                }),
+            });
+        });
+    }
+
+    /// Foreground turns started in this session that haven't ended yet.
+    pub(crate) fn active_turns(&self) -> &ActiveTurns {
+        self.event_recorder.active_turns()
+    }
+
+    pub(crate) async fn collaboration_mode(&self, thread_id: &str) -> Option<Value> {
+        self.collaboration_modes.lock().await.get(thread_id).cloned()
+    }
+
+    /// Stores the thread's collaboration mode; `null` clears it.
+    pub(crate) async fn set_collaboration_mode(&self, thread_id: &str, mode: Value) {
+        let mut modes = self.collaboration_modes.lock().await;
+        if mode.is_null() {
+            modes.remove(thread_id);
+        } else {
+            modes.insert(thread_id.to_string(), mode);
+        }
+    }
+
+    pub(crate) async fn thread_model(&self, thread_id: &str) -> Option<String> {
+        self.thread_models.lock().await.get(thread_id).cloned()
+    }
+
+    /// Stores the thread's model; `None` clears it.
+    pub(crate) async fn set_thread_model(&self, thread_id: &str, model: Option<String>) {
+        let mut models = self.thread_models.lock().await;
+        match model {
+            Some(model) => {
+                models.insert(thread_id.to_string(), model);
+            }
+            None => {
+                models.remove(thread_id);
+            }
+        }
+    }
+}
+
+/// Formats the JSON-RPC `error` object of a response, if it has one.
+fn rpc_error_message(response: &Value) -> Option<String> {
+    let error = response.get("error").filter(|error| !error.is_null())?;
+    let message = error
+        .get("message")
+        .and_then(|message| message.as_str())
+        .map(|message| message.to_string())
+        .unwrap_or_else(|| error.to_string());
+    Some(match error.get("code").and_then(|code| code.as_i64()) {
+        Some(code) => format!("{message} (code {code})"),
+        None => message,
+    })
 }
 
 fn extract_thread_id(value: &Value) -> Option<String> {
@@ -156,11 +588,42 @@ fn extract_thread_id(value: &Value) -> Option<String> {
         })
 }
 
-fn build_initialize_params(client_version: &str) -> Value {
+type BackgroundCallbacks = Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>;
+
+/// Drops background callbacks whose receiver has gone away, e.g. after a
+/// background turn panicked before removing its own entry.
+pub(crate) async fn prune_closed_callbacks(callbacks: &BackgroundCallbacks) -> usize {
+    let mut callbacks = callbacks.lock().await;
+    let before = callbacks.len();
+    callbacks.retain(|_, tx| !tx.is_closed());
+    before - callbacks.len()
+}
+
+/// Periodically prunes `callbacks` until the session owning them is dropped.
+pub(crate) fn spawn_callback_pruner(callbacks: &Arc<BackgroundCallbacks>, interval: Duration) {
+    let callbacks = Arc::downgrade(callbacks);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let Some(callbacks) = callbacks.upgrade() else {
+                break;
+            };
+            prune_closed_callbacks(&callbacks).await;
+        }
+    });
+}
+
+fn initialize_backoff_delay(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempt))
+}
+
+fn build_initialize_params(client_name: &str, client_version: &str) -> Value {
     json!({
         "clientInfo": {
-            "name": "codex_monitor",
-            "title": "Agent Monitor",
+            "name": CLIENT_ID,
+            "title": client_name,
             "version": client_version
         },
         "capabilities": {
@@ -305,30 +768,34 @@ pub(crate) fn build_codex_command_with_bin(
 pub(crate) async fn check_cli_installation(
     cli_bin: Option<String>,
     cli_name: &str,
-) -> Result<Option<String>, String> {
-    let mut command =
-        build_codex_command_with_bin(cli_bin, None, vec!["--version".to_string()])?;
+) -> Result<Option<String>, AppServerError> {
+    check_cli_installation_with(&SystemCommandRunner, cli_bin, cli_name).await
+}
+
+pub(crate) async fn check_cli_installation_with<R: CommandRunner + ?Sized>(
+    runner: &R,
+    cli_bin: Option<String>,
+    cli_name: &str,
+) -> Result<Option<String>, AppServerError> {
+    let mut command = build_codex_command_with_bin(cli_bin, None, vec!["--version".to_string()])
+        .map_err(AppServerError::SpawnFailed)?;
     command.stdout(std::process::Stdio::piped());
     command.stderr(std::process::Stdio::piped());
 
-    let output = match timeout(Duration::from_secs(5), command.output()).await {
-        Ok(result) => result.map_err(|e| {
-            if e.kind() == ErrorKind::NotFound {
-                format!(
-                    "{cli_name} CLI not found. Install {cli_name} and ensure `{bin}` is on your PATH.",
-                    bin = cli_name.to_lowercase()
-                )
-            } else {
-                e.to_string()
-            }
-        })?,
-        Err(_) => {
-            return Err(format!(
+    let output = runner
+        .output(command, Duration::from_secs(5))
+        .await
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => AppServerError::CliNotFound(format!(
+                "{cli_name} CLI not found. Install {cli_name} and ensure `{bin}` is on your PATH.",
+                bin = cli_name.to_lowercase()
+            )),
+            ErrorKind::TimedOut => AppServerError::Timeout(format!(
                 "Timed out while checking {cli_name} CLI. Make sure `{bin} --version` runs in Terminal.",
                 bin = cli_name.to_lowercase()
-            ));
-        }
-    };
+            )),
+            _ => AppServerError::Io(e.to_string()),
+        })?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -339,15 +806,15 @@ pub(crate) async fn check_cli_installation(
             stderr.trim()
         };
         if detail.is_empty() {
-            return Err(format!(
+            return Err(AppServerError::SpawnFailed(format!(
                 "{cli_name} CLI failed to start. Try running `{bin} --version` in Terminal.",
                 bin = cli_name.to_lowercase()
-            ));
+            )));
         }
-        return Err(format!(
+        return Err(AppServerError::SpawnFailed(format!(
             "{cli_name} CLI failed to start: {detail}. Try running `{bin} --version` in Terminal.",
             bin = cli_name.to_lowercase()
-        ));
+        )));
     }
 
     let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
@@ -356,16 +823,384 @@ pub(crate) async fn check_cli_installation(
 
 pub(crate) async fn check_codex_installation(
     codex_bin: Option<String>,
-) -> Result<Option<String>, String> {
+) -> Result<Option<String>, AppServerError> {
     check_cli_installation(codex_bin, "Codex").await
 }
 
+/// Decides which stderr lines are emitted as `codex/stderr`. A line matching
+/// a deny pattern is dropped unless an allow pattern matches it too; with no
+/// deny patterns everything is emitted.
+#[derive(Default)]
+pub(crate) struct StderrFilter {
+    deny: Vec<Regex>,
+    allow: Vec<Regex>,
+}
+
+impl StderrFilter {
+    /// Patterns that don't compile are logged and skipped rather than failing
+    /// the spawn over a bad setting.
+    pub(crate) fn new(deny: &[String], allow: &[String]) -> Self {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter(|pattern| !pattern.trim().is_empty())
+                .filter_map(|pattern| match Regex::new(pattern) {
+                    Ok(regex) => Some(regex),
+                    Err(err) => {
+                        eprintln!("app-server: ignoring stderr filter pattern {pattern:?}: {err}");
+                        None
+                    }
+                })
+                .collect()
+        };
+        Self {
+            deny: compile(deny),
+            allow: compile(allow),
+        }
+    }
+
+    pub(crate) fn should_emit(&self, line: &str) -> bool {
+        !self.deny.iter().any(|regex| regex.is_match(line))
+            || self.allow.iter().any(|regex| regex.is_match(line))
+    }
+}
+
+/// Recent lines the CLI wrote to stderr, or to stdout outside the JSON-RPC
+/// stream. A handshake that times out reports them, since they usually say
+/// why (an auth prompt, a config error).
+#[derive(Default)]
+struct StartupOutput {
+    lines: std::sync::Mutex<VecDeque<String>>,
+}
+
+impl StartupOutput {
+    fn push(&self, line: &str) {
+        let mut lines = self.lines.lock().unwrap_or_else(|err| err.into_inner());
+        if lines.len() == STARTUP_OUTPUT_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.trim_end().to_string());
+    }
+
+    fn summary(&self) -> Option<String> {
+        let lines = self.lines.lock().unwrap_or_else(|err| err.into_inner());
+        if lines.is_empty() {
+            return None;
+        }
+        Some(lines.iter().cloned().collect::<Vec<_>>().join("\n"))
+    }
+}
+
+/// What the stdout reader should do with an unparseable line.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseErrorVerdict {
+    /// Emit it as `codex/parseError`.
+    Report,
+    /// Too many in a row: emit a single `cli/unhealthy` instead.
+    BecameUnhealthy(usize),
+    /// Already reported the session as unhealthy; drop it.
+    Suppress,
+}
+
+/// Counts unparseable stdout lines since the last good one. Past `threshold`
+/// within `window` the session is treated as broken, so a CLI printing
+/// garbage raises one `cli/unhealthy` instead of a flood of parse errors.
+struct ParseErrorMonitor {
+    threshold: usize,
+    window: Duration,
+    recent: VecDeque<Instant>,
+    unhealthy: bool,
+}
+
+impl ParseErrorMonitor {
+    fn new(threshold: usize, window: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            window,
+            recent: VecDeque::new(),
+            unhealthy: false,
+        }
+    }
+
+    fn record_error(&mut self, now: Instant) -> ParseErrorVerdict {
+        if self.unhealthy {
+            return ParseErrorVerdict::Suppress;
+        }
+        self.recent.push_back(now);
+        while self
+            .recent
+            .front()
+            .is_some_and(|first| now.duration_since(*first) > self.window)
+        {
+            self.recent.pop_front();
+        }
+        if self.recent.len() >= self.threshold {
+            self.unhealthy = true;
+            ParseErrorVerdict::BecameUnhealthy(self.recent.len())
+        } else {
+            ParseErrorVerdict::Report
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.recent.clear();
+        self.unhealthy = false;
+    }
+}
+
+/// Leading stdout lines tolerated before the first JSON-RPC message. Past
+/// this many the output counts as parse errors after all.
+const MAX_STDOUT_BANNER_LINES: usize = 50;
+
+/// Some CLIs print a banner or update notice to stdout before the protocol
+/// starts. Until the first JSON-RPC message arrives, lines that aren't one
+/// are treated as that banner rather than as parse errors.
+struct StdoutBanner {
+    strict: bool,
+    lines: usize,
+}
+
+impl StdoutBanner {
+    fn new() -> Self {
+        Self {
+            strict: false,
+            lines: 0,
+        }
+    }
+
+    /// Whether a stdout line (`value` when it parsed as JSON) is banner
+    /// output. The first JSON-RPC message switches to strict mode for good.
+    fn is_banner(&mut self, value: Option<&Value>) -> bool {
+        if self.strict {
+            return false;
+        }
+        let is_message = value
+            .and_then(Value::as_object)
+            .is_some_and(|object| object.contains_key("method") || object.contains_key("id"));
+        if is_message || self.lines >= MAX_STDOUT_BANNER_LINES {
+            self.strict = true;
+            return false;
+        }
+        self.lines += 1;
+        true
+    }
+}
+
+struct InitializePlan {
+    params: Value,
+    attempts: u32,
+    attempt_timeout: Duration,
+    backoff: Duration,
+}
+
+async fn initialize_app_server(
+    session: &WorkspaceSession,
+    plan: &InitializePlan,
+    startup_output: &StartupOutput,
+) -> Result<(), AppServerError> {
+    // A cold CLI (e.g. first `npx` install) can miss the first deadline, so
+    // keep waiting on the same request while the child is alive. Asking again
+    // would only get "already initialized" once the first answer arrives.
+    let request = session.send_request("initialize", plan.params.clone());
+    tokio::pin!(request);
+    let mut init_response = None;
+    for attempt in 0..plan.attempts {
+        let mut wait = plan.attempt_timeout;
+        if attempt > 0 {
+            if !session.is_alive().await {
+                break;
+            }
+            wait += initialize_backoff_delay(plan.backoff, attempt - 1);
+        }
+        if let Ok(response) = timeout(wait, &mut request).await {
+            init_response = Some(response);
+            break;
+        }
+    }
+    let Some(init_response) = init_response else {
+        let mut message = "Codex app-server did not respond to initialize. Check that `codex app-server` works in Terminal."
+            .to_string();
+        if let Some(output) = startup_output.summary() {
+            message.push_str(&format!("\n\nCLI output:\n{output}"));
+        }
+        session.kill().await;
+        return Err(AppServerError::Timeout(message));
+    };
+    init_response?;
+    session
+        .send_notification("initialized", None)
+        .await
+        .map_err(AppServerError::Io)
+}
+
+pub(crate) fn emit_connected<E: EventSink>(event_sink: &E, session: &WorkspaceSession) {
+    let workspace_id = &session.entry.id;
+    event_sink.emit_app_server_event(AppServerEvent {
+        workspace_id: workspace_id.clone(),
+        message: json!({
+            "method": "codex/connected",
+            "params": {
+                "workspaceId": workspace_id,
+                "spawnDurationMs": session.spawn_duration_ms.load(Ordering::Relaxed),
+                "initializeDurationMs": session.initialize_duration_ms.get(),
+            }
+        }),
+    });
+}
+
+/// Emits each non-empty stderr line as `codex/stderr` unless `filter` drops
+/// it. Every line still goes to `startup_output` so a failed handshake can
+/// report it.
+async fn forward_stderr<R: AsyncRead + Unpin, E: EventSink>(
+    stderr: R,
+    workspace_id: String,
+    event_sink: E,
+    startup_output: Arc<StartupOutput>,
+    filter: Arc<StderrFilter>,
+) {
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        startup_output.push(&line);
+        if !filter.should_emit(&line) {
+            continue;
+        }
+        event_sink.emit_app_server_event(AppServerEvent {
+            workspace_id: workspace_id.clone(),
+            message: json!({
+                "method": "codex/stderr",
+                "params": { "message": line },
+            }),
+        });
+    }
+}
+
+async fn initialize_timed(
+    session: &WorkspaceSession,
+    plan: &InitializePlan,
+    startup_output: &StartupOutput,
+) -> Result<(), AppServerError> {
+    let initialize_started = Instant::now();
+    initialize_app_server(session, plan, startup_output).await?;
+    session.record_initialize_duration(initialize_started.elapsed());
+    Ok(())
+}
+
+/// The workspace's `codexBin` only fills in when no CLI binary is configured.
+fn resolve_app_server_bin(entry: &WorkspaceEntry, config: &CliSpawnConfig) -> Option<String> {
+    config
+        .cli_bin
+        .clone()
+        .filter(|value| !value.trim().is_empty())
+        .or_else(|| {
+            entry
+                .codex_bin
+                .clone()
+                .filter(|value| !value.trim().is_empty())
+        })
+}
+
+fn build_app_server_command(
+    entry: &WorkspaceEntry,
+    config: &CliSpawnConfig,
+) -> Result<Command, String> {
+    let mut command = build_codex_command_with_bin(
+        resolve_app_server_bin(entry, config),
+        config.cli_args.as_deref(),
+        vec!["app-server".to_string()],
+    )?;
+    command.current_dir(&entry.path);
+    if let Some(codex_home) = &config.cli_home {
+        command.env("CODEX_HOME", codex_home);
+    }
+    command.stdin(std::process::Stdio::piped());
+    command.stdout(std::process::Stdio::piped());
+    command.stderr(std::process::Stdio::piped());
+    Ok(command)
+}
+
+/// Stands in for the user's message in previewed adapter commands.
+pub(crate) const PREVIEW_PROMPT: &str = "<prompt>";
+
+/// The command `spawn_workspace_session` would run for `entry`, built by the
+/// same code but not spawned. Adapter CLIs start once per turn, so theirs is
+/// the first turn of a new thread with `PREVIEW_PROMPT` as the message.
+pub(crate) fn build_spawn_command(
+    entry: &WorkspaceEntry,
+    config: &CliSpawnConfig,
+) -> Result<Command, String> {
+    let profile: Box<dyn CliProfile> = match config.cli_type.as_str() {
+        "claude" => Box::new(ClaudeProfile::default()),
+        "gemini" => Box::new(GeminiProfile),
+        "cursor" => Box::new(CursorProfile),
+        _ => return build_app_server_command(entry, config),
+    };
+    profile.build_turn_command(config, None, PREVIEW_PROMPT, &entry.path, &json!({}))
+}
+
+/// `{ program, args, envKeys, cwd }` for `command`. Only the names of the
+/// variables it sets are listed; their values can hold tokens.
+pub(crate) fn describe_spawn_command(command: &Command) -> Value {
+    let command = command.as_std();
+    let mut env_keys: Vec<String> = command
+        .get_envs()
+        .filter(|(_, value)| value.is_some())
+        .map(|(key, _)| key.to_string_lossy().to_string())
+        .collect();
+    env_keys.sort();
+    json!({
+        "program": command.get_program().to_string_lossy(),
+        "args": command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>(),
+        "envKeys": env_keys,
+        "cwd": command
+            .get_current_dir()
+            .map(|dir| dir.to_string_lossy().to_string()),
+    })
+}
+
 pub(crate) async fn spawn_workspace_session<E: EventSink>(
     entry: WorkspaceEntry,
     config: CliSpawnConfig,
     client_version: String,
     event_sink: E,
-) -> Result<Arc<WorkspaceSession>, String> {
+) -> Result<Arc<WorkspaceSession>, AppServerError> {
+    spawn_workspace_session_with(entry, config, client_version, event_sink, true).await
+}
+
+/// Like `spawn_workspace_session`, but returns as soon as the app-server
+/// process is running. `initialize` finishes in the background and reports
+/// `codex/connected` or `codex/connectError`, so bulk connects don't wait on
+/// each handshake in turn.
+pub(crate) async fn spawn_workspace_session_deferred<E: EventSink>(
+    entry: WorkspaceEntry,
+    config: CliSpawnConfig,
+    client_version: String,
+    event_sink: E,
+) -> Result<Arc<WorkspaceSession>, AppServerError> {
+    spawn_workspace_session_with(entry, config, client_version, event_sink, false).await
+}
+
+async fn spawn_workspace_session_with<E: EventSink>(
+    entry: WorkspaceEntry,
+    config: CliSpawnConfig,
+    client_version: String,
+    event_sink: E,
+    wait_for_initialize: bool,
+) -> Result<Arc<WorkspaceSession>, AppServerError> {
+    let profile_home = match config.cli_type.as_str() {
+        "gemini" => config.cli_home.as_deref(),
+        "claude" => config.claude_home.as_deref(),
+        "cursor" => config.cursor_home.as_deref(),
+        _ => None,
+    };
+    if let Some(home) = profile_home {
+        ensure_cli_home_dir(home).map_err(AppServerError::Io)?;
+    }
     if config.cli_type == "claude" {
         return crate::backend::claude_adapter::spawn_claude_session(
             entry, config, event_sink,
@@ -385,34 +1220,26 @@ pub(crate) async fn spawn_workspace_session<E: EventSink>(
         .await;
     }
 
-    let codex_bin = config
-        .cli_bin
-        .filter(|value| !value.trim().is_empty())
-        .or_else(|| {
-            entry
-                .codex_bin
-                .clone()
-                .filter(|value| !value.trim().is_empty())
-        });
-    let _ = check_codex_installation(codex_bin.clone()).await?;
+    let spawn_started = Instant::now();
+    let event_recorder = Arc::new(EventRecorder::default());
+    let event_sink = RecordingEventSink::new(event_sink, Arc::clone(&event_recorder));
+    let read_buffer_capacity = config.read_buffer_capacity();
+    let _ = check_codex_installation(resolve_app_server_bin(&entry, &config)).await?;
 
-    let mut command = build_codex_command_with_bin(
-        codex_bin,
-        config.cli_args.as_deref(),
-        vec!["app-server".to_string()],
-    )?;
-    command.current_dir(&entry.path);
-    if let Some(codex_home) = config.cli_home {
-        command.env("CODEX_HOME", codex_home);
-    }
-    command.stdin(std::process::Stdio::piped());
-    command.stdout(std::process::Stdio::piped());
-    command.stderr(std::process::Stdio::piped());
+    let mut command =
+        build_app_server_command(&entry, &config).map_err(AppServerError::SpawnFailed)?;
 
-    let mut child = command.spawn().map_err(|e| e.to_string())?;
-    let stdin = child.stdin.take().ok_or("missing stdin")?;
-    let stdout = child.stdout.take().ok_or("missing stdout")?;
-    let stderr = child.stderr.take().ok_or("missing stderr")?;
+    let mut child = command.spawn().map_err(|e| {
+        if e.kind() == ErrorKind::NotFound {
+            AppServerError::CliNotFound(e.to_string())
+        } else {
+            AppServerError::SpawnFailed(e.to_string())
+        }
+    })?;
+    let missing_pipe = |pipe: &str| AppServerError::SpawnFailed(format!("missing {pipe}"));
+    let stdin = child.stdin.take().ok_or_else(|| missing_pipe("stdin"))?;
+    let stdout = child.stdout.take().ok_or_else(|| missing_pipe("stdout"))?;
+    let stderr = child.stderr.take().ok_or_else(|| missing_pipe("stderr"))?;
 
     let transport = AppServerTransport {
         child: Mutex::new(child),
@@ -425,28 +1252,89 @@ pub(crate) async fn spawn_workspace_session<E: EventSink>(
         entry: entry.clone(),
         background_thread_callbacks: Arc::new(Mutex::new(HashMap::new())),
         transport: SessionTransport::AppServer(transport),
+        started_at: now_ms(),
+        collaboration_modes: Mutex::new(entry.settings.thread_collaboration_modes.clone()),
+        thread_models: Mutex::new(HashMap::new()),
+        event_recorder,
+        spawn_duration_ms: AtomicU64::new(0),
+        initialize_duration_ms: OnceLock::new(),
+        pending_approvals: Mutex::new(PendingApprovals::default()),
+        approval_rules: Mutex::new(None),
     });
+    session.record_spawn_duration(spawn_started.elapsed());
+    spawn_callback_pruner(
+        &session.background_thread_callbacks,
+        CALLBACK_PRUNE_INTERVAL,
+    );
 
+    let startup_output = Arc::new(StartupOutput::default());
+    let approval_timeout = config
+        .approval_timeout_ms
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis);
+    let approval_timeout_decision = match config.approval_timeout_decision.as_deref() {
+        Some("accept") => "accept",
+        _ => "decline",
+    };
     let session_clone = Arc::clone(&session);
     let workspace_id = entry.id.clone();
     let event_sink_clone = event_sink.clone();
+    let stdout_output = Arc::clone(&startup_output);
+    let stderr_filter = Arc::new(StderrFilter::new(
+        &config.stderr_deny_patterns,
+        &config.stderr_allow_patterns,
+    ));
+    let banner_filter = Arc::clone(&stderr_filter);
     tokio::spawn(async move {
-        let mut lines = BufReader::new(stdout).lines();
+        let mut lines = cli_line_reader(stdout, read_buffer_capacity);
+        let mut parse_errors =
+            ParseErrorMonitor::new(PARSE_ERROR_UNHEALTHY_THRESHOLD, PARSE_ERROR_WINDOW);
+        let mut banner = StdoutBanner::new();
         while let Ok(Some(line)) = lines.next_line().await {
             if line.trim().is_empty() {
                 continue;
             }
-            let value: Value = match serde_json::from_str(&line) {
-                Ok(value) => value,
-                Err(err) => {
-                    let payload = AppServerEvent {
+            let parsed = serde_json::from_str::<Value>(&line);
+            if banner.is_banner(parsed.as_ref().ok()) {
+                stdout_output.push(&line);
+                if banner_filter.should_emit(&line) {
+                    event_sink_clone.emit_app_server_event(AppServerEvent {
                         workspace_id: workspace_id.clone(),
                         message: json!({
+                            "method": "codex/stderr",
+                            "params": { "message": line, "source": "stdout" },
+                        }),
+                    });
+                }
+                continue;
+            }
+            let value: Value = match parsed {
+                Ok(value) => {
+                    parse_errors.record_success();
+                    value
+                }
+                Err(err) => {
+                    stdout_output.push(&line);
+                    let message = match parse_errors.record_error(Instant::now()) {
+                        ParseErrorVerdict::Report => json!({
                             "method": "codex/parseError",
                             "params": { "error": err.to_string(), "raw": line },
                         }),
+                        ParseErrorVerdict::BecameUnhealthy(count) => json!({
+                            "method": "cli/unhealthy",
+                            "params": {
+                                "parseErrors": count,
+                                "windowMs": PARSE_ERROR_WINDOW.as_millis() as u64,
+                                "lastError": err.to_string(),
+                                "message": "The CLI keeps printing output that isn't JSON-RPC. Reconnect the workspace.",
+                            },
+                        }),
+                        ParseErrorVerdict::Suppress => continue,
                     };
-                    event_sink_clone.emit_app_server_event(payload);
+                    event_sink_clone.emit_app_server_event(AppServerEvent {
+                        workspace_id: workspace_id.clone(),
+                        message,
+                    });
                     continue;
                 }
             };
@@ -474,6 +1362,24 @@ pub(crate) async fn spawn_workspace_session<E: EventSink>(
                         }
                     }
                     if !sent_to_background {
+                        let method = value.get("method").and_then(Value::as_str);
+                        if method.is_some_and(is_approval_request_method)
+                            && session_clone.auto_approve(&value).await
+                        {
+                            continue;
+                        }
+                        if let (Some(wait), Some(method)) = (approval_timeout, method) {
+                            if is_approval_request_method(method) {
+                                session_clone
+                                    .track_approval_request(
+                                        &value,
+                                        wait,
+                                        approval_timeout_decision,
+                                        event_sink_clone.clone(),
+                                    )
+                                    .await;
+                            }
+                        }
                         let payload = AppServerEvent {
                             workspace_id: workspace_id.clone(),
                             message: value,
@@ -486,6 +1392,7 @@ pub(crate) async fn spawn_workspace_session<E: EventSink>(
                     }
                 }
             } else if has_method {
+                session_clone.forget_timed_out_approvals(&value).await;
                 let mut sent_to_background = false;
                 if let Some(ref tid) = thread_id {
                     let callbacks = session_clone.background_thread_callbacks.lock().await;
@@ -495,6 +1402,8 @@ pub(crate) async fn spawn_workspace_session<E: EventSink>(
                     }
                 }
                 if !sent_to_background {
+                    let mut value = value;
+                    tag_unsupported_method(&mut value);
                     let payload = AppServerEvent {
                         workspace_id: workspace_id.clone(),
                         message: value,
@@ -505,60 +1414,78 @@ pub(crate) async fn spawn_workspace_session<E: EventSink>(
         }
     });
 
-    let workspace_id = entry.id.clone();
-    let event_sink_clone = event_sink.clone();
+    tokio::spawn(forward_stderr(
+        stderr,
+        entry.id.clone(),
+        event_sink.clone(),
+        Arc::clone(&startup_output),
+        stderr_filter,
+    ));
+
+    let client_name = config
+        .client_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_CLIENT_NAME);
+    let plan = InitializePlan {
+        params: build_initialize_params(client_name, &client_version),
+        attempts: config
+            .initialize_attempts
+            .unwrap_or(DEFAULT_INITIALIZE_ATTEMPTS)
+            .max(1),
+        attempt_timeout: config
+            .initialize_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_INITIALIZE_TIMEOUT),
+        backoff: config
+            .initialize_backoff_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_INITIALIZE_BACKOFF),
+    };
+
+    if wait_for_initialize {
+        initialize_timed(&session, &plan, &startup_output).await?;
+        emit_connected(&event_sink, &session);
+        return Ok(session);
+    }
+
+    let background_session = Arc::clone(&session);
     tokio::spawn(async move {
-        let mut lines = BufReader::new(stderr).lines();
-        while let Ok(Some(line)) = lines.next_line().await {
-            if line.trim().is_empty() {
-                continue;
-            }
-            let payload = AppServerEvent {
-                workspace_id: workspace_id.clone(),
+        match initialize_timed(&background_session, &plan, &startup_output).await {
+            Ok(()) => emit_connected(&event_sink, &background_session),
+            Err(error) => event_sink.emit_app_server_event(AppServerEvent {
+                workspace_id: entry.id.clone(),
                 message: json!({
-                    "method": "codex/stderr",
-                    "params": { "message": line },
+                    "method": "codex/connectError",
+                    "params": {
+                        "workspaceId": entry.id,
+                        "code": error.code(),
+                        "error": error.message()
+                    }
                 }),
-            };
-            event_sink_clone.emit_app_server_event(payload);
+            }),
         }
     });
-
-    let init_params = build_initialize_params(&client_version);
-    let init_result = timeout(
-        Duration::from_secs(15),
-        session.send_request("initialize", init_params),
-    )
-    .await;
-    let init_response = match init_result {
-        Ok(response) => response,
-        Err(_) => {
-            session.kill().await;
-            return Err(
-                "Codex app-server did not respond to initialize. Check that `codex app-server` works in Terminal."
-                    .to_string(),
-            );
-        }
-    };
-    init_response?;
-    session.send_notification("initialized", None).await?;
-
-    let payload = AppServerEvent {
-        workspace_id: entry.id.clone(),
-        message: json!({
-            "method": "codex/connected",
-            "params": { "workspaceId": entry.id.clone() }
-        }),
-    };
-    event_sink.emit_app_server_event(payload);
-
     Ok(session)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{build_initialize_params, extract_thread_id, CliSpawnConfig};
-    use serde_json::json;
+    use super::{
+        build_initialize_params, build_spawn_command, check_cli_installation,
+        check_cli_installation_with, cli_line_reader, describe_spawn_command, extract_thread_id,
+        forward_stderr, initialize_backoff_delay, prune_closed_callbacks, rpc_error_message,
+        spawn_callback_pruner, spawn_workspace_session, AppServerError, CliSpawnConfig,
+        ParseErrorMonitor, ParseErrorVerdict, StartupOutput, StderrFilter, StdoutBanner,
+        WorkspaceSession, DEFAULT_READ_BUFFER_CAPACITY, PARSE_ERROR_UNHEALTHY_THRESHOLD, PREVIEW_PROMPT,
+    };
+    use crate::backend::test_support::{test_workspace_entry, RecordedRequests, TestAdapter};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::sync::{mpsc, Mutex};
 
     #[test]
     fn extract_thread_id_reads_camel_case() {
@@ -580,7 +1507,7 @@ mod tests {
 
     #[test]
     fn build_initialize_params_enables_experimental_api() {
-        let params = build_initialize_params("1.2.3");
+        let params = build_initialize_params("Agent Monitor", "1.2.3");
         assert_eq!(
             params
                 .get("capabilities")
@@ -590,6 +1517,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn build_initialize_params_reports_client_name() {
+        let params = build_initialize_params("Agent Monitor Beta", "2.0.0");
+        assert_eq!(params["clientInfo"]["name"], "codex_monitor");
+        assert_eq!(params["clientInfo"]["title"], "Agent Monitor Beta");
+        assert_eq!(params["clientInfo"]["version"], "2.0.0");
+    }
+
+    #[tokio::test]
+    async fn check_cli_installation_reports_missing_binary() {
+        let missing = std::env::temp_dir()
+            .join(format!("missing-cli-{}", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string();
+        let error = check_cli_installation(Some(missing), "Codex")
+            .await
+            .expect_err("missing binary should fail");
+        assert_eq!(error.code(), "cli_not_found");
+        assert!(error.message().contains("Codex CLI not found"));
+    }
+
+    #[tokio::test]
+    async fn check_cli_installation_maps_runner_outcomes() {
+        use crate::shared::process_core::MockCommandRunner;
+        use std::io::ErrorKind;
+
+        let runner = MockCommandRunner::new(vec![
+            MockCommandRunner::exited(true, "gemini 0.9.1\n", ""),
+            MockCommandRunner::failed(ErrorKind::NotFound),
+            MockCommandRunner::failed(ErrorKind::TimedOut),
+            MockCommandRunner::exited(false, "", "boom"),
+        ]);
+        let bin = Some("gemini".to_string());
+
+        let version = check_cli_installation_with(&runner, bin.clone(), "Gemini")
+            .await
+            .expect("version output");
+        assert_eq!(version.as_deref(), Some("gemini 0.9.1"));
+        let mut errors = Vec::new();
+        for _ in 0..3 {
+            errors.push(
+                check_cli_installation_with(&runner, bin.clone(), "Gemini")
+                    .await
+                    .expect_err("runner failure should be reported"),
+            );
+        }
+        assert_eq!(errors[0].code(), "cli_not_found");
+        assert_eq!(errors[1].code(), "timeout");
+        assert!(errors[1].message().contains("`gemini --version`"));
+        assert_eq!(errors[2].code(), "spawn_failed");
+        assert!(errors[2].message().contains("boom"));
+        assert!(runner
+            .calls()
+            .iter()
+            .all(|call| call.last().map(String::as_str) == Some("--version")));
+    }
+
     #[test]
     fn cli_spawn_config_defaults() {
         let config = CliSpawnConfig {
@@ -597,8 +1581,809 @@ mod tests {
             cli_bin: None,
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
         assert_eq!(config.cli_type, "codex");
         assert!(config.cli_bin.is_none());
     }
+
+    #[test]
+    fn read_buffer_capacity_defaults_to_64kb() {
+        let config = CliSpawnConfig::default();
+        assert_eq!(config.read_buffer_capacity(), DEFAULT_READ_BUFFER_CAPACITY);
+        let zero = CliSpawnConfig {
+            read_buffer_capacity: Some(0),
+            ..CliSpawnConfig::default()
+        };
+        assert_eq!(zero.read_buffer_capacity(), DEFAULT_READ_BUFFER_CAPACITY);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn line_reader_reads_lines_larger_than_capacity() {
+        let config = CliSpawnConfig {
+            read_buffer_capacity: Some(1024),
+            ..CliSpawnConfig::default()
+        };
+        let mut child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg("head -c 200000 /dev/zero | tr '\\0' a; echo; echo done")
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("spawn sh");
+        let stdout = child.stdout.take().expect("stdout");
+        let mut lines = cli_line_reader(stdout, config.read_buffer_capacity());
+
+        let large = lines.next_line().await.expect("read").expect("large line");
+        assert_eq!(large.len(), 200_000);
+        assert!(large.bytes().all(|byte| byte == b'a'));
+        let tail = lines.next_line().await.expect("read").expect("tail line");
+        assert_eq!(tail, "done");
+        let _ = child.wait().await;
+    }
+
+    #[test]
+    fn initialize_backoff_doubles_each_attempt() {
+        let base = Duration::from_millis(100);
+        assert_eq!(
+            initialize_backoff_delay(base, 0),
+            Duration::from_millis(100)
+        );
+        assert_eq!(
+            initialize_backoff_delay(base, 1),
+            Duration::from_millis(200)
+        );
+        assert_eq!(
+            initialize_backoff_delay(base, 3),
+            Duration::from_millis(800)
+        );
+    }
+
+    #[test]
+    fn rpc_error_message_reads_message_and_code() {
+        let response = json!({ "id": 1, "error": { "code": -32601, "message": "no such method" } });
+        assert_eq!(
+            rpc_error_message(&response).as_deref(),
+            Some("no such method (code -32601)")
+        );
+        let bare = json!({ "id": 1, "error": "boom" });
+        assert_eq!(rpc_error_message(&bare).as_deref(), Some("\"boom\""));
+        assert_eq!(rpc_error_message(&json!({ "id": 1, "result": {} })), None);
+        assert_eq!(rpc_error_message(&json!({ "id": 1, "error": null })), None);
+    }
+
+    #[tokio::test]
+    async fn prune_closed_callbacks_drops_senders_without_receivers() {
+        let callbacks = Mutex::new(HashMap::new());
+        let (live_tx, _live_rx) = mpsc::unbounded_channel();
+        let (dead_tx, dead_rx) = mpsc::unbounded_channel();
+        drop(dead_rx);
+        callbacks.lock().await.insert("live".to_string(), live_tx);
+        callbacks.lock().await.insert("dead".to_string(), dead_tx);
+
+        assert_eq!(prune_closed_callbacks(&callbacks).await, 1);
+        let remaining = callbacks.lock().await;
+        assert!(remaining.contains_key("live"));
+        assert!(!remaining.contains_key("dead"));
+    }
+
+    #[tokio::test]
+    async fn callback_pruner_removes_closed_receiver_on_next_cycle() {
+        let callbacks = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, rx) = mpsc::unbounded_channel();
+        callbacks.lock().await.insert("thread-1".to_string(), tx);
+        spawn_callback_pruner(&callbacks, Duration::from_millis(10));
+
+        drop(rx);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(callbacks.lock().await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[derive(Clone)]
+    struct NoopEventSink;
+
+    #[cfg(unix)]
+    impl crate::backend::events::EventSink for NoopEventSink {
+        fn emit_app_server_event(&self, _event: crate::backend::events::AppServerEvent) {}
+        fn emit_terminal_output(&self, _event: crate::backend::events::TerminalOutput) {}
+        fn emit_terminal_exit(&self, _event: crate::backend::events::TerminalExit) {}
+    }
+
+    /// Writes a fake `codex` that answers `--version` and feeds each stdin
+    /// line to `read_loop`, returning the workspace dir, entry and script path.
+    #[cfg(unix)]
+    fn fake_codex_workspace(
+        name: &str,
+        read_loop: &str,
+    ) -> (std::path::PathBuf, crate::types::WorkspaceEntry, String) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("app-server-{name}-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let script = dir.join("fake-codex");
+        std::fs::write(
+            &script,
+            format!(
+                r#"#!/bin/sh
+if [ "$1" = "--version" ]; then
+  echo "fake-codex 0.0.1"
+  exit 0
+fi
+{read_loop}"#
+            ),
+        )
+        .expect("write fake cli");
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+            .expect("chmod fake cli");
+
+        let entry = crate::types::WorkspaceEntry {
+            name: name.to_string(),
+            ..test_workspace_entry(&format!("{name}-ws"), &dir.to_string_lossy())
+        };
+        (dir, entry, script.to_string_lossy().to_string())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_waits_past_the_first_window_for_a_slow_initialize() {
+        // Answers the first `initialize` late and rejects any repeat, like a
+        // real app-server that is already initialized.
+        let (dir, entry, script) = fake_codex_workspace(
+            "retry",
+            r#"count=0
+while IFS= read -r line; do
+  case "$line" in
+    *'"method":"initialize"'*)
+      count=$((count + 1))
+      id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+      if [ "$count" -eq 1 ]; then
+        sleep 0.5
+        printf '{"id":%s,"result":{}}\n' "$id"
+      else
+        printf '{"id":%s,"error":{"code":-32600,"message":"already initialized"}}\n' "$id"
+      fi
+      ;;
+  esac
+done
+"#,
+        );
+        let config = CliSpawnConfig {
+            cli_type: "codex".to_string(),
+            cli_bin: Some(script),
+            initialize_attempts: Some(3),
+            initialize_timeout_ms: Some(300),
+            initialize_backoff_ms: Some(10),
+            ..CliSpawnConfig::default()
+        };
+
+        let session =
+            super::spawn_workspace_session(entry, config, "0.0.1".to_string(), NoopEventSink)
+                .await
+                .expect("spawn should take the late answer to the first initialize");
+        session.kill().await;
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn initialize_timeout_reports_cli_output() {
+        let (dir, entry, script) = fake_codex_workspace(
+            "auth-prompt",
+            r#"echo "Not signed in. Run 'codex login' to authenticate."
+echo "error: missing credentials" >&2
+while IFS= read -r line; do
+  :
+done
+"#,
+        );
+        let config = CliSpawnConfig {
+            cli_type: "codex".to_string(),
+            cli_bin: Some(script),
+            initialize_attempts: Some(1),
+            initialize_timeout_ms: Some(300),
+            ..CliSpawnConfig::default()
+        };
+
+        let error =
+            super::spawn_workspace_session(entry, config, "0.0.1".to_string(), NoopEventSink)
+                .await
+                .err()
+                .expect("initialize should time out");
+        assert_eq!(error.code(), "timeout");
+        assert!(error
+            .message()
+            .contains("Not signed in. Run 'codex login' to authenticate."));
+        assert!(error.message().contains("error: missing credentials"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_request_returns_err_for_json_rpc_error_response() {
+        let (dir, entry, script) = fake_codex_workspace(
+            "rpc-error",
+            r#"while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  [ -z "$id" ] && continue
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"id":%s,"result":{}}\n' "$id"
+      ;;
+    *)
+      printf '{"id":%s,"error":{"code":-32600,"message":"thread not found"}}\n' "$id"
+      ;;
+  esac
+done
+"#,
+        );
+        let config = CliSpawnConfig {
+            cli_type: "codex".to_string(),
+            cli_bin: Some(script),
+            ..CliSpawnConfig::default()
+        };
+
+        let session =
+            super::spawn_workspace_session(entry, config, "0.0.1".to_string(), NoopEventSink)
+                .await
+                .expect("spawn should succeed");
+        let result = session
+            .send_request("thread/resume", json!({ "threadId": "missing" }))
+            .await;
+        assert_eq!(
+            result,
+            Err(AppServerError::Protocol(
+                "thread not found (code -32600)".to_string()
+            ))
+        );
+        session.kill().await;
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingEventSink {
+        events: Arc<std::sync::Mutex<Vec<serde_json::Value>>>,
+    }
+
+    impl crate::backend::events::EventSink for RecordingEventSink {
+        fn emit_app_server_event(&self, event: crate::backend::events::AppServerEvent) {
+            self.events.lock().unwrap().push(event.message);
+        }
+        fn emit_terminal_output(&self, _event: crate::backend::events::TerminalOutput) {}
+        fn emit_terminal_exit(&self, _event: crate::backend::events::TerminalExit) {}
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn deferred_spawn_returns_before_initialize_completes() {
+        let (dir, entry, script) = fake_codex_workspace(
+            "deferred",
+            r#"while IFS= read -r line; do
+  case "$line" in
+    *'"method":"initialize"'*)
+      sleep 1
+      id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+      printf '{"id":%s,"result":{}}\n' "$id"
+      ;;
+  esac
+done
+"#,
+        );
+        let config = CliSpawnConfig {
+            cli_type: "codex".to_string(),
+            cli_bin: Some(script),
+            ..CliSpawnConfig::default()
+        };
+        let sink = RecordingEventSink::default();
+        let connected = |sink: &RecordingEventSink| {
+            sink.events
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|event| event["method"] == "codex/connected")
+        };
+
+        let session = super::spawn_workspace_session_deferred(
+            entry,
+            config,
+            "0.0.1".to_string(),
+            sink.clone(),
+        )
+        .await
+        .expect("deferred spawn should return once the process runs");
+        assert!(!connected(&sink), "initialize should still be pending");
+        assert_eq!(session.info().await.initialize_duration_ms, None);
+
+        for _ in 0..50 {
+            if connected(&sink) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(
+            connected(&sink),
+            "initialize should complete in the background"
+        );
+        let params = sink
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|event| event["method"] == "codex/connected")
+            .map(|event| event["params"].clone())
+            .expect("connected event");
+        assert!(params["spawnDurationMs"].is_u64(), "{params}");
+        let initialize_ms = params["initializeDurationMs"]
+            .as_u64()
+            .expect("initialize duration should be reported");
+        assert!(initialize_ms >= 900, "fake initialize sleeps 1s: {params}");
+        let info = session.info().await;
+        assert_eq!(info.initialize_duration_ms, Some(initialize_ms));
+        assert_eq!(
+            info.spawn_duration_ms,
+            params["spawnDurationMs"].as_u64().unwrap()
+        );
+        session.kill().await;
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn approval_session(requests: &RecordedRequests) -> Arc<WorkspaceSession> {
+        Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry("ws-1", "/tmp"),
+            Box::new(TestAdapter::new("claude").recording(Arc::clone(requests))),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ))
+    }
+
+    fn approval_request(id: u64, thread_id: &str) -> Value {
+        json!({
+            "id": id,
+            "method": "item/commandExecution/requestApproval",
+            "params": { "threadId": thread_id, "itemId": format!("i{id}") },
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unanswered_approval_is_declined_after_timeout() {
+        let requests = RecordedRequests::default();
+        let session = approval_session(&requests);
+        let sink = RecordingEventSink::default();
+        let wait = Duration::from_millis(300);
+        for id in [7, 8] {
+            session
+                .track_approval_request(&approval_request(id, "t1"), wait, "decline", sink.clone())
+                .await;
+        }
+
+        session
+            .send_response(json!(8), json!({ "decision": "accept" }))
+            .await
+            .expect("answer in time");
+        tokio::time::sleep(wait * 2).await;
+
+        let timed_out: Vec<Value> = sink
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event["method"] == "approval/timedOut")
+            .cloned()
+            .collect();
+        assert_eq!(timed_out.len(), 1, "{timed_out:?}");
+        assert_eq!(timed_out[0]["params"]["requestId"], 7);
+        assert_eq!(timed_out[0]["params"]["threadId"], "t1");
+        assert_eq!(timed_out[0]["params"]["decision"], "decline");
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                (
+                    "response".to_string(),
+                    json!({ "id": 8, "result": { "decision": "accept" } })
+                ),
+                (
+                    "response".to_string(),
+                    json!({ "id": 7, "result": { "decision": "decline" } })
+                ),
+            ]
+        );
+
+        let late = session
+            .send_response(json!(7), json!({ "decision": "accept" }))
+            .await;
+        assert!(late.is_err(), "late answers are rejected");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timed_out_approvals_are_forgotten_when_their_turn_ends() {
+        let requests = RecordedRequests::default();
+        let session = approval_session(&requests);
+        let wait = Duration::from_millis(300);
+        for (id, thread_id) in [(7, "t1"), (9, "t2")] {
+            session
+                .track_approval_request(
+                    &approval_request(id, thread_id),
+                    wait,
+                    "decline",
+                    RecordingEventSink::default(),
+                )
+                .await;
+        }
+        tokio::time::sleep(wait * 2).await;
+
+        let turn_event = |method: &str, will_retry: bool| {
+            json!({
+                "method": method,
+                "params": { "threadId": "t1", "turnId": "turn-1", "willRetry": will_retry },
+            })
+        };
+        session
+            .forget_timed_out_approvals(&turn_event("turn/error", true))
+            .await;
+        session
+            .forget_timed_out_approvals(&turn_event("turn/completed", false))
+            .await;
+
+        let accept = json!({ "decision": "accept" });
+        assert!(session
+            .send_response(json!(7), accept.clone())
+            .await
+            .is_ok());
+        assert!(session.send_response(json!(9), accept).await.is_err());
+    }
+
+    #[test]
+    fn parse_error_monitor_flags_once_and_resets_on_success() {
+        let mut monitor = ParseErrorMonitor::new(3, Duration::from_secs(10));
+        let start = Instant::now();
+        assert_eq!(monitor.record_error(start), ParseErrorVerdict::Report);
+        assert_eq!(monitor.record_error(start), ParseErrorVerdict::Report);
+        monitor.record_success();
+        assert_eq!(monitor.record_error(start), ParseErrorVerdict::Report);
+        assert_eq!(monitor.record_error(start), ParseErrorVerdict::Report);
+        assert_eq!(
+            monitor.record_error(start),
+            ParseErrorVerdict::BecameUnhealthy(3)
+        );
+        assert_eq!(monitor.record_error(start), ParseErrorVerdict::Suppress);
+        monitor.record_success();
+
+        // Errors spread wider than the window never add up.
+        let mut later = start;
+        for _ in 0..5 {
+            later += Duration::from_secs(6);
+            assert_eq!(monitor.record_error(later), ParseErrorVerdict::Report);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn garbage_output_reports_unhealthy_once() {
+        let (dir, entry, script) = fake_codex_workspace(
+            "unhealthy",
+            r#"while IFS= read -r line; do
+  case "$line" in
+    *'"method":"initialize"'*)
+      id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+      printf '{"id":%s,"result":{}}\n' "$id"
+      i=0
+      while [ "$i" -lt 60 ]; do
+        echo "garbage line $i"
+        i=$((i + 1))
+      done
+      echo "done" >&2
+      ;;
+  esac
+done
+"#,
+        );
+        let config = CliSpawnConfig {
+            cli_type: "codex".to_string(),
+            cli_bin: Some(script),
+            ..CliSpawnConfig::default()
+        };
+        let sink = RecordingEventSink::default();
+        let session = spawn_workspace_session(entry, config, "0.0.1".to_string(), sink.clone())
+            .await
+            .expect("spawn");
+        let count = |method: &str| {
+            sink.events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| event["method"] == method)
+                .count()
+        };
+        for _ in 0..100 {
+            if count("codex/stderr") > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // stdout and stderr are read separately; let stdout catch up.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(count("cli/unhealthy"), 1);
+        assert_eq!(
+            count("codex/parseError"),
+            PARSE_ERROR_UNHEALTHY_THRESHOLD - 1
+        );
+        let unhealthy = sink
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|event| event["method"] == "cli/unhealthy")
+            .cloned()
+            .expect("unhealthy event");
+        assert_eq!(
+            unhealthy["params"]["parseErrors"],
+            PARSE_ERROR_UNHEALTHY_THRESHOLD
+        );
+        session.kill().await;
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stdout_banner_before_handshake_is_not_a_parse_error() {
+        let (dir, entry, script) = fake_codex_workspace(
+            "banner",
+            r#"echo "  ___  ___  ___  "
+echo " | __|/ _ \|   \ "
+echo "A new version is available: 9.9.9"
+echo "1.2.3"
+while IFS= read -r line; do
+  case "$line" in
+    *'"method":"initialize"'*)
+      id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+      printf '{"id":%s,"result":{}}\n' "$id"
+      echo "not json after the handshake"
+      ;;
+  esac
+done
+"#,
+        );
+        let config = CliSpawnConfig {
+            cli_type: "codex".to_string(),
+            cli_bin: Some(script),
+            ..CliSpawnConfig::default()
+        };
+        let sink = RecordingEventSink::default();
+        let session = spawn_workspace_session(entry, config, "0.0.1".to_string(), sink.clone())
+            .await
+            .expect("banner should not break initialize");
+        let events_with = |method: &str| -> Vec<Value> {
+            sink.events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| event["method"] == method)
+                .cloned()
+                .collect()
+        };
+        for _ in 0..100 {
+            if !events_with("codex/parseError").is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let banner: Vec<Value> = events_with("codex/stderr")
+            .iter()
+            .map(|event| event["params"].clone())
+            .collect();
+        assert_eq!(banner.len(), 4);
+        assert!(banner.iter().all(|params| params["source"] == "stdout"));
+        assert_eq!(banner[2]["message"], "A new version is available: 9.9.9");
+        let parse_errors = events_with("codex/parseError");
+        assert_eq!(parse_errors.len(), 1);
+        assert_eq!(
+            parse_errors[0]["params"]["raw"],
+            "not json after the handshake"
+        );
+        session.kill().await;
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn stdout_banner_ends_at_first_message_or_cap() {
+        let mut banner = StdoutBanner::new();
+        assert!(banner.is_banner(None));
+        assert!(banner.is_banner(Some(&json!(1.5))));
+        assert!(!banner.is_banner(Some(&json!({ "id": 1, "result": {} }))));
+        assert!(!banner.is_banner(None));
+
+        let mut banner = StdoutBanner::new();
+        for _ in 0..MAX_STDOUT_BANNER_LINES {
+            assert!(banner.is_banner(None));
+        }
+        assert!(!banner.is_banner(None));
+    }
+
+    fn preview_entry() -> crate::types::WorkspaceEntry {
+        test_workspace_entry("preview-ws", "/tmp/preview-ws")
+    }
+
+    fn preview_config(cli_type: &str) -> CliSpawnConfig {
+        CliSpawnConfig {
+            cli_type: cli_type.to_string(),
+            cli_bin: Some(format!("/opt/bin/{cli_type}")),
+            cli_args: Some("--debug".to_string()),
+            cli_home: Some("/secret/gemini-home".into()),
+            claude_home: Some("/secret/claude-home".into()),
+            cursor_home: Some("/secret/cursor-home".into()),
+            sandbox_policy: Some("readOnly".to_string()),
+            system_prompt: Some("Be terse.".to_string()),
+            ..CliSpawnConfig::default()
+        }
+    }
+
+    #[test]
+    fn spawn_preview_matches_adapter_turn_commands() {
+        use crate::backend::{claude_adapter, cursor_adapter, gemini_adapter};
+
+        let entry = preview_entry();
+        let cwd = entry.path.as_str();
+        let cases = [
+            (
+                "claude",
+                claude_adapter::build_claude_command(
+                    &preview_config("claude"),
+                    None,
+                    false,
+                    PREVIEW_PROMPT,
+                    cwd,
+                    None,
+                    None,
+                ),
+                "CLAUDE_CONFIG_DIR",
+            ),
+            (
+                "gemini",
+                gemini_adapter::build_gemini_command(
+                    &preview_config("gemini"),
+                    None,
+                    PREVIEW_PROMPT,
+                    cwd,
+                ),
+                "GEMINI_HOME",
+            ),
+            (
+                "cursor",
+                cursor_adapter::build_cursor_command_with_mode(
+                    &preview_config("cursor"),
+                    None,
+                    PREVIEW_PROMPT,
+                    cwd,
+                    None,
+                ),
+                cursor_adapter::CURSOR_HOME_ENV,
+            ),
+        ];
+        for (cli_type, expected, home_env) in cases {
+            let preview = describe_spawn_command(
+                &build_spawn_command(&entry, &preview_config(cli_type)).expect("build"),
+            );
+            assert_eq!(
+                preview,
+                describe_spawn_command(&expected.expect("build expected")),
+                "{cli_type}"
+            );
+            assert_eq!(preview["program"], format!("/opt/bin/{cli_type}"));
+            assert_eq!(preview["args"][0], "--debug", "{cli_type}");
+            assert_eq!(preview["cwd"], cwd);
+            let env_keys = preview["envKeys"].as_array().expect("env keys");
+            assert!(
+                env_keys.contains(&json!(home_env)),
+                "{cli_type}: {env_keys:?}"
+            );
+            assert!(!preview.to_string().contains("/secret/"), "{preview}");
+        }
+    }
+
+    #[test]
+    fn spawn_preview_applies_cli_specific_flags() {
+        let entry = preview_entry();
+        let args = |cli_type: &str| {
+            describe_spawn_command(
+                &build_spawn_command(&entry, &preview_config(cli_type)).expect("build"),
+            )["args"]
+                .clone()
+        };
+        assert_eq!(
+            args("claude"),
+            json!([
+                "--debug",
+                "-p",
+                "--output-format",
+                "stream-json",
+                "--verbose",
+                "--append-system-prompt",
+                "Be terse.",
+                PREVIEW_PROMPT
+            ])
+        );
+        assert_eq!(
+            args("gemini"),
+            json!([
+                "--debug",
+                "--output-format",
+                "stream-json",
+                "--sandbox",
+                "--approval-mode",
+                "default",
+                "-p",
+                format!("Be terse.\n\n{PREVIEW_PROMPT}")
+            ])
+        );
+        assert_eq!(
+            args("cursor"),
+            json!([
+                "--debug",
+                "-p",
+                "--output-format",
+                "stream-json",
+                PREVIEW_PROMPT
+            ])
+        );
+        assert_eq!(args("codex"), json!(["--debug", "app-server"]));
+    }
+
+    #[tokio::test]
+    async fn stderr_filter_suppresses_denied_lines_but_keeps_them_for_diagnostics() {
+        let stderr = b"npm WARN exec The following package was not found\n\
+npm http fetch GET 200 https://registry.npmjs.org/@openai%2fcodex\n\
+npm ERR! code E404\n\
+error: config.toml is invalid\n";
+        let sink = RecordingEventSink::default();
+        let startup_output = Arc::new(StartupOutput::default());
+        let filter = StderrFilter::new(
+            &["^npm ".to_string(), "(".to_string()],
+            &["^npm ERR!".to_string()],
+        );
+
+        forward_stderr(
+            &stderr[..],
+            "ws-1".to_string(),
+            sink.clone(),
+            Arc::clone(&startup_output),
+            Arc::new(filter),
+        )
+        .await;
+
+        let emitted: Vec<_> = sink
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| event["params"]["message"].clone())
+            .collect();
+        assert_eq!(
+            emitted,
+            vec![
+                json!("npm ERR! code E404"),
+                json!("error: config.toml is invalid")
+            ]
+        );
+        let diagnostics = startup_output.summary().expect("diagnostics");
+        assert_eq!(diagnostics.lines().count(), 4, "{diagnostics}");
+        assert!(
+            diagnostics.contains("npm http fetch GET 200"),
+            "{diagnostics}"
+        );
+    }
+
+    #[test]
+    fn stderr_filter_without_patterns_emits_everything() {
+        let filter = StderrFilter::default();
+        assert!(filter.should_emit("npm WARN exec"));
+        assert!(StderrFilter::new(&[], &["^npm".to_string()]).should_emit("anything"));
+    }
 }
@@ -1,12 +1,24 @@
 use serde_json::{json, Value};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
-use crate::backend::adapter_base::{build_adapter_command, spawn_adapter_session, CliProfile};
+use crate::backend::adapter_base::{
+    build_adapter_command, is_turn_ending_event, spawn_adapter_session, CliProfile,
+    CompactionTokens,
+};
 use crate::backend::app_server::{CliSpawnConfig, WorkspaceSession};
+use crate::backend::errors::AppServerError;
 use crate::backend::events::EventSink;
+use crate::codex::home::resolve_home_dir;
+use crate::shared::sandbox_setup_core::is_gondolin_server;
 use crate::types::WorkspaceEntry;
 
-pub(crate) struct ClaudeProfile;
+#[derive(Default)]
+pub(crate) struct ClaudeProfile {
+    /// Content-block state for each turn still streaming, keyed by turn id.
+    streams: Mutex<HashMap<String, ClaudeStreamState>>,
+}
 
 impl CliProfile for ClaudeProfile {
     fn build_turn_command(
@@ -18,11 +30,25 @@ impl CliProfile for ClaudeProfile {
         params: &Value,
     ) -> Result<tokio::process::Command, String> {
         let effort = params.get("effort").and_then(|v| v.as_str());
-        build_claude_command(config, session_id, prompt, cwd, effort)
+        let model = params.get("model").and_then(|v| v.as_str());
+        let fork_session = params.get("forkSession").and_then(Value::as_bool) == Some(true);
+        build_claude_command(config, session_id, fork_session, prompt, cwd, effort, model)
     }
 
     fn parse_stream_line(&self, line: &str, thread_id: &str, turn_id: &str) -> Option<Value> {
-        parse_stream_json_line(line, thread_id, turn_id)
+        let mut streams = self.streams.lock().unwrap_or_else(|err| err.into_inner());
+        let state = streams.entry(turn_id.to_string()).or_default();
+        let event = parse_stream_json_line_with_state(line, thread_id, turn_id, state);
+        let turn_finished = event.as_ref().is_some_and(is_turn_ending_event);
+        if turn_finished {
+            streams.remove(turn_id);
+        }
+        event
+    }
+
+    fn finish_turn(&self, turn_id: &str) {
+        let mut streams = self.streams.lock().unwrap_or_else(|err| err.into_inner());
+        streams.remove(turn_id);
     }
 
     fn extract_session_id(&self, line: &str) -> Option<String> {
@@ -71,14 +97,241 @@ impl CliProfile for ClaudeProfile {
     fn provider_name(&self) -> &str {
         "claude"
     }
+
+    fn app_list(&self) -> Value {
+        claude_app_list()
+    }
+
+    fn mcp_server_list(&self, config: &CliSpawnConfig, cwd: &str) -> Value {
+        let user_config = claude_user_config_path(config.claude_home.as_deref());
+        let servers = claude_mcp_servers(Path::new(cwd), user_config.as_deref());
+        json!({ "result": { "data": servers, "nextCursor": null } })
+    }
+
+    fn skills_list(&self, config: &CliSpawnConfig, cwd: &str) -> Value {
+        let user_dir = config
+            .claude_home
+            .clone()
+            .or_else(|| resolve_home_dir().map(|home| home.join(".claude")));
+        let skills = claude_skills(Path::new(cwd), user_dir.as_deref());
+        json!({ "result": { "skills": skills } })
+    }
+
+    fn compact_prompt(&self) -> Option<&str> {
+        Some("/compact")
+    }
+
+    fn parse_compaction_line(&self, line: &str) -> Option<CompactionTokens> {
+        parse_compact_boundary(line)
+    }
+}
+
+/// Reads the token counts from the `compact_boundary` system event Claude
+/// prints when `/compact` runs. Current CLIs report only `pre_tokens`.
+fn parse_compact_boundary(line: &str) -> Option<CompactionTokens> {
+    let event = parse_json_object_line(line)?;
+    if event.get("type")?.as_str()? != "system"
+        || event.get("subtype")?.as_str()? != "compact_boundary"
+    {
+        return None;
+    }
+    let metadata = event.get("compact_metadata");
+    let tokens = |key: &str| metadata?.get(key)?.as_u64();
+    Some(CompactionTokens {
+        before: tokens("pre_tokens"),
+        after: tokens("post_tokens"),
+    })
+}
+
+/// Tools Claude ships with that behave like apps: always available, nothing
+/// to install.
+fn claude_app_list() -> Value {
+    let app = |id: &str, name: &str, description: &str| {
+        json!({
+            "id": id,
+            "name": name,
+            "description": description,
+            "isAccessible": true,
+            "installUrl": null,
+            "distributionChannel": "builtin"
+        })
+    };
+    json!({
+        "result": {
+            "data": [
+                app("claude-web-search", "Web Search", "Search the web during a turn."),
+                app("claude-web-fetch", "Web Fetch", "Read a web page by URL."),
+                app(
+                    "claude-mcp",
+                    "MCP servers",
+                    "Tools from servers registered with `claude mcp add`."
+                )
+            ],
+            "nextCursor": null
+        }
+    })
+}
+
+/// Where Claude keeps user- and local-scoped settings: `.claude.json` in the
+/// profile directory when one is configured, otherwise in the home directory.
+fn claude_user_config_path(claude_home: Option<&Path>) -> Option<PathBuf> {
+    match claude_home {
+        Some(dir) => Some(dir.join(".claude.json")),
+        None => resolve_home_dir().map(|home| home.join(".claude.json")),
+    }
+}
+
+fn read_json_file(path: &Path) -> Option<Value> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// MCP servers registered with `claude mcp add`, merged the way Claude
+/// resolves them: `local` (this project in the user config) over `project`
+/// (`.mcp.json` in the workspace) over `user`. Gondolin, which sandbox setup
+/// registers, is marked `managed`.
+pub(crate) fn claude_mcp_servers(workspace_path: &Path, user_config: Option<&Path>) -> Vec<Value> {
+    let user_config = user_config.and_then(read_json_file);
+    let project_config = read_json_file(&workspace_path.join(".mcp.json"));
+    let local_servers = user_config.as_ref().and_then(|config| {
+        let projects = config.get("projects")?.as_object()?;
+        let project = projects.get(workspace_path.to_string_lossy().as_ref())?;
+        project.get("mcpServers")
+    });
+    let servers_in = |config: Option<&Value>| config.and_then(|config| config.get("mcpServers"));
+    let scopes = [
+        ("local", local_servers),
+        ("project", servers_in(project_config.as_ref())),
+        ("user", servers_in(user_config.as_ref())),
+    ];
+
+    let mut seen = HashSet::new();
+    let mut servers = Vec::new();
+    for (scope, entries) in scopes {
+        let Some(entries) = entries.and_then(Value::as_object) else {
+            continue;
+        };
+        for (name, server) in entries {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let default_transport = if server.get("url").is_some() {
+                "http"
+            } else {
+                "stdio"
+            };
+            let transport = server
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or(default_transport);
+            servers.push(json!({
+                "name": name,
+                "scope": scope,
+                "type": transport,
+                "command": server.get("command"),
+                "args": server.get("args").cloned().unwrap_or_else(|| json!([])),
+                "url": server.get("url"),
+                "managed": is_gondolin_server(name, server),
+                "authStatus": null,
+                "tools": {},
+            }));
+        }
+    }
+    servers.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    servers
+}
+
+/// `name` and `description` from a `SKILL.md` YAML frontmatter block.
+fn skill_frontmatter(content: &str) -> (Option<String>, Option<String>) {
+    let mut lines = content.lines();
+    if lines.next().map(str::trim) != Some("---") {
+        return (None, None);
+    }
+    let (mut name, mut description) = (None, None);
+    for line in lines.take_while(|line| line.trim() != "---") {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        if value.is_empty() {
+            continue;
+        }
+        match key.trim() {
+            "name" => name = Some(value.to_string()),
+            "description" => description = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    (name, description)
+}
+
+/// Skills Claude loads from `<dir>/skills/<name>/SKILL.md`: the workspace's
+/// `.claude` dir (`project`) shadows the profile dir (`user`). Names come
+/// from the frontmatter, falling back to the skill's directory name.
+pub(crate) fn claude_skills(workspace_path: &Path, user_dir: Option<&Path>) -> Vec<Value> {
+    let project_dir = workspace_path.join(".claude");
+    let scopes = [("project", Some(project_dir.as_path())), ("user", user_dir)];
+
+    let mut seen = HashSet::new();
+    let mut skills = Vec::new();
+    for (scope, dir) in scopes {
+        let Some(entries) = dir.and_then(|dir| std::fs::read_dir(dir.join("skills")).ok()) else {
+            continue;
+        };
+        let mut skill_files: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path().join("SKILL.md"))
+            .filter(|path| path.is_file())
+            .collect();
+        skill_files.sort();
+        for path in skill_files {
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let (name, description) = skill_frontmatter(&content);
+            let Some(name) = name.or_else(|| {
+                path.parent()
+                    .and_then(Path::file_name)
+                    .map(|dir| dir.to_string_lossy().to_string())
+            }) else {
+                continue;
+            };
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            skills.push(json!({
+                "name": name,
+                "description": description,
+                "path": path.to_string_lossy(),
+                "scope": scope,
+            }));
+        }
+    }
+    skills.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    skills
+}
+
+/// Env var Claude reads its profile directory from.
+pub(crate) const CLAUDE_HOME_ENV: &str = "CLAUDE_CONFIG_DIR";
+
+/// Prompts longer than this are piped over stdin instead of passed as an
+/// argument, staying well under the OS argument length limits. On Windows an
+/// npm-installed `claude.cmd` runs behind `cmd /C`, which caps the whole
+/// command line at 8191 characters, so every prompt goes over stdin there.
+pub(crate) const CLAUDE_STDIN_PROMPT_BYTES: usize = if cfg!(windows) { 0 } else { 16 * 1024 };
+
+pub(crate) fn claude_prompt_uses_stdin(prompt: &str) -> bool {
+    prompt.len() > CLAUDE_STDIN_PROMPT_BYTES
 }
 
 pub(crate) fn build_claude_command(
     config: &CliSpawnConfig,
     session_id: Option<&str>,
+    fork_session: bool,
     prompt: &str,
     cwd: &str,
     effort: Option<&str>,
+    model: Option<&str>,
 ) -> Result<tokio::process::Command, String> {
     let mut args = vec![
         "-p".to_string(),
@@ -89,11 +342,32 @@ pub(crate) fn build_claude_command(
     if let Some(sid) = session_id {
         args.push("--resume".to_string());
         args.push(sid.to_string());
+        // A duplicated thread branches off the source's session instead of
+        // appending to it.
+        if fork_session {
+            args.push("--fork-session".to_string());
+        }
+    }
+    if let Some(model) = model.map(str::trim).filter(|model| !model.is_empty()) {
+        args.push("--model".to_string());
+        args.push(model.to_string());
+    }
+    if let Some(system_prompt) = config.system_prompt() {
+        args.push("--append-system-prompt".to_string());
+        args.push(system_prompt.to_string());
+    }
+    // With no positional prompt, `claude -p` reads the prompt from stdin;
+    // piping stdin tells the adapter to write it there.
+    let prompt_via_stdin = claude_prompt_uses_stdin(prompt);
+    if !prompt_via_stdin {
+        args.push(prompt.to_string());
     }
-    args.push(prompt.to_string());
 
-    let home_env = config.cli_home.as_ref().map(|h| ("CLAUDE_HOME", h));
+    let home_env = config.claude_home.as_ref().map(|h| (CLAUDE_HOME_ENV, h));
     let mut command = build_adapter_command(config, args, cwd, home_env)?;
+    if prompt_via_stdin {
+        command.stdin(std::process::Stdio::piped());
+    }
 
     if let Some(effort_value) = effort {
         if effort_value == "max" {
@@ -107,25 +381,113 @@ pub(crate) fn build_claude_command(
     Ok(command)
 }
 
+enum StreamBlock {
+    Text {
+        item_id: String,
+        text: String,
+    },
+    /// `input_json` collects the `input_json_delta` fragments; they are only
+    /// valid JSON together, so it is parsed once the block stops.
+    ToolUse {
+        tool_id: String,
+        input_json: String,
+    },
+}
+
+/// What a turn's stream has opened so far, so `content_block_stop` (which only
+/// carries an index) can finalize the right item.
+#[derive(Default)]
+pub(crate) struct ClaudeStreamState {
+    blocks: HashMap<u64, StreamBlock>,
+    completed_tools: HashSet<String>,
+}
+
 pub(crate) fn parse_stream_json_line(
     line: &str,
     thread_id: &str,
     turn_id: &str,
 ) -> Option<Value> {
-    let event: Value = serde_json::from_str(line).ok()?;
+    parse_stream_json_line_with_state(line, thread_id, turn_id, &mut ClaudeStreamState::default())
+}
+
+fn tool_completed_event(
+    thread_id: &str,
+    turn_id: &str,
+    tool_id: &str,
+    input: Option<Value>,
+) -> Value {
+    let mut item = json!({
+        "id": tool_id,
+        "type": "tool_use"
+    });
+    if let Some(input) = input {
+        item["input"] = input;
+    }
+    json!({
+        "method": "item/completed",
+        "params": {
+            "threadId": thread_id,
+            "turnId": turn_id,
+            "item": item
+        }
+    })
+}
+
+/// Parses a stream-json line, tolerating trailing garbage after the object
+/// (the CLI occasionally emits lines like `{...}}`). Only the leading JSON
+/// object is used; a line that doesn't start with one yields `None`.
+fn parse_json_object_line(line: &str) -> Option<Value> {
+    if let Ok(event) = serde_json::from_str::<Value>(line) {
+        return Some(event);
+    }
+    let event = serde_json::Deserializer::from_str(line)
+        .into_iter::<Value>()
+        .next()?
+        .ok()?;
+    event.is_object().then_some(event)
+}
+
+pub(crate) fn parse_stream_json_line_with_state(
+    line: &str,
+    thread_id: &str,
+    turn_id: &str,
+    state: &mut ClaudeStreamState,
+) -> Option<Value> {
+    let event = parse_json_object_line(line)?;
+    let block_index = event.get("index").and_then(Value::as_u64);
     let event_type = event.get("type")?.as_str()?;
 
-    let msg_item_id = format!("msg_{turn_id}");
+    // Each text block gets its own item so separate blocks render as separate
+    // messages; events without an index keep the per-turn id.
+    let msg_item_id = match block_index {
+        Some(index) => format!("msg_{turn_id}_{index}"),
+        None => format!("msg_{turn_id}"),
+    };
 
     match event_type {
         "system" => {
             let subtype = event.get("subtype").and_then(|s| s.as_str()).unwrap_or("");
             if subtype == "init" {
+                let tools: Vec<&str> = event
+                    .get("tools")
+                    .and_then(Value::as_array)
+                    .map(|tools| {
+                        tools
+                            .iter()
+                            .filter_map(|tool| {
+                                tool.as_str()
+                                    .or_else(|| tool.get("name").and_then(Value::as_str))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
                 Some(json!({
                     "method": "turn/started",
                     "params": {
                         "threadId": thread_id,
-                        "turnId": turn_id
+                        "turnId": turn_id,
+                        "model": event.get("model").and_then(Value::as_str),
+                        "tools": tools
                     }
                 }))
             } else {
@@ -138,6 +500,12 @@ pub(crate) fn parse_stream_json_line(
             match delta_type {
                 "text_delta" => {
                     let text = delta.get("text")?.as_str()?;
+                    if let Some(StreamBlock::Text {
+                        text: block_text, ..
+                    }) = block_index.and_then(|index| state.blocks.get_mut(&index))
+                    {
+                        block_text.push_str(text);
+                    }
                     Some(json!({
                         "method": "item/agentMessage/delta",
                         "params": {
@@ -148,16 +516,55 @@ pub(crate) fn parse_stream_json_line(
                         }
                     }))
                 }
-                "input_json_delta" => None,
+                "input_json_delta" => {
+                    let partial_json = delta.get("partial_json")?.as_str()?;
+                    let Some(StreamBlock::ToolUse {
+                        tool_id,
+                        input_json,
+                    }) = block_index.and_then(|index| state.blocks.get_mut(&index))
+                    else {
+                        return None;
+                    };
+                    input_json.push_str(partial_json);
+                    Some(json!({
+                        "method": "item/toolInput/delta",
+                        "params": {
+                            "threadId": thread_id,
+                            "turnId": turn_id,
+                            "itemId": tool_id,
+                            "delta": partial_json
+                        }
+                    }))
+                }
                 _ => None,
             }
         }
         "content_block_start" => {
             let block = event.get("content_block")?;
             let block_type = block.get("type")?.as_str()?;
-            if block_type == "tool_use" {
+            if block_type == "text" {
+                if let Some(index) = block_index {
+                    state.blocks.insert(
+                        index,
+                        StreamBlock::Text {
+                            item_id: msg_item_id,
+                            text: String::new(),
+                        },
+                    );
+                }
+                None
+            } else if block_type == "tool_use" {
                 let tool_name = block.get("name").and_then(|n| n.as_str()).unwrap_or("tool");
                 let tool_id = block.get("id").and_then(|i| i.as_str()).unwrap_or("");
+                if let Some(index) = block_index {
+                    state.blocks.insert(
+                        index,
+                        StreamBlock::ToolUse {
+                            tool_id: tool_id.to_string(),
+                            input_json: String::new(),
+                        },
+                    );
+                }
                 Some(json!({
                     "method": "item/started",
                     "params": {
@@ -174,18 +581,63 @@ pub(crate) fn parse_stream_json_line(
                 None
             }
         }
-        "tool_result" => {
-            let tool_use_id = event.get("tool_use_id").and_then(|i| i.as_str()).unwrap_or("");
-            Some(json!({
+        "content_block_stop" => match state.blocks.remove(&block_index?)? {
+            StreamBlock::Text { item_id, text } => Some(json!({
                 "method": "item/completed",
                 "params": {
                     "threadId": thread_id,
                     "turnId": turn_id,
                     "item": {
-                        "id": tool_use_id,
-                        "type": "tool_use"
+                        "id": item_id,
+                        "type": "agentMessage",
+                        "text": text
                     }
                 }
+            })),
+            StreamBlock::ToolUse {
+                tool_id,
+                input_json,
+            } => {
+                // Partial input that never became valid JSON is dropped.
+                let input = serde_json::from_str::<Value>(&input_json).ok();
+                state
+                    .completed_tools
+                    .insert(tool_id.clone())
+                    .then(|| tool_completed_event(thread_id, turn_id, &tool_id, input))
+            }
+        },
+        "tool_result" => {
+            let tool_use_id = event.get("tool_use_id").and_then(|i| i.as_str()).unwrap_or("");
+            // Already finalized if its content block stopped first.
+            if !state.completed_tools.insert(tool_use_id.to_string()) {
+                return None;
+            }
+            Some(tool_completed_event(thread_id, turn_id, tool_use_id, None))
+        }
+        // Anthropic API failures (overloaded, invalid_request, ...) end the
+        // turn; surface them right away instead of waiting for the process
+        // to exit.
+        "error" => {
+            let error = event.get("error");
+            let error_type = error
+                .and_then(|error| error.get("type"))
+                .and_then(Value::as_str)
+                .unwrap_or("error");
+            let message = error
+                .and_then(|error| error.get("message"))
+                .and_then(Value::as_str)
+                .unwrap_or(error_type);
+            Some(json!({
+                "method": "turn/error",
+                "params": {
+                    "threadId": thread_id,
+                    "turnId": turn_id,
+                    "error": {
+                        "type": error_type,
+                        "message": message
+                    },
+                    "willRetry": false
+                }
             }))
         }
         "result" => {
@@ -194,8 +646,12 @@ pub(crate) fn parse_stream_json_line(
                 "params": {
                     "threadId": thread_id,
                     "turnId": turn_id,
-                    "costUsd": event.get("cost_usd"),
-                    "durationMs": event.get("duration_ms")
+                    "costUsd": event.get("total_cost_usd").or_else(|| event.get("cost_usd")),
+                    "durationMs": event.get("duration_ms"),
+                    "usage": {
+                        "inputTokens": event.get("usage").and_then(|u| u.get("input_tokens")),
+                        "outputTokens": event.get("usage").and_then(|u| u.get("output_tokens"))
+                    }
                 }
             }))
         }
@@ -204,7 +660,7 @@ pub(crate) fn parse_stream_json_line(
 }
 
 fn extract_session_id_from_line(line: &str) -> Option<String> {
-    let event: Value = serde_json::from_str(line).ok()?;
+    let event = parse_json_object_line(line)?;
     if event.get("type")?.as_str()? != "system" {
         return None;
     }
@@ -221,14 +677,23 @@ pub(crate) async fn spawn_claude_session<E: EventSink>(
     entry: WorkspaceEntry,
     config: CliSpawnConfig,
     event_sink: E,
-) -> Result<Arc<WorkspaceSession>, String> {
-    spawn_adapter_session(ClaudeProfile, "Claude", entry, config, event_sink).await
+) -> Result<Arc<WorkspaceSession>, AppServerError> {
+    spawn_adapter_session(
+        ClaudeProfile::default(),
+        "Claude",
+        entry,
+        config,
+        event_sink,
+    )
+    .await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::backend::adapter_base::{GenericAdapterSession, ThreadStore};
+    use crate::backend::adapter_base::{
+        assert_valid_turn_sequence, GenericAdapterSession, ThreadStore,
+    };
     use crate::backend::app_server::CliAdapter;
     use crate::backend::events::AppServerEvent;
     use std::collections::HashMap;
@@ -239,6 +704,12 @@ mod tests {
     }
 
     fn test_adapter() -> GenericAdapterSession<ClaudeProfile> {
+        test_adapter_with_emitter(test_emitter())
+    }
+
+    fn test_adapter_with_emitter(
+        emitter: Arc<dyn Fn(AppServerEvent) + Send + Sync>,
+    ) -> GenericAdapterSession<ClaudeProfile> {
         let entry = crate::types::WorkspaceEntry {
             id: "test-ws".to_string(),
             name: "Test".to_string(),
@@ -254,12 +725,13 @@ mod tests {
             cli_bin: None,
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
         GenericAdapterSession::new(
-            ClaudeProfile,
+            ClaudeProfile::default(),
             &entry,
             config,
-            test_emitter(),
+            emitter,
             Arc::new(Mutex::new(HashMap::new())),
         )
     }
@@ -271,8 +743,9 @@ mod tests {
             cli_bin: Some("claude".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
-        let result = build_claude_command(&config, None, "hello world", "/tmp", None);
+        let result = build_claude_command(&config, None, false, "hello world", "/tmp", None, None);
         assert!(result.is_ok());
     }
 
@@ -283,11 +756,53 @@ mod tests {
             cli_bin: Some("claude".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
-        let result = build_claude_command(&config, Some("session-123"), "hello", "/tmp", None);
+        let result = build_claude_command(
+            &config,
+            Some("session-123"),
+            false,
+            "hello",
+            "/tmp",
+            None,
+            None,
+        );
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn build_claude_command_appends_workspace_system_prompt() {
+        let args_for = |system_prompt: Option<&str>| {
+            let config = CliSpawnConfig {
+                cli_type: "claude".to_string(),
+                cli_bin: Some("claude".to_string()),
+                system_prompt: system_prompt.map(str::to_string),
+                ..CliSpawnConfig::default()
+            };
+            let command =
+                build_claude_command(&config, None, false, "hello", "/tmp", None, None).unwrap();
+            command
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let args = args_for(Some("Reply tersely."));
+        let flag = args
+            .iter()
+            .position(|arg| arg == "--append-system-prompt")
+            .expect("system prompt flag");
+        assert_eq!(args[flag + 1], "Reply tersely.");
+        if !claude_prompt_uses_stdin("hello") {
+            assert_eq!(args.last().map(String::as_str), Some("hello"));
+        }
+
+        for blank in [None, Some(""), Some("  \n")] {
+            assert!(!args_for(blank).contains(&"--append-system-prompt".to_string()));
+        }
+    }
+
     #[test]
     fn build_claude_command_with_effort() {
         let config = CliSpawnConfig {
@@ -295,11 +810,90 @@ mod tests {
             cli_bin: Some("claude".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
-        let result = build_claude_command(&config, None, "hello", "/tmp", Some("low"));
+        let result = build_claude_command(&config, None, false, "hello", "/tmp", Some("low"), None);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn build_claude_command_exports_claude_home() {
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            cli_bin: Some("claude".to_string()),
+            claude_home: Some(std::path::PathBuf::from("/tmp/claude-profile")),
+            ..CliSpawnConfig::default()
+        };
+        let command =
+            build_claude_command(&config, None, false, "hello", "/tmp", None, None).unwrap();
+        let home = command
+            .as_std()
+            .get_envs()
+            .find(|(key, _)| *key == CLAUDE_HOME_ENV)
+            .and_then(|(_, value)| value);
+        assert_eq!(home, Some(std::ffi::OsStr::new("/tmp/claude-profile")));
+
+        let config = CliSpawnConfig {
+            claude_home: None,
+            ..config
+        };
+        let command =
+            build_claude_command(&config, None, false, "hello", "/tmp", None, None).unwrap();
+        assert!(command
+            .as_std()
+            .get_envs()
+            .all(|(key, _)| key != CLAUDE_HOME_ENV));
+    }
+
+    #[test]
+    fn build_claude_command_passes_model() {
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            cli_bin: Some("claude".to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let command = build_claude_command(
+            &config,
+            None,
+            false,
+            "hello",
+            "/tmp",
+            None,
+            Some("claude-opus-4"),
+        )
+        .unwrap();
+        let args: Vec<_> = command.as_std().get_args().collect();
+        let position = args.iter().position(|arg| *arg == "--model").unwrap();
+        assert_eq!(args[position + 1], "claude-opus-4");
+
+        let command =
+            build_claude_command(&config, None, false, "hello", "/tmp", None, Some(" ")).unwrap();
+        assert!(command.as_std().get_args().all(|arg| arg != "--model"));
+    }
+
+    #[test]
+    fn long_prompt_is_piped_over_stdin() {
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            cli_bin: Some("claude".to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let command =
+            build_claude_command(&config, None, false, "hello", "/tmp", None, None).unwrap();
+        let passed_as_arg = command.as_std().get_args().last().unwrap() == "hello";
+        assert_eq!(passed_as_arg, !cfg!(windows));
+        assert_eq!(claude_prompt_uses_stdin("hello"), cfg!(windows));
+
+        let long_prompt = "x".repeat(CLAUDE_STDIN_PROMPT_BYTES + 1);
+        let command =
+            build_claude_command(&config, None, false, &long_prompt, "/tmp", None, None).unwrap();
+        assert!(command
+            .as_std()
+            .get_args()
+            .all(|arg| arg != long_prompt.as_str()));
+        assert!(claude_prompt_uses_stdin(&long_prompt));
+    }
+
     #[test]
     fn build_claude_command_with_max_effort() {
         let config = CliSpawnConfig {
@@ -307,8 +901,9 @@ mod tests {
             cli_bin: Some("claude".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
-        let result = build_claude_command(&config, None, "hello", "/tmp", Some("max"));
+        let result = build_claude_command(&config, None, false, "hello", "/tmp", Some("max"), None);
         assert!(result.is_ok());
     }
 
@@ -324,6 +919,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_stream_json_init_carries_model_and_tools() {
+        let line = r#"{"type":"system","subtype":"init","session_id":"s1","tools":["Read","Bash",{"name":"Edit"}],"model":"claude-opus-4"}"#;
+        let event = parse_stream_json_line(line, "t1", "turn1").unwrap();
+        assert_eq!(event["params"]["model"], "claude-opus-4");
+        assert_eq!(event["params"]["tools"], json!(["Read", "Bash", "Edit"]));
+
+        let line = r#"{"type":"system","subtype":"init","session_id":"s1"}"#;
+        let event = parse_stream_json_line(line, "t1", "turn1").unwrap();
+        assert!(event["params"]["model"].is_null());
+        assert_eq!(event["params"]["tools"], json!([]));
+    }
+
     #[test]
     fn parse_stream_json_text_delta_has_item_id() {
         let line = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"hello"}}"#;
@@ -359,6 +967,68 @@ mod tests {
         assert!(parse_stream_json_line(line, "t1", "turn1").is_none());
     }
 
+    #[test]
+    fn tool_input_deltas_assemble_into_completed_input() {
+        let profile = ClaudeProfile::default();
+        let lines = [
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","name":"Read","id":"tool-1"}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\": \"src/"}}"#,
+            r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"main.rs\", \"limit\": 20}"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+        ];
+        let events: Vec<Value> = lines
+            .iter()
+            .filter_map(|line| profile.parse_stream_line(line, "t1", "turn1"))
+            .collect();
+
+        let deltas: Vec<&Value> = events
+            .iter()
+            .filter(|event| event["method"] == "item/toolInput/delta")
+            .map(|event| &event["params"])
+            .collect();
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0]["itemId"], "tool-1");
+        assert_eq!(deltas[0]["delta"], "{\"path\": \"src/");
+        let completed = events
+            .iter()
+            .find(|event| event["method"] == "item/completed")
+            .expect("tool completes on block stop");
+        assert_eq!(
+            completed["params"]["item"]["input"],
+            json!({ "path": "src/main.rs", "limit": 20 })
+        );
+    }
+
+    #[test]
+    fn finished_turn_drops_its_stream_state() {
+        let profile = ClaudeProfile::default();
+        // The process dies mid-block, so no turn-ending event is parsed.
+        let line =
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#;
+        profile.parse_stream_line(line, "t1", "turn1");
+        assert_eq!(profile.streams.lock().unwrap().len(), 1);
+
+        profile.finish_turn("turn1");
+        assert!(profile.streams.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_tool_input_completes_without_input() {
+        let profile = ClaudeProfile::default();
+        let lines = [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","name":"Bash","id":"tool-2"}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{\"command\": \"ls"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+        ];
+        let completed = lines
+            .iter()
+            .filter_map(|line| profile.parse_stream_line(line, "t1", "turn1"))
+            .find(|event| event["method"] == "item/completed")
+            .expect("tool still completes");
+        assert_eq!(completed["params"]["item"]["id"], "tool-2");
+        assert!(completed["params"]["item"].get("input").is_none());
+    }
+
     #[test]
     fn parse_stream_json_tool_result_emits_item_completed() {
         let line = r#"{"type":"tool_result","tool_use_id":"tool-1","content":"done"}"#;
@@ -371,11 +1041,125 @@ mod tests {
         assert_eq!(item.get("id").and_then(|i| i.as_str()), Some("tool-1"));
     }
 
+    #[test]
+    fn content_block_stop_completes_tool_without_result() {
+        let profile = ClaudeProfile::default();
+        let lines = [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Reading"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","name":"Read","id":"tool-9"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+            r#"{"type":"tool_result","tool_use_id":"tool-9","content":"done"}"#,
+        ];
+        let events: Vec<Value> = lines
+            .iter()
+            .filter_map(|line| profile.parse_stream_line(line, "t1", "turn1"))
+            .collect();
+        let completed: Vec<&Value> = events
+            .iter()
+            .filter(|event| event["method"] == "item/completed")
+            .map(|event| &event["params"]["item"])
+            .collect();
+        assert_eq!(completed.len(), 2, "tool_result after stop is not repeated");
+        assert_eq!(completed[0]["type"], "agentMessage");
+        assert_eq!(completed[0]["id"], "msg_turn1_0");
+        assert_eq!(completed[0]["text"], "Reading");
+        assert_eq!(completed[1]["type"], "tool_use");
+        assert_eq!(completed[1]["id"], "tool-9");
+
+        let stray_stop = r#"{"type":"content_block_stop","index":7}"#;
+        assert!(profile
+            .parse_stream_line(stray_stop, "t1", "turn1")
+            .is_none());
+    }
+
+    #[test]
+    fn separate_text_blocks_get_distinct_item_ids() {
+        let profile = ClaudeProfile::default();
+        let lines = [
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"First"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"content_block_start","index":2,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":2,"delta":{"type":"text_delta","text":"Second"}}"#,
+            r#"{"type":"content_block_stop","index":2}"#,
+        ];
+        let events: Vec<Value> = lines
+            .iter()
+            .filter_map(|line| profile.parse_stream_line(line, "t1", "turn1"))
+            .collect();
+        let deltas: Vec<&Value> = events
+            .iter()
+            .filter(|event| event["method"] == "item/agentMessage/delta")
+            .map(|event| &event["params"]["itemId"])
+            .collect();
+        assert_eq!(deltas, vec!["msg_turn1_0", "msg_turn1_2"]);
+        let completed: Vec<(&Value, &Value)> = events
+            .iter()
+            .filter(|event| event["method"] == "item/completed")
+            .map(|event| &event["params"]["item"])
+            .map(|item| (&item["id"], &item["text"]))
+            .collect();
+        assert_eq!(
+            completed,
+            vec![
+                (&json!("msg_turn1_0"), &json!("First")),
+                (&json!("msg_turn1_2"), &json!("Second"))
+            ]
+        );
+    }
+
+    const STREAM_TRANSCRIPT: &[&str] = &[
+        r#"{"type":"system","subtype":"init","session_id":"s1","model":"claude-sonnet-4-5","tools":["Read"]}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Let me look."}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","name":"Read","id":"tool-1"}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"path\":"}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"a.rs\"}"}}"#,
+        r#"{"type":"content_block_stop","index":1}"#,
+        r#"{"type":"tool_result","tool_use_id":"tool-1","content":"fn main() {}"}"#,
+        r#"{"type":"content_block_start","index":2,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"content_block_delta","index":2,"delta":{"type":"text_delta","text":"Looks fine."}}"#,
+        r#"{"type":"content_block_stop","index":2}"#,
+        r#"{"type":"result","subtype":"success","total_cost_usd":0.01,"usage":{"input_tokens":10,"output_tokens":5}}"#,
+    ];
+
+    fn transcript_events(lines: &[&str]) -> Vec<Value> {
+        let profile = ClaudeProfile::default();
+        lines
+            .iter()
+            .filter_map(|line| profile.parse_stream_line(line, "t1", "turn1"))
+            .collect()
+    }
+
+    #[test]
+    fn stream_transcript_is_a_valid_turn_sequence() {
+        let events = transcript_events(STREAM_TRANSCRIPT);
+        assert_valid_turn_sequence(&events);
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event["method"] == "item/completed")
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid turn sequence: turn never completed")]
+    fn truncated_stream_transcript_fails_turn_sequence_check() {
+        let events = transcript_events(&STREAM_TRANSCRIPT[..STREAM_TRANSCRIPT.len() - 1]);
+        assert_valid_turn_sequence(&events);
+    }
+
     const SUPPORTED_METHODS: &[&str] = &[
         "item/agentMessage/delta",
         "item/completed",
         "item/started",
         "turn/completed",
+        "turn/error",
         "turn/started",
     ];
 
@@ -387,6 +1171,7 @@ mod tests {
             r#"{"type":"content_block_start","content_block":{"type":"tool_use","name":"Read","id":"t1"}}"#,
             r#"{"type":"tool_result","tool_use_id":"t1","content":"ok"}"#,
             r#"{"type":"result","subtype":"success","cost_usd":0.01,"duration_ms":100}}"#,
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
         ];
         for line in test_lines {
             if let Some(event) = parse_stream_json_line(line, "thread1", "turn1") {
@@ -411,6 +1196,80 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_stream_json_result_with_trailing_brace_still_completes_turn() {
+        let line = r#"{"type":"result","subtype":"success","cost_usd":0.01,"duration_ms":100}}"#;
+        let event = parse_stream_json_line(line, "t1", "turn1").expect("turn/completed");
+        assert_eq!(event["method"], "turn/completed");
+        assert_eq!(event["params"]["durationMs"], 100);
+        assert_eq!(event["params"]["costUsd"], 0.01);
+
+        let with_garbage = r#"{"type":"result","duration_ms":5} trailing"#;
+        assert_eq!(
+            parse_stream_json_line(with_garbage, "t1", "turn1").unwrap()["method"],
+            "turn/completed"
+        );
+        for unparseable in [
+            r#"}{"type":"result"}"#,
+            r#"{"type":"result""#,
+            "[1]]",
+            "oops",
+        ] {
+            assert!(parse_stream_json_line(unparseable, "t1", "turn1").is_none());
+        }
+    }
+
+    #[test]
+    fn compact_boundary_reports_tokens() {
+        let profile = ClaudeProfile::default();
+        assert_eq!(profile.compact_prompt(), Some("/compact"));
+        let boundary = r#"{"type":"system","subtype":"compact_boundary","session_id":"s1","compact_metadata":{"trigger":"manual","pre_tokens":48000}}"#;
+        assert_eq!(
+            profile.parse_compaction_line(boundary),
+            Some(CompactionTokens {
+                before: Some(48000),
+                after: None,
+            })
+        );
+        let with_after = r#"{"type":"system","subtype":"compact_boundary","compact_metadata":{"pre_tokens":48000,"post_tokens":6000}}"#;
+        assert_eq!(
+            profile.parse_compaction_line(with_after).unwrap().after,
+            Some(6000)
+        );
+        let init = r#"{"type":"system","subtype":"init","session_id":"s1"}"#;
+        assert!(profile.parse_compaction_line(init).is_none());
+    }
+
+    #[test]
+    fn parse_stream_json_result_includes_usage() {
+        let line = r#"{"type":"result","subtype":"success","total_cost_usd":0.05,"usage":{"input_tokens":120,"output_tokens":45}}"#;
+        let event = parse_stream_json_line(line, "t1", "turn1").unwrap();
+        let params = event.get("params").unwrap();
+        assert_eq!(params["costUsd"].as_f64(), Some(0.05));
+        assert_eq!(params["usage"]["inputTokens"].as_u64(), Some(120));
+        assert_eq!(params["usage"]["outputTokens"].as_u64(), Some(45));
+    }
+
+    #[test]
+    fn parse_stream_json_error_ends_turn() {
+        let line = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let event = parse_stream_json_line(line, "t1", "turn1").unwrap();
+        assert_eq!(event["method"], "turn/error");
+        assert_eq!(
+            event["params"],
+            json!({
+                "threadId": "t1",
+                "turnId": "turn1",
+                "error": { "type": "overloaded_error", "message": "Overloaded" },
+                "willRetry": false
+            })
+        );
+        assert!(is_turn_ending_event(&event));
+
+        let bare = parse_stream_json_line(r#"{"type":"error"}"#, "t1", "turn1").unwrap();
+        assert_eq!(bare["params"]["error"]["message"], "error");
+    }
+
     #[test]
     fn parse_stream_json_unknown_type() {
         let line = r#"{"type":"unknown_event"}"#;
@@ -452,6 +1311,11 @@ mod tests {
                 created_at: 1000,
                 updated_at: 2000,
                 archived: false,
+                usage: Default::default(),
+                model: None,
+                reported_model: None,
+                first_prompt: None,
+                fork_pending: false,
             },
         );
         store.save(&path).unwrap();
@@ -509,6 +1373,652 @@ mod tests {
         assert!(unknown_result.is_err());
     }
 
+    async fn thread_name(
+        adapter: &GenericAdapterSession<ClaudeProfile>,
+        id: &str,
+    ) -> Option<String> {
+        let list = adapter
+            .send_request("thread/list", json!({}))
+            .await
+            .unwrap();
+        list["result"]["threads"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|thread| thread["id"] == id)
+            .and_then(|thread| thread["name"].as_str())
+            .map(|name| name.to_string())
+    }
+
+    #[tokio::test]
+    async fn thread_fork_applies_requested_name() {
+        let adapter = test_adapter();
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let source_id = started["result"]["threadId"].as_str().unwrap().to_string();
+        adapter
+            .send_request(
+                "thread/name/set",
+                json!({ "threadId": source_id, "name": "Original" }),
+            )
+            .await
+            .unwrap();
+
+        let named = adapter
+            .send_request(
+                "thread/fork",
+                json!({ "threadId": source_id, "name": "  Experiment  " }),
+            )
+            .await
+            .unwrap();
+        let named_id = named["result"]["threadId"].as_str().unwrap();
+        assert_eq!(
+            thread_name(&adapter, named_id).await.as_deref(),
+            Some("Experiment")
+        );
+
+        let default = adapter
+            .send_request("thread/fork", json!({ "threadId": source_id, "name": "" }))
+            .await
+            .unwrap();
+        let default_id = default["result"]["threadId"].as_str().unwrap();
+        assert_eq!(
+            thread_name(&adapter, default_id).await.as_deref(),
+            Some("Original (fork)")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn thread_model_applies_to_turns_without_model() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir =
+            std::env::temp_dir().join(format!("claude-model-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let args_path = temp_dir.join("args.txt");
+        let script = temp_dir.join("fake-claude");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"$@\" > '{path}.tmp'\nmv '{path}.tmp' '{path}'\n",
+                path = args_path.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let entry = crate::types::WorkspaceEntry {
+            id: "model-ws".to_string(),
+            name: "Model".to_string(),
+            path: temp_dir.to_string_lossy().to_string(),
+            codex_bin: None,
+            kind: crate::types::WorkspaceKind::Main,
+            parent_id: None,
+            worktree: None,
+            settings: crate::types::WorkspaceSettings::default(),
+        };
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            cli_bin: Some(script.to_string_lossy().to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let store_path = temp_dir.join("threads.json");
+        let adapter = GenericAdapterSession::new(
+            ClaudeProfile::default(),
+            &entry,
+            config,
+            test_emitter(),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(store_path.clone());
+
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let thread_id = started["result"]["threadId"].as_str().unwrap().to_string();
+        adapter
+            .send_request(
+                "thread/model/set",
+                json!({ "threadId": thread_id, "model": "claude-opus-4" }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            ThreadStore::load(&store_path).threads[&thread_id]
+                .model
+                .as_deref(),
+            Some("claude-opus-4")
+        );
+
+        adapter
+            .send_request(
+                "turn/start",
+                json!({ "threadId": thread_id, "input": "hello", "model": null }),
+            )
+            .await
+            .unwrap();
+        let mut args = None;
+        for _ in 0..100 {
+            if let Ok(content) = std::fs::read_to_string(&args_path) {
+                args = Some(content);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let args = args.expect("fake claude should record its arguments");
+        let args: Vec<&str> = args.lines().collect();
+        let position = args.iter().position(|arg| *arg == "--model").unwrap();
+        assert_eq!(args[position + 1], "claude-opus-4");
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn compact_runs_slash_compact_on_the_thread_session() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir =
+            std::env::temp_dir().join(format!("claude-compact-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let args_path = temp_dir.join("compact-args.txt");
+        let script = temp_dir.join("fake-claude");
+        std::fs::write(
+            &script,
+            format!(
+                r#"#!/bin/sh
+for arg in "$@"; do
+  if [ "$arg" = "/compact" ]; then
+    printf '%s\n' "$@" > '{path}'
+    echo '{{"type":"system","subtype":"compact_boundary","session_id":"s1","compact_metadata":{{"trigger":"manual","pre_tokens":48000}}}}'
+    echo '{{"type":"result","subtype":"success","session_id":"s1"}}'
+    exit 0
+  fi
+done
+echo '{{"type":"system","subtype":"init","session_id":"s1"}}'
+echo '{{"type":"result","subtype":"success","duration_ms":10}}'
+"#,
+                path = args_path.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let entry = crate::types::WorkspaceEntry {
+            id: "compact-ws".to_string(),
+            name: "Compact".to_string(),
+            path: temp_dir.to_string_lossy().to_string(),
+            codex_bin: None,
+            kind: crate::types::WorkspaceKind::Main,
+            parent_id: None,
+            worktree: None,
+            settings: crate::types::WorkspaceSettings::default(),
+        };
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            cli_bin: Some(script.to_string_lossy().to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let events = Arc::new(std::sync::Mutex::new(Vec::<Value>::new()));
+        let sink = events.clone();
+        let store_path = temp_dir.join("threads.json");
+        let adapter = GenericAdapterSession::new(
+            ClaudeProfile::default(),
+            &entry,
+            config,
+            Arc::new(move |event: AppServerEvent| {
+                sink.lock().unwrap().push(event.message);
+            }),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(store_path);
+
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let thread_id = started["result"]["threadId"].as_str().unwrap().to_string();
+        let nothing_yet = adapter
+            .send_request("thread/compact/start", json!({ "threadId": thread_id }))
+            .await
+            .unwrap();
+        assert_eq!(nothing_yet["result"]["compacted"], false);
+
+        adapter
+            .send_request(
+                "turn/start",
+                json!({ "threadId": thread_id, "input": "hello" }),
+            )
+            .await
+            .unwrap();
+        let mut compacted = None;
+        for _ in 0..100 {
+            let turn_done = events
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|event| event["method"] == "turn/completed");
+            if turn_done {
+                compacted = adapter
+                    .send_request("thread/compact/start", json!({ "threadId": thread_id }))
+                    .await
+                    .ok();
+                if compacted.is_some() {
+                    break;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let compacted = compacted.expect("compaction result");
+        assert_eq!(compacted["result"]["compacted"], true);
+        assert_eq!(compacted["result"]["tokensBefore"], 48000);
+        assert!(compacted["result"]["tokensAfter"].is_null());
+
+        let args = std::fs::read_to_string(&args_path).unwrap();
+        let args: Vec<&str> = args.lines().collect();
+        let resume = args.iter().position(|arg| *arg == "--resume").unwrap();
+        assert_eq!(args[resume + 1], "s1");
+        let emitted = events.lock().unwrap();
+        let event = emitted
+            .iter()
+            .find(|event| event["method"] == "thread/compacted")
+            .expect("thread/compacted event");
+        assert_eq!(event["params"]["threadId"], thread_id.as_str());
+        assert_eq!(event["params"]["tokensBefore"], 48000);
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn long_prompt_reaches_claude_over_stdin() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir =
+            std::env::temp_dir().join(format!("claude-stdin-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let stdin_path = temp_dir.join("stdin.txt");
+        let script = temp_dir.join("fake-claude");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\ncat > '{path}.tmp'\nmv '{path}.tmp' '{path}'\n",
+                path = stdin_path.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let entry = crate::types::WorkspaceEntry {
+            id: "stdin-ws".to_string(),
+            name: "Stdin".to_string(),
+            path: temp_dir.to_string_lossy().to_string(),
+            codex_bin: None,
+            kind: crate::types::WorkspaceKind::Main,
+            parent_id: None,
+            worktree: None,
+            settings: crate::types::WorkspaceSettings::default(),
+        };
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            cli_bin: Some(script.to_string_lossy().to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let adapter = GenericAdapterSession::new(
+            ClaudeProfile::default(),
+            &entry,
+            config,
+            test_emitter(),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("threads.json"));
+
+        let started = adapter
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let thread_id = started["result"]["threadId"].as_str().unwrap();
+        let long_prompt = "y".repeat(CLAUDE_STDIN_PROMPT_BYTES * 2);
+        adapter
+            .send_request(
+                "turn/start",
+                json!({ "threadId": thread_id, "input": long_prompt }),
+            )
+            .await
+            .unwrap();
+        let mut received = None;
+        for _ in 0..100 {
+            if let Ok(content) = std::fs::read_to_string(&stdin_path) {
+                received = Some(content);
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            received.expect("fake claude should record its stdin"),
+            long_prompt
+        );
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn skills_list_reports_claude_skills_by_scope() {
+        let temp_dir = std::env::temp_dir().join(format!("claude-skills-{}", uuid::Uuid::new_v4()));
+        let workspace = temp_dir.join("project");
+        let profile = temp_dir.join("profile");
+        let write_skill = |dir: &Path, content: &str| {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("SKILL.md"), content).unwrap();
+        };
+        write_skill(
+            &workspace.join(".claude/skills/release"),
+            "---\nname: release-notes\ndescription: \"Draft release notes from merged PRs\"\n---\n# Steps\n",
+        );
+        write_skill(
+            &profile.join("skills/release-notes"),
+            "---\nname: release-notes\ndescription: User copy\n---\n",
+        );
+        write_skill(&profile.join("skills/pdf"), "Extract text from PDFs.\n");
+        std::fs::create_dir_all(profile.join("skills/empty")).unwrap();
+
+        let entry = crate::types::WorkspaceEntry {
+            id: "skills-ws".to_string(),
+            name: "Skills".to_string(),
+            path: workspace.to_string_lossy().to_string(),
+            codex_bin: None,
+            kind: crate::types::WorkspaceKind::Main,
+            parent_id: None,
+            worktree: None,
+            settings: crate::types::WorkspaceSettings::default(),
+        };
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            claude_home: Some(profile.clone()),
+            ..CliSpawnConfig::default()
+        };
+        let adapter = GenericAdapterSession::new(
+            ClaudeProfile::default(),
+            &entry,
+            config.clone(),
+            test_emitter(),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("threads.json"));
+
+        let response = adapter
+            .send_request("skills/list", json!({ "cwd": entry.path }))
+            .await
+            .unwrap();
+        assert!(response["result"].get("unsupported").is_none());
+        let skills = response["result"]["skills"].as_array().unwrap();
+        let summary: Vec<(&str, &str, Option<&str>)> = skills
+            .iter()
+            .map(|skill| {
+                (
+                    skill["name"].as_str().unwrap(),
+                    skill["scope"].as_str().unwrap(),
+                    skill["description"].as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("pdf", "user", None),
+                (
+                    "release-notes",
+                    "project",
+                    Some("Draft release notes from merged PRs")
+                ),
+            ]
+        );
+        assert!(skills[1]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("release/SKILL.md"));
+
+        let cursor =
+            crate::backend::cursor_adapter::CursorProfile.skills_list(&config, &entry.path);
+        assert_eq!(cursor["result"]["unsupported"], true);
+        assert_eq!(cursor["result"]["skills"], json!([]));
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn mcp_server_list_merges_claude_config_scopes() {
+        let temp_dir = std::env::temp_dir().join(format!("claude-mcp-{}", uuid::Uuid::new_v4()));
+        let workspace = temp_dir.join("project");
+        let profile = temp_dir.join("profile");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::create_dir_all(&profile).unwrap();
+        std::fs::write(
+            workspace.join(".mcp.json"),
+            json!({
+                "mcpServers": {
+                    "gondolin": { "command": "npx", "args": ["-y", "@earendil-works/gondolin", "mcp"] },
+                    "docs": { "type": "http", "url": "https://docs.example.com/mcp" }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(
+            profile.join(".claude.json"),
+            json!({
+                "mcpServers": {
+                    "docs": { "command": "docs-user" },
+                    "github": { "command": "github-mcp" }
+                },
+                "projects": {
+                    workspace.to_string_lossy(): {
+                        "mcpServers": { "db": { "command": "db-mcp", "args": ["--ro"] } }
+                    },
+                    "/elsewhere": { "mcpServers": { "other": { "command": "x" } } }
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let entry = crate::types::WorkspaceEntry {
+            id: "mcp-ws".to_string(),
+            name: "Mcp".to_string(),
+            path: workspace.to_string_lossy().to_string(),
+            codex_bin: None,
+            kind: crate::types::WorkspaceKind::Main,
+            parent_id: None,
+            worktree: None,
+            settings: crate::types::WorkspaceSettings::default(),
+        };
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            claude_home: Some(profile.clone()),
+            ..CliSpawnConfig::default()
+        };
+        let adapter = GenericAdapterSession::new(
+            ClaudeProfile::default(),
+            &entry,
+            config,
+            test_emitter(),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("threads.json"));
+
+        let response = adapter
+            .send_request(
+                "mcpServerStatus/list",
+                json!({ "cursor": null, "limit": null }),
+            )
+            .await
+            .unwrap();
+        let servers = response["result"]["data"].as_array().unwrap();
+        let summary: Vec<(&str, &str, bool)> = servers
+            .iter()
+            .map(|server| {
+                (
+                    server["name"].as_str().unwrap(),
+                    server["scope"].as_str().unwrap(),
+                    server["managed"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("db", "local", false),
+                ("docs", "project", false),
+                ("github", "user", false),
+                ("gondolin", "project", true),
+            ]
+        );
+        assert_eq!(servers[1]["type"], "http");
+        assert_eq!(servers[0]["args"], json!(["--ro"]));
+
+        let empty = claude_mcp_servers(&temp_dir.join("missing"), None);
+        assert!(empty.is_empty());
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn app_list_returns_builtin_claude_apps() {
+        let response = test_adapter()
+            .send_request("app/list", json!({ "cursor": null, "limit": 100 }))
+            .await
+            .unwrap();
+        let apps = response["result"]["data"].as_array().unwrap();
+        assert!(!apps.is_empty());
+        assert!(apps.iter().all(|app| {
+            app["id"].as_str().is_some_and(|id| !id.is_empty())
+                && app["name"].as_str().is_some_and(|name| !name.is_empty())
+                && app["isAccessible"] == true
+        }));
+        assert!(response["result"]["nextCursor"].is_null());
+    }
+
+    #[tokio::test]
+    async fn thread_duplicate_forks_source_session_on_first_turn() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("claude-duplicate-test-{}", uuid::Uuid::new_v4()));
+        let store_path = temp_dir.join("threads.json");
+        let mut store = ThreadStore::default();
+        store.threads.insert(
+            "source".to_string(),
+            crate::backend::adapter_base::ThreadMetadata {
+                cli_session_id: Some("claude-session-1".to_string()),
+                name: Some("Original".to_string()),
+                created_at: 1000,
+                updated_at: 2000,
+                archived: false,
+                usage: Default::default(),
+                model: None,
+                reported_model: None,
+                first_prompt: None,
+                fork_pending: false,
+            },
+        );
+        store.save(&store_path).unwrap();
+
+        let adapter = test_adapter().with_thread_store_path(store_path.clone());
+        let result = adapter
+            .send_request("thread/duplicate", json!({ "threadId": "source" }))
+            .await
+            .unwrap();
+        let duplicate_id = result["result"]["threadId"].as_str().unwrap().to_string();
+        assert_ne!(duplicate_id, "source");
+
+        let persisted = ThreadStore::load(&store_path);
+        let duplicate = &persisted.threads[&duplicate_id];
+        assert_eq!(
+            duplicate.cli_session_id.as_deref(),
+            Some("claude-session-1")
+        );
+        assert_eq!(duplicate.name.as_deref(), Some("Original (copy)"));
+        assert!(duplicate.fork_pending);
+        assert!(!persisted.threads["source"].fork_pending);
+
+        let config = CliSpawnConfig {
+            cli_type: "claude".to_string(),
+            cli_bin: Some("claude".to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let command = ClaudeProfile::default()
+            .build_turn_command(
+                &config,
+                duplicate.cli_session_id.as_deref(),
+                "hello",
+                "/tmp",
+                &json!({ "forkSession": true }),
+            )
+            .unwrap();
+        let args: Vec<_> = command.as_std().get_args().collect();
+        let resume = args.iter().position(|arg| *arg == "--resume").unwrap();
+        assert_eq!(args[resume + 1], "claude-session-1");
+        assert_eq!(args[resume + 2], "--fork-session");
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn thread_resume_reports_missing_session_id() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("claude-resume-test-{}", uuid::Uuid::new_v4()));
+        let store_path = temp_dir.join("threads.json");
+        let mut store = ThreadStore::default();
+        for (id, session_id) in [("fresh", None), ("continued", Some("claude-session-1"))] {
+            store.threads.insert(
+                id.to_string(),
+                crate::backend::adapter_base::ThreadMetadata {
+                    cli_session_id: session_id.map(str::to_string),
+                    name: None,
+                    created_at: 1000,
+                    updated_at: 2000,
+                    archived: false,
+                    usage: Default::default(),
+                    model: None,
+                    reported_model: None,
+                    first_prompt: None,
+                    fork_pending: false,
+                },
+            );
+        }
+        store.save(&store_path).unwrap();
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let captured = events.clone();
+        let adapter = test_adapter_with_emitter(Arc::new(move |event: AppServerEvent| {
+            captured.lock().unwrap().push(event.message);
+        }))
+        .with_thread_store_path(store_path);
+
+        let degraded = adapter
+            .send_request("thread/resume", json!({ "threadId": "fresh" }))
+            .await
+            .unwrap();
+        assert_eq!(degraded["result"]["resumable"], false);
+        {
+            let events = events.lock().unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0]["method"], "codex/resumeWarning");
+            assert_eq!(events[0]["params"]["threadId"], "fresh");
+        }
+
+        let resumed = adapter
+            .send_request("thread/resume", json!({ "threadId": "continued" }))
+            .await
+            .unwrap();
+        assert_eq!(resumed["result"]["resumable"], true);
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
     #[tokio::test]
     async fn thread_start_response_has_thread_id_and_thread_object() {
         let adapter = test_adapter();
@@ -3,7 +3,9 @@ use std::sync::Arc;
 
 use crate::backend::adapter_base::{build_adapter_command, spawn_adapter_session, CliProfile};
 use crate::backend::app_server::{CliSpawnConfig, WorkspaceSession};
+use crate::backend::errors::AppServerError;
 use crate::backend::events::EventSink;
+use crate::cursor::stream::{extract_cursor_session_id, parse_cursor_line};
 use crate::types::WorkspaceEntry;
 
 pub(crate) struct CursorProfile;
@@ -15,13 +17,19 @@ impl CliProfile for CursorProfile {
         session_id: Option<&str>,
         prompt: &str,
         cwd: &str,
-        _params: &Value,
+        params: &Value,
     ) -> Result<tokio::process::Command, String> {
-        build_cursor_command(config, session_id, prompt, cwd)
+        let mode = cursor_mode(params).or_else(|| {
+            config
+                .cursor_default_mode
+                .as_deref()
+                .filter(|mode| *mode != DEFAULT_CURSOR_MODE)
+        });
+        build_cursor_command_with_mode(config, session_id, prompt, cwd, mode)
     }
 
     fn parse_stream_line(&self, line: &str, thread_id: &str, turn_id: &str) -> Option<Value> {
-        parse_cursor_stream_line(line, thread_id, turn_id)
+        parse_cursor_line(line, thread_id, turn_id)
     }
 
     fn extract_session_id(&self, line: &str) -> Option<String> {
@@ -42,11 +50,57 @@ impl CliProfile for CursorProfile {
     }
 }
 
-pub(crate) fn build_cursor_command(
+/// Env var Cursor's agent CLI reads its profile directory from.
+pub(crate) const CURSOR_HOME_ENV: &str = "CURSOR_CONFIG_DIR";
+
+/// Modes Cursor's agent CLI accepts for `--mode`.
+pub(crate) const CURSOR_MODES: &[&str] = &["agent", "plan", "ask", "debug"];
+
+/// Cursor's own default mode, which takes no flag.
+pub(crate) const DEFAULT_CURSOR_MODE: &str = "agent";
+
+pub(crate) fn cursor_mode_list() -> Value {
+    json!({
+        "modes": CURSOR_MODES,
+        "defaultMode": DEFAULT_CURSOR_MODE
+    })
+}
+
+pub(crate) fn validate_cursor_mode(mode: &str) -> Result<(), String> {
+    if CURSOR_MODES.contains(&mode) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unknown Cursor mode \"{mode}\"; expected one of: {}",
+            CURSOR_MODES.join(", ")
+        ))
+    }
+}
+
+/// Cursor `--mode` for a turn: `plan` for the plan collaboration mode and
+/// `ask` for a read-only sandbox. Other turns run in Cursor's default agent
+/// mode, or the workspace's `cursor_default_mode` when one is set.
+pub(crate) fn cursor_mode(params: &Value) -> Option<&'static str> {
+    let collaboration_mode = params
+        .get("collaborationMode")
+        .and_then(|mode| mode.get("mode"))
+        .and_then(Value::as_str);
+    if collaboration_mode == Some("plan") {
+        return Some("plan");
+    }
+    let sandbox = params
+        .get("sandboxPolicy")
+        .and_then(|policy| policy.get("type"))
+        .and_then(Value::as_str);
+    (sandbox == Some("readOnly")).then_some("ask")
+}
+
+pub(crate) fn build_cursor_command_with_mode(
     config: &CliSpawnConfig,
     session_id: Option<&str>,
     prompt: &str,
     cwd: &str,
+    mode: Option<&str>,
 ) -> Result<tokio::process::Command, String> {
     let mut args = vec![
         "-p".to_string(),
@@ -57,142 +111,31 @@ pub(crate) fn build_cursor_command(
         args.push("--resume".to_string());
         args.push(sid.to_string());
     }
-    args.push(prompt.to_string());
-
-    build_adapter_command(config, args, cwd, None)
-}
-
-pub(crate) fn parse_cursor_stream_line(
-    line: &str,
-    thread_id: &str,
-    turn_id: &str,
-) -> Option<Value> {
-    let event: Value = serde_json::from_str(line).ok()?;
-    let event_type = event.get("type")?.as_str()?;
-
-    let msg_item_id = format!("msg_{turn_id}");
-
-    match event_type {
-        "system" => {
-            let subtype = event.get("subtype").and_then(|s| s.as_str()).unwrap_or("");
-            if subtype == "init" {
-                Some(json!({
-                    "method": "turn/started",
-                    "params": {
-                        "threadId": thread_id,
-                        "turnId": turn_id
-                    }
-                }))
-            } else {
-                None
-            }
-        }
-        "assistant" => {
-            let text = event
-                .get("message")
-                .and_then(|m| m.get("content"))
-                .and_then(|c| c.as_array())
-                .and_then(|arr| arr.first())
-                .and_then(|item| item.get("text"))
-                .and_then(|t| t.as_str())?;
-            Some(json!({
-                "method": "item/agentMessage/delta",
-                "params": {
-                    "threadId": thread_id,
-                    "turnId": turn_id,
-                    "itemId": msg_item_id,
-                    "delta": text
-                }
-            }))
-        }
-        "tool_call" => {
-            let subtype = event.get("subtype").and_then(|s| s.as_str()).unwrap_or("");
-            let call_id = event
-                .get("call_id")
-                .and_then(|c| c.as_str())
-                .unwrap_or("");
-
-            match subtype {
-                "started" => {
-                    let tool_name = extract_tool_name_from_cursor_event(&event);
-                    Some(json!({
-                        "method": "item/started",
-                        "params": {
-                            "threadId": thread_id,
-                            "turnId": turn_id,
-                            "item": {
-                                "id": call_id,
-                                "type": "tool_use",
-                                "name": tool_name
-                            }
-                        }
-                    }))
-                }
-                "completed" => Some(json!({
-                    "method": "item/completed",
-                    "params": {
-                        "threadId": thread_id,
-                        "turnId": turn_id,
-                        "item": {
-                            "id": call_id,
-                            "type": "tool_use"
-                        }
-                    }
-                })),
-                _ => None,
-            }
-        }
-        "result" => Some(json!({
-            "method": "turn/completed",
-            "params": {
-                "threadId": thread_id,
-                "turnId": turn_id,
-                "durationMs": event.get("duration_ms")
-            }
-        })),
-        _ => None,
+    if let Some(mode) = mode {
+        args.push("--mode".to_string());
+        args.push(mode.to_string());
     }
-}
-
-fn extract_tool_name_from_cursor_event(event: &Value) -> &str {
-    if let Some(obj) = event.as_object() {
-        for key in obj.keys() {
-            if key.ends_with("ToolCall") {
-                return key.strip_suffix("ToolCall").unwrap_or(key);
-            }
-        }
-    }
-    event
-        .get("tool_name")
-        .and_then(|n| n.as_str())
-        .unwrap_or("tool")
-}
+    args.push(prompt.to_string());
 
-fn extract_cursor_session_id(line: &str) -> Option<String> {
-    let event: Value = serde_json::from_str(line).ok()?;
-    if event.get("type")?.as_str()? != "system" {
-        return None;
-    }
-    if event.get("subtype").and_then(|s| s.as_str()) != Some("init") {
-        return None;
-    }
-    event
-        .get("session_id")
-        .and_then(|s| s.as_str())
-        .map(|s| s.to_string())
+    let home_env = config.cursor_home.as_ref().map(|h| (CURSOR_HOME_ENV, h));
+    build_adapter_command(config, args, cwd, home_env)
 }
 
 pub(crate) async fn spawn_cursor_session<E: EventSink>(
     entry: WorkspaceEntry,
     config: CliSpawnConfig,
     event_sink: E,
-) -> Result<Arc<WorkspaceSession>, String> {
+) -> Result<Arc<WorkspaceSession>, AppServerError> {
+    if let Some(mode) = config.cursor_default_mode.as_deref() {
+        validate_cursor_mode(mode).map_err(AppServerError::SpawnFailed)?;
+    }
     spawn_adapter_session(CursorProfile, "Cursor", entry, config, event_sink).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::test_support::test_workspace_entry;
 
     #[test]
     fn build_cursor_command_basic() {
@@ -201,8 +144,9 @@ mod tests {
             cli_bin: Some("cursor".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
-        let result = build_cursor_command(&config, None, "hello", "/tmp");
+        let result = build_cursor_command_with_mode(&config, None, "hello", "/tmp", None);
         assert!(result.is_ok());
     }
 
@@ -213,151 +157,144 @@ mod tests {
             cli_bin: Some("cursor".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
-        let result = build_cursor_command(&config, Some("sess-1"), "hello", "/tmp");
+        let result = build_cursor_command_with_mode(&config, Some("sess-1"), "hello", "/tmp", None);
         assert!(result.is_ok());
     }
 
     #[test]
-    fn parse_system_init() {
-        let line = r#"{"type":"system","subtype":"init","session_id":"cs-1"}"#;
-        let event = parse_cursor_stream_line(line, "t1", "turn1").unwrap();
-        assert_eq!(
-            event.get("method").and_then(|v| v.as_str()),
-            Some("turn/started")
-        );
-    }
-
-    #[test]
-    fn parse_system_non_init_is_dropped() {
-        let line = r#"{"type":"system","subtype":"config","data":{}}"#;
-        assert!(parse_cursor_stream_line(line, "t1", "turn1").is_none());
+    fn build_cursor_command_exports_cursor_home() {
+        let config = CliSpawnConfig {
+            cli_type: "cursor".to_string(),
+            cli_bin: Some("cursor".to_string()),
+            cursor_home: Some(std::path::PathBuf::from("/tmp/cursor-profile")),
+            ..CliSpawnConfig::default()
+        };
+        let command = build_cursor_command_with_mode(&config, None, "hello", "/tmp", None).unwrap();
+        let home = command
+            .as_std()
+            .get_envs()
+            .find(|(key, _)| *key == CURSOR_HOME_ENV)
+            .and_then(|(_, value)| value);
+        assert_eq!(home, Some(std::ffi::OsStr::new("/tmp/cursor-profile")));
     }
 
     #[test]
-    fn parse_assistant_message() {
-        let line = r#"{"type":"assistant","message":{"content":[{"text":"Hello world"}]}}"#;
-        let event = parse_cursor_stream_line(line, "t1", "turn1").unwrap();
+    fn cursor_mode_follows_turn_params() {
+        assert_eq!(cursor_mode(&json!({})), None);
         assert_eq!(
-            event.get("method").and_then(|v| v.as_str()),
-            Some("item/agentMessage/delta")
+            cursor_mode(&json!({ "collaborationMode": { "mode": "plan" } })),
+            Some("plan")
         );
-        let params = event.get("params").unwrap();
         assert_eq!(
-            params.get("delta").and_then(|d| d.as_str()),
-            Some("Hello world")
+            cursor_mode(&json!({ "sandboxPolicy": { "type": "readOnly" } })),
+            Some("ask")
         );
-        assert!(params.get("itemId").is_some());
-    }
-
-    #[test]
-    fn parse_assistant_message_empty_content_is_dropped() {
-        let line = r#"{"type":"assistant","message":{"content":[]}}"#;
-        assert!(parse_cursor_stream_line(line, "t1", "turn1").is_none());
-    }
-
-    #[test]
-    fn parse_tool_call_started() {
-        let line = r#"{"type":"tool_call","subtype":"started","call_id":"c1","ReadToolCall":{"path":"test.rs"}}"#;
-        let event = parse_cursor_stream_line(line, "t1", "turn1").unwrap();
-        assert_eq!(
-            event.get("method").and_then(|v| v.as_str()),
-            Some("item/started")
-        );
-        let item = event.get("params").and_then(|p| p.get("item")).unwrap();
-        assert_eq!(item.get("id").and_then(|i| i.as_str()), Some("c1"));
-        assert_eq!(item.get("name").and_then(|n| n.as_str()), Some("Read"));
-    }
-
-    #[test]
-    fn parse_tool_call_completed() {
-        let line = r#"{"type":"tool_call","subtype":"completed","call_id":"c1"}"#;
-        let event = parse_cursor_stream_line(line, "t1", "turn1").unwrap();
         assert_eq!(
-            event.get("method").and_then(|v| v.as_str()),
-            Some("item/completed")
+            cursor_mode(&json!({
+                "collaborationMode": { "mode": "code" },
+                "sandboxPolicy": { "type": "workspaceWrite" }
+            })),
+            None
         );
     }
 
     #[test]
-    fn parse_result_event() {
-        let line = r#"{"type":"result","duration_ms":1500}"#;
-        let event = parse_cursor_stream_line(line, "t1", "turn1").unwrap();
-        assert_eq!(
-            event.get("method").and_then(|v| v.as_str()),
-            Some("turn/completed")
-        );
-        assert_eq!(
-            event
-                .get("params")
-                .and_then(|p| p.get("durationMs"))
-                .and_then(|d| d.as_u64()),
-            Some(1500)
-        );
+    fn turn_command_passes_mode_only_when_set() {
+        let config = CliSpawnConfig {
+            cli_type: "cursor".to_string(),
+            cli_bin: Some("cursor".to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let args = |params: Value| -> Vec<String> {
+            CursorProfile
+                .build_turn_command(&config, None, "hello", "/tmp", &params)
+                .unwrap()
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+        assert!(!args(json!({})).contains(&"--mode".to_string()));
+        let planned = args(json!({ "collaborationMode": { "mode": "plan" } }));
+        let position = planned.iter().position(|arg| arg == "--mode").unwrap();
+        assert_eq!(planned[position + 1], "plan");
+        assert_eq!(planned.last().map(String::as_str), Some("hello"));
     }
 
     #[test]
-    fn parse_unknown_event() {
-        let line = r#"{"type":"internal_debug","data":{}}"#;
-        assert!(parse_cursor_stream_line(line, "t1", "turn1").is_none());
+    fn cursor_mode_list_reports_supported_modes() {
+        let list = cursor_mode_list();
+        assert_eq!(list["defaultMode"], "agent");
+        assert_eq!(list["modes"], json!(["agent", "plan", "ask", "debug"]));
+        for mode in CURSOR_MODES {
+            assert!(validate_cursor_mode(mode).is_ok());
+        }
     }
 
     #[test]
-    fn extract_session_id_from_init() {
-        let line = r#"{"type":"system","subtype":"init","session_id":"cs-42"}"#;
+    fn turn_command_falls_back_to_default_mode() {
+        let config = CliSpawnConfig {
+            cli_type: "cursor".to_string(),
+            cli_bin: Some("cursor".to_string()),
+            cursor_default_mode: Some("ask".to_string()),
+            ..CliSpawnConfig::default()
+        };
+        let args = |config: &CliSpawnConfig, params: Value| -> Vec<String> {
+            CursorProfile
+                .build_turn_command(config, None, "hello", "/tmp", &params)
+                .unwrap()
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+        let mode_of = |args: Vec<String>| {
+            let position = args.iter().position(|arg| arg == "--mode")?;
+            args.get(position + 1).cloned()
+        };
+        assert_eq!(mode_of(args(&config, json!({}))).as_deref(), Some("ask"));
         assert_eq!(
-            extract_cursor_session_id(line),
-            Some("cs-42".to_string())
+            mode_of(args(
+                &config,
+                json!({ "collaborationMode": { "mode": "plan" } })
+            ))
+            .as_deref(),
+            Some("plan")
         );
+        let agent = CliSpawnConfig {
+            cursor_default_mode: Some("agent".to_string()),
+            ..config
+        };
+        assert_eq!(mode_of(args(&agent, json!({}))), None);
     }
 
-    #[test]
-    fn extract_session_id_from_non_init() {
-        let line = r#"{"type":"assistant","message":{"content":[{"text":"hi"}]}}"#;
-        assert_eq!(extract_cursor_session_id(line), None);
-    }
+    #[tokio::test]
+    async fn spawn_rejects_unknown_default_mode() {
+        use crate::backend::events::{AppServerEvent, TerminalExit, TerminalOutput};
 
-    #[test]
-    fn extract_tool_name_from_tool_call_key() {
-        let event: Value =
-            serde_json::from_str(r#"{"type":"tool_call","subtype":"started","call_id":"c1","EditToolCall":{"path":"x"}}"#)
-                .unwrap();
-        assert_eq!(extract_tool_name_from_cursor_event(&event), "Edit");
-    }
+        struct NoopSink;
 
-    #[test]
-    fn extract_tool_name_fallback() {
-        let event: Value =
-            serde_json::from_str(r#"{"type":"tool_call","subtype":"started","call_id":"c1","tool_name":"Bash"}"#)
-                .unwrap();
-        assert_eq!(extract_tool_name_from_cursor_event(&event), "Bash");
-    }
+        impl EventSink for NoopSink {
+            fn emit_app_server_event(&self, _event: AppServerEvent) {}
+            fn emit_terminal_output(&self, _event: TerminalOutput) {}
+            fn emit_terminal_exit(&self, _event: TerminalExit) {}
+        }
 
-    const SUPPORTED_METHODS: &[&str] = &[
-        "item/agentMessage/delta",
-        "item/completed",
-        "item/started",
-        "turn/completed",
-        "turn/started",
-    ];
+        let entry = test_workspace_entry("cursor-ws", &std::env::temp_dir().to_string_lossy());
+        let config = CliSpawnConfig {
+            cli_type: "cursor".to_string(),
+            cli_bin: Some("cursor-agent-that-does-not-exist".to_string()),
+            cursor_default_mode: Some("yolo".to_string()),
+            ..CliSpawnConfig::default()
+        };
 
-    #[test]
-    fn all_emitted_methods_are_supported_by_frontend() {
-        let test_lines = vec![
-            r#"{"type":"system","subtype":"init","session_id":"s1"}"#,
-            r#"{"type":"assistant","message":{"content":[{"text":"hi"}]}}"#,
-            r#"{"type":"tool_call","subtype":"started","call_id":"c1","ReadToolCall":{}}"#,
-            r#"{"type":"tool_call","subtype":"completed","call_id":"c1"}"#,
-            r#"{"type":"result","duration_ms":100}"#,
-        ];
-        for line in test_lines {
-            if let Some(event) = parse_cursor_stream_line(line, "thread1", "turn1") {
-                let method = event.get("method").and_then(|m| m.as_str()).unwrap();
-                assert!(
-                    SUPPORTED_METHODS.contains(&method),
-                    "Emitted method '{method}' is not in SUPPORTED_APP_SERVER_METHODS"
-                );
-            }
-        }
+        let error = spawn_cursor_session(entry, config, NoopSink)
+            .await
+            .err()
+            .expect("unknown mode should be rejected");
+        assert_eq!(error.code(), "spawn_failed");
+        assert!(error.message().contains("Unknown Cursor mode \"yolo\""));
     }
 }
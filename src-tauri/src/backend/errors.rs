@@ -0,0 +1,90 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Failure categories for spawning and talking to a CLI session. Commands
+/// still hand plain strings to the frontend (see `From<AppServerError> for
+/// String`); `code()` is the stable value to branch on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", content = "message", rename_all = "snake_case")]
+pub(crate) enum AppServerError {
+    CliNotFound(String),
+    Timeout(String),
+    SpawnFailed(String),
+    Protocol(String),
+    Io(String),
+    /// The workspace directory no longer exists, e.g. a deleted worktree.
+    WorkspaceMissing(String),
+}
+
+impl AppServerError {
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Self::CliNotFound(_) => "cli_not_found",
+            Self::Timeout(_) => "timeout",
+            Self::SpawnFailed(_) => "spawn_failed",
+            Self::Protocol(_) => "protocol",
+            Self::Io(_) => "io",
+            Self::WorkspaceMissing(_) => "workspace_missing",
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            Self::CliNotFound(message)
+            | Self::Timeout(message)
+            | Self::SpawnFailed(message)
+            | Self::Protocol(message)
+            | Self::Io(message)
+            | Self::WorkspaceMissing(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for AppServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppServerError {}
+
+impl From<AppServerError> for String {
+    fn from(error: AppServerError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppServerError;
+    use serde_json::json;
+
+    #[test]
+    fn each_variant_has_a_stable_code() {
+        let cases = [
+            (AppServerError::CliNotFound("a".into()), "cli_not_found"),
+            (AppServerError::Timeout("b".into()), "timeout"),
+            (AppServerError::SpawnFailed("c".into()), "spawn_failed"),
+            (AppServerError::Protocol("d".into()), "protocol"),
+            (AppServerError::Io("e".into()), "io"),
+            (
+                AppServerError::WorkspaceMissing("f".into()),
+                "workspace_missing",
+            ),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.code(), code);
+            assert_eq!(
+                serde_json::to_value(&error).unwrap(),
+                json!({ "code": code, "message": error.message() })
+            );
+        }
+    }
+
+    #[test]
+    fn converts_to_plain_message_for_commands() {
+        let error = AppServerError::Timeout("Timed out while checking Codex CLI.".into());
+        assert_eq!(error.to_string(), "Timed out while checking Codex CLI.");
+        assert_eq!(String::from(error), "Timed out while checking Codex CLI.");
+    }
+}
@@ -0,0 +1,847 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use crate::backend::events::{AppServerEvent, EventSink, TerminalExit, TerminalOutput};
+
+/// Size at which a recording is rotated to `<path>.1` and restarted.
+pub(crate) const MAX_EVENT_RECORDING_BYTES: u64 = 8 * 1024 * 1024;
+/// Finished turn ids remembered so a late `turn/start` response can't revive
+/// a turn whose end event already went by.
+const FINISHED_TURNS_REMEMBERED: usize = 64;
+/// Turns whose tool usage is kept for `get_turn_summary`; the oldest is
+/// dropped first.
+const TURN_SUMMARIES_REMEMBERED: usize = 64;
+
+#[derive(Default)]
+struct ActiveTurnsState {
+    /// Turn id -> thread id.
+    active: HashMap<String, String>,
+    finished: VecDeque<String>,
+}
+
+/// Turns a session has started and not yet seen end, so they can all be
+/// interrupted at once.
+#[derive(Default)]
+pub(crate) struct ActiveTurns {
+    state: Mutex<ActiveTurnsState>,
+}
+
+fn event_turn_id(params: &Value) -> Option<&str> {
+    params
+        .get("turnId")
+        .or_else(|| params.get("turn").and_then(|turn| turn.get("id")))
+        .and_then(Value::as_str)
+}
+
+impl ActiveTurns {
+    pub(crate) fn start(&self, thread_id: &str, turn_id: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.finished.iter().any(|id| id == turn_id) {
+            return;
+        }
+        state
+            .active
+            .insert(turn_id.to_string(), thread_id.to_string());
+    }
+
+    pub(crate) fn finish(&self, turn_id: &str) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.active.remove(turn_id);
+        if state.finished.len() >= FINISHED_TURNS_REMEMBERED {
+            state.finished.pop_front();
+        }
+        state.finished.push_back(turn_id.to_string());
+    }
+
+    /// `(thread_id, turn_id)` pairs still in flight, ordered by thread.
+    pub(crate) fn snapshot(&self) -> Vec<(String, String)> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let mut turns: Vec<(String, String)> = state
+            .active
+            .iter()
+            .map(|(turn_id, thread_id)| (thread_id.clone(), turn_id.clone()))
+            .collect();
+        turns.sort();
+        turns
+    }
+
+    /// Updates the set from a turn lifecycle notification. A `turn/error`
+    /// that will be retried leaves its turn running.
+    pub(crate) fn observe(&self, message: &Value) {
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return;
+        };
+        let params = message.get("params").unwrap_or(&Value::Null);
+        let Some(turn_id) = event_turn_id(params) else {
+            return;
+        };
+        match method {
+            "turn/started" => {
+                if let Some(thread_id) = params.get("threadId").and_then(Value::as_str) {
+                    self.start(thread_id, turn_id);
+                }
+            }
+            "turn/completed" | "turn/interrupted" => self.finish(turn_id),
+            "turn/error" if params.get("willRetry").and_then(Value::as_bool) != Some(true) => {
+                self.finish(turn_id)
+            }
+            _ => {}
+        }
+    }
+}
+
+struct TurnRecord {
+    thread_id: String,
+    started: Instant,
+    duration: Option<Duration>,
+    /// Tool name -> uses, in order of first use.
+    tools: Vec<(String, u64)>,
+    /// Item ids already counted, so a tool seen in both `item/started` and
+    /// `item/completed` counts once.
+    counted_items: HashSet<String>,
+}
+
+#[derive(Default)]
+struct TurnSummariesState {
+    turns: HashMap<String, TurnRecord>,
+    order: VecDeque<String>,
+}
+
+/// Tool invocations and duration of the session's recent turns, built from
+/// the item and turn events that pass through the recorder.
+#[derive(Default)]
+pub(crate) struct TurnSummaries {
+    state: Mutex<TurnSummariesState>,
+}
+
+/// Name a tool item is counted under, or `None` for items that aren't tool
+/// calls (messages, reasoning, plans, ...). Claude `tool_use` items carry the
+/// tool's own name (`Read`, `Bash`, `mcp__server__tool`); Codex items are
+/// counted by type, with MCP calls as `server/tool`.
+fn tool_name(item: &Value) -> Option<String> {
+    let text = |key: &str| {
+        item.get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    match text("type")? {
+        "tool_use" => Some(text("name").unwrap_or("tool").to_string()),
+        "mcpToolCall" => Some(match (text("server"), text("tool")) {
+            (Some(server), Some(tool)) => format!("{server}/{tool}"),
+            (server, tool) => tool.or(server).unwrap_or("mcpToolCall").to_string(),
+        }),
+        "collabToolCall" | "collabAgentToolCall" => {
+            Some(text("tool").unwrap_or("collabToolCall").to_string())
+        }
+        kind @ ("commandExecution" | "fileChange" | "webSearch" | "imageView") => {
+            Some(kind.to_string())
+        }
+        _ => None,
+    }
+}
+
+impl TurnSummaries {
+    /// Updates the turn an item or lifecycle notification belongs to.
+    pub(crate) fn observe(&self, message: &Value) {
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return;
+        };
+        let params = message.get("params").unwrap_or(&Value::Null);
+        let Some(turn_id) = event_turn_id(params) else {
+            return;
+        };
+        let ends_turn = match method {
+            "turn/completed" | "turn/interrupted" => true,
+            "turn/error" => params.get("willRetry").and_then(Value::as_bool) != Some(true),
+            "turn/started" | "item/started" | "item/completed" => false,
+            _ => return,
+        };
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if !state.turns.contains_key(turn_id) {
+            if state.order.len() >= TURN_SUMMARIES_REMEMBERED {
+                if let Some(oldest) = state.order.pop_front() {
+                    state.turns.remove(&oldest);
+                }
+            }
+            state.order.push_back(turn_id.to_string());
+            state.turns.insert(
+                turn_id.to_string(),
+                TurnRecord {
+                    thread_id: params
+                        .get("threadId")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    started: Instant::now(),
+                    duration: None,
+                    tools: Vec::new(),
+                    counted_items: HashSet::new(),
+                },
+            );
+        }
+        let Some(record) = state.turns.get_mut(turn_id) else {
+            return;
+        };
+        if ends_turn {
+            if record.duration.is_none() {
+                // Prefer the CLI's own timing (Claude's `result` reports it).
+                let reported = params
+                    .get("durationMs")
+                    .and_then(Value::as_u64)
+                    .map(Duration::from_millis);
+                record.duration = Some(reported.unwrap_or_else(|| record.started.elapsed()));
+            }
+            return;
+        }
+        let Some(item) = params.get("item") else {
+            return;
+        };
+        let Some(name) = tool_name(item) else {
+            return;
+        };
+        if let Some(item_id) = item.get("id").and_then(Value::as_str) {
+            if !record.counted_items.insert(item_id.to_string()) {
+                return;
+            }
+        }
+        match record.tools.iter_mut().find(|(tool, _)| *tool == name) {
+            Some((_, count)) => *count += 1,
+            None => record.tools.push((name, 1)),
+        }
+    }
+
+    /// `{ threadId, turnId, tools: [{ name, count }], durationMs, completed }`
+    /// for a remembered turn. A turn still running reports its time so far.
+    pub(crate) fn summary(&self, thread_id: &str, turn_id: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let record = state
+            .turns
+            .get(turn_id)
+            .filter(|record| record.thread_id.is_empty() || record.thread_id == thread_id)?;
+        let tools: Vec<Value> = record
+            .tools
+            .iter()
+            .map(|(name, count)| json!({ "name": name, "count": count }))
+            .collect();
+        let duration = record.duration.unwrap_or_else(|| record.started.elapsed());
+        Some(json!({
+            "threadId": thread_id,
+            "turnId": turn_id,
+            "tools": tools,
+            "durationMs": duration.as_millis() as u64,
+            "completed": record.duration.is_some(),
+        }))
+    }
+}
+
+/// Streaming notifications a `DeltaThrottle` may merge.
+const THROTTLED_DELTA_METHODS: &[&str] = &[
+    "item/agentMessage/delta",
+    "item/commandExecution/outputDelta",
+    "item/fileChange/outputDelta",
+    "item/plan/delta",
+    "item/reasoning/summaryTextDelta",
+    "item/reasoning/textDelta",
+];
+
+struct PendingDelta {
+    /// Method and params without `delta`; only identical keys merge.
+    key: Value,
+    event: AppServerEvent,
+    since: Instant,
+    generation: u64,
+}
+
+#[derive(Default)]
+struct DeltaThrottleState {
+    interval: Duration,
+    pending: Option<PendingDelta>,
+    generation: u64,
+}
+
+/// Coalesces consecutive deltas for the same item that arrive within the
+/// interval into one event with the combined text. Any other event flushes
+/// the held delta first, so ordering is preserved. A zero interval (the
+/// default) passes every event straight through.
+#[derive(Default)]
+pub(crate) struct DeltaThrottle {
+    state: Mutex<DeltaThrottleState>,
+}
+
+fn delta_key(event: &AppServerEvent) -> Option<(Value, String)> {
+    let method = event.message.get("method")?.as_str()?;
+    if !THROTTLED_DELTA_METHODS.contains(&method) {
+        return None;
+    }
+    let mut params = event.message.get("params")?.as_object()?.clone();
+    let delta = params.remove("delta")?.as_str()?.to_string();
+    Some((json!({ "method": method, "params": params }), delta))
+}
+
+impl DeltaThrottle {
+    pub(crate) fn interval(&self) -> Duration {
+        self.state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .interval
+    }
+
+    pub(crate) fn set_interval(&self, interval: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.interval = interval;
+    }
+
+    /// Emits `event` through `sink`, or holds it back to merge with the
+    /// deltas that follow. Returns the generation and interval of a newly
+    /// held delta, which must be passed to `flush` once the interval is up.
+    fn emit<E: EventSink>(&self, event: AppServerEvent, sink: &E) -> Option<(u64, Duration)> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let throttled = if state.interval.is_zero() {
+            None
+        } else {
+            delta_key(&event)
+        };
+        let Some((key, delta)) = throttled else {
+            if let Some(pending) = state.pending.take() {
+                sink.emit_app_server_event(pending.event);
+            }
+            sink.emit_app_server_event(event);
+            return None;
+        };
+        let interval = state.interval;
+        if let Some(pending) = state.pending.as_mut().filter(|pending| pending.key == key) {
+            if let Some(Value::String(text)) = pending.event.message.pointer_mut("/params/delta") {
+                text.push_str(&delta);
+            }
+            if pending.since.elapsed() >= interval {
+                if let Some(pending) = state.pending.take() {
+                    sink.emit_app_server_event(pending.event);
+                }
+            }
+            return None;
+        }
+        if let Some(pending) = state.pending.take() {
+            sink.emit_app_server_event(pending.event);
+        }
+        state.generation += 1;
+        let generation = state.generation;
+        state.pending = Some(PendingDelta {
+            key,
+            event,
+            since: Instant::now(),
+            generation,
+        });
+        Some((generation, interval))
+    }
+
+    /// Emits the held delta if it is still the one from `generation`.
+    fn flush<E: EventSink>(&self, generation: u64, sink: &E) {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.generation == generation)
+        {
+            if let Some(pending) = state.pending.take() {
+                sink.emit_app_server_event(pending.event);
+            }
+        }
+    }
+}
+
+struct RecordingTarget {
+    path: PathBuf,
+    file: File,
+    written: u64,
+}
+
+/// Appends a session's events as JSONL while a user-started recording is
+/// active. Used to capture raw CLI traffic when debugging protocol issues.
+/// Every event passes through it, so it also tracks the session's turns and
+/// their tool usage, and holds the throttle for its deltas.
+pub(crate) struct EventRecorder {
+    target: Mutex<Option<RecordingTarget>>,
+    max_bytes: u64,
+    turns: ActiveTurns,
+    summaries: TurnSummaries,
+    throttle: DeltaThrottle,
+}
+
+impl Default for EventRecorder {
+    fn default() -> Self {
+        Self::with_max_bytes(MAX_EVENT_RECORDING_BYTES)
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_os_string();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+fn open_append(path: &Path) -> Result<(File, u64), String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| format!("Failed to open {}: {err}", path.display()))?;
+    let written = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    Ok((file, written))
+}
+
+impl EventRecorder {
+    pub(crate) fn with_max_bytes(max_bytes: u64) -> Self {
+        Self {
+            target: Mutex::new(None),
+            max_bytes,
+            turns: ActiveTurns::default(),
+            summaries: TurnSummaries::default(),
+            throttle: DeltaThrottle::default(),
+        }
+    }
+
+    pub(crate) fn active_turns(&self) -> &ActiveTurns {
+        &self.turns
+    }
+
+    pub(crate) fn turn_summaries(&self) -> &TurnSummaries {
+        &self.summaries
+    }
+
+    pub(crate) fn delta_throttle(&self) -> &DeltaThrottle {
+        &self.throttle
+    }
+
+    /// Starts appending to `path`, replacing any recording already running.
+    pub(crate) fn start(&self, path: &Path) -> Result<(), String> {
+        if !path.is_absolute() {
+            return Err("Recording path must be absolute".to_string());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create {}: {err}", parent.display()))?;
+        }
+        let (file, written) = open_append(path)?;
+        let mut target = self.target.lock().unwrap_or_else(|err| err.into_inner());
+        *target = Some(RecordingTarget {
+            path: path.to_path_buf(),
+            file,
+            written,
+        });
+        Ok(())
+    }
+
+    /// Stops recording. Returns the path that was being written, if any.
+    pub(crate) fn stop(&self) -> Option<PathBuf> {
+        let mut target = self.target.lock().unwrap_or_else(|err| err.into_inner());
+        target.take().map(|target| target.path)
+    }
+
+    pub(crate) fn recording_path(&self) -> Option<PathBuf> {
+        let target = self.target.lock().unwrap_or_else(|err| err.into_inner());
+        target.as_ref().map(|target| target.path.clone())
+    }
+
+    /// Tracks turn lifecycle events and appends `event` when recording. Write
+    /// failures end the recording so a full disk doesn't turn every event
+    /// into an error.
+    pub(crate) fn record(&self, event: &AppServerEvent) {
+        self.turns.observe(&event.message);
+        self.summaries.observe(&event.message);
+        let mut guard = self.target.lock().unwrap_or_else(|err| err.into_inner());
+        let Some(target) = guard.as_mut() else {
+            return;
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let mut line = json!({
+            "ts": timestamp,
+            "workspaceId": event.workspace_id,
+            "message": event.message,
+        })
+        .to_string();
+        line.push('\n');
+        let len = line.len() as u64;
+        if target.written > 0 && target.written + len > self.max_bytes {
+            if let Err(err) = rotate(target) {
+                eprintln!("event recording stopped: {err}");
+                *guard = None;
+                return;
+            }
+        }
+        if let Err(err) = target.file.write_all(line.as_bytes()) {
+            eprintln!(
+                "event recording stopped: failed to write {}: {err}",
+                target.path.display()
+            );
+            *guard = None;
+            return;
+        }
+        target.written += len;
+    }
+}
+
+fn rotate(target: &mut RecordingTarget) -> Result<(), String> {
+    let rotated = rotated_path(&target.path);
+    fs::rename(&target.path, &rotated)
+        .map_err(|err| format!("failed to rotate {}: {err}", target.path.display()))?;
+    let (file, written) = open_append(&target.path)?;
+    target.file = file;
+    target.written = written;
+    Ok(())
+}
+
+/// Wraps a sink so every app-server event also goes to the session's
+/// recorder before it is emitted, and deltas pass through its throttle.
+#[derive(Clone)]
+pub(crate) struct RecordingEventSink<E> {
+    inner: E,
+    recorder: Arc<EventRecorder>,
+}
+
+impl<E: EventSink> RecordingEventSink<E> {
+    pub(crate) fn new(inner: E, recorder: Arc<EventRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl<E: EventSink> EventSink for RecordingEventSink<E> {
+    fn emit_app_server_event(&self, event: AppServerEvent) {
+        self.recorder.record(&event);
+        let throttle = self.recorder.delta_throttle();
+        let Some((generation, interval)) = throttle.emit(event, &self.inner) else {
+            return;
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let sink = self.clone();
+                runtime.spawn(async move {
+                    tokio::time::sleep(interval).await;
+                    sink.recorder
+                        .delta_throttle()
+                        .flush(generation, &sink.inner);
+                });
+            }
+            Err(_) => throttle.flush(generation, &self.inner),
+        }
+    }
+
+    fn emit_terminal_output(&self, event: TerminalOutput) {
+        self.inner.emit_terminal_output(event);
+    }
+
+    fn emit_terminal_exit(&self, event: TerminalExit) {
+        self.inner.emit_terminal_exit(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rotated_path, EventRecorder, RecordingEventSink};
+    use crate::backend::events::{AppServerEvent, EventSink, TerminalExit, TerminalOutput};
+    use serde_json::{json, Value};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct CollectingSink {
+        events: Arc<Mutex<Vec<AppServerEvent>>>,
+    }
+
+    impl EventSink for CollectingSink {
+        fn emit_app_server_event(&self, event: AppServerEvent) {
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn emit_terminal_output(&self, _event: TerminalOutput) {}
+
+        fn emit_terminal_exit(&self, _event: TerminalExit) {}
+    }
+
+    fn temp_recording_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("event-recording-{}", uuid::Uuid::new_v4()))
+            .join("events.jsonl")
+    }
+
+    fn event(method: &str) -> AppServerEvent {
+        AppServerEvent {
+            workspace_id: "ws-1".to_string(),
+            message: json!({ "method": method, "params": {} }),
+        }
+    }
+
+    fn recorded_methods(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| {
+                let value: Value = serde_json::from_str(line).expect("jsonl line");
+                assert_eq!(value["workspaceId"], "ws-1");
+                value["message"]["method"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_events_only_while_active() {
+        let path = temp_recording_path();
+        let recorder = Arc::new(EventRecorder::default());
+        let inner = CollectingSink::default();
+        let sink = RecordingEventSink::new(inner.clone(), Arc::clone(&recorder));
+
+        sink.emit_app_server_event(event("before/start"));
+        recorder.start(&path).expect("start recording");
+        assert_eq!(recorder.recording_path().as_ref(), Some(&path));
+        sink.emit_app_server_event(event("turn/started"));
+        sink.emit_app_server_event(event("codex/parseError"));
+        assert_eq!(recorder.stop().as_ref(), Some(&path));
+        sink.emit_app_server_event(event("after/stop"));
+
+        assert_eq!(
+            recorded_methods(&path),
+            vec!["turn/started".to_string(), "codex/parseError".to_string()]
+        );
+        assert_eq!(inner.events.lock().unwrap().len(), 4);
+        assert!(recorder.recording_path().is_none());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn rotates_when_recording_exceeds_cap() {
+        let path = temp_recording_path();
+        let recorder = EventRecorder::with_max_bytes(120);
+        recorder.start(&path).expect("start recording");
+
+        recorder.record(&event("first"));
+        recorder.record(&event("second"));
+
+        assert_eq!(
+            recorded_methods(&rotated_path(&path)),
+            vec!["first".to_string()]
+        );
+        assert_eq!(recorded_methods(&path), vec!["second".to_string()]);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn rejects_relative_paths() {
+        let recorder = EventRecorder::default();
+        assert!(recorder.start(Path::new("events.jsonl")).is_err());
+        assert!(recorder.recording_path().is_none());
+    }
+
+    fn delta(item_id: &str, text: &str) -> AppServerEvent {
+        AppServerEvent {
+            workspace_id: "ws-1".to_string(),
+            message: json!({
+                "method": "item/agentMessage/delta",
+                "params": { "threadId": "t1", "itemId": item_id, "delta": text }
+            }),
+        }
+    }
+
+    fn emitted(inner: &CollectingSink) -> Vec<(String, Option<String>)> {
+        inner
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| {
+                (
+                    event.message["method"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    event.message["params"]["delta"]
+                        .as_str()
+                        .map(str::to_string),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn throttle_coalesces_rapid_deltas_per_item() {
+        let recorder = Arc::new(EventRecorder::default());
+        let inner = CollectingSink::default();
+        let sink = RecordingEventSink::new(inner.clone(), Arc::clone(&recorder));
+        recorder
+            .delta_throttle()
+            .set_interval(std::time::Duration::from_secs(60));
+
+        for text in ["Hel", "lo, ", "world"] {
+            sink.emit_app_server_event(delta("msg-1", text));
+        }
+        assert!(emitted(&inner).is_empty());
+        sink.emit_app_server_event(delta("msg-2", "Next"));
+        sink.emit_app_server_event(delta("msg-2", " item"));
+        sink.emit_app_server_event(event("item/completed"));
+
+        let delta_method = "item/agentMessage/delta".to_string();
+        assert_eq!(
+            emitted(&inner),
+            vec![
+                (delta_method.clone(), Some("Hello, world".to_string())),
+                (delta_method, Some("Next item".to_string())),
+                ("item/completed".to_string(), None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn throttle_flushes_held_delta_after_interval() {
+        let recorder = Arc::new(EventRecorder::default());
+        let inner = CollectingSink::default();
+        let sink = RecordingEventSink::new(inner.clone(), Arc::clone(&recorder));
+        let interval = std::time::Duration::from_millis(20);
+        recorder.delta_throttle().set_interval(interval);
+
+        sink.emit_app_server_event(delta("msg-1", "a"));
+        sink.emit_app_server_event(delta("msg-1", "b"));
+        tokio::time::sleep(interval * 5).await;
+        sink.emit_app_server_event(delta("msg-1", "c"));
+        tokio::time::sleep(interval * 5).await;
+
+        let delta_method = "item/agentMessage/delta".to_string();
+        assert_eq!(
+            emitted(&inner),
+            vec![
+                (delta_method.clone(), Some("ab".to_string())),
+                (delta_method, Some("c".to_string())),
+            ]
+        );
+
+        recorder
+            .delta_throttle()
+            .set_interval(std::time::Duration::ZERO);
+        sink.emit_app_server_event(delta("msg-1", "d"));
+        assert_eq!(emitted(&inner).len(), 3);
+    }
+
+    #[test]
+    fn tracks_turns_from_lifecycle_events() {
+        let recorder = EventRecorder::default();
+        let turn_event = |method: &str, params: Value| AppServerEvent {
+            workspace_id: "ws-1".to_string(),
+            message: json!({ "method": method, "params": params }),
+        };
+        recorder.record(&turn_event(
+            "turn/started",
+            json!({ "threadId": "t1", "turn": { "id": "turn-1" } }),
+        ));
+        recorder.record(&turn_event(
+            "turn/started",
+            json!({ "threadId": "t2", "turnId": "turn-2" }),
+        ));
+        recorder.record(&turn_event(
+            "turn/error",
+            json!({ "threadId": "t2", "turnId": "turn-2", "willRetry": true }),
+        ));
+        let turns = recorder.active_turns();
+        assert_eq!(
+            turns.snapshot(),
+            vec![
+                ("t1".to_string(), "turn-1".to_string()),
+                ("t2".to_string(), "turn-2".to_string())
+            ]
+        );
+
+        recorder.record(&turn_event(
+            "turn/completed",
+            json!({ "threadId": "t1", "turn": { "id": "turn-1" } }),
+        ));
+        recorder.record(&turn_event(
+            "turn/error",
+            json!({ "threadId": "t2", "turnId": "turn-2", "willRetry": false }),
+        ));
+        assert!(turns.snapshot().is_empty());
+
+        // A `turn/start` response processed after the turn already ended.
+        turns.start("t1", "turn-1");
+        assert!(turns.snapshot().is_empty());
+    }
+
+    #[test]
+    fn summarizes_tool_usage_per_turn() {
+        let recorder = EventRecorder::default();
+        let item_event = |method: &str, turn_id: &str, item: Value| AppServerEvent {
+            workspace_id: "ws-1".to_string(),
+            message: json!({
+                "method": method,
+                "params": { "threadId": "t1", "turnId": turn_id, "item": item }
+            }),
+        };
+        recorder.record(&AppServerEvent {
+            workspace_id: "ws-1".to_string(),
+            message: json!({
+                "method": "turn/started",
+                "params": { "threadId": "t1", "turnId": "turn-1" }
+            }),
+        });
+        for (id, name) in [("tu1", "Read"), ("tu2", "Bash"), ("tu3", "Read")] {
+            let item = json!({ "id": id, "type": "tool_use", "name": name });
+            recorder.record(&item_event("item/started", "turn-1", item));
+            let completed = json!({ "id": id, "type": "tool_use" });
+            recorder.record(&item_event("item/completed", "turn-1", completed));
+        }
+        recorder.record(&item_event(
+            "item/completed",
+            "turn-1",
+            json!({ "id": "mcp1", "type": "mcpToolCall", "server": "docs", "tool": "search" }),
+        ));
+        recorder.record(&item_event(
+            "item/completed",
+            "turn-1",
+            json!({ "id": "msg1", "type": "agentMessage", "text": "done" }),
+        ));
+        recorder.record(&item_event(
+            "item/started",
+            "turn-2",
+            json!({ "id": "cmd1", "type": "commandExecution" }),
+        ));
+
+        let summaries = recorder.turn_summaries();
+        let running = summaries.summary("t1", "turn-1").expect("turn-1 summary");
+        assert_eq!(running["completed"], false);
+        recorder.record(&AppServerEvent {
+            workspace_id: "ws-1".to_string(),
+            message: json!({
+                "method": "turn/completed",
+                "params": { "threadId": "t1", "turnId": "turn-1", "durationMs": 1234 }
+            }),
+        });
+
+        let summary = summaries.summary("t1", "turn-1").expect("turn-1 summary");
+        assert_eq!(
+            summary["tools"],
+            json!([
+                { "name": "Read", "count": 2 },
+                { "name": "Bash", "count": 1 },
+                { "name": "docs/search", "count": 1 }
+            ])
+        );
+        assert_eq!(summary["durationMs"], 1234);
+        assert_eq!(summary["completed"], true);
+        assert_eq!(
+            summaries.summary("t1", "turn-2").unwrap()["tools"],
+            json!([{ "name": "commandExecution", "count": 1 }])
+        );
+        assert!(summaries.summary("other-thread", "turn-1").is_none());
+        assert!(summaries.summary("t1", "missing").is_none());
+    }
+}
@@ -1,6 +1,60 @@
 use serde::Serialize;
 use serde_json::Value;
 
+/// Notification methods the frontend knows how to handle. Keep in sync with
+/// `SUPPORTED_APP_SERVER_METHODS` in `src/utils/appServerEvents.ts`.
+pub(crate) const SUPPORTED_APP_SERVER_METHODS: &[&str] = &[
+    "account/login/completed",
+    "account/rateLimits/updated",
+    "account/updated",
+    "approval/timedOut",
+    "cli/unhealthy",
+    "codex/backgroundThread",
+    "codex/connected",
+    "codex/event/skills_update_available",
+    "error",
+    "item/agentMessage/delta",
+    "item/commandExecution/outputDelta",
+    "item/commandExecution/terminalInteraction",
+    "item/completed",
+    "item/fileChange/outputDelta",
+    "item/plan/delta",
+    "item/reasoning/summaryPartAdded",
+    "item/reasoning/summaryTextDelta",
+    "item/reasoning/textDelta",
+    "item/started",
+    "item/tool/requestUserInput",
+    "item/toolInput/delta",
+    "thread/compacted",
+    "thread/name/updated",
+    "thread/started",
+    "thread/tokenUsage/updated",
+    "turn/completed",
+    "turn/diff/updated",
+    "turn/error",
+    "turn/interrupted",
+    "turn/plan/updated",
+    "turn/retrying",
+    "turn/started",
+    "workspace/missing",
+];
+
+/// Marks notifications whose method is outside the supported set with
+/// `"unsupported": true` so the UI can log them instead of losing them.
+pub(crate) fn tag_unsupported_method(message: &mut Value) {
+    let Some(object) = message.as_object_mut() else {
+        return;
+    };
+    let supported = object
+        .get("method")
+        .and_then(|method| method.as_str())
+        .map(|method| SUPPORTED_APP_SERVER_METHODS.contains(&method.trim()))
+        .unwrap_or(true);
+    if !supported {
+        object.insert("unsupported".to_string(), Value::Bool(true));
+    }
+}
+
 #[derive(Serialize, Clone)]
 pub(crate) struct AppServerEvent {
     pub(crate) workspace_id: String,
@@ -29,3 +83,26 @@ pub(crate) trait EventSink: Clone + Send + Sync + 'static {
     fn emit_terminal_output(&self, event: TerminalOutput);
     fn emit_terminal_exit(&self, event: TerminalExit);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::tag_unsupported_method;
+    use serde_json::json;
+
+    #[test]
+    fn known_method_passes_through_untagged() {
+        let mut message = json!({ "method": "turn/completed", "params": { "threadId": "t1" } });
+        let original = message.clone();
+        tag_unsupported_method(&mut message);
+        assert_eq!(message, original);
+    }
+
+    #[test]
+    fn unknown_method_is_tagged_not_dropped() {
+        let mut message = json!({ "method": "thread/somethingNew", "params": { "a": 1 } });
+        tag_unsupported_method(&mut message);
+        assert_eq!(message["unsupported"], true);
+        assert_eq!(message["method"], "thread/somethingNew");
+        assert_eq!(message["params"]["a"], 1);
+    }
+}
@@ -1,8 +1,11 @@
 use serde_json::{json, Value};
 use std::sync::Arc;
 
-use crate::backend::adapter_base::{build_adapter_command, spawn_adapter_session, CliProfile};
+use crate::backend::adapter_base::{
+    build_adapter_command, reasoning_delta_event, spawn_adapter_session, CliProfile,
+};
 use crate::backend::app_server::{CliSpawnConfig, WorkspaceSession};
+use crate::backend::errors::AppServerError;
 use crate::backend::events::EventSink;
 use crate::types::WorkspaceEntry;
 
@@ -29,15 +32,7 @@ impl CliProfile for GeminiProfile {
     }
 
     fn model_list(&self) -> Value {
-        json!({
-            "result": {
-                "models": [
-                    { "id": "gemini-2.5-flash", "name": "Gemini 2.5 Flash" },
-                    { "id": "gemini-2.5-pro", "name": "Gemini 2.5 Pro" }
-                ],
-                "defaultModel": "gemini-2.5-flash"
-            }
-        })
+        gemini_model_list()
     }
 
     fn provider_name(&self) -> &str {
@@ -45,22 +40,71 @@ impl CliProfile for GeminiProfile {
     }
 }
 
+fn gemini_model_list() -> Value {
+    json!({
+        "result": {
+            "models": [
+                { "id": "gemini-2.5-flash", "name": "Gemini 2.5 Flash" },
+                { "id": "gemini-2.5-pro", "name": "Gemini 2.5 Pro" }
+            ],
+            "defaultModel": "gemini-2.5-flash"
+        }
+    })
+}
+
+/// Known Gemini models, tagged `source: "fallback"`, for when `model/list`
+/// fails and the picker would otherwise be empty.
+pub(crate) fn gemini_fallback_model_list() -> Value {
+    let mut list = gemini_model_list();
+    list["result"]["source"] = json!("fallback");
+    list
+}
+
+/// Maps a workspace sandbox policy onto Gemini's `--sandbox` and
+/// `--approval-mode` flags. No policy leaves Gemini on its own defaults.
+/// Without `use_sandbox` only the approval mode is passed.
+fn gemini_sandbox_args(policy: Option<&str>, use_sandbox: bool) -> Vec<String> {
+    let (sandboxed, approval_mode) = match policy {
+        Some("readOnly") => (true, "default"),
+        Some("workspaceWrite") => (true, "auto_edit"),
+        Some("full") => (false, "yolo"),
+        _ => return Vec::new(),
+    };
+    let mut args = Vec::new();
+    if sandboxed && use_sandbox {
+        args.push("--sandbox".to_string());
+    }
+    args.push("--approval-mode".to_string());
+    args.push(approval_mode.to_string());
+    args
+}
+
+/// Gemini has no flag that adds to its system prompt (`GEMINI_SYSTEM_MD`
+/// replaces it outright), so the workspace instruction leads each prompt.
+fn gemini_prompt_with_system_prompt(config: &CliSpawnConfig, prompt: &str) -> String {
+    match config.system_prompt() {
+        Some(system_prompt) => format!("{system_prompt}\n\n{prompt}"),
+        None => prompt.to_string(),
+    }
+}
+
 pub(crate) fn build_gemini_command(
     config: &CliSpawnConfig,
     session_id: Option<&str>,
     prompt: &str,
     cwd: &str,
 ) -> Result<tokio::process::Command, String> {
-    let mut args = vec![
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "-p".to_string(),
-    ];
+    let mut args = vec!["--output-format".to_string(), "stream-json".to_string()];
+    args.extend(gemini_sandbox_args(
+        config.sandbox_policy.as_deref(),
+        !config.gemini_sandbox_disabled,
+    ));
+    args.push("-p".to_string());
     if let Some(sid) = session_id {
         args.push("--resume".to_string());
         args.push(sid.to_string());
     }
-    args.push(prompt.to_string());
+    args.push(gemini_prompt_with_system_prompt(config, prompt));
 
     let home_env = config.cli_home.as_ref().map(|h| ("GEMINI_HOME", h));
     build_adapter_command(config, args, cwd, home_env)
@@ -77,6 +121,16 @@ pub(crate) fn parse_gemini_stream_line(
     let msg_item_id = format!("msg_{turn_id}");
 
     match event_type {
+        "thought" | "thinking" | "reasoning" => {
+            let text = gemini_reasoning_text(&event)?;
+            let item_id = format!("reasoning_{turn_id}");
+            Some(reasoning_delta_event(thread_id, turn_id, &item_id, &text))
+        }
+        "message" if event.get("thought").and_then(Value::as_bool) == Some(true) => {
+            let text = gemini_reasoning_text(&event)?;
+            let item_id = format!("reasoning_{turn_id}");
+            Some(reasoning_delta_event(thread_id, turn_id, &item_id, &text))
+        }
         "init" => Some(json!({
             "method": "turn/started",
             "params": {
@@ -144,13 +198,46 @@ pub(crate) fn parse_gemini_stream_line(
             "params": {
                 "threadId": thread_id,
                 "turnId": turn_id,
-                "durationMs": event.get("stats").and_then(|s| s.get("duration_ms"))
+                "durationMs": event.get("stats").and_then(|s| s.get("duration_ms")),
+                "usage": {
+                    "inputTokens": event.get("stats").and_then(|s| s.get("input_tokens")),
+                    "outputTokens": event.get("stats").and_then(|s| s.get("output_tokens"))
+                }
             }
         })),
         _ => None,
     }
 }
 
+/// Text of a Gemini reasoning event: its `content`/`delta`/`text`, or a
+/// thought summary's `subject` and `description`, either inline or under
+/// `value`.
+fn gemini_reasoning_text(event: &Value) -> Option<String> {
+    let source = event
+        .get("value")
+        .filter(|v| v.is_object())
+        .unwrap_or(event);
+    let text = ["content", "delta", "text"]
+        .iter()
+        .find_map(|key| source.get(*key).and_then(Value::as_str))
+        .filter(|text| !text.is_empty());
+    if let Some(text) = text {
+        return Some(text.to_string());
+    }
+    let part = |key: &str| {
+        source
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+    };
+    match (part("subject"), part("description")) {
+        (Some(subject), Some(description)) => Some(format!("**{subject}**\n\n{description}")),
+        (Some(only), None) | (None, Some(only)) => Some(only.to_string()),
+        (None, None) => None,
+    }
+}
+
 fn extract_gemini_session_id(line: &str) -> Option<String> {
     let event: Value = serde_json::from_str(line).ok()?;
     if event.get("type")?.as_str()? != "init" {
@@ -166,13 +253,76 @@ pub(crate) async fn spawn_gemini_session<E: EventSink>(
     entry: WorkspaceEntry,
     config: CliSpawnConfig,
     event_sink: E,
-) -> Result<Arc<WorkspaceSession>, String> {
+) -> Result<Arc<WorkspaceSession>, AppServerError> {
     spawn_adapter_session(GeminiProfile, "Gemini", entry, config, event_sink).await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::adapter_base::{
+        GenericAdapterSession, ThreadMetadata, ThreadStore, THREAD_NOT_IN_SESSION,
+    };
+    use crate::backend::app_server::CliAdapter;
+    use crate::backend::test_support::test_workspace_entry;
+    use std::collections::HashMap;
+    use tokio::sync::Mutex;
+
+    fn test_adapter(store_path: &std::path::Path) -> GenericAdapterSession<GeminiProfile> {
+        let entry = test_workspace_entry("gemini-ws", "/tmp");
+        let config = CliSpawnConfig {
+            cli_type: "gemini".to_string(),
+            ..CliSpawnConfig::default()
+        };
+        GenericAdapterSession::new(
+            GeminiProfile,
+            &entry,
+            config,
+            Arc::new(|_| {}),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(store_path.to_path_buf())
+    }
+
+    #[tokio::test]
+    async fn resume_unknown_thread_reports_missing_from_session() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("gemini-resume-test-{}", uuid::Uuid::new_v4()));
+        let store_path = temp_dir.join("threads.json");
+        let adapter = test_adapter(&store_path);
+
+        let error = adapter
+            .send_request("thread/resume", json!({ "threadId": "old-thread" }))
+            .await
+            .expect_err("unknown thread should not resume");
+        assert_eq!(error, format!("{THREAD_NOT_IN_SESSION}: old-thread"));
+
+        // A thread written to the store after the session loaded is picked up.
+        let mut store = ThreadStore::default();
+        store.threads.insert(
+            "old-thread".to_string(),
+            ThreadMetadata {
+                cli_session_id: Some("gs-1".to_string()),
+                name: None,
+                created_at: 1000,
+                updated_at: 2000,
+                archived: false,
+                usage: Default::default(),
+                model: None,
+                reported_model: None,
+                first_prompt: None,
+                fork_pending: false,
+            },
+        );
+        store.save(&store_path).unwrap();
+        let resumed = adapter
+            .send_request("thread/resume", json!({ "threadId": "old-thread" }))
+            .await
+            .expect("thread from the store file resumes");
+        assert_eq!(resumed["result"]["resumable"], true);
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
 
     #[test]
     fn build_gemini_command_basic() {
@@ -181,6 +331,7 @@ mod tests {
             cli_bin: Some("gemini".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
         let result = build_gemini_command(&config, None, "hello", "/tmp");
         assert!(result.is_ok());
@@ -193,11 +344,110 @@ mod tests {
             cli_bin: Some("gemini".to_string()),
             cli_args: None,
             cli_home: None,
+            ..CliSpawnConfig::default()
         };
         let result = build_gemini_command(&config, Some("sess-1"), "hello", "/tmp");
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn build_gemini_command_applies_sandbox_policy() {
+        let args_for = |policy: Option<&str>| {
+            let config = CliSpawnConfig {
+                cli_type: "gemini".to_string(),
+                cli_bin: Some("gemini".to_string()),
+                sandbox_policy: policy.map(str::to_string),
+                ..CliSpawnConfig::default()
+            };
+            let command = build_gemini_command(&config, None, "hello", "/tmp").unwrap();
+            command
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let approval_mode = |args: &[String]| {
+            let flag = args.iter().position(|arg| arg == "--approval-mode")?;
+            args.get(flag + 1).cloned()
+        };
+
+        let read_only = args_for(Some("readOnly"));
+        assert!(read_only.contains(&"--sandbox".to_string()));
+        assert_eq!(approval_mode(&read_only).as_deref(), Some("default"));
+
+        let workspace_write = args_for(Some("workspaceWrite"));
+        assert!(workspace_write.contains(&"--sandbox".to_string()));
+        assert_eq!(
+            approval_mode(&workspace_write).as_deref(),
+            Some("auto_edit")
+        );
+
+        let full = args_for(Some("full"));
+        assert!(!full.contains(&"--sandbox".to_string()));
+        assert_eq!(approval_mode(&full).as_deref(), Some("yolo"));
+
+        let default = args_for(None);
+        assert!(!default.contains(&"--sandbox".to_string()));
+        assert!(!default.contains(&"--approval-mode".to_string()));
+        let prompt = default.iter().position(|arg| arg == "-p").unwrap();
+        assert_eq!(default[prompt + 1], "hello");
+    }
+
+    #[test]
+    fn build_gemini_command_skips_sandbox_when_disabled() {
+        let args_for = |disabled: bool| {
+            let config = CliSpawnConfig {
+                cli_type: "gemini".to_string(),
+                cli_bin: Some("gemini".to_string()),
+                sandbox_policy: Some("workspaceWrite".to_string()),
+                gemini_sandbox_disabled: disabled,
+                ..CliSpawnConfig::default()
+            };
+            let command = build_gemini_command(&config, None, "hello", "/tmp").unwrap();
+            command
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(args_for(false).contains(&"--sandbox".to_string()));
+        let plain = args_for(true);
+        assert!(!plain.contains(&"--sandbox".to_string()));
+        let flag = plain
+            .iter()
+            .position(|arg| arg == "--approval-mode")
+            .unwrap();
+        assert_eq!(plain[flag + 1], "auto_edit");
+    }
+
+    #[test]
+    fn build_gemini_command_leads_prompt_with_system_prompt() {
+        let prompt_for = |system_prompt: Option<&str>| {
+            let config = CliSpawnConfig {
+                cli_type: "gemini".to_string(),
+                cli_bin: Some("gemini".to_string()),
+                system_prompt: system_prompt.map(str::to_string),
+                ..CliSpawnConfig::default()
+            };
+            let command = build_gemini_command(&config, None, "hello", "/tmp").unwrap();
+            let args = command
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            args.last().cloned().unwrap()
+        };
+
+        assert_eq!(
+            prompt_for(Some(" Reply tersely. ")),
+            "Reply tersely.\n\nhello"
+        );
+        assert_eq!(prompt_for(Some("")), "hello");
+        assert_eq!(prompt_for(None), "hello");
+    }
+
     #[test]
     fn parse_init_event() {
         let line = r#"{"type":"init","session_id":"gs-1","model":"gemini-2.5-flash"}"#;
@@ -263,6 +513,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reasoning_variants_map_to_canonical_delta() {
+        let expected = |delta: &str| {
+            json!({
+                "method": "item/reasoning/textDelta",
+                "params": {
+                    "threadId": "t1",
+                    "turnId": "turn1",
+                    "itemId": "reasoning_turn1",
+                    "delta": delta
+                }
+            })
+        };
+        let cases = [
+            (
+                r#"{"type":"thought","value":{"subject":"Planning","description":"Read the tests first."}}"#,
+                "**Planning**\n\nRead the tests first.",
+            ),
+            (
+                r#"{"type":"thought","subject":"Checking imports"}"#,
+                "Checking imports",
+            ),
+            (
+                r#"{"type":"thinking","content":"Looking at main.rs"}"#,
+                "Looking at main.rs",
+            ),
+            (r#"{"type":"reasoning","delta":"so the fix"}"#, "so the fix"),
+            (
+                r#"{"type":"message","role":"assistant","thought":true,"content":"Hmm.","delta":true}"#,
+                "Hmm.",
+            ),
+        ];
+        for (line, delta) in cases {
+            assert_eq!(
+                parse_gemini_stream_line(line, "t1", "turn1"),
+                Some(expected(delta)),
+                "{line}"
+            );
+        }
+        assert!(
+            parse_gemini_stream_line(r#"{"type":"thought","value":{}}"#, "t1", "turn1").is_none()
+        );
+    }
+
     #[test]
     fn parse_unknown_event() {
         let line = r#"{"type":"debug","msg":"internal"}"#;
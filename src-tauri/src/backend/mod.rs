@@ -1,6 +1,11 @@
 pub(crate) mod adapter_base;
 pub(crate) mod app_server;
+pub(crate) mod approval_rules;
 pub(crate) mod claude_adapter;
 pub(crate) mod cursor_adapter;
+pub(crate) mod errors;
+pub(crate) mod event_recorder;
 pub(crate) mod events;
 pub(crate) mod gemini_adapter;
+#[cfg(test)]
+pub(crate) mod test_support;
@@ -0,0 +1,102 @@
+//! Shared fakes for tests that drive a `WorkspaceSession` without a CLI.
+
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::backend::app_server::CliAdapter;
+use crate::types::{WorkspaceEntry, WorkspaceKind, WorkspaceSettings};
+
+pub(crate) type RecordedRequests = Arc<Mutex<Vec<(String, Value)>>>;
+
+type Responder = Box<dyn Fn(&str, &Value) -> Result<Value, String> + Send + Sync>;
+
+/// `CliAdapter` that records requests (plus responses and `kill`, as
+/// `"response"` and `"kill"` entries) and answers them through a closure. By default every request succeeds with an
+/// empty `result`.
+pub(crate) struct TestAdapter {
+    cli_type: &'static str,
+    requests: RecordedRequests,
+    killed: Arc<AtomicBool>,
+    respond: Responder,
+}
+
+impl TestAdapter {
+    pub(crate) fn new(cli_type: &'static str) -> Self {
+        Self {
+            cli_type,
+            requests: RecordedRequests::default(),
+            killed: Arc::default(),
+            respond: Box::new(|_, _| Ok(json!({ "result": {} }))),
+        }
+    }
+
+    /// Records every request into `requests`.
+    pub(crate) fn recording(mut self, requests: RecordedRequests) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Sets `killed` once the session kills the adapter.
+    pub(crate) fn killed_flag(mut self, killed: Arc<AtomicBool>) -> Self {
+        self.killed = killed;
+        self
+    }
+
+    pub(crate) fn responding<F>(mut self, respond: F) -> Self
+    where
+        F: Fn(&str, &Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.respond = Box::new(respond);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl CliAdapter for TestAdapter {
+    async fn send_request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let response = (self.respond)(method, &params);
+        self.requests
+            .lock()
+            .unwrap()
+            .push((method.to_string(), params));
+        response
+    }
+
+    async fn send_notification(&self, _method: &str, _params: Option<Value>) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn send_response(&self, id: Value, result: Value) -> Result<(), String> {
+        self.requests.lock().unwrap().push((
+            "response".to_string(),
+            json!({ "id": id, "result": result }),
+        ));
+        Ok(())
+    }
+
+    async fn kill(&self) {
+        self.requests
+            .lock()
+            .unwrap()
+            .push(("kill".to_string(), Value::Null));
+        self.killed.store(true, Ordering::SeqCst);
+    }
+
+    fn cli_type(&self) -> &str {
+        self.cli_type
+    }
+}
+
+pub(crate) fn test_workspace_entry(id: &str, path: &str) -> WorkspaceEntry {
+    WorkspaceEntry {
+        id: id.to_string(),
+        name: id.to_string(),
+        path: path.to_string(),
+        codex_bin: None,
+        kind: WorkspaceKind::Main,
+        parent_id: None,
+        worktree: None,
+        settings: WorkspaceSettings::default(),
+    }
+}
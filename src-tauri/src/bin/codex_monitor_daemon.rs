@@ -7,6 +7,8 @@ mod codex_args;
 mod codex_home;
 #[path = "../codex/config.rs"]
 mod codex_config;
+#[path = "../cursor/stream.rs"]
+mod cursor_stream;
 #[path = "../files/io.rs"]
 mod file_io;
 #[path = "../files/ops.rs"]
@@ -40,6 +42,12 @@ mod codex {
     }
 }
 
+mod cursor {
+    pub(crate) mod stream {
+        pub(crate) use crate::cursor_stream::*;
+    }
+}
+
 mod files {
     pub(crate) mod io {
         pub(crate) use crate::file_io::*;
@@ -68,18 +76,20 @@ use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{broadcast, mpsc, Mutex};
 
 use backend::app_server::{
-    spawn_workspace_session, CliSpawnConfig, WorkspaceSession,
+    spawn_workspace_session, spawn_workspace_session_deferred, CliSpawnConfig, WorkspaceSession,
 };
 use backend::events::{AppServerEvent, EventSink, TerminalExit, TerminalOutput};
 use storage::{read_settings, read_workspaces};
 use shared::{
-    agent_profiles_core, codex_core, files_core, git_core, settings_core, workspaces_core,
-    worktree_core,
+    agent_profiles_core, codex_aux_core, codex_core, files_core, git_core, settings_core,
+    workspaces_core, worktree_core,
 };
 use shared::codex_core::CodexLoginCancelState;
+use shared::process_core::SystemStdioRequestRunner;
 use workspace_settings::apply_workspace_settings_update;
 use types::{
-    AppSettings, WorkspaceEntry, WorkspaceInfo, WorkspaceSettings, WorktreeSetupStatus,
+    AppSettings, SessionInfo, WorkspaceEntry, WorkspaceInfo, WorkspacePathValidation,
+    WorkspaceSettings, WorktreeSetupStatus,
 };
 
 const DEFAULT_LISTEN_ADDR: &str = "127.0.0.1:4732";
@@ -90,12 +100,24 @@ fn spawn_with_client(
     entry: WorkspaceEntry,
     config: CliSpawnConfig,
 ) -> impl std::future::Future<Output = Result<Arc<WorkspaceSession>, String>> {
-    spawn_workspace_session(
-        entry,
-        config,
-        client_version,
-        event_sink,
-    )
+    async move {
+        spawn_workspace_session(entry, config, client_version, event_sink)
+            .await
+            .map_err(String::from)
+    }
+}
+
+fn spawn_deferred_with_client(
+    event_sink: DaemonEventSink,
+    client_version: String,
+    entry: WorkspaceEntry,
+    config: CliSpawnConfig,
+) -> impl std::future::Future<Output = Result<Arc<WorkspaceSession>, String>> {
+    async move {
+        spawn_workspace_session_deferred(entry, config, client_version, event_sink)
+            .await
+            .map_err(String::from)
+    }
 }
 
 #[derive(Clone)]
@@ -141,6 +163,7 @@ struct DaemonState {
     app_settings: Mutex<AppSettings>,
     event_sink: DaemonEventSink,
     codex_login_cancels: Mutex<HashMap<String, CodexLoginCancelState>>,
+    run_metadata_cancels: codex_aux_core::BackgroundPromptCancels,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -164,6 +187,7 @@ impl DaemonState {
             app_settings: Mutex::new(app_settings),
             event_sink,
             codex_login_cancels: Mutex::new(HashMap::new()),
+            run_metadata_cancels: Mutex::new(HashMap::new()),
         }
     }
 
@@ -175,6 +199,10 @@ impl DaemonState {
         workspaces_core::is_workspace_path_dir_core(&path)
     }
 
+    async fn validate_workspace_path(&self, path: String) -> WorkspacePathValidation {
+        workspaces_core::validate_workspace_path_core(&path)
+    }
+
     async fn add_workspace(
         &self,
         path: String,
@@ -386,6 +414,7 @@ impl DaemonState {
         client_version: String,
     ) -> Result<WorkspaceInfo, String> {
         let client_version = client_version.clone();
+        let deferred_client_version = client_version.clone();
         workspaces_core::update_workspace_settings_core(
             id,
             settings,
@@ -404,6 +433,14 @@ impl DaemonState {
                     config,
                 )
             },
+            move |entry, config| {
+                spawn_deferred_with_client(
+                    self.event_sink.clone(),
+                    deferred_client_version.clone(),
+                    entry,
+                    config,
+                )
+            },
         )
         .await
     }
@@ -432,6 +469,19 @@ impl DaemonState {
         self.update_workspace_cli_bin(id, codex_bin).await
     }
 
+    async fn disconnect_workspace(&self, id: String) {
+        workspaces_core::disconnect_workspace_core(id, &self.sessions).await;
+    }
+
+    async fn preview_spawn_command(&self, workspace_id: String) -> Result<Value, String> {
+        workspaces_core::preview_spawn_command_core(
+            workspace_id,
+            &self.workspaces,
+            &self.app_settings,
+        )
+        .await
+    }
+
     async fn connect_workspace(&self, id: String, client_version: String) -> Result<(), String> {
         {
             let sessions = self.sessions.lock().await;
@@ -547,8 +597,21 @@ impl DaemonState {
         codex_core::resume_thread_core(&self.sessions, workspace_id, thread_id).await
     }
 
-    async fn fork_thread(&self, workspace_id: String, thread_id: String) -> Result<Value, String> {
-        codex_core::fork_thread_core(&self.sessions, workspace_id, thread_id).await
+    async fn fork_thread(
+        &self,
+        workspace_id: String,
+        thread_id: String,
+        name: Option<String>,
+    ) -> Result<Value, String> {
+        codex_core::fork_thread_core(&self.sessions, workspace_id, thread_id, name).await
+    }
+
+    async fn duplicate_thread(
+        &self,
+        workspace_id: String,
+        thread_id: String,
+    ) -> Result<Value, String> {
+        codex_core::duplicate_thread_core(&self.sessions, workspace_id, thread_id).await
     }
 
     async fn list_threads(
@@ -561,6 +624,21 @@ impl DaemonState {
         codex_core::list_threads_core(&self.sessions, workspace_id, cursor, limit, sort_key).await
     }
 
+    async fn list_all_threads(
+        &self,
+        cursor: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<Value, String> {
+        codex_core::list_all_threads_core(
+            &self.sessions,
+            &self.workspaces,
+            &self.app_settings,
+            cursor,
+            limit,
+        )
+        .await
+    }
+
     async fn list_mcp_server_status(
         &self,
         workspace_id: String,
@@ -578,6 +656,18 @@ impl DaemonState {
         codex_core::compact_thread_core(&self.sessions, workspace_id, thread_id).await
     }
 
+    async fn get_thread_usage(
+        &self,
+        workspace_id: String,
+        thread_id: String,
+    ) -> Result<Value, String> {
+        codex_core::get_thread_usage_core(&self.sessions, workspace_id, thread_id).await
+    }
+
+    async fn repair_thread_store(&self, workspace_id: String) -> Result<Value, String> {
+        codex_core::repair_thread_store_core(&self.sessions, &self.app_settings, workspace_id).await
+    }
+
     async fn set_thread_name(
         &self,
         workspace_id: String,
@@ -587,6 +677,10 @@ impl DaemonState {
         codex_core::set_thread_name_core(&self.sessions, workspace_id, thread_id, name).await
     }
 
+    async fn list_active_sessions(&self) -> Vec<SessionInfo> {
+        codex_core::list_active_sessions_core(&self.sessions).await
+    }
+
     async fn send_user_message(
         &self,
         workspace_id: String,
@@ -596,8 +690,20 @@ impl DaemonState {
         effort: Option<String>,
         access_mode: Option<String>,
         images: Option<Vec<String>>,
+        files: Option<Vec<String>>,
         collaboration_mode: Option<Value>,
+        cwd: Option<String>,
     ) -> Result<Value, String> {
+        let (warn_chars, model_aliases) = {
+            let settings = self.app_settings.lock().await;
+            (
+                settings.message_warn_chars,
+                settings.model_alias_map.clone(),
+            )
+        };
+        let default_approval_policy =
+            workspaces_core::workspace_default_approval_policy(&self.workspaces, &workspace_id)
+                .await;
         codex_core::send_user_message_core(
             &self.sessions,
             workspace_id,
@@ -607,7 +713,13 @@ impl DaemonState {
             effort,
             access_mode,
             images,
+            files,
             collaboration_mode,
+            cwd,
+            default_approval_policy,
+            warn_chars,
+            &model_aliases,
+            &self.event_sink,
         )
         .await
     }
@@ -621,6 +733,19 @@ impl DaemonState {
         codex_core::turn_interrupt_core(&self.sessions, workspace_id, thread_id, turn_id).await
     }
 
+    async fn interrupt_workspace(&self, workspace_id: String) -> Result<Value, String> {
+        codex_core::interrupt_workspace_core(&self.sessions, workspace_id, &self.event_sink).await
+    }
+
+    async fn get_turn_summary(
+        &self,
+        workspace_id: String,
+        thread_id: String,
+        turn_id: String,
+    ) -> Result<Value, String> {
+        codex_core::get_turn_summary_core(&self.sessions, workspace_id, thread_id, turn_id).await
+    }
+
     async fn start_review(
         &self,
         workspace_id: String,
@@ -633,13 +758,68 @@ impl DaemonState {
     }
 
     async fn model_list(&self, workspace_id: String) -> Result<Value, String> {
-        codex_core::model_list_core(&self.sessions, workspace_id).await
+        let model_aliases = self.app_settings.lock().await.model_alias_map.clone();
+        codex_core::model_list_core(&self.sessions, workspace_id, &model_aliases).await
     }
 
     async fn collaboration_mode_list(&self, workspace_id: String) -> Result<Value, String> {
         codex_core::collaboration_mode_list_core(&self.sessions, workspace_id).await
     }
 
+    async fn set_collaboration_mode(
+        &self,
+        workspace_id: String,
+        thread_id: String,
+        mode: Value,
+    ) -> Result<Value, String> {
+        codex_core::set_collaboration_mode_core(
+            &self.sessions,
+            &self.workspaces,
+            &self.storage_path,
+            workspace_id,
+            thread_id,
+            mode,
+        )
+        .await
+    }
+
+    async fn set_thread_model(
+        &self,
+        workspace_id: String,
+        thread_id: String,
+        model: Option<String>,
+    ) -> Result<Value, String> {
+        codex_core::set_thread_model_core(&self.sessions, workspace_id, thread_id, model).await
+    }
+
+    async fn get_collaboration_mode(
+        &self,
+        workspace_id: String,
+        thread_id: String,
+    ) -> Result<Value, String> {
+        codex_core::get_collaboration_mode_core(&self.sessions, workspace_id, thread_id).await
+    }
+
+    async fn start_event_recording(
+        &self,
+        workspace_id: String,
+        path: String,
+    ) -> Result<Value, String> {
+        codex_core::start_event_recording_core(&self.sessions, workspace_id, path).await
+    }
+
+    async fn stop_event_recording(&self, workspace_id: String) -> Result<Value, String> {
+        codex_core::stop_event_recording_core(&self.sessions, workspace_id).await
+    }
+
+    async fn set_event_throttle(
+        &self,
+        workspace_id: String,
+        min_interval_ms: u64,
+    ) -> Result<Value, String> {
+        codex_core::set_event_throttle_core(&self.sessions, workspace_id, min_interval_ms).await
+    }
+
     async fn account_rate_limits(&self, workspace_id: String) -> Result<Value, String> {
         codex_core::account_rate_limits_core(&self.sessions, workspace_id).await
     }
@@ -686,11 +866,137 @@ impl DaemonState {
         workspace_id: String,
         command: Vec<String>,
     ) -> Result<Value, String> {
-        codex_core::remember_approval_rule_core(&self.workspaces, workspace_id, command).await
+        codex_core::remember_approval_rule_core(
+            &self.workspaces,
+            &self.sessions,
+            workspace_id,
+            command,
+        )
+        .await
+    }
+
+    async fn clear_approval_rule(
+        &self,
+        workspace_id: String,
+        command: Vec<String>,
+    ) -> Result<Value, String> {
+        codex_core::clear_approval_rule_core(
+            &self.workspaces,
+            &self.sessions,
+            workspace_id,
+            command,
+        )
+        .await
+    }
+
+    async fn clear_all_approval_rules(&self, workspace_id: String) -> Result<Value, String> {
+        codex_core::clear_all_approval_rules_core(&self.workspaces, &self.sessions, workspace_id)
+            .await
+    }
+
+    fn hide_background_thread(&self, workspace_id: &str, thread_id: &str) {
+        self.event_sink.emit_app_server_event(AppServerEvent {
+            workspace_id: workspace_id.to_string(),
+            message: json!({
+                "method": "codex/backgroundThread",
+                "params": {
+                    "threadId": thread_id,
+                    "action": "hide"
+                }
+            }),
+        });
+    }
+
+    async fn get_commit_message_prompt(
+        &self,
+        workspace_id: String,
+        staged_only: bool,
+    ) -> Result<String, String> {
+        let max_diff_bytes = self.app_settings.lock().await.commit_message_diff_max_bytes;
+        codex_aux_core::commit_message_prompt_core(
+            &self.workspaces,
+            &workspace_id,
+            max_diff_bytes,
+            staged_only,
+        )
+        .await
+    }
+
+    async fn generate_commit_message(
+        &self,
+        workspace_id: String,
+        staged_only: bool,
+    ) -> Result<String, String> {
+        let (max_diff_bytes, keep_visible) = {
+            let settings = self.app_settings.lock().await;
+            (
+                settings.commit_message_diff_max_bytes,
+                settings.keep_background_threads_visible,
+            )
+        };
+        codex_aux_core::generate_commit_message_core(
+            &self.sessions,
+            &self.workspaces,
+            workspace_id,
+            max_diff_bytes,
+            staged_only,
+            |workspace_id, thread_id| self.hide_background_thread(workspace_id, thread_id),
+            keep_visible,
+        )
+        .await
+    }
+
+    async fn generate_run_metadata(
+        &self,
+        workspace_id: String,
+        prompt: String,
+    ) -> Result<Value, String> {
+        let keep_visible = self
+            .app_settings
+            .lock()
+            .await
+            .keep_background_threads_visible;
+        codex_aux_core::generate_run_metadata_core(
+            &self.sessions,
+            &self.run_metadata_cancels,
+            workspace_id,
+            &prompt,
+            |workspace_id, thread_id| self.hide_background_thread(workspace_id, thread_id),
+            keep_visible,
+        )
+        .await
+    }
+
+    async fn quick_prompt(
+        &self,
+        cli_type: String,
+        cwd: String,
+        prompt: String,
+        client_version: String,
+    ) -> Result<String, String> {
+        codex_aux_core::quick_prompt_core(
+            cli_type,
+            cwd,
+            prompt,
+            &self.app_settings,
+            |entry, config| {
+                spawn_with_client(self.event_sink.clone(), client_version, entry, config)
+            },
+        )
+        .await
+    }
+
+    async fn test_mcp_server(&self, command: String, args: Vec<String>) -> Result<Value, String> {
+        codex_aux_core::test_mcp_server_core(&SystemStdioRequestRunner, command, args).await
+    }
+
+    async fn generate_run_metadata_cancel(&self, workspace_id: String) -> Value {
+        codex_aux_core::cancel_background_prompt_core(&self.run_metadata_cancels, &workspace_id)
+            .await
     }
 
     async fn get_config_model(&self, workspace_id: String) -> Result<Value, String> {
-        codex_core::get_config_model_core(&self.workspaces, workspace_id).await
+        codex_core::get_config_model_core(&self.workspaces, &self.app_settings, workspace_id).await
     }
 }
 
@@ -1032,6 +1338,11 @@ async fn handle_rpc_request(
             let is_dir = state.is_workspace_path_dir(path).await;
             serde_json::to_value(is_dir).map_err(|err| err.to_string())
         }
+        "validate_workspace_path" => {
+            let path = parse_string(&params, "path")?;
+            let validation = state.validate_workspace_path(path).await;
+            serde_json::to_value(validation).map_err(|err| err.to_string())
+        }
         "add_workspace" => {
             let path = parse_string(&params, "path")?;
             let codex_bin = parse_optional_string(&params, "codex_bin");
@@ -1063,6 +1374,15 @@ async fn handle_rpc_request(
             state.connect_workspace(id, client_version).await?;
             Ok(json!({ "ok": true }))
         }
+        "disconnect_workspace" => {
+            let id = parse_string(&params, "id")?;
+            state.disconnect_workspace(id).await;
+            Ok(json!({ "ok": true }))
+        }
+        "preview_spawn_command" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            state.preview_spawn_command(workspace_id).await
+        }
         "remove_workspace" => {
             let id = parse_string(&params, "id")?;
             state.remove_workspace(id).await?;
@@ -1179,6 +1499,31 @@ async fn handle_rpc_request(
             let path = settings_core::get_codex_config_path_core()?;
             Ok(Value::String(path))
         }
+        "get_cli_home_path" => {
+            let cli_type = parse_string(&params, "cliType")?;
+            let path = settings_core::get_cli_home_path_core(&cli_type)?;
+            Ok(Value::String(path))
+        }
+        "get_gemini_setting" => {
+            let key = parse_string(&params, "key")?;
+            settings_core::get_gemini_setting_core(&key)
+        }
+        "set_gemini_setting" => {
+            let key = parse_string(&params, "key")?;
+            let value = params.get("value").cloned().unwrap_or(Value::Null);
+            settings_core::set_gemini_setting_core(&key, value)?;
+            Ok(json!({ "ok": true }))
+        }
+        "set_thread_store_dir" => {
+            let dir = parse_optional_string(&params, "dir");
+            settings_core::set_thread_store_dir_core(
+                dir,
+                &state.sessions,
+                &state.app_settings,
+                &state.settings_path,
+            )
+            .await
+        }
         "get_config_model" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
             state.get_config_model(workspace_id).await
@@ -1195,7 +1540,13 @@ async fn handle_rpc_request(
         "fork_thread" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
             let thread_id = parse_string(&params, "threadId")?;
-            state.fork_thread(workspace_id, thread_id).await
+            let name = parse_optional_string(&params, "name");
+            state.fork_thread(workspace_id, thread_id, name).await
+        }
+        "duplicate_thread" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let thread_id = parse_string(&params, "threadId")?;
+            state.duplicate_thread(workspace_id, thread_id).await
         }
         "list_threads" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
@@ -1204,6 +1555,11 @@ async fn handle_rpc_request(
             let sort_key = parse_optional_string(&params, "sortKey");
             state.list_threads(workspace_id, cursor, limit, sort_key).await
         }
+        "list_all_threads" => {
+            let cursor = parse_optional_string(&params, "cursor");
+            let limit = parse_optional_u32(&params, "limit");
+            state.list_all_threads(cursor, limit).await
+        }
         "list_mcp_server_status" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
             let cursor = parse_optional_string(&params, "cursor");
@@ -1220,12 +1576,25 @@ async fn handle_rpc_request(
             let thread_id = parse_string(&params, "threadId")?;
             state.compact_thread(workspace_id, thread_id).await
         }
+        "get_thread_usage" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let thread_id = parse_string(&params, "threadId")?;
+            state.get_thread_usage(workspace_id, thread_id).await
+        }
+        "repair_thread_store" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            state.repair_thread_store(workspace_id).await
+        }
         "set_thread_name" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
             let thread_id = parse_string(&params, "threadId")?;
             let name = parse_string(&params, "name")?;
             state.set_thread_name(workspace_id, thread_id, name).await
         }
+        "list_active_sessions" => {
+            let sessions = state.list_active_sessions().await;
+            serde_json::to_value(sessions).map_err(|err| err.to_string())
+        }
         "send_user_message" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
             let thread_id = parse_string(&params, "threadId")?;
@@ -1234,7 +1603,9 @@ async fn handle_rpc_request(
             let effort = parse_optional_string(&params, "effort");
             let access_mode = parse_optional_string(&params, "accessMode");
             let images = parse_optional_string_array(&params, "images");
+            let files = parse_optional_string_array(&params, "files");
             let collaboration_mode = parse_optional_value(&params, "collaborationMode");
+            let cwd = parse_optional_string(&params, "cwd");
             state
                 .send_user_message(
                     workspace_id,
@@ -1244,7 +1615,9 @@ async fn handle_rpc_request(
                     effort,
                     access_mode,
                     images,
+                    files,
                     collaboration_mode,
+                    cwd,
                 )
                 .await
         }
@@ -1254,6 +1627,16 @@ async fn handle_rpc_request(
             let turn_id = parse_string(&params, "turnId")?;
             state.turn_interrupt(workspace_id, thread_id, turn_id).await
         }
+        "interrupt_workspace" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            state.interrupt_workspace(workspace_id).await
+        }
+        "get_turn_summary" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let thread_id = parse_string(&params, "threadId")?;
+            let turn_id = parse_string(&params, "turnId")?;
+            state.get_turn_summary(workspace_id, thread_id, turn_id).await
+        }
         "start_review" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
             let thread_id = parse_string(&params, "threadId")?;
@@ -1273,6 +1656,42 @@ async fn handle_rpc_request(
             let workspace_id = parse_string(&params, "workspaceId")?;
             state.collaboration_mode_list(workspace_id).await
         }
+        "set_collaboration_mode" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let thread_id = parse_string(&params, "threadId")?;
+            let mode = parse_optional_value(&params, "mode").unwrap_or(Value::Null);
+            state
+                .set_collaboration_mode(workspace_id, thread_id, mode)
+                .await
+        }
+        "set_thread_model" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let thread_id = parse_string(&params, "threadId")?;
+            let model = parse_optional_string(&params, "model");
+            state.set_thread_model(workspace_id, thread_id, model).await
+        }
+        "get_collaboration_mode" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let thread_id = parse_string(&params, "threadId")?;
+            state.get_collaboration_mode(workspace_id, thread_id).await
+        }
+        "start_event_recording" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let path = parse_string(&params, "path")?;
+            state.start_event_recording(workspace_id, path).await
+        }
+        "stop_event_recording" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            state.stop_event_recording(workspace_id).await
+        }
+        "set_event_throttle" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let min_interval_ms = params
+                .get("minIntervalMs")
+                .and_then(Value::as_u64)
+                .ok_or("missing or invalid `minIntervalMs`")?;
+            state.set_event_throttle(workspace_id, min_interval_ms).await
+        }
         "account_rate_limits" => {
             let workspace_id = parse_string(&params, "workspaceId")?;
             state.account_rate_limits(workspace_id).await
@@ -1317,6 +1736,65 @@ async fn handle_rpc_request(
             let command = parse_string_array(&params, "command")?;
             state.remember_approval_rule(workspace_id, command).await
         }
+        "list_approval_rules" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            codex_core::list_approval_rules_core(workspace_id).await
+        }
+        "clear_approval_rule" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let command = parse_string_array(&params, "command")?;
+            state.clear_approval_rule(workspace_id, command).await
+        }
+        "clear_all_approval_rules" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            state.clear_all_approval_rules(workspace_id).await
+        }
+        "get_workspace_diff_structured" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let diff =
+                codex_aux_core::workspace_diff_structured_core(&state.workspaces, &workspace_id)
+                    .await?;
+            serde_json::to_value(diff).map_err(|err| err.to_string())
+        }
+        "get_commit_message_prompt" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let staged_only = parse_optional_bool(&params, "stagedOnly").unwrap_or(false);
+            let prompt = state
+                .get_commit_message_prompt(workspace_id, staged_only)
+                .await?;
+            Ok(Value::String(prompt))
+        }
+        "generate_commit_message" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let staged_only = parse_optional_bool(&params, "stagedOnly").unwrap_or(false);
+            let message = state
+                .generate_commit_message(workspace_id, staged_only)
+                .await?;
+            Ok(Value::String(message))
+        }
+        "generate_run_metadata" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            let prompt = parse_string(&params, "prompt")?;
+            state.generate_run_metadata(workspace_id, prompt).await
+        }
+        "quick_prompt" => {
+            let cli_type = parse_string(&params, "cliType")?;
+            let cwd = parse_string(&params, "cwd")?;
+            let prompt = parse_string(&params, "prompt")?;
+            let reply = state
+                .quick_prompt(cli_type, cwd, prompt, client_version)
+                .await?;
+            Ok(Value::String(reply))
+        }
+        "test_mcp_server" => {
+            let command = parse_string(&params, "command")?;
+            let args = parse_optional_string_array(&params, "args").unwrap_or_default();
+            state.test_mcp_server(command, args).await
+        }
+        "generate_run_metadata_cancel" => {
+            let workspace_id = parse_string(&params, "workspaceId")?;
+            Ok(state.generate_run_metadata_cancel(workspace_id).await)
+        }
         _ => Err(format!("unknown method: {method}")),
     }
 }
@@ -10,6 +10,80 @@ pub(crate) fn parse_codex_args(value: Option<&str>) -> Result<Vec<String>, Strin
         .map(|args| args.into_iter().filter(|arg| !arg.is_empty()).collect())
 }
 
+/// Flags each adapter passes itself to get stream-json output; user args that
+/// set them would break event parsing.
+fn protocol_flags(cli_type: &str) -> &'static [&'static str] {
+    match cli_type {
+        "claude" => &[
+            "-p",
+            "--print",
+            "--output-format",
+            "--input-format",
+            "--resume",
+        ],
+        "gemini" => &["-p", "--prompt", "--output-format", "--resume"],
+        "cursor" => &["-p", "--print", "--output-format", "--resume"],
+        _ => &[],
+    }
+}
+
+/// Parses `value` the same way a spawn would and rejects flags the adapter
+/// for `cli_type` needs to control.
+pub(crate) fn validate_cli_args(
+    cli_type: &str,
+    value: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let args = parse_codex_args(value)?;
+    let reserved = protocol_flags(cli_type);
+    for arg in &args {
+        let flag = arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag);
+        if reserved.contains(&flag) {
+            return Err(format!(
+                "`{flag}` is set by Agent Monitor for {cli_type} and cannot be overridden."
+            ));
+        }
+    }
+    Ok(args)
+}
+
+pub(crate) fn validate_gemini_args(value: Option<&str>) -> Result<Vec<String>, String> {
+    validate_cli_args("gemini", value)
+}
+
+pub(crate) fn validate_claude_args(value: Option<&str>) -> Result<Vec<String>, String> {
+    validate_cli_args("claude", value)
+}
+
+pub(crate) fn validate_cursor_args(value: Option<&str>) -> Result<Vec<String>, String> {
+    validate_cli_args("cursor", value)
+}
+
+/// Checks the Gemini, Claude and Cursor args of an app or workspace settings
+/// update before it is saved.
+pub(crate) fn validate_adapter_args(
+    gemini: Option<&str>,
+    claude: Option<&str>,
+    cursor: Option<&str>,
+) -> Result<(), String> {
+    validate_gemini_args(gemini)?;
+    validate_claude_args(claude)?;
+    validate_cursor_args(cursor)?;
+    Ok(())
+}
+
+/// Recommended starting args for `cli_type`, offered by the settings UI.
+/// Users can edit them freely; Codex takes its defaults from `config.toml`,
+/// so it has no preset.
+pub(crate) fn default_args_preset(cli_type: &str) -> String {
+    match cli_type {
+        "gemini" => "--model gemini-2.5-pro",
+        "claude" => "--model claude-sonnet-4-20250514",
+        "cursor" => "--model auto",
+        _ => "",
+    }
+    .to_string()
+}
+
 pub(crate) fn resolve_workspace_codex_args(
     entry: &WorkspaceEntry,
     parent_entry: Option<&WorkspaceEntry>,
@@ -48,7 +122,10 @@ fn normalize_codex_args(value: &str) -> Option<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_codex_args, resolve_workspace_codex_args};
+    use super::{
+        default_args_preset, parse_codex_args, resolve_workspace_codex_args, validate_adapter_args,
+        validate_claude_args, validate_cli_args, validate_cursor_args, validate_gemini_args,
+    };
     use crate::types::{AppSettings, WorkspaceEntry, WorkspaceKind, WorkspaceSettings};
 
     #[test]
@@ -71,6 +148,54 @@ mod tests {
         assert_eq!(args, vec!["--path", "a b", "--name=c d"]);
     }
 
+    #[test]
+    fn validates_cli_args_and_returns_tokens() {
+        let args = validate_gemini_args(Some("--model gemini-2.5-pro --sandbox")).expect("valid");
+        assert_eq!(args, vec!["--model", "gemini-2.5-pro", "--sandbox"]);
+        assert!(validate_claude_args(Some("  ")).expect("empty").is_empty());
+        assert!(validate_cursor_args(None).expect("none").is_empty());
+    }
+
+    #[test]
+    fn validate_cli_args_reports_malformed_input() {
+        let err = validate_gemini_args(Some("--model \"unterminated")).expect_err("malformed");
+        assert!(err.starts_with("Invalid Codex args"));
+    }
+
+    #[test]
+    fn validate_cli_args_rejects_protocol_flags() {
+        let err = validate_claude_args(Some("--output-format=json")).expect_err("reserved");
+        assert!(err.contains("--output-format"));
+        assert!(validate_gemini_args(Some("--resume abc")).is_err());
+        assert!(validate_cursor_args(Some("-p")).is_err());
+        assert!(validate_cli_args("codex", Some("--output-format json")).is_ok());
+    }
+
+    #[test]
+    fn validate_adapter_args_names_the_offending_cli() {
+        assert!(validate_adapter_args(Some("--sandbox"), None, Some("--model auto")).is_ok());
+        let err = validate_adapter_args(None, Some("--print"), None).expect_err("reserved");
+        assert!(err.contains("claude"), "{err}");
+    }
+
+    #[test]
+    fn default_args_presets_pass_validation() {
+        let gemini = default_args_preset("gemini");
+        assert!(!validate_gemini_args(Some(&gemini))
+            .expect("gemini")
+            .is_empty());
+        let claude = default_args_preset("claude");
+        assert!(!validate_claude_args(Some(&claude))
+            .expect("claude")
+            .is_empty());
+        let cursor = default_args_preset("cursor");
+        assert!(!validate_cursor_args(Some(&cursor))
+            .expect("cursor")
+            .is_empty());
+        let codex = default_args_preset("codex");
+        assert!(parse_codex_args(Some(&codex)).expect("codex").is_empty());
+    }
+
     #[test]
     fn resolves_workspace_codex_args_precedence() {
         let mut app_settings = AppSettings::default();
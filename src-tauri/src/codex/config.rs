@@ -129,6 +129,15 @@ pub(crate) fn read_config_model(codex_home: Option<PathBuf>) -> Result<Option<St
     read_config_model_from_root(&root)
 }
 
+pub(crate) fn read_config_effort(codex_home: Option<PathBuf>) -> Result<Option<String>, String> {
+    let root = codex_home.or_else(resolve_default_codex_home);
+    let Some(root) = root else {
+        return Err("Unable to resolve CODEX_HOME".to_string());
+    };
+    let contents = read_config_contents_from_root(&root)?;
+    Ok(contents.as_deref().and_then(parse_effort_from_toml))
+}
+
 fn resolve_default_codex_home() -> Option<PathBuf> {
     crate::codex::home::resolve_default_codex_home()
 }
@@ -170,6 +179,17 @@ fn parse_model_from_toml(contents: &str) -> Option<String> {
     }
 }
 
+fn parse_effort_from_toml(contents: &str) -> Option<String> {
+    let parsed: TomlValue = toml::from_str(contents).ok()?;
+    let effort = parsed.get("model_reasoning_effort")?.as_str()?;
+    let trimmed = effort.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_ascii_lowercase())
+    }
+}
+
 fn parse_personality_from_toml(contents: &str) -> Option<&'static str> {
     let parsed: TomlValue = toml::from_str(contents).ok()?;
     let value = parsed.get("personality")?.as_str()?;
@@ -342,7 +362,30 @@ impl<T> RetainWithIndex<T> for Vec<T> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_personality_from_toml, remove_top_level_key, upsert_top_level_string_key};
+    use super::{
+        parse_effort_from_toml, parse_model_from_toml, parse_personality_from_toml,
+        remove_top_level_key, upsert_top_level_string_key,
+    };
+
+    #[test]
+    fn parse_effort_reads_reasoning_effort_when_present() {
+        let contents = "model = \"gpt-5\"\nmodel_reasoning_effort = \" High \"\n";
+        assert_eq!(parse_model_from_toml(contents).as_deref(), Some("gpt-5"));
+        assert_eq!(parse_effort_from_toml(contents).as_deref(), Some("high"));
+    }
+
+    #[test]
+    fn parse_effort_is_none_when_absent_or_blank() {
+        assert_eq!(parse_effort_from_toml("model = \"gpt-5\"\n"), None);
+        assert_eq!(
+            parse_effort_from_toml("model_reasoning_effort = \"  \"\n"),
+            None
+        );
+        assert_eq!(
+            parse_effort_from_toml("[profiles.fast]\nmodel_reasoning_effort = \"low\"\n"),
+            None
+        );
+    }
 
     #[test]
     fn parse_personality_reads_supported_values() {
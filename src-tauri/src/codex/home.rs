@@ -1,6 +1,8 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::backend::claude_adapter::CLAUDE_HOME_ENV;
+use crate::backend::cursor_adapter::CURSOR_HOME_ENV;
 use crate::types::WorkspaceEntry;
 
 pub(crate) fn resolve_workspace_codex_home(
@@ -43,7 +45,59 @@ pub(crate) fn resolve_default_codex_home() -> Option<PathBuf> {
     resolve_home_dir().map(|home| home.join(".codex"))
 }
 
-fn normalize_codex_home(value: &str) -> Option<PathBuf> {
+pub(crate) fn resolve_default_gemini_home() -> Option<PathBuf> {
+    if let Ok(value) = env::var("GEMINI_HOME") {
+        if let Some(path) = normalize_codex_home(&value) {
+            return Some(path);
+        }
+    }
+    resolve_home_dir().map(|home| home.join(".gemini"))
+}
+
+pub(crate) fn resolve_default_claude_home() -> Option<PathBuf> {
+    resolve_default_home_from_env(CLAUDE_HOME_ENV, ".claude")
+}
+
+pub(crate) fn resolve_default_cursor_home() -> Option<PathBuf> {
+    resolve_default_home_from_env(CURSOR_HOME_ENV, ".cursor")
+}
+
+fn resolve_default_home_from_env(var: &str, dir_name: &str) -> Option<PathBuf> {
+    if let Ok(value) = env::var(var) {
+        if let Some(path) = normalize_codex_home(&value) {
+            return Some(path);
+        }
+    }
+    resolve_home_dir().map(|home| home.join(dir_name))
+}
+
+/// Home directory `cli_type` uses when no workspace override is set, following
+/// the same env var and `$HOME` fallbacks as the CLI itself.
+pub(crate) fn resolve_default_cli_home(cli_type: &str) -> Result<PathBuf, String> {
+    let (resolved, env_var) = match cli_type {
+        "codex" => (resolve_default_codex_home(), "CODEX_HOME"),
+        "gemini" => (resolve_default_gemini_home(), "GEMINI_HOME"),
+        "claude" => (resolve_default_claude_home(), CLAUDE_HOME_ENV),
+        "cursor" => (resolve_default_cursor_home(), CURSOR_HOME_ENV),
+        other => return Err(format!("Unsupported CLI type: {other}")),
+    };
+    resolved.ok_or_else(|| format!("Unable to resolve {cli_type} home: set {env_var} or HOME"))
+}
+
+/// Makes sure a resolved CLI home exists (creating it if needed) so spawn
+/// failures point at the bad path instead of a confusing CLI error.
+pub(crate) fn ensure_cli_home_dir(path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    if path.exists() {
+        return Err(format!("CLI home {} is not a directory", path.display()));
+    }
+    std::fs::create_dir_all(path)
+        .map_err(|err| format!("Failed to create CLI home {}: {err}", path.display()))
+}
+
+pub(crate) fn normalize_codex_home(value: &str) -> Option<PathBuf> {
     let trimmed = value.trim();
     if trimmed.is_empty() {
         return None;
@@ -61,7 +115,7 @@ fn normalize_codex_home(value: &str) -> Option<PathBuf> {
     Some(PathBuf::from(trimmed))
 }
 
-fn normalize_codex_home_with_base(value: &str, base: &PathBuf) -> Option<PathBuf> {
+pub(crate) fn normalize_codex_home_with_base(value: &str, base: &PathBuf) -> Option<PathBuf> {
     let path = normalize_codex_home(value)?;
     if path.is_absolute() {
         Some(path)
@@ -170,7 +224,7 @@ fn join_env_path(prefix: &str, remainder: &str) -> PathBuf {
     }
 }
 
-fn resolve_home_dir() -> Option<PathBuf> {
+pub(crate) fn resolve_home_dir() -> Option<PathBuf> {
     if let Ok(value) = env::var("HOME") {
         if !value.trim().is_empty() {
             return Some(PathBuf::from(value));
@@ -288,4 +342,104 @@ mod tests {
             None => std::env::remove_var("APPDATA"),
         }
     }
+
+    #[test]
+    fn cli_home_with_base_expands_tilde() {
+        let _guard = ENV_LOCK.lock().expect("lock env");
+        let home_dir = std::env::temp_dir().join("gemini-home-tilde-test");
+        let prev_home = std::env::var("HOME").ok();
+        std::env::set_var("HOME", home_dir.to_string_lossy().to_string());
+
+        let resolved =
+            normalize_codex_home_with_base("~/.gemini-work", &PathBuf::from("/repo"));
+        assert_eq!(resolved, Some(home_dir.join(".gemini-work")));
+
+        match prev_home {
+            Some(value) => std::env::set_var("HOME", value),
+            None => std::env::remove_var("HOME"),
+        }
+    }
+
+    #[test]
+    fn default_cli_home_uses_env_override_then_home_dir() {
+        let _guard = ENV_LOCK.lock().expect("lock env");
+        let home_dir = std::env::temp_dir().join("cli-home-default-test");
+        let vars = [
+            "HOME",
+            "USERPROFILE",
+            "CODEX_HOME",
+            "GEMINI_HOME",
+            CLAUDE_HOME_ENV,
+            CURSOR_HOME_ENV,
+        ];
+        let previous: Vec<_> = vars
+            .iter()
+            .map(|var| (*var, std::env::var(var).ok()))
+            .collect();
+        for var in vars {
+            std::env::remove_var(var);
+        }
+        std::env::set_var("HOME", home_dir.to_string_lossy().to_string());
+
+        for (cli_type, dir_name) in [
+            ("codex", ".codex"),
+            ("gemini", ".gemini"),
+            ("claude", ".claude"),
+            ("cursor", ".cursor"),
+        ] {
+            assert_eq!(
+                resolve_default_cli_home(cli_type),
+                Ok(home_dir.join(dir_name))
+            );
+        }
+
+        std::env::set_var("CODEX_HOME", "/tmp/codex-env");
+        std::env::set_var("GEMINI_HOME", "~/gemini-env");
+        std::env::set_var(CLAUDE_HOME_ENV, "/tmp/claude-env");
+        std::env::set_var(CURSOR_HOME_ENV, "  ");
+        assert_eq!(
+            resolve_default_cli_home("codex"),
+            Ok(PathBuf::from("/tmp/codex-env"))
+        );
+        assert_eq!(
+            resolve_default_cli_home("gemini"),
+            Ok(home_dir.join("gemini-env"))
+        );
+        assert_eq!(
+            resolve_default_cli_home("claude"),
+            Ok(PathBuf::from("/tmp/claude-env"))
+        );
+        assert_eq!(
+            resolve_default_cli_home("cursor"),
+            Ok(home_dir.join(".cursor"))
+        );
+
+        std::env::remove_var(CLAUDE_HOME_ENV);
+        std::env::remove_var("HOME");
+        let err = resolve_default_cli_home("claude").expect_err("no home to resolve");
+        assert!(err.contains(CLAUDE_HOME_ENV), "{err}");
+        assert!(resolve_default_cli_home("unknown").is_err());
+
+        for (var, value) in previous {
+            match value {
+                Some(value) => std::env::set_var(var, value),
+                None => std::env::remove_var(var),
+            }
+        }
+    }
+
+    #[test]
+    fn ensure_cli_home_dir_creates_missing_directory() {
+        let root = std::env::temp_dir().join(format!("cli-home-{}", uuid::Uuid::new_v4()));
+        let nested = root.join("nested").join(".gemini");
+
+        ensure_cli_home_dir(&nested).expect("home should be created");
+        assert!(nested.is_dir());
+
+        let file_path = root.join("not-a-dir");
+        std::fs::write(&file_path, "x").expect("write file");
+        assert!(ensure_cli_home_dir(&file_path).is_err());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
 }
@@ -1,4 +1,5 @@
 use serde_json::{json, Map, Value};
+use std::future::Future;
 use std::sync::Arc;
 
 use tauri::{AppHandle, Emitter, State};
@@ -7,21 +8,29 @@ pub(crate) mod args;
 pub(crate) mod config;
 pub(crate) mod home;
 
-use crate::backend::app_server::{spawn_workspace_session as spawn_workspace_session_inner, CliSpawnConfig};
+use crate::backend::app_server::{
+    spawn_workspace_session as spawn_workspace_session_inner,
+    spawn_workspace_session_deferred as spawn_workspace_session_deferred_inner, CliSpawnConfig,
+};
 pub(crate) use crate::backend::app_server::WorkspaceSession;
+use crate::backend::cursor_adapter;
 use crate::backend::events::AppServerEvent;
 use crate::event_sink::TauriEventSink;
 use crate::remote_backend;
-use crate::shared::codex_core;
+use crate::shared::process_core::SystemStdioRequestRunner;
+use crate::shared::{codex_aux_core, codex_core, workspaces_core};
 use crate::state::AppState;
-use crate::types::WorkspaceEntry;
+use crate::types::{SessionInfo, WorkspaceDiffStructured, WorkspaceEntry};
 
 pub(crate) async fn spawn_workspace_session(
     entry: WorkspaceEntry,
-    config: CliSpawnConfig,
+    mut config: CliSpawnConfig,
     app_handle: AppHandle,
 ) -> Result<Arc<WorkspaceSession>, String> {
     let client_version = app_handle.package_info().version.to_string();
+    config
+        .client_name
+        .get_or_insert_with(|| app_handle.package_info().name.clone());
     let event_sink = TauriEventSink::new(app_handle);
     spawn_workspace_session_inner(
         entry,
@@ -30,6 +39,24 @@ pub(crate) async fn spawn_workspace_session(
         event_sink,
     )
     .await
+    .map_err(String::from)
+}
+
+/// Like `spawn_workspace_session`, but returns once the process is running
+/// and finishes `initialize` in the background.
+pub(crate) async fn spawn_workspace_session_deferred(
+    entry: WorkspaceEntry,
+    mut config: CliSpawnConfig,
+    app_handle: AppHandle,
+) -> Result<Arc<WorkspaceSession>, String> {
+    let client_version = app_handle.package_info().version.to_string();
+    config
+        .client_name
+        .get_or_insert_with(|| app_handle.package_info().name.clone());
+    let event_sink = TauriEventSink::new(app_handle);
+    spawn_workspace_session_deferred_inner(entry, config, client_version, event_sink)
+        .await
+        .map_err(String::from)
 }
 
 #[tauri::command]
@@ -38,8 +65,17 @@ pub(crate) async fn codex_doctor(
     codex_args: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<Value, String> {
-    crate::shared::codex_aux_core::codex_doctor_core(&state.app_settings, codex_bin, codex_args)
-        .await
+    codex_aux_core::codex_doctor_core(&state.app_settings, codex_bin, codex_args).await
+}
+
+#[tauri::command]
+pub(crate) async fn doctor_all(state: State<'_, AppState>) -> Result<Value, String> {
+    Ok(codex_aux_core::doctor_all_core(&state.app_settings).await)
+}
+
+#[tauri::command]
+pub(crate) fn cursor_mode_list() -> Value {
+    cursor_adapter::cursor_mode_list()
 }
 
 #[tauri::command]
@@ -85,6 +121,7 @@ pub(crate) async fn resume_thread(
 pub(crate) async fn fork_thread(
     workspace_id: String,
     thread_id: String,
+    name: Option<String>,
     state: State<'_, AppState>,
     app: AppHandle,
 ) -> Result<Value, String> {
@@ -93,12 +130,32 @@ pub(crate) async fn fork_thread(
             &*state,
             app,
             "fork_thread",
+            json!({ "workspaceId": workspace_id, "threadId": thread_id, "name": name }),
+        )
+        .await;
+    }
+
+    codex_core::fork_thread_core(&state.sessions, workspace_id, thread_id, name).await
+}
+
+#[tauri::command]
+pub(crate) async fn duplicate_thread(
+    workspace_id: String,
+    thread_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "duplicate_thread",
             json!({ "workspaceId": workspace_id, "threadId": thread_id }),
         )
         .await;
     }
 
-    codex_core::fork_thread_core(&state.sessions, workspace_id, thread_id).await
+    codex_core::duplicate_thread_core(&state.sessions, workspace_id, thread_id).await
 }
 
 #[tauri::command]
@@ -128,6 +185,34 @@ pub(crate) async fn list_threads(
     codex_core::list_threads_core(&state.sessions, workspace_id, cursor, limit, sort_key).await
 }
 
+/// Recent threads across all workspaces for the global activity view.
+#[tauri::command]
+pub(crate) async fn list_all_threads(
+    cursor: Option<String>,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "list_all_threads",
+            json!({ "cursor": cursor, "limit": limit }),
+        )
+        .await;
+    }
+
+    codex_core::list_all_threads_core(
+        &state.sessions,
+        &state.workspaces,
+        &state.app_settings,
+        cursor,
+        limit,
+    )
+    .await
+}
+
 #[tauri::command]
 pub(crate) async fn list_mcp_server_status(
     workspace_id: String,
@@ -189,6 +274,45 @@ pub(crate) async fn compact_thread(
     codex_core::compact_thread_core(&state.sessions, workspace_id, thread_id).await
 }
 
+#[tauri::command]
+pub(crate) async fn get_thread_usage(
+    workspace_id: String,
+    thread_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "get_thread_usage",
+            json!({ "workspaceId": workspace_id, "threadId": thread_id }),
+        )
+        .await;
+    }
+
+    codex_core::get_thread_usage_core(&state.sessions, workspace_id, thread_id).await
+}
+
+#[tauri::command]
+pub(crate) async fn repair_thread_store(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "repair_thread_store",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await;
+    }
+
+    codex_core::repair_thread_store_core(&state.sessions, &state.app_settings, workspace_id).await
+}
+
 #[tauri::command]
 pub(crate) async fn set_thread_name(
     workspace_id: String,
@@ -210,6 +334,20 @@ pub(crate) async fn set_thread_name(
     codex_core::set_thread_name_core(&state.sessions, workspace_id, thread_id, name).await
 }
 
+#[tauri::command]
+pub(crate) async fn list_active_sessions(
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Vec<SessionInfo>, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        let response =
+            remote_backend::call_remote(&*state, app, "list_active_sessions", json!({})).await?;
+        return serde_json::from_value(response).map_err(|err| err.to_string());
+    }
+
+    Ok(codex_core::list_active_sessions_core(&state.sessions).await)
+}
+
 #[tauri::command]
 pub(crate) async fn send_user_message(
     workspace_id: String,
@@ -219,7 +357,9 @@ pub(crate) async fn send_user_message(
     effort: Option<String>,
     access_mode: Option<String>,
     images: Option<Vec<String>>,
+    files: Option<Vec<String>>,
     collaboration_mode: Option<Value>,
+    cwd: Option<String>,
     state: State<'_, AppState>,
     app: AppHandle,
 ) -> Result<Value, String> {
@@ -230,6 +370,12 @@ pub(crate) async fn send_user_message(
                 .map(remote_backend::normalize_path_for_remote)
                 .collect::<Vec<_>>()
         });
+        let files = files.map(|paths| {
+            paths
+                .into_iter()
+                .map(remote_backend::normalize_path_for_remote)
+                .collect::<Vec<_>>()
+        });
         let mut payload = Map::new();
         payload.insert("workspaceId".to_string(), json!(workspace_id));
         payload.insert("threadId".to_string(), json!(thread_id));
@@ -238,11 +384,18 @@ pub(crate) async fn send_user_message(
         payload.insert("effort".to_string(), json!(effort));
         payload.insert("accessMode".to_string(), json!(access_mode));
         payload.insert("images".to_string(), json!(images));
+        payload.insert("files".to_string(), json!(files));
         if let Some(mode) = collaboration_mode {
             if !mode.is_null() {
                 payload.insert("collaborationMode".to_string(), mode);
             }
         }
+        if let Some(cwd) = cwd {
+            payload.insert(
+                "cwd".to_string(),
+                json!(remote_backend::normalize_path_for_remote(cwd)),
+            );
+        }
         return remote_backend::call_remote(
             &*state,
             app,
@@ -252,6 +405,15 @@ pub(crate) async fn send_user_message(
         .await;
     }
 
+    let (warn_chars, model_aliases) = {
+        let settings = state.app_settings.lock().await;
+        (
+            settings.message_warn_chars,
+            settings.model_alias_map.clone(),
+        )
+    };
+    let default_approval_policy =
+        workspaces_core::workspace_default_approval_policy(&state.workspaces, &workspace_id).await;
     codex_core::send_user_message_core(
         &state.sessions,
         workspace_id,
@@ -261,7 +423,13 @@ pub(crate) async fn send_user_message(
         effort,
         access_mode,
         images,
+        files,
         collaboration_mode,
+        cwd,
+        default_approval_policy,
+        warn_chars,
+        &model_aliases,
+        &TauriEventSink::new(app),
     )
     .await
 }
@@ -285,6 +453,135 @@ pub(crate) async fn collaboration_mode_list(
     codex_core::collaboration_mode_list_core(&state.sessions, workspace_id).await
 }
 
+#[tauri::command]
+pub(crate) async fn set_collaboration_mode(
+    workspace_id: String,
+    thread_id: String,
+    mode: Value,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "set_collaboration_mode",
+            json!({ "workspaceId": workspace_id, "threadId": thread_id, "mode": mode }),
+        )
+        .await;
+    }
+
+    codex_core::set_collaboration_mode_core(
+        &state.sessions,
+        &state.workspaces,
+        &state.storage_path,
+        workspace_id,
+        thread_id,
+        mode,
+    )
+    .await
+}
+
+#[tauri::command]
+pub(crate) async fn set_thread_model(
+    workspace_id: String,
+    thread_id: String,
+    model: Option<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "set_thread_model",
+            json!({ "workspaceId": workspace_id, "threadId": thread_id, "model": model }),
+        )
+        .await;
+    }
+
+    codex_core::set_thread_model_core(&state.sessions, workspace_id, thread_id, model).await
+}
+
+#[tauri::command]
+pub(crate) async fn get_collaboration_mode(
+    workspace_id: String,
+    thread_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "get_collaboration_mode",
+            json!({ "workspaceId": workspace_id, "threadId": thread_id }),
+        )
+        .await;
+    }
+
+    codex_core::get_collaboration_mode_core(&state.sessions, workspace_id, thread_id).await
+}
+
+#[tauri::command]
+pub(crate) async fn start_event_recording(
+    workspace_id: String,
+    path: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "start_event_recording",
+            json!({ "workspaceId": workspace_id, "path": path }),
+        )
+        .await;
+    }
+
+    codex_core::start_event_recording_core(&state.sessions, workspace_id, path).await
+}
+
+#[tauri::command]
+pub(crate) async fn stop_event_recording(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "stop_event_recording",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await;
+    }
+
+    codex_core::stop_event_recording_core(&state.sessions, workspace_id).await
+}
+
+#[tauri::command]
+pub(crate) async fn set_event_throttle(
+    workspace_id: String,
+    min_interval_ms: u64,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "set_event_throttle",
+            json!({ "workspaceId": workspace_id, "minIntervalMs": min_interval_ms }),
+        )
+        .await;
+    }
+
+    codex_core::set_event_throttle_core(&state.sessions, workspace_id, min_interval_ms).await
+}
+
 #[tauri::command]
 pub(crate) async fn turn_interrupt(
     workspace_id: String,
@@ -306,6 +603,49 @@ pub(crate) async fn turn_interrupt(
     codex_core::turn_interrupt_core(&state.sessions, workspace_id, thread_id, turn_id).await
 }
 
+/// Interrupts every in-flight turn in the workspace, e.g. for "stop all".
+#[tauri::command]
+pub(crate) async fn interrupt_workspace(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "interrupt_workspace",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await;
+    }
+
+    codex_core::interrupt_workspace_core(&state.sessions, workspace_id, &TauriEventSink::new(app))
+        .await
+}
+
+/// Tools a finished (or running) turn used and how long it took.
+#[tauri::command]
+pub(crate) async fn get_turn_summary(
+    workspace_id: String,
+    thread_id: String,
+    turn_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "get_turn_summary",
+            json!({ "workspaceId": workspace_id, "threadId": thread_id, "turnId": turn_id }),
+        )
+        .await;
+    }
+
+    codex_core::get_turn_summary_core(&state.sessions, workspace_id, thread_id, turn_id).await
+}
+
 #[tauri::command]
 pub(crate) async fn start_review(
     workspace_id: String,
@@ -349,7 +689,8 @@ pub(crate) async fn model_list(
         .await;
     }
 
-    codex_core::model_list_core(&state.sessions, workspace_id).await
+    let model_aliases = state.app_settings.lock().await.model_alias_map.clone();
+    codex_core::model_list_core(&state.sessions, workspace_id, &model_aliases).await
 }
 
 #[tauri::command]
@@ -493,21 +834,71 @@ pub(crate) async fn respond_to_server_request(
 }
 
 /// Gets the diff content for commit message generation
+#[tauri::command]
+pub(crate) async fn get_workspace_diff_structured(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<WorkspaceDiffStructured, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        let response = remote_backend::call_remote(
+            &*state,
+            app,
+            "get_workspace_diff_structured",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await?;
+        return serde_json::from_value(response).map_err(|err| err.to_string());
+    }
+
+    codex_aux_core::workspace_diff_structured_core(&state.workspaces, &workspace_id).await
+}
+
 #[tauri::command]
 pub(crate) async fn get_commit_message_prompt(
     workspace_id: String,
+    staged_only: Option<bool>,
     state: State<'_, AppState>,
+    app: AppHandle,
 ) -> Result<String, String> {
-    // Get the diff from git
-    let diff = crate::git::get_workspace_diff(&workspace_id, &state).await?;
+    let state = &*state;
+    commit_message_prompt(state, workspace_id, staged_only, |method, params| {
+        remote_backend::call_remote(state, app, method, params)
+    })
+    .await
+}
 
-    if diff.trim().is_empty() {
-        return Err("No changes to generate commit message for".to_string());
+async fn commit_message_prompt<F, Fut>(
+    state: &AppState,
+    workspace_id: String,
+    staged_only: Option<bool>,
+    call_remote: F,
+) -> Result<String, String>
+where
+    F: FnOnce(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    if remote_backend::is_remote_mode(state).await {
+        let response = call_remote(
+            "get_commit_message_prompt",
+            json!({ "workspaceId": workspace_id, "stagedOnly": staged_only }),
+        )
+        .await?;
+        return serde_json::from_value(response).map_err(|err| err.to_string());
     }
 
-    Ok(crate::shared::codex_aux_core::build_commit_message_prompt(
-        &diff,
-    ))
+    let max_diff_bytes = state
+        .app_settings
+        .lock()
+        .await
+        .commit_message_diff_max_bytes;
+    codex_aux_core::commit_message_prompt_core(
+        &state.workspaces,
+        &workspace_id,
+        max_diff_bytes,
+        staged_only.unwrap_or(false),
+    )
+    .await
 }
 
 #[tauri::command]
@@ -515,8 +906,100 @@ pub(crate) async fn remember_approval_rule(
     workspace_id: String,
     command: Vec<String>,
     state: State<'_, AppState>,
+    app: AppHandle,
 ) -> Result<Value, String> {
-    codex_core::remember_approval_rule_core(&state.workspaces, workspace_id, command).await
+    let state = &*state;
+    remember_rule(state, workspace_id, command, |method, params| {
+        remote_backend::call_remote(state, app, method, params)
+    })
+    .await
+}
+
+async fn remember_rule<F, Fut>(
+    state: &AppState,
+    workspace_id: String,
+    command: Vec<String>,
+    call_remote: F,
+) -> Result<Value, String>
+where
+    F: FnOnce(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    if remote_backend::is_remote_mode(state).await {
+        return call_remote(
+            "remember_approval_rule",
+            json!({ "workspaceId": workspace_id, "command": command }),
+        )
+        .await;
+    }
+
+    codex_core::remember_approval_rule_core(
+        &state.workspaces,
+        &state.sessions,
+        workspace_id,
+        command,
+    )
+    .await
+}
+
+#[tauri::command]
+pub(crate) async fn list_approval_rules(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "list_approval_rules",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await;
+    }
+
+    codex_core::list_approval_rules_core(workspace_id).await
+}
+
+#[tauri::command]
+pub(crate) async fn clear_approval_rule(
+    workspace_id: String,
+    command: Vec<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "clear_approval_rule",
+            json!({ "workspaceId": workspace_id, "command": command }),
+        )
+        .await;
+    }
+
+    codex_core::clear_approval_rule_core(&state.workspaces, &state.sessions, workspace_id, command)
+        .await
+}
+
+#[tauri::command]
+pub(crate) async fn clear_all_approval_rules(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "clear_all_approval_rules",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await;
+    }
+
+    codex_core::clear_all_approval_rules_core(&state.workspaces, &state.sessions, workspace_id)
+        .await
 }
 
 #[tauri::command]
@@ -535,53 +1018,83 @@ pub(crate) async fn get_config_model(
         .await;
     }
 
-    codex_core::get_config_model_core(&state.workspaces, workspace_id).await
+    codex_core::get_config_model_core(&state.workspaces, &state.app_settings, workspace_id).await
+}
+
+fn emit_hide_background_thread(app: &AppHandle, workspace_id: &str, thread_id: &str) {
+    let _ = app.emit(
+        "app-server-event",
+        AppServerEvent {
+            workspace_id: workspace_id.to_string(),
+            message: json!({
+                "method": "codex/backgroundThread",
+                "params": {
+                    "threadId": thread_id,
+                    "action": "hide"
+                }
+            }),
+        },
+    );
 }
 
 /// Generates a commit message in the background without showing in the main chat
 #[tauri::command]
 pub(crate) async fn generate_commit_message(
     workspace_id: String,
+    staged_only: Option<bool>,
     state: State<'_, AppState>,
     app: AppHandle,
 ) -> Result<String, String> {
-    let diff = crate::git::get_workspace_diff(&workspace_id, &state).await?;
+    let state = &*state;
+    let remote_app = app.clone();
+    commit_message(
+        state,
+        workspace_id,
+        staged_only,
+        |workspace_id, thread_id| emit_hide_background_thread(&app, workspace_id, thread_id),
+        |method, params| remote_backend::call_remote(state, remote_app, method, params),
+    )
+    .await
+}
 
-    if diff.trim().is_empty() {
-        return Err("No changes to generate commit message for".to_string());
+async fn commit_message<H, F, Fut>(
+    state: &AppState,
+    workspace_id: String,
+    staged_only: Option<bool>,
+    on_hide_thread: H,
+    call_remote: F,
+) -> Result<String, String>
+where
+    H: Fn(&str, &str),
+    F: FnOnce(&'static str, Value) -> Fut,
+    Fut: Future<Output = Result<Value, String>>,
+{
+    if remote_backend::is_remote_mode(state).await {
+        let response = call_remote(
+            "generate_commit_message",
+            json!({ "workspaceId": workspace_id, "stagedOnly": staged_only }),
+        )
+        .await?;
+        return serde_json::from_value(response).map_err(|err| err.to_string());
     }
 
-    let prompt = crate::shared::codex_aux_core::build_commit_message_prompt(&diff);
-    let response = crate::shared::codex_aux_core::run_background_prompt_core(
+    let (max_diff_bytes, keep_visible) = {
+        let settings = state.app_settings.lock().await;
+        (
+            settings.commit_message_diff_max_bytes,
+            settings.keep_background_threads_visible,
+        )
+    };
+    codex_aux_core::generate_commit_message_core(
         &state.sessions,
+        &state.workspaces,
         workspace_id,
-        prompt,
-        |workspace_id, thread_id| {
-            let _ = app.emit(
-                "app-server-event",
-                AppServerEvent {
-                    workspace_id: workspace_id.to_string(),
-                    message: json!({
-                        "method": "codex/backgroundThread",
-                        "params": {
-                            "threadId": thread_id,
-                            "action": "hide"
-                        }
-                    }),
-                },
-            );
-        },
-        "Timeout waiting for commit message generation",
-        "Unknown error during commit message generation",
+        max_diff_bytes,
+        staged_only.unwrap_or(false),
+        on_hide_thread,
+        keep_visible,
     )
-    .await?;
-
-    let trimmed = response.trim().to_string();
-    if trimmed.is_empty() {
-        return Err("No commit message was generated".to_string());
-    }
-
-    Ok(trimmed)
+    .await
 }
 
 #[tauri::command]
@@ -601,59 +1114,208 @@ pub(crate) async fn generate_run_metadata(
         .await;
     }
 
-    let cleaned_prompt = prompt.trim();
-    if cleaned_prompt.is_empty() {
-        return Err("Prompt is required.".to_string());
-    }
-
-    let title_prompt = crate::shared::codex_aux_core::build_run_metadata_prompt(cleaned_prompt);
-    let response_text = crate::shared::codex_aux_core::run_background_prompt_core(
+    let keep_visible = state
+        .app_settings
+        .lock()
+        .await
+        .keep_background_threads_visible;
+    codex_aux_core::generate_run_metadata_core(
         &state.sessions,
+        &state.run_metadata_cancels,
         workspace_id,
-        title_prompt,
-        |workspace_id, thread_id| {
-            let _ = app.emit(
-                "app-server-event",
-                AppServerEvent {
-                    workspace_id: workspace_id.to_string(),
-                    message: json!({
-                        "method": "codex/backgroundThread",
-                        "params": {
-                            "threadId": thread_id,
-                            "action": "hide"
-                        }
-                    }),
-                },
-            );
-        },
-        "Timeout waiting for metadata generation",
-        "Unknown error during metadata generation",
+        &prompt,
+        |workspace_id, thread_id| emit_hide_background_thread(&app, workspace_id, thread_id),
+        keep_visible,
+    )
+    .await
+}
+
+/// Runs a one-off prompt against `cwd` without registering a workspace.
+#[tauri::command]
+pub(crate) async fn quick_prompt(
+    cli_type: String,
+    cwd: String,
+    prompt: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<String, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        let response = remote_backend::call_remote(
+            &*state,
+            app,
+            "quick_prompt",
+            json!({ "cliType": cli_type, "cwd": cwd, "prompt": prompt }),
+        )
+        .await?;
+        return serde_json::from_value(response).map_err(|err| err.to_string());
+    }
+
+    codex_aux_core::quick_prompt_core(
+        cli_type,
+        cwd,
+        prompt,
+        &state.app_settings,
+        |entry, config| spawn_workspace_session(entry, config, app.clone()),
+    )
+    .await
+}
+
+/// Starts a stdio MCP server just long enough to check its `initialize`
+/// handshake; the server never joins any CLI's config.
+#[tauri::command]
+pub(crate) async fn test_mcp_server(
+    command: String,
+    args: Vec<String>,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "test_mcp_server",
+            json!({ "command": command, "args": args }),
+        )
+        .await;
+    }
+
+    codex_aux_core::test_mcp_server_core(&SystemStdioRequestRunner, command, args).await
+}
+
+/// Aborts an in-flight `generate_run_metadata` for the workspace and archives
+/// its background thread.
+#[tauri::command]
+pub(crate) async fn generate_run_metadata_cancel(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "generate_run_metadata_cancel",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await;
+    }
+
+    Ok(
+        codex_aux_core::cancel_background_prompt_core(&state.run_metadata_cancels, &workspace_id)
+            .await,
     )
-    .await?;
-
-    let trimmed = response_text.trim();
-    if trimmed.is_empty() {
-        return Err("No metadata was generated".to_string());
-    }
-
-    let json_value = crate::shared::codex_aux_core::extract_json_value(trimmed)
-        .ok_or_else(|| "Failed to parse metadata JSON".to_string())?;
-    let title = json_value
-        .get("title")
-        .and_then(|v| v.as_str())
-        .map(|v| v.trim().to_string())
-        .filter(|v| !v.is_empty())
-        .ok_or_else(|| "Missing title in metadata".to_string())?;
-    let worktree_name = json_value
-        .get("worktreeName")
-        .or_else(|| json_value.get("worktree_name"))
-        .and_then(|v| v.as_str())
-        .map(crate::shared::codex_aux_core::sanitize_run_worktree_name)
-        .filter(|v| !v.is_empty())
-        .ok_or_else(|| "Missing worktree name in metadata".to_string())?;
-
-    Ok(json!({
-        "title": title,
-        "worktreeName": worktree_name
-    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::BackendMode;
+    use std::sync::Mutex as StdMutex;
+
+    async fn remote_state() -> AppState {
+        let data_dir =
+            std::env::temp_dir().join(format!("codex-remote-routing-{}", uuid::Uuid::new_v4()));
+        let state = AppState::from_paths(
+            data_dir.join("workspaces.json"),
+            data_dir.join("settings.json"),
+        );
+        state.app_settings.lock().await.backend_mode = BackendMode::Remote;
+        state
+    }
+
+    fn recording_remote(
+        calls: &StdMutex<Vec<(String, Value)>>,
+        response: Value,
+    ) -> impl FnOnce(&'static str, Value) -> std::future::Ready<Result<Value, String>> + '_ {
+        move |method, params| {
+            calls.lock().unwrap().push((method.to_string(), params));
+            std::future::ready(Ok(response))
+        }
+    }
+
+    #[tokio::test]
+    async fn remote_mode_routes_commit_message_prompt() {
+        let state = remote_state().await;
+        let calls = StdMutex::new(Vec::new());
+
+        let prompt = commit_message_prompt(
+            &state,
+            "ws-1".to_string(),
+            Some(true),
+            recording_remote(&calls, json!("remote prompt")),
+        )
+        .await
+        .expect("remote prompt");
+
+        assert_eq!(prompt, "remote prompt");
+        assert_eq!(
+            calls.into_inner().unwrap(),
+            vec![(
+                "get_commit_message_prompt".to_string(),
+                json!({ "workspaceId": "ws-1", "stagedOnly": true }),
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_mode_routes_generate_commit_message() {
+        let state = remote_state().await;
+        let calls = StdMutex::new(Vec::new());
+
+        let message = commit_message(
+            &state,
+            "ws-1".to_string(),
+            None,
+            |_, _| panic!("remote mode must not start a local background thread"),
+            recording_remote(&calls, json!("feat: remote")),
+        )
+        .await
+        .expect("remote message");
+
+        assert_eq!(message, "feat: remote");
+        assert_eq!(
+            calls.into_inner().unwrap(),
+            vec![(
+                "generate_commit_message".to_string(),
+                json!({ "workspaceId": "ws-1", "stagedOnly": null }),
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_mode_routes_remember_approval_rule() {
+        let state = remote_state().await;
+        let calls = StdMutex::new(Vec::new());
+
+        let result = remember_rule(
+            &state,
+            "ws-1".to_string(),
+            vec!["git".to_string(), "status".to_string()],
+            recording_remote(&calls, json!({ "ok": true })),
+        )
+        .await
+        .expect("remote rule");
+
+        assert_eq!(result, json!({ "ok": true }));
+        assert_eq!(
+            calls.into_inner().unwrap(),
+            vec![(
+                "remember_approval_rule".to_string(),
+                json!({ "workspaceId": "ws-1", "command": ["git", "status"] }),
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn local_mode_does_not_call_remote() {
+        let state = remote_state().await;
+        state.app_settings.lock().await.backend_mode = BackendMode::Local;
+
+        let result = commit_message_prompt(&state, "missing".to_string(), None, |_, _| {
+            std::future::ready(Err("remote called in local mode".to_string()))
+        })
+        .await;
+
+        assert!(matches!(result, Err(err) if err != "remote called in local mode"));
+    }
 }
@@ -0,0 +1,355 @@
+//! Cursor's `stream-json` output. Unlike the Codex app-server, Cursor prints
+//! its own event shapes rather than JSON-RPC, so each line is translated here
+//! before the adapter's reader emits it.
+
+use serde_json::{json, Value};
+
+use crate::backend::adapter_base::reasoning_delta_event;
+
+/// Translates one line of Cursor's `stream-json` output into the app-server
+/// notification the UI expects. Cursor's events aren't JSON-RPC; lines with
+/// nothing to show (user echoes, config, partial tool output) map to `None`.
+pub(crate) fn parse_cursor_line(line: &str, thread_id: &str, turn_id: &str) -> Option<Value> {
+    let event: Value = serde_json::from_str(line).ok()?;
+    let event_type = event.get("type")?.as_str()?;
+
+    let msg_item_id = format!("msg_{turn_id}");
+
+    match event_type {
+        "system" => {
+            let subtype = event.get("subtype").and_then(|s| s.as_str()).unwrap_or("");
+            if subtype == "init" {
+                Some(json!({
+                    "method": "turn/started",
+                    "params": {
+                        "threadId": thread_id,
+                        "turnId": turn_id
+                    }
+                }))
+            } else {
+                None
+            }
+        }
+        "assistant" => {
+            let text = event
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(|c| c.as_array())?
+                .iter()
+                .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
+                .collect::<String>();
+            if text.is_empty() {
+                return None;
+            }
+            Some(json!({
+                "method": "item/agentMessage/delta",
+                "params": {
+                    "threadId": thread_id,
+                    "turnId": turn_id,
+                    "itemId": msg_item_id,
+                    "delta": text
+                }
+            }))
+        }
+        "tool_call" => {
+            let subtype = event.get("subtype").and_then(|s| s.as_str()).unwrap_or("");
+            let call_id = event.get("call_id").and_then(|c| c.as_str()).unwrap_or("");
+
+            match subtype {
+                "started" => {
+                    let tool_name = extract_tool_name_from_cursor_event(&event);
+                    Some(json!({
+                        "method": "item/started",
+                        "params": {
+                            "threadId": thread_id,
+                            "turnId": turn_id,
+                            "item": {
+                                "id": call_id,
+                                "type": "tool_use",
+                                "name": tool_name
+                            }
+                        }
+                    }))
+                }
+                "completed" => Some(json!({
+                    "method": "item/completed",
+                    "params": {
+                        "threadId": thread_id,
+                        "turnId": turn_id,
+                        "item": {
+                            "id": call_id,
+                            "type": "tool_use"
+                        }
+                    }
+                })),
+                _ => None,
+            }
+        }
+        "thinking" => {
+            if event.get("subtype").and_then(|s| s.as_str()) != Some("delta") {
+                return None;
+            }
+            let text = event.get("text").and_then(|t| t.as_str())?;
+            if text.is_empty() {
+                return None;
+            }
+            let item_id = format!("reasoning_{turn_id}");
+            Some(reasoning_delta_event(thread_id, turn_id, &item_id, text))
+        }
+        "result" if event.get("is_error").and_then(Value::as_bool) == Some(true) => {
+            let message = event
+                .get("result")
+                .and_then(|r| r.as_str())
+                .filter(|r| !r.trim().is_empty())
+                .unwrap_or("Cursor agent failed");
+            Some(json!({
+                "method": "turn/error",
+                "params": {
+                    "threadId": thread_id,
+                    "turnId": turn_id,
+                    "error": {
+                        "type": event.get("subtype").and_then(|s| s.as_str()).unwrap_or("error"),
+                        "message": message
+                    },
+                    "willRetry": false
+                }
+            }))
+        }
+        "result" => Some(json!({
+            "method": "turn/completed",
+            "params": {
+                "threadId": thread_id,
+                "turnId": turn_id,
+                "durationMs": event.get("duration_ms")
+            }
+        })),
+        _ => None,
+    }
+}
+
+/// Tool name from the `<name>ToolCall` key, either at the top level or nested
+/// under `tool_call` as current Cursor builds send it.
+fn extract_tool_name_from_cursor_event(event: &Value) -> &str {
+    for obj in [Some(event), event.get("tool_call")]
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object)
+    {
+        for key in obj.keys() {
+            if let Some(name) = key.strip_suffix("ToolCall") {
+                return name;
+            }
+        }
+    }
+    event
+        .get("tool_name")
+        .and_then(|n| n.as_str())
+        .unwrap_or("tool")
+}
+
+pub(crate) fn extract_cursor_session_id(line: &str) -> Option<String> {
+    let event: Value = serde_json::from_str(line).ok()?;
+    if event.get("type")?.as_str()? != "system" {
+        return None;
+    }
+    if event.get("subtype").and_then(|s| s.as_str()) != Some("init") {
+        return None;
+    }
+    event
+        .get("session_id")
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::events::SUPPORTED_APP_SERVER_METHODS;
+
+    #[test]
+    fn parse_system_init() {
+        let line = r#"{"type":"system","subtype":"init","session_id":"cs-1"}"#;
+        let event = parse_cursor_line(line, "t1", "turn1").unwrap();
+        assert_eq!(
+            event.get("method").and_then(|v| v.as_str()),
+            Some("turn/started")
+        );
+    }
+
+    #[test]
+    fn parse_system_non_init_is_dropped() {
+        let line = r#"{"type":"system","subtype":"config","data":{}}"#;
+        assert!(parse_cursor_line(line, "t1", "turn1").is_none());
+    }
+
+    #[test]
+    fn parse_assistant_message() {
+        let line = r#"{"type":"assistant","message":{"content":[{"text":"Hello world"}]}}"#;
+        let event = parse_cursor_line(line, "t1", "turn1").unwrap();
+        assert_eq!(
+            event.get("method").and_then(|v| v.as_str()),
+            Some("item/agentMessage/delta")
+        );
+        let params = event.get("params").unwrap();
+        assert_eq!(
+            params.get("delta").and_then(|d| d.as_str()),
+            Some("Hello world")
+        );
+        assert!(params.get("itemId").is_some());
+    }
+
+    #[test]
+    fn parse_assistant_message_empty_content_is_dropped() {
+        let line = r#"{"type":"assistant","message":{"content":[]}}"#;
+        assert!(parse_cursor_line(line, "t1", "turn1").is_none());
+    }
+
+    #[test]
+    fn parse_tool_call_started() {
+        let line = r#"{"type":"tool_call","subtype":"started","call_id":"c1","ReadToolCall":{"path":"test.rs"}}"#;
+        let event = parse_cursor_line(line, "t1", "turn1").unwrap();
+        assert_eq!(
+            event.get("method").and_then(|v| v.as_str()),
+            Some("item/started")
+        );
+        let item = event.get("params").and_then(|p| p.get("item")).unwrap();
+        assert_eq!(item.get("id").and_then(|i| i.as_str()), Some("c1"));
+        assert_eq!(item.get("name").and_then(|n| n.as_str()), Some("Read"));
+    }
+
+    #[test]
+    fn parse_tool_call_completed() {
+        let line = r#"{"type":"tool_call","subtype":"completed","call_id":"c1"}"#;
+        let event = parse_cursor_line(line, "t1", "turn1").unwrap();
+        assert_eq!(
+            event.get("method").and_then(|v| v.as_str()),
+            Some("item/completed")
+        );
+    }
+
+    #[test]
+    fn parse_result_event() {
+        let line = r#"{"type":"result","duration_ms":1500}"#;
+        let event = parse_cursor_line(line, "t1", "turn1").unwrap();
+        assert_eq!(
+            event.get("method").and_then(|v| v.as_str()),
+            Some("turn/completed")
+        );
+        assert_eq!(
+            event
+                .get("params")
+                .and_then(|p| p.get("durationMs"))
+                .and_then(|d| d.as_u64()),
+            Some(1500)
+        );
+    }
+
+    #[test]
+    fn parse_unknown_event() {
+        let line = r#"{"type":"internal_debug","data":{}}"#;
+        assert!(parse_cursor_line(line, "t1", "turn1").is_none());
+    }
+
+    #[test]
+    fn extract_session_id_from_init() {
+        let line = r#"{"type":"system","subtype":"init","session_id":"cs-42"}"#;
+        assert_eq!(extract_cursor_session_id(line), Some("cs-42".to_string()));
+    }
+
+    #[test]
+    fn extract_session_id_from_non_init() {
+        let line = r#"{"type":"assistant","message":{"content":[{"text":"hi"}]}}"#;
+        assert_eq!(extract_cursor_session_id(line), None);
+    }
+
+    #[test]
+    fn extract_tool_name_from_tool_call_key() {
+        let event: Value =
+            serde_json::from_str(r#"{"type":"tool_call","subtype":"started","call_id":"c1","EditToolCall":{"path":"x"}}"#)
+                .unwrap();
+        assert_eq!(extract_tool_name_from_cursor_event(&event), "Edit");
+    }
+
+    #[test]
+    fn extract_tool_name_from_nested_tool_call() {
+        let event: Value = serde_json::from_str(
+            r#"{"type":"tool_call","subtype":"started","call_id":"c1","tool_call":{"shellToolCall":{"args":{}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(extract_tool_name_from_cursor_event(&event), "shell");
+    }
+
+    #[test]
+    fn extract_tool_name_fallback() {
+        let event: Value = serde_json::from_str(
+            r#"{"type":"tool_call","subtype":"started","call_id":"c1","tool_name":"Bash"}"#,
+        )
+        .unwrap();
+        assert_eq!(extract_tool_name_from_cursor_event(&event), "Bash");
+    }
+
+    /// A turn as `cursor-agent -p --output-format stream-json` prints it.
+    const SAMPLE_STREAM: &[&str] = &[
+        r#"{"type":"system","subtype":"init","apiKeySource":"login","cwd":"/repo","session_id":"s1","model":"Claude 4 Sonnet","permissionMode":"default"}"#,
+        r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"fix it"}]},"session_id":"s1"}"#,
+        r#"{"type":"thinking","subtype":"delta","text":"Looking at the file","session_id":"s1"}"#,
+        r#"{"type":"thinking","subtype":"completed","session_id":"s1"}"#,
+        r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Reading it."}]},"session_id":"s1"}"#,
+        r#"{"type":"tool_call","subtype":"started","call_id":"c1","tool_call":{"readToolCall":{"args":{"path":"a.rs"}}},"session_id":"s1"}"#,
+        r#"{"type":"tool_call","subtype":"completed","call_id":"c1","tool_call":{"readToolCall":{"result":{"success":{}}}},"session_id":"s1"}"#,
+        r#"{"type":"result","subtype":"success","duration_ms":1200,"is_error":false,"result":"Reading it.","session_id":"s1"}"#,
+        r#"{"type":"result","subtype":"error","duration_ms":10,"is_error":true,"result":"Rate limited","session_id":"s1"}"#,
+    ];
+
+    #[test]
+    fn all_emitted_methods_are_supported_by_frontend() {
+        let methods: Vec<String> = SAMPLE_STREAM
+            .iter()
+            .filter_map(|line| parse_cursor_line(line, "thread1", "turn1"))
+            .map(|event| event["method"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                "turn/started",
+                "item/reasoning/textDelta",
+                "item/agentMessage/delta",
+                "item/started",
+                "item/completed",
+                "turn/completed",
+                "turn/error",
+            ]
+        );
+        for method in &methods {
+            assert!(
+                SUPPORTED_APP_SERVER_METHODS.contains(&method.as_str()),
+                "Emitted method '{method}' is not in SUPPORTED_APP_SERVER_METHODS"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_assistant_message_joins_text_parts() {
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Hello "},{"type":"text","text":"world"}]}}"#;
+        let event = parse_cursor_line(line, "t1", "turn1").unwrap();
+        assert_eq!(event["params"]["delta"], "Hello world");
+    }
+
+    #[test]
+    fn parse_thinking_delta_as_reasoning() {
+        let line = r#"{"type":"thinking","subtype":"delta","text":"hmm"}"#;
+        let expected = reasoning_delta_event("t1", "turn1", "reasoning_turn1", "hmm");
+        assert_eq!(parse_cursor_line(line, "t1", "turn1"), Some(expected));
+        let done = r#"{"type":"thinking","subtype":"completed"}"#;
+        assert!(parse_cursor_line(done, "t1", "turn1").is_none());
+    }
+
+    #[test]
+    fn parse_error_result_as_turn_error() {
+        let line = r#"{"type":"result","subtype":"error","is_error":true,"result":"Rate limited"}"#;
+        let event = parse_cursor_line(line, "t1", "turn1").unwrap();
+        assert_eq!(event["method"], "turn/error");
+        assert_eq!(event["params"]["error"]["message"], "Rate limited");
+        assert_eq!(event["params"]["willRetry"], false);
+    }
+}
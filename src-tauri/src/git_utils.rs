@@ -1,10 +1,11 @@
 use std::collections::HashSet;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use git2::{DiffOptions, Repository, Tree};
 use ignore::WalkBuilder;
 
-use crate::types::{GitLogEntry, WorkspaceEntry};
+pub(crate) use crate::shared::git_core::{diff_patch_to_string, resolve_git_root};
+use crate::types::GitLogEntry;
 use crate::utils::normalize_git_path;
 
 pub(crate) fn image_mime_type(path: &str) -> Option<&'static str> {
@@ -82,14 +83,6 @@ pub(crate) fn diff_stats_for_path(
     Ok((additions, deletions))
 }
 
-pub(crate) fn diff_patch_to_string(patch: &mut git2::Patch) -> Result<String, git2::Error> {
-    let buf = patch.to_buf()?;
-    Ok(buf
-        .as_str()
-        .map(|value| value.to_string())
-        .unwrap_or_else(|| String::from_utf8_lossy(&buf).to_string()))
-}
-
 #[cfg(test)]
 mod tests {
     use super::{checkout_branch, image_mime_type};
@@ -153,29 +146,6 @@ pub(crate) fn parse_github_repo(remote_url: &str) -> Option<String> {
     }
 }
 
-pub(crate) fn resolve_git_root(entry: &WorkspaceEntry) -> Result<PathBuf, String> {
-    let base = PathBuf::from(&entry.path);
-    let root = entry
-        .settings
-        .git_root
-        .as_ref()
-        .map(|value| value.trim())
-        .filter(|value| !value.is_empty());
-    let Some(root) = root else {
-        return Ok(base);
-    };
-    let root_path = if Path::new(root).is_absolute() {
-        PathBuf::from(root)
-    } else {
-        base.join(root)
-    };
-    if root_path.is_dir() {
-        Ok(root_path)
-    } else {
-        Err(format!("Git root not found: {root}"))
-    }
-}
-
 fn should_skip_dir(name: &str) -> bool {
     matches!(
         name,
@@ -4,6 +4,7 @@ use tauri::{RunEvent, WindowEvent};
 
 mod backend;
 mod codex;
+mod cursor;
 mod files;
 mod dictation;
 mod event_sink;
@@ -74,7 +75,13 @@ pub fn run() {
             settings::get_app_settings,
             settings::update_app_settings,
             settings::get_codex_config_path,
+            settings::get_cli_home_path,
+            settings::get_gemini_setting,
+            settings::set_gemini_setting,
+            settings::set_thread_store_dir,
             settings::detect_installed_clis,
+            settings::validate_cli_args,
+            settings::get_default_args_preset,
             files::file_read,
             files::file_write,
             files::agent_profiles_list,
@@ -82,8 +89,11 @@ pub fn run() {
             codex::get_config_model,
             menu::menu_set_accelerators,
             codex::codex_doctor,
+            codex::doctor_all,
+            codex::cursor_mode_list,
             workspaces::list_workspaces,
             workspaces::is_workspace_path_dir,
+            workspaces::validate_workspace_path,
             workspaces::add_workspace,
             workspaces::add_clone,
             workspaces::add_worktree,
@@ -99,22 +109,44 @@ pub fn run() {
             workspaces::update_workspace_codex_bin,
             codex::start_thread,
             codex::send_user_message,
+            codex::list_active_sessions,
             codex::turn_interrupt,
+            codex::interrupt_workspace,
+            codex::get_turn_summary,
             codex::start_review,
             codex::respond_to_server_request,
             codex::remember_approval_rule,
+            codex::list_approval_rules,
+            codex::clear_approval_rule,
+            codex::clear_all_approval_rules,
+            codex::get_workspace_diff_structured,
             codex::get_commit_message_prompt,
             codex::generate_commit_message,
             codex::generate_run_metadata,
+            codex::quick_prompt,
+            codex::test_mcp_server,
+            codex::generate_run_metadata_cancel,
             codex::resume_thread,
             codex::fork_thread,
+            codex::duplicate_thread,
             codex::list_threads,
+            codex::list_all_threads,
             codex::list_mcp_server_status,
             codex::archive_thread,
             codex::compact_thread,
+            codex::get_thread_usage,
+            codex::repair_thread_store,
             codex::set_thread_name,
             codex::collaboration_mode_list,
+            codex::set_collaboration_mode,
+            codex::set_thread_model,
+            codex::get_collaboration_mode,
+            codex::start_event_recording,
+            codex::stop_event_recording,
+            codex::set_event_throttle,
             workspaces::connect_workspace,
+            workspaces::disconnect_workspace,
+            workspaces::preview_spawn_command,
             git::get_git_status,
             git::list_git_roots,
             git::get_git_diffs,
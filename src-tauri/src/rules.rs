@@ -6,6 +6,9 @@ use std::time::{Duration, Instant, SystemTime};
 
 const RULES_DIR: &str = "rules";
 const DEFAULT_RULES_FILE: &str = "default.rules";
+/// Comment written above each rule this app appends, so forgetting a rule
+/// never touches an identical one the user wrote by hand.
+const APP_RULE_MARKER: &str = "# Added by Agent Monitor";
 
 pub(crate) fn default_rules_path(codex_home: &Path) -> PathBuf {
     codex_home.join(RULES_DIR).join(DEFAULT_RULES_FILE)
@@ -35,6 +38,8 @@ pub(crate) fn append_prefix_rule(path: &Path, pattern: &[String]) -> Result<(),
     }
 
     let rule = format_prefix_rule(pattern);
+    updated.push_str(APP_RULE_MARKER);
+    updated.push('\n');
     updated.push_str(&rule);
 
     if !updated.ends_with('\n') {
@@ -44,6 +49,57 @@ pub(crate) fn append_prefix_rule(path: &Path, pattern: &[String]) -> Result<(),
     fs::write(path, updated).map_err(|err| err.to_string())
 }
 
+/// Deletes the allow `prefix_rule`s for exactly `pattern` that
+/// `append_prefix_rule` wrote, along with their marker and the blank line
+/// before them. Rules without the marker are left alone. Returns whether any
+/// rule was removed; a missing file has nothing to remove.
+pub(crate) fn remove_prefix_rule(path: &Path, pattern: &[String]) -> Result<bool, String> {
+    if pattern.is_empty() || !path.exists() {
+        return Ok(false);
+    }
+
+    let _lock = acquire_rules_lock(path)?;
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.to_string()),
+    };
+    let lines: Vec<&str> = existing.lines().collect();
+    let ranges: Vec<(usize, usize)> = allow_rule_line_ranges(&existing, pattern)
+        .into_iter()
+        .filter(|(start, _)| *start > 0 && lines[start - 1].trim() == APP_RULE_MARKER)
+        .map(|(start, end)| (start - 1, end))
+        .collect();
+    if ranges.is_empty() {
+        return Ok(false);
+    }
+
+    let mut kept: Vec<&str> = Vec::with_capacity(lines.len());
+    for (index, line) in lines.iter().enumerate() {
+        if ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&index))
+        {
+            continue;
+        }
+        let next_starts_removed_rule = ranges.iter().any(|(start, _)| *start == index + 1);
+        if line.trim().is_empty() && next_starts_removed_rule {
+            continue;
+        }
+        kept.push(line);
+    }
+    while kept.first().is_some_and(|line| line.trim().is_empty()) {
+        kept.remove(0);
+    }
+
+    let mut updated = kept.join("\n");
+    if !updated.is_empty() {
+        updated.push('\n');
+    }
+    fs::write(path, updated).map_err(|err| err.to_string())?;
+    Ok(true)
+}
+
 struct RulesFileLock {
     path: PathBuf,
 }
@@ -110,22 +166,28 @@ fn format_pattern_list(pattern: &[String]) -> String {
 }
 
 fn rule_already_present(contents: &str, pattern: &[String]) -> bool {
+    !allow_rule_line_ranges(contents, pattern).is_empty()
+}
+
+/// First and last line index of each allow `prefix_rule` for `pattern`.
+fn allow_rule_line_ranges(contents: &str, pattern: &[String]) -> Vec<(usize, usize)> {
     let target_pattern = normalize_rule_value(&format!("[{}]", format_pattern_list(pattern)));
-    let mut in_rule = false;
+    let mut ranges = Vec::new();
+    let mut rule_start = None;
     let mut pattern_matches = false;
     let mut decision_allows = false;
 
-    for line in contents.lines() {
+    for (index, line) in contents.lines().enumerate() {
         let trimmed = line.trim();
         if trimmed.starts_with("prefix_rule(") {
-            in_rule = true;
+            rule_start = Some(index);
             pattern_matches = false;
             decision_allows = false;
             continue;
         }
-        if !in_rule {
+        let Some(start) = rule_start else {
             continue;
-        }
+        };
         if trimmed.starts_with("pattern") {
             if let Some((_, value)) = trimmed.split_once('=') {
                 let candidate = value.trim().trim_end_matches(',');
@@ -142,12 +204,12 @@ fn rule_already_present(contents: &str, pattern: &[String]) -> bool {
             }
         } else if trimmed.starts_with(')') {
             if pattern_matches && decision_allows {
-                return true;
+                ranges.push((start, index));
             }
-            in_rule = false;
+            rule_start = None;
         }
     }
-    false
+    ranges
 }
 
 fn normalize_rule_value(value: &str) -> String {
@@ -162,3 +224,35 @@ fn escape_string(value: &str) -> String {
         .replace('\r', "\\r")
         .replace('\t', "\\t")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{append_prefix_rule, remove_prefix_rule};
+
+    #[test]
+    fn removing_a_rule_leaves_identical_hand_written_rules() {
+        let temp_dir = std::env::temp_dir().join(format!("rules-test-{}", uuid::Uuid::new_v4()));
+        let path = temp_dir.join("default.rules");
+        let pattern = vec!["npm".to_string(), "test".to_string()];
+        let hand_written =
+            "prefix_rule(\n    pattern = [\"npm\", \"test\"],\n    decision = \"allow\",\n)\n";
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        std::fs::write(&path, hand_written).unwrap();
+
+        // Already allowed by hand, so nothing is appended or removed.
+        append_prefix_rule(&path, &pattern).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), hand_written);
+        assert_eq!(remove_prefix_rule(&path, &pattern), Ok(false));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), hand_written);
+
+        let ls = vec!["ls".to_string()];
+        append_prefix_rule(&path, &ls).unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("# Added by Agent Monitor\nprefix_rule(\n    pattern = [\"ls\"]"));
+        assert_eq!(remove_prefix_rule(&path, &ls), Ok(true));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), hand_written);
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+}
@@ -1,9 +1,13 @@
+use serde_json::Value;
 use tauri::{State, Window};
 
+use crate::codex::args::{default_args_preset, validate_cli_args as validate_cli_args_inner};
 use crate::state::AppState;
 use crate::shared::cli_detect_core::{self, DetectedClis};
 use crate::shared::settings_core::{
-    get_app_settings_core, get_codex_config_path_core, update_app_settings_core,
+    get_app_settings_core, get_cli_home_path_core, get_codex_config_path_core,
+    get_gemini_setting_core, set_gemini_setting_core, set_thread_store_dir_core,
+    update_app_settings_core,
 };
 use crate::types::AppSettings;
 use crate::window;
@@ -35,7 +39,51 @@ pub(crate) async fn get_codex_config_path() -> Result<String, String> {
     get_codex_config_path_core()
 }
 
+#[tauri::command]
+pub(crate) async fn get_cli_home_path(cli_type: String) -> Result<String, String> {
+    get_cli_home_path_core(&cli_type)
+}
+
+#[tauri::command]
+pub(crate) async fn get_gemini_setting(key: String) -> Result<Value, String> {
+    get_gemini_setting_core(&key)
+}
+
+#[tauri::command]
+pub(crate) async fn set_gemini_setting(key: String, value: Value) -> Result<(), String> {
+    set_gemini_setting_core(&key, value)
+}
+
+#[tauri::command]
+pub(crate) async fn set_thread_store_dir(
+    dir: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Value, String> {
+    set_thread_store_dir_core(
+        dir,
+        &state.sessions,
+        &state.app_settings,
+        &state.settings_path,
+    )
+    .await
+}
+
 #[tauri::command]
 pub(crate) async fn detect_installed_clis() -> Result<DetectedClis, String> {
     Ok(cli_detect_core::detect_installed_clis().await)
 }
+
+#[tauri::command]
+pub(crate) async fn validate_cli_args(
+    cli_type: String,
+    args: Option<String>,
+) -> Result<Vec<String>, String> {
+    validate_cli_args_inner(&cli_type, args.as_deref())
+}
+
+#[tauri::command]
+pub(crate) async fn get_default_args_preset(cli_type: String) -> Result<String, String> {
+    let preset = default_args_preset(&cli_type);
+    validate_cli_args_inner(&cli_type, Some(&preset))?;
+    Ok(preset)
+}
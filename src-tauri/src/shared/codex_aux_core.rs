@@ -1,28 +1,204 @@
 use serde_json::{json, Value};
 use std::collections::HashMap;
+use std::future::Future;
 use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, oneshot, Mutex};
 use tokio::time::timeout;
 
 use crate::backend::app_server::{
-    build_codex_command_with_bin, build_codex_path_env, check_cli_installation, WorkspaceSession,
+    build_codex_command_with_bin, build_codex_path_env, check_cli_installation, CliSpawnConfig,
+    WorkspaceSession,
 };
-use crate::shared::process_core::tokio_command;
-use crate::shared::workspaces_core::resolve_default_cli_bin;
-use crate::types::AppSettings;
+use crate::shared::git_core::{
+    collect_workspace_diff_parts, collect_workspace_diff_structured, resolve_git_root,
+    WorkspaceDiffParts,
+};
+use crate::shared::process_core::{tokio_command, StdioRequestRunner};
+use crate::shared::workspaces_core::{build_cli_spawn_config, resolve_default_cli_bin};
+use crate::types::{
+    AppSettings, WorkspaceDiffStructured, WorkspaceEntry, WorkspaceKind, WorkspaceSettings,
+};
+
+/// Upper bound on the text collected from a background prompt before we stop
+/// listening and return what was gathered.
+pub(crate) const MAX_BACKGROUND_RESPONSE_BYTES: usize = 16 * 1024;
+
+/// Builds the commit message prompt, capping the embedded diff at
+/// `max_diff_bytes` (0 disables the cap).
+pub(crate) fn build_commit_message_prompt(diff: &str, max_diff_bytes: usize) -> String {
+    commit_message_prompt_with(&cap_commit_diff(diff, max_diff_bytes))
+}
+
+/// Builds the commit message prompt from a structured diff: a per-file
+/// summary (status and line counts, so renames and large edits stay visible
+/// even when the patch is capped) followed by the patch.
+pub(crate) fn build_structured_commit_message_prompt(
+    diff: &WorkspaceDiffStructured,
+    max_diff_bytes: usize,
+) -> String {
+    let mut changes = String::from("Files changed:\n");
+    for file in &diff.files {
+        let status = match file.status.as_str() {
+            "A" => "added",
+            "D" => "deleted",
+            "R" => "renamed",
+            _ => "modified",
+        };
+        changes.push_str(&format!(
+            "- {} ({status}, +{} -{})\n",
+            file.path, file.additions, file.deletions
+        ));
+    }
+    changes.push_str("\nDiff:\n");
+    changes.push_str(&cap_commit_diff(&diff.diff, max_diff_bytes));
+    commit_message_prompt_with(&changes)
+}
 
-pub(crate) fn build_commit_message_prompt(diff: &str) -> String {
+fn commit_message_prompt_with(changes: &str) -> String {
     format!(
         "Generate a concise git commit message for the following changes. \
 Follow conventional commit format (e.g., feat:, fix:, refactor:, docs:, etc.). \
 Keep the summary line under 72 characters. \
 Only output the commit message, nothing else.\n\n\
-Changes:\n{diff}"
+Changes:\n{changes}"
     )
 }
 
+struct DiffSection {
+    path: String,
+    header: String,
+    hunks: Vec<String>,
+    added: usize,
+    removed: usize,
+}
+
+fn file_marker_path(line: &str) -> Option<&str> {
+    line.trim_end().strip_prefix("=== ")?.strip_suffix(" ===")
+}
+
+fn diff_git_path(line: &str) -> Option<&str> {
+    line.strip_prefix("diff --git ")?
+        .trim_end()
+        .rsplit_once(" b/")
+        .map(|(_, path)| path)
+}
+
+/// Splits a combined diff (`=== path ===` sections as produced by
+/// `git_core::collect_workspace_diff`, or plain `diff --git` output) into files.
+fn split_diff_sections(diff: &str) -> Vec<DiffSection> {
+    let mut sections: Vec<DiffSection> = Vec::new();
+    for line in diff.split_inclusive('\n') {
+        let marker = file_marker_path(line);
+        let git_path = diff_git_path(line);
+        // A `diff --git` line right after a `=== path ===` marker belongs to
+        // the same file.
+        let starts_file = marker.is_some()
+            || (git_path.is_some()
+                && sections.last().is_none_or(|section| {
+                    !section.hunks.is_empty() || section.header.contains("diff --git ")
+                }));
+        if starts_file || sections.is_empty() {
+            sections.push(DiffSection {
+                path: marker.or(git_path).unwrap_or_default().to_string(),
+                header: String::new(),
+                hunks: Vec::new(),
+                added: 0,
+                removed: 0,
+            });
+        }
+        let section = sections.last_mut().expect("section pushed above");
+        if line.starts_with("@@") {
+            section.hunks.push(String::new());
+        }
+        match section.hunks.last_mut() {
+            Some(hunk) => {
+                if line.starts_with('+') {
+                    section.added += 1;
+                } else if line.starts_with('-') {
+                    section.removed += 1;
+                }
+                hunk.push_str(line);
+            }
+            None => section.header.push_str(line),
+        }
+    }
+    sections
+}
+
+/// Cuts `text` at the last line break that fits in `budget` bytes.
+fn take_whole_lines(text: &str, budget: usize) -> &str {
+    if text.len() <= budget {
+        return text;
+    }
+    match text[..budget].rfind('\n') {
+        Some(end) => &text[..=end],
+        None => "",
+    }
+}
+
+/// Caps a diff at `max_bytes` for prompt embedding. File headers are kept
+/// first so every touched file stays visible, then hunks are filled in from
+/// the top until the budget runs out. A truncated diff ends with a marker and
+/// per-file line stats.
+pub(crate) fn cap_commit_diff(diff: &str, max_bytes: usize) -> String {
+    if max_bytes == 0 || diff.len() <= max_bytes {
+        return diff.to_string();
+    }
+    let sections = split_diff_sections(diff);
+    let mut budget = max_bytes;
+    let mut kept_headers = 0;
+    for section in &sections {
+        if section.header.len() > budget {
+            break;
+        }
+        budget -= section.header.len();
+        kept_headers += 1;
+    }
+
+    let mut output = String::new();
+    let mut omitted_hunks = 0;
+    for section in &sections[..kept_headers] {
+        output.push_str(&section.header);
+        let mut dropped = 0;
+        for hunk in &section.hunks {
+            if dropped > 0 || budget == 0 {
+                dropped += 1;
+                continue;
+            }
+            let kept = take_whole_lines(hunk, budget);
+            output.push_str(kept);
+            budget -= kept.len();
+            if kept.len() < hunk.len() {
+                dropped += 1;
+                budget = 0;
+            }
+        }
+        if dropped > 0 {
+            if !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(&format!("[... {dropped} hunk(s) omitted]\n"));
+        }
+        omitted_hunks += dropped;
+    }
+    let omitted_files = sections.len() - kept_headers;
+
+    output.push_str(&format!(
+        "\n[diff truncated: exceeded {max_bytes} bytes; {omitted_hunks} hunk(s) and \
+{omitted_files} file(s) omitted]\n\nPer-file changes:\n"
+    ));
+    for section in &sections {
+        output.push_str(&format!(
+            "{} (+{} -{})\n",
+            section.path, section.added, section.removed
+        ));
+    }
+    output
+}
+
 pub(crate) fn build_run_metadata_prompt(cleaned_prompt: &str) -> String {
     format!(
         "You create concise run metadata for a coding task.\n\
@@ -103,12 +279,213 @@ pub(crate) fn sanitize_run_worktree_name(value: &str) -> String {
     format!("feat/{}", cleaned.trim_start_matches('/'))
 }
 
+/// Appends `delta` to `buffer` without letting it grow past `limit` bytes.
+/// Returns `true` once the limit has been reached and collection should stop.
+pub(crate) fn append_capped_delta(buffer: &mut String, delta: &str, limit: usize) -> bool {
+    let remaining = limit.saturating_sub(buffer.len());
+    if delta.len() <= remaining {
+        buffer.push_str(delta);
+        return buffer.len() >= limit;
+    }
+    let mut cut = remaining;
+    while cut > 0 && !delta.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    buffer.push_str(&delta[..cut]);
+    true
+}
+
+fn truncation_note(limit: usize) -> String {
+    format!("\n\n[truncated: response exceeded {} KB]", limit / 1024)
+}
+
+fn locate_cli_bin(bin: &str, path_env: Option<&str>) -> Option<PathBuf> {
+    let candidate = Path::new(bin);
+    if candidate.components().count() > 1 || candidate.is_absolute() {
+        return Some(candidate.to_path_buf());
+    }
+    let search = path_env
+        .map(std::ffi::OsString::from)
+        .or_else(|| std::env::var_os("PATH"))?;
+    std::env::split_paths(&search)
+        .map(|dir| dir.join(bin))
+        .find(|path| path.symlink_metadata().is_ok())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    metadata.is_file()
+}
+
+/// Describes what the configured bin actually points at so spawn failures
+/// caused by aliases, plain files, or broken links are easy to spot.
+pub(crate) fn inspect_cli_bin(bin: &str, path_env: Option<&str>) -> Value {
+    let Some(path) = locate_cli_bin(bin, path_env) else {
+        return json!({
+            "bin": bin,
+            "resolvedPath": Value::Null,
+            "exists": false,
+            "isFile": false,
+            "executable": false,
+            "isSymlink": false,
+            "symlinkTarget": Value::Null,
+        });
+    };
+    let link_metadata = path.symlink_metadata().ok();
+    let is_symlink = link_metadata
+        .as_ref()
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false);
+    let symlink_target = if is_symlink {
+        std::fs::read_link(&path).ok()
+    } else {
+        None
+    };
+    let metadata = std::fs::metadata(&path).ok();
+    json!({
+        "bin": bin,
+        "resolvedPath": path,
+        "exists": metadata.is_some(),
+        "isFile": metadata.as_ref().map(|m| m.is_file()).unwrap_or(false),
+        "executable": metadata.as_ref().map(is_executable).unwrap_or(false),
+        "isSymlink": is_symlink,
+        "symlinkTarget": symlink_target,
+    })
+}
+
+#[cfg(windows)]
+const NODE_BIN: &str = "node.exe";
+#[cfg(not(windows))]
+const NODE_BIN: &str = "node";
+
+/// Every `node` on the assembled PATH, in lookup order. The first entry is the
+/// one a plain `node` invocation resolves to.
+fn find_node_binaries(path_env: Option<&str>) -> Vec<PathBuf> {
+    let Some(search) = path_env
+        .map(std::ffi::OsString::from)
+        .or_else(|| std::env::var_os("PATH"))
+    else {
+        return Vec::new();
+    };
+    let mut found: Vec<PathBuf> = Vec::new();
+    for dir in std::env::split_paths(&search) {
+        let candidate = dir.join(NODE_BIN);
+        let executable = std::fs::metadata(&candidate)
+            .map(|metadata| is_executable(&metadata))
+            .unwrap_or(false);
+        if executable && !found.contains(&candidate) {
+            found.push(candidate);
+        }
+    }
+    found
+}
+
+fn node_major_version(version: &str) -> Option<u32> {
+    version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .next()
+        .and_then(|major| major.parse().ok())
+}
+
+async fn read_node_version(node: &Path) -> Option<String> {
+    let mut command = tokio_command(node);
+    command.arg("--version");
+    command.stdout(std::process::Stdio::piped());
+    command.stderr(std::process::Stdio::piped());
+    match timeout(Duration::from_secs(5), command.output()).await {
+        Ok(Ok(output)) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            (!version.is_empty()).then_some(version)
+        }
+        _ => None,
+    }
+}
+
+/// Resolves which `node` the CLI will pick up and lists every node on the
+/// PATH when they disagree on the major version. Version managers like nvm
+/// and volta make it easy to end up with a different node than expected.
+async fn inspect_node_binaries(path_env: Option<&str>) -> (Option<PathBuf>, Vec<Value>) {
+    let binaries = find_node_binaries(path_env);
+    let node_path = binaries.first().cloned();
+    let mut versions = Vec::with_capacity(binaries.len());
+    for binary in binaries {
+        let version = read_node_version(&binary).await;
+        versions.push((binary, version));
+    }
+    let mut majors: Vec<u32> = versions
+        .iter()
+        .filter_map(|(_, version)| version.as_deref().and_then(node_major_version))
+        .collect();
+    majors.sort_unstable();
+    majors.dedup();
+    if majors.len() < 2 {
+        return (node_path, Vec::new());
+    }
+    let conflicts = versions
+        .into_iter()
+        .map(|(path, version)| json!({ "path": path, "version": version }))
+        .collect();
+    (node_path, conflicts)
+}
+
+/// The Claude adapter drives turns with `--output-format stream-json --verbose`,
+/// which older CLIs do not offer; their help output never mentions it.
+fn evaluate_claude_stream_json_support(help: Result<String, String>) -> (bool, Option<String>) {
+    match help {
+        Ok(text) if text.contains("stream-json") => (true, None),
+        Ok(_) => (
+            false,
+            Some(
+                "This Claude CLI does not support `--output-format stream-json`. \
+Update it with `npm install -g @anthropic-ai/claude-code` (or `claude update`)."
+                    .to_string(),
+            ),
+        ),
+        Err(err) => (false, Some(format!("Failed to run `claude --help`: {err}"))),
+    }
+}
+
+fn describe_bin_problem(details: &Value) -> Option<String> {
+    let flag = |key: &str| details.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let bin = details
+        .get("bin")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if flag("isSymlink") && !flag("exists") {
+        let target = details
+            .get("symlinkTarget")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown target");
+        return Some(format!("`{bin}` is a broken symlink to {target}"));
+    }
+    if !flag("exists") {
+        return Some(format!(
+            "`{bin}` was not found; shell aliases and functions are not visible to the app"
+        ));
+    }
+    if !flag("isFile") {
+        return Some(format!("`{bin}` is not a regular file"));
+    }
+    if !flag("executable") {
+        return Some(format!("`{bin}` is not executable"));
+    }
+    None
+}
+
 pub(crate) async fn codex_doctor_core(
     app_settings: &Mutex<AppSettings>,
     codex_bin: Option<String>,
     codex_args: Option<String>,
 ) -> Result<Value, String> {
-    let (cli_type, default_bin, default_args) = {
+    let (cli_type, default_bin, default_args, gemini_use_sandbox) = {
         let settings = app_settings.lock().await;
         let default = resolve_default_cli_bin(&settings);
         let args = match settings.cli_type.as_str() {
@@ -117,25 +494,57 @@ pub(crate) async fn codex_doctor_core(
             "claude" => settings.claude_args.clone(),
             _ => settings.codex_args.clone(),
         };
-        (settings.cli_type.clone(), default, args)
+        (
+            settings.cli_type.clone(),
+            default,
+            args,
+            settings.gemini_use_sandbox,
+        )
     };
-    let cli_name = match cli_type.as_str() {
-        "claude" => "Claude",
-        "gemini" => "Gemini",
-        "cursor" => "Cursor",
-        _ => "Codex",
-    };
-    let is_codex = cli_type.as_str() == "codex" || !["claude", "gemini", "cursor"].contains(&cli_type.as_str());
     let resolved = codex_bin
-        .clone()
         .filter(|value| !value.trim().is_empty())
         .or(default_bin);
     let resolved_args = codex_args
-        .clone()
         .filter(|value| !value.trim().is_empty())
         .or(default_args);
+    let mut report = cli_doctor_core(&cli_type, resolved, resolved_args).await?;
+    if cli_type == "gemini" {
+        annotate_gemini_sandbox(&mut report, gemini_use_sandbox);
+    }
+    Ok(report)
+}
+
+/// Records in a Gemini doctor report whether turns run under Gemini's own
+/// sandbox or plain, with the gondolin MCP server as the only sandbox.
+fn annotate_gemini_sandbox(report: &mut Value, use_sandbox: bool) {
+    report["sandboxMode"] = json!(if use_sandbox { "gemini" } else { "gondolin" });
+    report["sandboxDetails"] = if use_sandbox {
+        Value::Null
+    } else {
+        json!("Gemini runs without --sandbox (geminiUseSandbox is off); sandboxing relies on the gondolin MCP server.")
+    };
+}
+
+async fn cli_doctor_core(
+    cli_type: &str,
+    resolved: Option<String>,
+    resolved_args: Option<String>,
+) -> Result<Value, String> {
+    let cli_name = match cli_type {
+        "claude" => "Claude",
+        "gemini" => "Gemini",
+        "cursor" => "Cursor",
+        _ => "Codex",
+    };
+    let is_codex = cli_type == "codex" || !["claude", "gemini", "cursor"].contains(&cli_type);
     let path_env = build_codex_path_env(resolved.as_deref());
-    let version = check_cli_installation(resolved.clone(), cli_name).await?;
+    let bin_details = inspect_cli_bin(resolved.as_deref().unwrap_or("codex"), path_env.as_deref());
+    let version = check_cli_installation(resolved.clone(), cli_name)
+        .await
+        .map_err(|err| match describe_bin_problem(&bin_details) {
+            Some(problem) => format!("{err} ({problem})"),
+            None => err.to_string(),
+        })?;
 
     let (app_server_ok, app_server_details) = if is_codex {
         let mut command = build_codex_command_with_bin(
@@ -162,7 +571,31 @@ pub(crate) async fn codex_doctor_core(
         (true, None)
     };
 
-    let (node_ok, node_version, node_details) = if is_codex {
+    let (stream_json_ok, stream_json_details) = if cli_type == "claude" {
+        let mut command = build_codex_command_with_bin(
+            resolved.clone(),
+            resolved_args.as_deref(),
+            vec!["--help".to_string()],
+        )?;
+        command.stdout(std::process::Stdio::piped());
+        command.stderr(std::process::Stdio::piped());
+        let help = match timeout(Duration::from_secs(5), command.output()).await {
+            Ok(Ok(output)) => {
+                let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                Ok(text)
+            }
+            Ok(Err(err)) => Err(err.to_string()),
+            Err(_) => Err("Timed out while running `claude --help`.".to_string()),
+        };
+        let (ok, details) = evaluate_claude_stream_json_support(help);
+        (Some(ok), details)
+    } else {
+        (None, None)
+    };
+
+    let checks_node = is_codex || cli_type == "gemini";
+    let (node_ok, node_version, node_details) = if checks_node {
         let mut node_command = tokio_command("node");
         if let Some(ref path_env) = path_env {
             node_command.env("PATH", path_env);
@@ -220,9 +653,14 @@ pub(crate) async fn codex_doctor_core(
     } else {
         (true, None, None)
     };
+    let (node_path, node_conflicts) = if checks_node {
+        inspect_node_binaries(path_env.as_deref()).await
+    } else {
+        (None, Vec::new())
+    };
 
     Ok(json!({
-        "ok": version.is_some() && app_server_ok,
+        "ok": version.is_some() && app_server_ok && stream_json_ok.unwrap_or(true),
         "codexBin": resolved,
         "version": version,
         "appServerOk": app_server_ok,
@@ -231,16 +669,192 @@ pub(crate) async fn codex_doctor_core(
         "nodeOk": node_ok,
         "nodeVersion": node_version,
         "nodeDetails": node_details,
+        "nodePath": node_path,
+        "nodeConflicts": node_conflicts,
+        "binDetails": bin_details,
+        "streamJsonOk": stream_json_ok,
+        "streamJsonDetails": stream_json_details,
     }))
 }
 
+/// Runs the doctor for one CLI in `doctor_all`. A CLI without a configured
+/// bin is reported as skipped rather than failed.
+async fn doctor_all_entry(cli_type: &str, bin: Option<String>, args: Option<String>) -> Value {
+    let Some(bin) = bin.filter(|value| !value.trim().is_empty()) else {
+        return json!({ "status": "skipped", "ok": false });
+    };
+    match cli_doctor_core(cli_type, Some(bin), args).await {
+        Ok(mut report) => {
+            let ok = report["ok"].as_bool().unwrap_or(false);
+            report["status"] = json!(if ok { "ok" } else { "failed" });
+            report
+        }
+        Err(error) => json!({ "status": "failed", "ok": false, "error": error }),
+    }
+}
+
+/// Checks the Gemini, Claude and Cursor CLIs concurrently. `ok` is true when
+/// every CLI that was checked passed.
+pub(crate) async fn doctor_all_core(app_settings: &Mutex<AppSettings>) -> Value {
+    let settings = app_settings.lock().await.clone();
+    let (mut gemini, claude, cursor) = tokio::join!(
+        doctor_all_entry("gemini", settings.gemini_bin, settings.gemini_args),
+        doctor_all_entry("claude", settings.claude_bin, settings.claude_args),
+        doctor_all_entry("cursor", settings.cursor_bin, settings.cursor_args),
+    );
+    if gemini["status"] != "skipped" {
+        annotate_gemini_sandbox(&mut gemini, settings.gemini_use_sandbox);
+    }
+    let ok = [&gemini, &claude, &cursor]
+        .iter()
+        .all(|report| report["status"] != "failed");
+    json!({ "ok": ok, "gemini": gemini, "claude": claude, "cursor": cursor })
+}
+
+/// How long an MCP server gets to answer `initialize` when tested.
+const MCP_TEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+const MCP_PROTOCOL_VERSION: &str = "2025-06-18";
+
+fn describe_mcp_test_error(command: &str, error: &std::io::Error) -> String {
+    match error.kind() {
+        ErrorKind::NotFound => format!(
+            "`{command}` was not found. Check the command name or use an absolute path."
+        ),
+        ErrorKind::PermissionDenied => {
+            format!("`{command}` could not be run: permission denied. Check that it is executable.")
+        }
+        ErrorKind::TimedOut => format!(
+            "`{command}` started but did not answer `initialize` within {}s. Check that it is a stdio MCP server and that its arguments are right.",
+            MCP_TEST_TIMEOUT.as_secs()
+        ),
+        ErrorKind::UnexpectedEof => {
+            format!("`{command}` exited before answering `initialize` ({error}).")
+        }
+        _ => format!("Failed to run `{command}`: {error}"),
+    }
+}
+
+/// Starts the stdio MCP server `command args`, performs the `initialize`
+/// handshake and reports the capabilities it advertises. The server is
+/// stopped afterwards either way; `ok` is false with an explanation in
+/// `details` when it fails to start or answer.
+pub(crate) async fn test_mcp_server_core(
+    runner: &dyn StdioRequestRunner,
+    command: String,
+    args: Vec<String>,
+) -> Result<Value, String> {
+    let command = command.trim();
+    if command.is_empty() {
+        return Err("MCP server command is required.".to_string());
+    }
+    let mut process = tokio_command(command);
+    process.args(&args);
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+            "protocolVersion": MCP_PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": { "name": "Agent Monitor", "version": env!("CARGO_PKG_VERSION") }
+        }
+    });
+    let failure =
+        |details: String| json!({ "ok": false, "details": details, "capabilities": null });
+
+    let response = match runner.request(process, request, MCP_TEST_TIMEOUT).await {
+        Ok(response) => response,
+        Err(error) => return Ok(failure(describe_mcp_test_error(command, &error))),
+    };
+    if let Some(error) = response.get("error") {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown error");
+        return Ok(failure(format!(
+            "`{command}` rejected `initialize`: {message}"
+        )));
+    }
+    let Some(result) = response.get("result").filter(|result| result.is_object()) else {
+        return Ok(failure(format!(
+            "`{command}` answered `initialize` without a result."
+        )));
+    };
+    let server_name = result
+        .pointer("/serverInfo/name")
+        .and_then(Value::as_str)
+        .unwrap_or(command);
+    let server_version = result
+        .pointer("/serverInfo/version")
+        .and_then(Value::as_str)
+        .map(|version| format!(" {version}"))
+        .unwrap_or_default();
+    let protocol = result
+        .get("protocolVersion")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown");
+    Ok(json!({
+        "ok": true,
+        "details": format!(
+            "{server_name}{server_version} answered `initialize` (protocol {protocol})."
+        ),
+        "capabilities": result.get("capabilities").cloned().unwrap_or_else(|| json!({})),
+    }))
+}
+
+/// Cancel handles for in-flight background prompts, keyed by workspace.
+pub(crate) type BackgroundPromptCancels = Mutex<HashMap<String, oneshot::Sender<()>>>;
+
+pub(crate) const BACKGROUND_PROMPT_CANCELED: &str = "Background generation canceled.";
+
+/// Registers a cancel handle for `workspace_id`. A newer registration drops
+/// the previous sender, which cancels the older generation.
+async fn register_background_cancel(
+    cancels: &BackgroundPromptCancels,
+    workspace_id: &str,
+) -> oneshot::Receiver<()> {
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    cancels
+        .lock()
+        .await
+        .insert(workspace_id.to_string(), cancel_tx);
+    cancel_rx
+}
+
+/// Drops the finished generation's handle unless a newer one replaced it.
+async fn clear_background_cancel(cancels: &BackgroundPromptCancels, workspace_id: &str) {
+    let mut cancels = cancels.lock().await;
+    if cancels
+        .get(workspace_id)
+        .is_some_and(|cancel_tx| cancel_tx.is_closed())
+    {
+        cancels.remove(workspace_id);
+    }
+}
+
+pub(crate) async fn cancel_background_prompt_core(
+    cancels: &BackgroundPromptCancels,
+    workspace_id: &str,
+) -> Value {
+    let cancel_tx = cancels.lock().await.remove(workspace_id);
+    let canceled = cancel_tx.is_some_and(|cancel_tx| cancel_tx.send(()).is_ok());
+    json!({ "canceled": canceled })
+}
+
+/// Runs `prompt` in a hidden thread and collects the agent's reply. When
+/// `cancel_rx` fires (or its sender is dropped) collection stops early; the
+/// thread is archived either way. `keep_visible` skips both the hide and the
+/// archive so the thread can be inspected.
 pub(crate) async fn run_background_prompt_core<F>(
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
     prompt: String,
     on_hide_thread: F,
+    keep_visible: bool,
     timeout_error: &str,
     turn_error_fallback: &str,
+    cancel_rx: Option<oneshot::Receiver<()>>,
 ) -> Result<String, String>
 where
     F: Fn(&str, &str),
@@ -252,7 +866,119 @@ where
             .ok_or("workspace not connected")?
             .clone()
     };
+    run_background_prompt_on_session(
+        &session,
+        prompt,
+        on_hide_thread,
+        keep_visible,
+        false,
+        timeout_error,
+        turn_error_fallback,
+        cancel_rx,
+    )
+    .await
+}
 
+/// Undoes a background prompt's side effects if its future is dropped before
+/// finishing (e.g. the invoking window closed): interrupts the turn, removes
+/// the thread callback, archives the thread and, for throwaway sessions,
+/// kills the CLI. Completed prompts clean up through
+/// [`BackgroundThreadGuard::release`] instead.
+struct BackgroundThreadGuard {
+    session: Arc<WorkspaceSession>,
+    thread_id: Option<String>,
+    /// `turn/interrupt` params while the turn may still be running.
+    turn: Option<Value>,
+    keep_visible: bool,
+    kill_on_drop: bool,
+    armed: bool,
+}
+
+impl BackgroundThreadGuard {
+    fn new(session: &Arc<WorkspaceSession>, keep_visible: bool, kill_on_drop: bool) -> Self {
+        Self {
+            session: Arc::clone(session),
+            thread_id: None,
+            turn: None,
+            keep_visible,
+            kill_on_drop,
+            armed: true,
+        }
+    }
+
+    /// Stops the turn if it is still running, so an adapter's CLI process
+    /// doesn't outlive the prompt.
+    async fn interrupt_turn(&mut self) {
+        if let Some(params) = self.turn.take() {
+            let _ = self.session.send_request("turn/interrupt", params).await;
+        }
+    }
+
+    /// Removes the callback and archives the thread, then disarms the guard.
+    async fn release(&mut self) {
+        self.armed = false;
+        if let Some(thread_id) = self.thread_id.take() {
+            cleanup_background_thread(&self.session, &thread_id, self.keep_visible).await;
+        }
+    }
+}
+
+impl Drop for BackgroundThreadGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let session = Arc::clone(&self.session);
+        let thread_id = self.thread_id.take();
+        let turn = self.turn.take();
+        let keep_visible = self.keep_visible;
+        let kill_on_drop = self.kill_on_drop;
+        runtime.spawn(async move {
+            if let Some(params) = turn {
+                let _ = session.send_request("turn/interrupt", params).await;
+            }
+            if let Some(thread_id) = thread_id {
+                cleanup_background_thread(&session, &thread_id, keep_visible).await;
+            }
+            if kill_on_drop {
+                session.kill().await;
+            }
+        });
+    }
+}
+
+async fn cleanup_background_thread(
+    session: &WorkspaceSession,
+    thread_id: &str,
+    keep_visible: bool,
+) {
+    {
+        let mut callbacks = session.background_thread_callbacks.lock().await;
+        callbacks.remove(thread_id);
+    }
+    archive_background_thread(session, thread_id, keep_visible).await;
+}
+
+/// `kill_on_drop` marks `session` as owned by this prompt, so it is killed
+/// if the prompt is dropped mid-flight.
+async fn run_background_prompt_on_session<F>(
+    session: &Arc<WorkspaceSession>,
+    prompt: String,
+    on_hide_thread: F,
+    keep_visible: bool,
+    kill_on_drop: bool,
+    timeout_error: &str,
+    turn_error_fallback: &str,
+    cancel_rx: Option<oneshot::Receiver<()>>,
+) -> Result<String, String>
+where
+    F: Fn(&str, &str),
+{
+    let mut guard = BackgroundThreadGuard::new(session, keep_visible, kill_on_drop);
+    let workspace_id = session.entry.id.as_str();
     let thread_params = json!({
         "cwd": session.entry.path,
         "approvalPolicy": "never"
@@ -287,7 +1013,11 @@ where
         })?
         .to_string();
 
-    on_hide_thread(&workspace_id, &thread_id);
+    guard.thread_id = Some(thread_id.clone());
+
+    if !keep_visible {
+        on_hide_thread(workspace_id, &thread_id);
+    }
 
     let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
     {
@@ -306,13 +1036,8 @@ where
     let turn_result = match turn_result {
         Ok(result) => result,
         Err(error) => {
-            {
-                let mut callbacks = session.background_thread_callbacks.lock().await;
-                callbacks.remove(&thread_id);
-            }
-            let archive_params = json!({ "threadId": thread_id.as_str() });
-            let _ = session.send_request("thread/archive", archive_params).await;
-            return Err(error);
+            guard.release().await;
+            return Err(error.into());
         }
     };
 
@@ -321,33 +1046,67 @@ where
             .get("message")
             .and_then(|m| m.as_str())
             .unwrap_or(turn_error_fallback);
-        {
-            let mut callbacks = session.background_thread_callbacks.lock().await;
-            callbacks.remove(&thread_id);
-        }
-        let archive_params = json!({ "threadId": thread_id.as_str() });
-        let _ = session.send_request("thread/archive", archive_params).await;
+        guard.release().await;
         return Err(error_msg.to_string());
     }
+    let turn_id = turn_result
+        .get("result")
+        .and_then(|r| r.get("turn"))
+        .and_then(|t| t.get("id"))
+        .and_then(Value::as_str);
+    guard.turn = Some(json!({ "threadId": thread_id, "turnId": turn_id }));
 
     let mut response_text = String::new();
+    let mut turn_finished = false;
+    let mut truncated = false;
+    let canceled = async move {
+        match cancel_rx {
+            Some(cancel_rx) => {
+                let _ = cancel_rx.await;
+            }
+            None => std::future::pending::<()>().await,
+        }
+    };
     let collect_result = timeout(Duration::from_secs(60), async {
-        while let Some(event) = rx.recv().await {
+        tokio::pin!(canceled);
+        loop {
+            let event = tokio::select! {
+                _ = &mut canceled => return Err(BACKGROUND_PROMPT_CANCELED.to_string()),
+                event = rx.recv() => event,
+            };
+            let Some(event) = event else {
+                break;
+            };
             let method = event.get("method").and_then(|m| m.as_str()).unwrap_or("");
             match method {
                 "item/agentMessage/delta" => {
                     if let Some(params) = event.get("params") {
                         if let Some(delta) = params.get("delta").and_then(|d| d.as_str()) {
-                            response_text.push_str(delta);
+                            if append_capped_delta(
+                                &mut response_text,
+                                delta,
+                                MAX_BACKGROUND_RESPONSE_BYTES,
+                            ) {
+                                truncated = true;
+                                break;
+                            }
                         }
                     }
                 }
-                "turn/completed" => break,
+                "turn/completed" => {
+                    turn_finished = true;
+                    break;
+                }
                 "turn/error" => {
-                    let error_msg = event
-                        .get("params")
-                        .and_then(|p| p.get("error"))
+                    turn_finished = true;
+                    let error = event.get("params").and_then(|p| p.get("error"));
+                    let error_msg = error
                         .and_then(|e| e.as_str())
+                        .or_else(|| {
+                            error
+                                .and_then(|e| e.get("message"))
+                                .and_then(|m| m.as_str())
+                        })
                         .unwrap_or(turn_error_fallback);
                     return Err(error_msg.to_string());
                 }
@@ -358,13 +1117,12 @@ where
     })
     .await;
 
-    {
-        let mut callbacks = session.background_thread_callbacks.lock().await;
-        callbacks.remove(&thread_id);
+    // A timeout, cancel or truncated reply leaves the turn running.
+    if turn_finished {
+        guard.turn = None;
     }
-
-    let archive_params = json!({ "threadId": thread_id });
-    let _ = session.send_request("thread/archive", archive_params).await;
+    guard.interrupt_turn().await;
+    guard.release().await;
 
     match collect_result {
         Ok(Ok(())) => {}
@@ -372,10 +1130,1074 @@ where
         Err(_) => return Err(timeout_error.to_string()),
     }
 
-    let trimmed = response_text.trim().to_string();
+    let mut trimmed = response_text.trim().to_string();
     if trimmed.is_empty() {
         return Err("No response was generated".to_string());
     }
+    if truncated {
+        trimmed.push_str(&truncation_note(MAX_BACKGROUND_RESPONSE_BYTES));
+    }
 
     Ok(trimmed)
 }
+
+async fn archive_background_thread(
+    session: &WorkspaceSession,
+    thread_id: &str,
+    keep_visible: bool,
+) {
+    if keep_visible {
+        return;
+    }
+    let archive_params = json!({ "threadId": thread_id });
+    let _ = session.send_request("thread/archive", archive_params).await;
+}
+
+/// Upper bound on a whole quick prompt once its session is up, covering
+/// thread and turn startup as well as reply collection.
+const QUICK_PROMPT_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Thread store of a quick prompt's throwaway session, removed once the
+/// prompt returns or is dropped so its `quick-prompt-<uuid>.json` never
+/// lands in the user's thread store dir.
+struct QuickPromptStoreDir(PathBuf);
+
+impl Drop for QuickPromptStoreDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs `prompt` once against `cwd` in a throwaway session that is never
+/// added to `sessions` and is killed before returning.
+pub(crate) async fn quick_prompt_core<F, Fut>(
+    cli_type: String,
+    cwd: String,
+    prompt: String,
+    app_settings: &Mutex<AppSettings>,
+    spawn_session: F,
+) -> Result<String, String>
+where
+    F: FnOnce(WorkspaceEntry, CliSpawnConfig) -> Fut,
+    Fut: Future<Output = Result<Arc<WorkspaceSession>, String>>,
+{
+    if prompt.trim().is_empty() {
+        return Err("Prompt is required.".to_string());
+    }
+    if !["codex", "claude", "gemini", "cursor"].contains(&cli_type.as_str()) {
+        return Err(format!("Unsupported CLI type: {cli_type}"));
+    }
+    if !Path::new(&cwd).is_dir() {
+        return Err("Quick prompt directory must be a folder.".to_string());
+    }
+
+    let entry = WorkspaceEntry {
+        id: format!("quick-prompt-{}", uuid::Uuid::new_v4()),
+        name: "Quick prompt".to_string(),
+        path: cwd,
+        codex_bin: None,
+        kind: WorkspaceKind::Main,
+        parent_id: None,
+        worktree: None,
+        settings: WorkspaceSettings::default(),
+    };
+    let mut settings = app_settings.lock().await.clone();
+    settings.cli_type = cli_type;
+    let store_dir = QuickPromptStoreDir(std::env::temp_dir().join(&entry.id));
+    let mut config = build_cli_spawn_config(&entry, None, &settings);
+    config.thread_store_dir = Some(store_dir.0.clone());
+    let session = spawn_session(entry, config).await?;
+
+    let result = timeout(
+        QUICK_PROMPT_TIMEOUT,
+        run_background_prompt_on_session(
+            &session,
+            prompt,
+            |_, _| {},
+            false,
+            true,
+            "Timeout waiting for quick prompt response",
+            "Unknown error during quick prompt",
+            None,
+        ),
+    )
+    .await;
+    session.kill().await;
+    drop(store_dir);
+
+    result.unwrap_or_else(|_| Err("Timeout waiting for quick prompt response".to_string()))
+}
+
+async fn workspace_git_root(
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    workspace_id: &str,
+) -> Result<PathBuf, String> {
+    let entry = workspaces
+        .lock()
+        .await
+        .get(workspace_id)
+        .cloned()
+        .ok_or("workspace not found")?;
+    resolve_git_root(&entry)
+}
+
+pub(crate) async fn workspace_diff_structured_core(
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    workspace_id: &str,
+) -> Result<WorkspaceDiffStructured, String> {
+    let repo_root = workspace_git_root(workspaces, workspace_id).await?;
+    collect_workspace_diff_structured(&repo_root, false).await
+}
+
+pub(crate) const NO_CHANGES_MESSAGE: &str = "No changes to generate commit message for";
+pub(crate) const NOTHING_STAGED_MESSAGE: &str =
+    "No staged changes to generate commit message for; stage files with git add first";
+
+/// Picks the raw diff to describe: staged changes when there are any,
+/// otherwise the whole working tree unless `staged_only` is set.
+fn select_commit_diff(parts: WorkspaceDiffParts, staged_only: bool) -> Result<String, String> {
+    if !parts.staged.trim().is_empty() {
+        return Ok(parts.staged);
+    }
+    if parts.working_tree.trim().is_empty() {
+        return Err(NO_CHANGES_MESSAGE.to_string());
+    }
+    if staged_only {
+        return Err(NOTHING_STAGED_MESSAGE.to_string());
+    }
+    Ok(parts.working_tree)
+}
+
+/// Builds the commit prompt from the structured diff, falling back to the raw
+/// combined diff when the structured one is unavailable or empty (e.g. only
+/// untracked files changed). With `staged_only`, unstaged changes are never
+/// described and a clean index is reported separately from a clean tree.
+pub(crate) async fn commit_message_prompt_core(
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    workspace_id: &str,
+    max_diff_bytes: usize,
+    staged_only: bool,
+) -> Result<String, String> {
+    let repo_root = workspace_git_root(workspaces, workspace_id).await?;
+    if let Ok(structured) = collect_workspace_diff_structured(&repo_root, staged_only).await {
+        if !structured.files.is_empty() {
+            return Ok(build_structured_commit_message_prompt(
+                &structured,
+                max_diff_bytes,
+            ));
+        }
+    }
+    let diff = select_commit_diff(collect_workspace_diff_parts(&repo_root)?, staged_only)?;
+    Ok(build_commit_message_prompt(&diff, max_diff_bytes))
+}
+
+/// Generates a commit message in a hidden background thread.
+pub(crate) async fn generate_commit_message_core<F>(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    workspace_id: String,
+    max_diff_bytes: usize,
+    staged_only: bool,
+    on_hide_thread: F,
+    keep_visible: bool,
+) -> Result<String, String>
+where
+    F: Fn(&str, &str),
+{
+    let prompt =
+        commit_message_prompt_core(workspaces, &workspace_id, max_diff_bytes, staged_only).await?;
+    let response = run_background_prompt_core(
+        sessions,
+        workspace_id,
+        prompt,
+        on_hide_thread,
+        keep_visible,
+        "Timeout waiting for commit message generation",
+        "Unknown error during commit message generation",
+        None,
+    )
+    .await?;
+
+    let trimmed = response.trim().to_string();
+    if trimmed.is_empty() {
+        return Err("No commit message was generated".to_string());
+    }
+
+    Ok(trimmed)
+}
+
+/// Generates a run title and worktree name for `prompt` in a hidden
+/// background thread. `cancels` lets `cancel_background_prompt_core` abort it
+/// when the dialog that asked for it is dismissed.
+pub(crate) async fn generate_run_metadata_core<F>(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    cancels: &BackgroundPromptCancels,
+    workspace_id: String,
+    prompt: &str,
+    on_hide_thread: F,
+    keep_visible: bool,
+) -> Result<Value, String>
+where
+    F: Fn(&str, &str),
+{
+    let cleaned_prompt = prompt.trim();
+    if cleaned_prompt.is_empty() {
+        return Err("Prompt is required.".to_string());
+    }
+
+    let title_prompt = build_run_metadata_prompt(cleaned_prompt);
+    let cancel_rx = register_background_cancel(cancels, &workspace_id).await;
+    let response = run_background_prompt_core(
+        sessions,
+        workspace_id.clone(),
+        title_prompt,
+        on_hide_thread,
+        keep_visible,
+        "Timeout waiting for metadata generation",
+        "Unknown error during metadata generation",
+        Some(cancel_rx),
+    )
+    .await;
+    clear_background_cancel(cancels, &workspace_id).await;
+    let response_text = response?;
+
+    let trimmed = response_text.trim();
+    if trimmed.is_empty() {
+        return Err("No metadata was generated".to_string());
+    }
+
+    let json_value =
+        extract_json_value(trimmed).ok_or_else(|| "Failed to parse metadata JSON".to_string())?;
+    let title = json_value
+        .get("title")
+        .and_then(|v| v.as_str())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| "Missing title in metadata".to_string())?;
+    let worktree_name = json_value
+        .get("worktreeName")
+        .or_else(|| json_value.get("worktree_name"))
+        .and_then(|v| v.as_str())
+        .map(sanitize_run_worktree_name)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| "Missing worktree name in metadata".to_string())?;
+
+    Ok(json!({
+        "title": title,
+        "worktreeName": worktree_name
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        annotate_gemini_sandbox, append_capped_delta, build_commit_message_prompt,
+        cancel_background_prompt_core, cap_commit_diff, commit_message_prompt_core,
+        describe_bin_problem, doctor_all_core, evaluate_claude_stream_json_support,
+        generate_run_metadata_core, inspect_cli_bin, inspect_node_binaries, node_major_version,
+        quick_prompt_core, select_commit_diff, test_mcp_server_core, truncation_note,
+        BackgroundPromptCancels, BACKGROUND_PROMPT_CANCELED, MAX_BACKGROUND_RESPONSE_BYTES,
+        MCP_PROTOCOL_VERSION, NOTHING_STAGED_MESSAGE, NO_CHANGES_MESSAGE,
+    };
+    use crate::backend::app_server::WorkspaceSession;
+    use crate::backend::test_support::{test_workspace_entry, RecordedRequests, TestAdapter};
+    use crate::shared::git_core::WorkspaceDiffParts;
+    use crate::shared::process_core::StdioRequestRunner;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::Mutex;
+    use tokio::time::timeout;
+
+    /// Starts background threads and turns but never streams a reply, so
+    /// collection runs until it is canceled.
+    fn silent_adapter(requests: &RecordedRequests) -> Box<TestAdapter> {
+        Box::new(
+            TestAdapter::new("claude")
+                .recording(Arc::clone(requests))
+                .responding(|method, _| match method {
+                    "thread/start" => Ok(json!({ "result": { "threadId": "bg-thread" } })),
+                    "turn/start" => Ok(json!({ "result": { "turn": { "id": "bg-turn" } } })),
+                    _ => Ok(json!({ "result": {} })),
+                }),
+        )
+    }
+
+    type ThreadCallbacks = Arc<Mutex<HashMap<String, tokio::sync::mpsc::UnboundedSender<Value>>>>;
+
+    /// Answers every turn with the same canned deltas followed by
+    /// `turn/completed`.
+    fn canned_adapter(callbacks: ThreadCallbacks, deltas: Vec<&'static str>) -> TestAdapter {
+        TestAdapter::new("claude").responding(move |method, _| match method {
+            "thread/start" => Ok(json!({ "result": { "threadId": "quick-thread" } })),
+            "turn/start" => {
+                let callbacks = callbacks.try_lock().map_err(|err| err.to_string())?;
+                let tx = callbacks
+                    .get("quick-thread")
+                    .ok_or("no background callback")?;
+                for delta in &deltas {
+                    let _ = tx.send(json!({
+                        "method": "item/agentMessage/delta",
+                        "params": { "threadId": "quick-thread", "delta": delta }
+                    }));
+                }
+                let _ = tx.send(json!({
+                    "method": "turn/completed",
+                    "params": { "threadId": "quick-thread" }
+                }));
+                Ok(json!({ "result": {} }))
+            }
+            _ => Ok(json!({ "result": {} })),
+        })
+    }
+
+    fn sample_diff(path: &str, hunks: usize, lines_per_hunk: usize) -> String {
+        let mut diff =
+            format!("=== {path} ===\ndiff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n");
+        for hunk in 0..hunks {
+            diff.push_str(&format!(
+                "@@ -{0},1 +{0},{1} @@\n",
+                hunk * 100 + 1,
+                lines_per_hunk
+            ));
+            diff.push_str("-old line\n");
+            for line in 0..lines_per_hunk {
+                diff.push_str(&format!("+hunk {hunk} line {line}\n"));
+            }
+        }
+        diff
+    }
+
+    #[test]
+    fn commit_diff_under_cap_is_embedded_unchanged() {
+        let diff = sample_diff("src/lib.rs", 2, 3);
+        assert_eq!(cap_commit_diff(&diff, diff.len()), diff);
+        assert_eq!(cap_commit_diff(&diff, 0), diff);
+        let prompt = build_commit_message_prompt(&diff, 64 * 1024);
+        assert!(prompt.ends_with(&diff));
+        assert!(!prompt.contains("[diff truncated"));
+    }
+
+    #[test]
+    fn commit_diff_over_cap_keeps_headers_and_top_hunks() {
+        let diff = format!(
+            "{}\n\n{}",
+            sample_diff("src/big.rs", 20, 40),
+            sample_diff("src/small.rs", 1, 2)
+        );
+        let max_bytes = 2048;
+        let capped = cap_commit_diff(&diff, max_bytes);
+
+        let (body, summary) = capped
+            .split_once("\n[diff truncated")
+            .expect("truncation marker");
+        assert!(
+            body.len() <= max_bytes + 64,
+            "body should stay near the cap"
+        );
+        assert!(body.starts_with("=== src/big.rs ===\ndiff --git a/src/big.rs b/src/big.rs\n"));
+        assert!(body.contains("+hunk 0 line 0\n"));
+        assert!(!body.contains("+hunk 19 line 0"));
+        assert!(body.contains("=== src/small.rs ===\n"));
+        assert!(body.contains("hunk(s) omitted]"));
+        assert!(summary.contains("src/big.rs (+800 -20)"));
+        assert!(summary.contains("src/small.rs (+2 -1)"));
+    }
+
+    #[test]
+    fn claude_stream_json_probe_accepts_supported_help() {
+        let help = "Options:\n  --output-format <format>  Output format: \"text\", \"json\", or \"stream-json\"\n";
+        assert_eq!(
+            evaluate_claude_stream_json_support(Ok(help.to_string())),
+            (true, None)
+        );
+    }
+
+    #[test]
+    fn claude_stream_json_probe_reports_unsupported_help() {
+        let help = "Options:\n  --output-format <format>  Output format: \"text\" or \"json\"\n";
+        let (ok, details) = evaluate_claude_stream_json_support(Ok(help.to_string()));
+        assert!(!ok);
+        assert!(details.expect("details").contains("stream-json"));
+
+        let (ok, details) = evaluate_claude_stream_json_support(Err("not found".to_string()));
+        assert!(!ok);
+        assert!(details.expect("details").contains("not found"));
+    }
+
+    #[test]
+    fn append_capped_delta_collects_until_limit() {
+        let mut buffer = String::new();
+        assert!(!append_capped_delta(&mut buffer, "feat: ", 16));
+        assert!(!append_capped_delta(&mut buffer, "add", 16));
+        assert_eq!(buffer, "feat: add");
+    }
+
+    #[test]
+    fn append_capped_delta_truncates_oversized_deltas() {
+        let mut buffer = String::new();
+        let chunk = "x".repeat(4096);
+        let mut stopped_after = None;
+        for index in 0..16 {
+            if append_capped_delta(&mut buffer, &chunk, MAX_BACKGROUND_RESPONSE_BYTES) {
+                stopped_after = Some(index);
+                break;
+            }
+        }
+        assert_eq!(stopped_after, Some(3));
+        assert_eq!(buffer.len(), MAX_BACKGROUND_RESPONSE_BYTES);
+
+        assert!(append_capped_delta(
+            &mut buffer,
+            "more",
+            MAX_BACKGROUND_RESPONSE_BYTES
+        ));
+        assert_eq!(buffer.len(), MAX_BACKGROUND_RESPONSE_BYTES);
+    }
+
+    #[test]
+    fn append_capped_delta_respects_char_boundaries() {
+        let mut buffer = String::from("ab");
+        assert!(append_capped_delta(&mut buffer, "\u{e9}\u{e9}", 5));
+        assert_eq!(buffer, "ab\u{e9}");
+    }
+
+    #[test]
+    fn truncation_note_mentions_limit() {
+        assert!(truncation_note(MAX_BACKGROUND_RESPONSE_BYTES).contains("16 KB"));
+    }
+
+    #[cfg(unix)]
+    fn bin_test_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("doctor-bin-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inspect_cli_bin_reports_executable_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = bin_test_dir();
+        let bin = dir.join("fake-cli");
+        std::fs::write(&bin, "#!/bin/sh\n").expect("write bin");
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+
+        let path_env = dir.to_string_lossy().to_string();
+        let details = inspect_cli_bin("fake-cli", Some(&path_env));
+        assert_eq!(details["exists"], true);
+        assert_eq!(details["isFile"], true);
+        assert_eq!(details["executable"], true);
+        assert_eq!(details["isSymlink"], false);
+        assert_eq!(details["resolvedPath"], bin.to_string_lossy().as_ref());
+        assert!(describe_bin_problem(&details).is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inspect_cli_bin_flags_non_executable_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = bin_test_dir();
+        let bin = dir.join("plain-file");
+        std::fs::write(&bin, "alias gemini=npx").expect("write bin");
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o644)).expect("chmod");
+
+        let details = inspect_cli_bin(&bin.to_string_lossy(), None);
+        assert_eq!(details["exists"], true);
+        assert_eq!(details["isFile"], true);
+        assert_eq!(details["executable"], false);
+        assert!(describe_bin_problem(&details)
+            .expect("problem")
+            .contains("not executable"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inspect_cli_bin_flags_dangling_symlink() {
+        let dir = bin_test_dir();
+        let target = dir.join("missing-target");
+        let link = dir.join("gemini");
+        std::os::unix::fs::symlink(&target, &link).expect("create symlink");
+
+        let details = inspect_cli_bin(&link.to_string_lossy(), None);
+        assert_eq!(details["exists"], false);
+        assert_eq!(details["isSymlink"], true);
+        assert_eq!(details["symlinkTarget"], target.to_string_lossy().as_ref());
+        assert!(describe_bin_problem(&details)
+            .expect("problem")
+            .contains("broken symlink"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn node_major_version_parses_prefixed_versions() {
+        assert_eq!(node_major_version("v20.11.1\n"), Some(20));
+        assert_eq!(node_major_version("18.0.0"), Some(18));
+        assert_eq!(node_major_version("unknown"), None);
+    }
+
+    #[cfg(unix)]
+    fn write_fake_node(dir: &std::path::Path, version: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let node = dir.join("node");
+        std::fs::write(&node, format!("#!/bin/sh\necho {version}\n")).expect("write node");
+        std::fs::set_permissions(&node, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+        node
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn inspect_node_binaries_flags_conflicting_majors() {
+        let first = bin_test_dir();
+        let second = bin_test_dir();
+        let first_node = write_fake_node(&first, "v18.19.0");
+        let second_node = write_fake_node(&second, "v22.1.0");
+        let path_env = std::env::join_paths([&first, &second])
+            .expect("join paths")
+            .to_string_lossy()
+            .to_string();
+
+        let (node_path, conflicts) = inspect_node_binaries(Some(&path_env)).await;
+        assert_eq!(node_path.as_deref(), Some(first_node.as_path()));
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0]["path"], first_node.to_string_lossy().as_ref());
+        assert_eq!(conflicts[0]["version"], "v18.19.0");
+        assert_eq!(conflicts[1]["path"], second_node.to_string_lossy().as_ref());
+        assert_eq!(conflicts[1]["version"], "v22.1.0");
+
+        write_fake_node(&second, "v18.2.0");
+        let (_, conflicts) = inspect_node_binaries(Some(&path_env)).await;
+        assert!(conflicts.is_empty());
+
+        let _ = std::fs::remove_dir_all(first);
+        let _ = std::fs::remove_dir_all(second);
+    }
+
+    #[tokio::test]
+    async fn doctor_all_skips_unconfigured_clis() {
+        let missing = std::env::temp_dir()
+            .join(format!("missing-claude-{}", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string();
+        let settings = Mutex::new(crate::types::AppSettings {
+            gemini_bin: None,
+            claude_bin: Some(missing),
+            cursor_bin: Some("  ".to_string()),
+            ..crate::types::AppSettings::default()
+        });
+
+        let report = doctor_all_core(&settings).await;
+
+        assert_eq!(
+            report["gemini"],
+            json!({ "status": "skipped", "ok": false })
+        );
+        assert_eq!(report["cursor"]["status"], "skipped");
+        assert_eq!(report["claude"]["status"], "failed");
+        assert_eq!(report["claude"]["ok"], false);
+        assert!(report["claude"]["error"]
+            .as_str()
+            .is_some_and(|error| error.contains("Claude CLI not found")));
+        assert_eq!(report["ok"], false);
+
+        let settings = Mutex::new(crate::types::AppSettings {
+            gemini_bin: None,
+            claude_bin: None,
+            cursor_bin: None,
+            ..crate::types::AppSettings::default()
+        });
+        assert_eq!(doctor_all_core(&settings).await["ok"], true);
+    }
+
+    #[test]
+    fn gemini_doctor_report_reflects_sandbox_setting() {
+        let mut report = json!({ "ok": true });
+        annotate_gemini_sandbox(&mut report, true);
+        assert_eq!(report["sandboxMode"], "gemini");
+        assert!(report["sandboxDetails"].is_null());
+
+        annotate_gemini_sandbox(&mut report, false);
+        assert_eq!(report["sandboxMode"], "gondolin");
+        assert!(report["sandboxDetails"]
+            .as_str()
+            .is_some_and(|details| details.contains("gondolin")));
+    }
+
+    #[tokio::test]
+    async fn commit_message_prompt_reads_workspace_diff() {
+        let root = std::env::temp_dir().join(format!("commit-prompt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).expect("create repo root");
+        let repo = git2::Repository::init(&root).expect("init repo");
+        let entry = test_workspace_entry("ws-1", &root.to_string_lossy());
+        let workspaces = Mutex::new(HashMap::from([("ws-1".to_string(), entry)]));
+
+        let err = commit_message_prompt_core(&workspaces, "ws-1", 0, false)
+            .await
+            .expect_err("clean repo has nothing to describe");
+        assert_eq!(err, "No changes to generate commit message for");
+        let err = commit_message_prompt_core(&workspaces, "missing", 0, false)
+            .await
+            .expect_err("unknown workspace");
+        assert_eq!(err, "workspace not found");
+
+        let err = commit_message_prompt_core(&workspaces, "ws-1", 0, true)
+            .await
+            .expect_err("clean repo has nothing staged either");
+        assert_eq!(err, NO_CHANGES_MESSAGE);
+
+        std::fs::write(root.join("notes.txt"), "hello\n").expect("write file");
+        let prompt = commit_message_prompt_core(&workspaces, "ws-1", 0, false)
+            .await
+            .expect("prompt");
+        assert!(prompt.contains("=== notes.txt ==="), "{prompt}");
+        assert!(prompt.contains("+hello"), "{prompt}");
+        let err = commit_message_prompt_core(&workspaces, "ws-1", 0, true)
+            .await
+            .expect_err("unstaged changes only");
+        assert_eq!(err, NOTHING_STAGED_MESSAGE);
+
+        let mut index = repo.index().expect("index");
+        index
+            .add_path(std::path::Path::new("notes.txt"))
+            .expect("stage file");
+        index.write().expect("write index");
+        let prompt = commit_message_prompt_core(&workspaces, "ws-1", 0, false)
+            .await
+            .expect("prompt");
+        assert!(
+            prompt.contains("Files changed:\n- notes.txt (added, +1 -0)\n"),
+            "{prompt}"
+        );
+        assert!(prompt.contains("+hello"), "{prompt}");
+        let staged = commit_message_prompt_core(&workspaces, "ws-1", 0, true)
+            .await
+            .expect("staged prompt");
+        assert_eq!(staged, prompt);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn commit_diff_selection_distinguishes_nothing_staged() {
+        let parts = |staged: &str, working_tree: &str| WorkspaceDiffParts {
+            staged: staged.to_string(),
+            working_tree: working_tree.to_string(),
+        };
+
+        // Staged only: `git add` on a new file, nothing else touched.
+        let staged_only = parts("+staged\n", "+staged\n");
+        assert_eq!(
+            select_commit_diff(staged_only.clone(), false).as_deref(),
+            Ok("+staged\n")
+        );
+        assert_eq!(
+            select_commit_diff(staged_only, true).as_deref(),
+            Ok("+staged\n")
+        );
+
+        // Unstaged only: edits in the working tree, empty index diff.
+        let unstaged_only = parts("", "+edited\n");
+        assert_eq!(
+            select_commit_diff(unstaged_only.clone(), false).as_deref(),
+            Ok("+edited\n")
+        );
+        assert_eq!(
+            select_commit_diff(unstaged_only, true),
+            Err(NOTHING_STAGED_MESSAGE.to_string())
+        );
+
+        for staged_only in [false, true] {
+            assert_eq!(
+                select_commit_diff(parts("", "\n"), staged_only),
+                Err(NO_CHANGES_MESSAGE.to_string())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_run_metadata_archives_background_thread() {
+        let requests = RecordedRequests::default();
+        let session = Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry("ws-1", "/tmp"),
+            silent_adapter(&requests),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ));
+        let sessions = Mutex::new(HashMap::from([("ws-1".to_string(), session)]));
+        let cancels = BackgroundPromptCancels::default();
+
+        let generation = generate_run_metadata_core(
+            &sessions,
+            &cancels,
+            "ws-1".to_string(),
+            "Fix the login redirect",
+            |_, _| {},
+            false,
+        );
+        let cancel = async {
+            while !requests
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|(method, _)| method == "turn/start")
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            cancel_background_prompt_core(&cancels, "ws-1").await
+        };
+        let (result, canceled) = tokio::join!(generation, cancel);
+
+        assert_eq!(canceled, json!({ "canceled": true }));
+        assert_eq!(result, Err(BACKGROUND_PROMPT_CANCELED.to_string()));
+        let archived = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(method, _)| method == "thread/archive")
+            .map(|(_, params)| params.clone());
+        assert_eq!(archived, Some(json!({ "threadId": "bg-thread" })));
+        assert!(requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(method, params)| method == "turn/interrupt" && params["turnId"] == "bg-turn"));
+        assert!(cancels.lock().await.is_empty());
+        assert_eq!(
+            cancel_background_prompt_core(&cancels, "ws-1").await,
+            json!({ "canceled": false })
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_visible_skips_hiding_and_archiving_background_thread() {
+        let requests = RecordedRequests::default();
+        let session = Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry("ws-1", "/tmp"),
+            silent_adapter(&requests),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ));
+        let sessions = Mutex::new(HashMap::from([("ws-1".to_string(), session)]));
+        let cancels = BackgroundPromptCancels::default();
+        let hidden = std::sync::atomic::AtomicBool::new(false);
+
+        let generation = generate_run_metadata_core(
+            &sessions,
+            &cancels,
+            "ws-1".to_string(),
+            "Fix the login redirect",
+            |_, _| hidden.store(true, std::sync::atomic::Ordering::SeqCst),
+            true,
+        );
+        let cancel = async {
+            while !requests
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|(method, _)| method == "turn/start")
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            cancel_background_prompt_core(&cancels, "ws-1").await
+        };
+        let (result, _) = tokio::join!(generation, cancel);
+
+        assert_eq!(result, Err(BACKGROUND_PROMPT_CANCELED.to_string()));
+        assert!(!hidden.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(!requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(method, _)| method == "thread/archive"));
+    }
+
+    #[tokio::test]
+    async fn quick_prompt_collects_reply_and_kills_session() {
+        let settings = Mutex::new(crate::types::AppSettings::default());
+        let killed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let spawned = std::sync::Mutex::new(None);
+        let store = std::sync::Mutex::new(None);
+        let cwd = std::env::temp_dir().to_string_lossy().to_string();
+
+        let reply = quick_prompt_core(
+            "claude".to_string(),
+            cwd.clone(),
+            "What does this repo do?".to_string(),
+            &settings,
+            |entry, config| {
+                *spawned.lock().unwrap() = Some((entry.path.clone(), config.cli_type.clone()));
+                let store_dir = config.thread_store_dir.clone().unwrap();
+                std::fs::create_dir_all(&store_dir).unwrap();
+                std::fs::write(store_dir.join(format!("{}.json", entry.id)), "{}").unwrap();
+                *store.lock().unwrap() = Some(store_dir);
+                let callbacks = ThreadCallbacks::default();
+                let adapter = canned_adapter(
+                    Arc::clone(&callbacks),
+                    vec!["It monitors ", "coding agents."],
+                )
+                .killed_flag(Arc::clone(&killed));
+                async move {
+                    Ok(Arc::new(WorkspaceSession::new_with_adapter(
+                        entry,
+                        Box::new(adapter),
+                        callbacks,
+                        Arc::default(),
+                    )))
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(reply, Ok("It monitors coding agents.".to_string()));
+        assert_eq!(
+            spawned.lock().unwrap().clone(),
+            Some((cwd, "claude".to_string()))
+        );
+        assert!(killed.load(std::sync::atomic::Ordering::SeqCst));
+        let store_dir = store.lock().unwrap().clone().unwrap();
+        assert!(store_dir.starts_with(std::env::temp_dir()));
+        assert!(
+            !store_dir.exists(),
+            "quick prompt thread store should be removed"
+        );
+    }
+
+    #[tokio::test]
+    async fn dropping_quick_prompt_mid_flight_cleans_up_background_work() {
+        let settings = Mutex::new(crate::types::AppSettings::default());
+        let requests = RecordedRequests::default();
+        let callbacks = ThreadCallbacks::default();
+        let cwd = std::env::temp_dir().to_string_lossy().to_string();
+
+        let prompt = quick_prompt_core(
+            "claude".to_string(),
+            cwd,
+            "What does this repo do?".to_string(),
+            &settings,
+            |entry, _| {
+                let session = WorkspaceSession::new_with_adapter(
+                    entry,
+                    silent_adapter(&requests),
+                    Arc::clone(&callbacks),
+                    Arc::default(),
+                );
+                async move { Ok(Arc::new(session)) }
+            },
+        );
+        let turn_started = async {
+            while !requests
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|(method, _)| method == "turn/start")
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::select! {
+            _ = prompt => panic!("quick prompt should still be waiting for a reply"),
+            _ = turn_started => {}
+        }
+        assert!(callbacks.lock().await.contains_key("bg-thread"));
+
+        let killed = async {
+            while !requests
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|(method, _)| method == "kill")
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        timeout(Duration::from_secs(5), killed)
+            .await
+            .expect("dropped quick prompt should kill its session");
+
+        assert!(callbacks.lock().await.is_empty());
+        let methods: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(method, _)| method.clone())
+            .collect();
+        assert_eq!(
+            methods,
+            vec![
+                "thread/start",
+                "turn/start",
+                "turn/interrupt",
+                "thread/archive",
+                "kill"
+            ]
+        );
+        let interrupt = requests.lock().unwrap()[2].1.clone();
+        assert_eq!(
+            interrupt,
+            json!({ "threadId": "bg-thread", "turnId": "bg-turn" })
+        );
+    }
+
+    /// Answers every stdio request with a canned result and records the
+    /// command line and request it was given.
+    struct FakeStdioRunner {
+        reply: fn() -> std::io::Result<Value>,
+        calls: std::sync::Mutex<Vec<(Vec<String>, Value)>>,
+    }
+
+    impl FakeStdioRunner {
+        fn new(reply: fn() -> std::io::Result<Value>) -> Self {
+            Self {
+                reply,
+                calls: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StdioRequestRunner for FakeStdioRunner {
+        async fn request(
+            &self,
+            command: tokio::process::Command,
+            request: Value,
+            _limit: Duration,
+        ) -> std::io::Result<Value> {
+            let command = command.as_std();
+            let mut line = vec![command.get_program().to_string_lossy().to_string()];
+            line.extend(
+                command
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy().to_string()),
+            );
+            self.calls.lock().unwrap().push((line, request));
+            (self.reply)()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mcp_server_reports_capabilities_after_handshake() {
+        let runner = FakeStdioRunner::new(|| {
+            Ok(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "result": {
+                    "protocolVersion": "2025-06-18",
+                    "capabilities": { "tools": { "listChanged": true } },
+                    "serverInfo": { "name": "filesystem", "version": "1.2.0" }
+                }
+            }))
+        });
+
+        let report = test_mcp_server_core(
+            &runner,
+            " npx ".to_string(),
+            vec![
+                "-y".to_string(),
+                "@modelcontextprotocol/server-filesystem".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report["ok"], true);
+        assert_eq!(
+            report["capabilities"],
+            json!({ "tools": { "listChanged": true } })
+        );
+        assert_eq!(
+            report["details"],
+            "filesystem 1.2.0 answered `initialize` (protocol 2025-06-18)."
+        );
+        let calls = runner.calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(
+            calls[0].0,
+            vec!["npx", "-y", "@modelcontextprotocol/server-filesystem"]
+        );
+        assert_eq!(calls[0].1["method"], "initialize");
+        assert_eq!(
+            calls[0].1["params"]["protocolVersion"],
+            MCP_PROTOCOL_VERSION
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mcp_server_explains_failed_handshakes() {
+        let cases: [(fn() -> std::io::Result<Value>, &str); 4] = [
+            (
+                || Err(std::io::Error::new(ErrorKind::NotFound, "missing")),
+                "`my-server` was not found",
+            ),
+            (
+                || Err(std::io::Error::new(ErrorKind::TimedOut, "slow")),
+                "did not answer `initialize` within 15s",
+            ),
+            (
+                || {
+                    Err(std::io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "server exited before responding: bad flag --port",
+                    ))
+                },
+                "exited before answering `initialize` (server exited before responding: bad flag --port)",
+            ),
+            (
+                || {
+                    Ok(json!({
+                        "jsonrpc": "2.0",
+                        "id": 1,
+                        "error": { "code": -32602, "message": "unsupported protocol version" }
+                    }))
+                },
+                "rejected `initialize`: unsupported protocol version",
+            ),
+        ];
+        for (reply, expected) in cases {
+            let runner = FakeStdioRunner::new(reply);
+            let report = test_mcp_server_core(&runner, "my-server".to_string(), Vec::new())
+                .await
+                .unwrap();
+            assert_eq!(report["ok"], false);
+            assert!(report["capabilities"].is_null());
+            let details = report["details"].as_str().unwrap();
+            assert!(details.contains(expected), "{details}");
+        }
+
+        let runner = FakeStdioRunner::new(|| Ok(Value::Null));
+        assert_eq!(
+            test_mcp_server_core(&runner, "  ".to_string(), Vec::new()).await,
+            Err("MCP server command is required.".to_string())
+        );
+        assert!(runner.calls.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn quick_prompt_rejects_missing_directory() {
+        let settings = Mutex::new(crate::types::AppSettings::default());
+        let missing = std::env::temp_dir()
+            .join(format!("quick-prompt-{}", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string();
+        let result = quick_prompt_core(
+            "claude".to_string(),
+            missing,
+            "hello".to_string(),
+            &settings,
+            |_, _| async { Err::<Arc<WorkspaceSession>, String>("should not spawn".to_string()) },
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err("Quick prompt directory must be a folder.".to_string())
+        );
+    }
+}
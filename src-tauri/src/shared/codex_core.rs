@@ -1,6 +1,6 @@
 use serde_json::{json, Map, Value};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
@@ -10,12 +10,19 @@ use tokio::sync::{oneshot, Mutex};
 use tokio::time::timeout;
 use tokio::time::Instant;
 
+use crate::backend::adapter_base;
 use crate::backend::app_server::WorkspaceSession;
+use crate::backend::approval_rules;
+use crate::backend::events::{AppServerEvent, EventSink};
+use crate::backend::gemini_adapter::gemini_fallback_model_list;
 use crate::codex::config as codex_config;
 use crate::codex::home::{resolve_default_codex_home, resolve_workspace_codex_home};
 use crate::rules;
+use crate::storage::write_workspaces;
 use crate::shared::account::{build_account_response, read_auth_account};
-use crate::types::WorkspaceEntry;
+use crate::shared::workspaces_core::resolve_workspace_cli_home;
+use crate::shared::{codex_aux_core, git_core, settings_core};
+use crate::types::{AppSettings, SessionInfo, WorkspaceEntry};
 
 const LOGIN_START_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -62,6 +69,18 @@ async fn resolve_codex_home_for_workspace_core(
         .ok_or_else(|| "Unable to resolve CODEX_HOME".to_string())
 }
 
+pub(crate) async fn list_active_sessions_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+) -> Vec<SessionInfo> {
+    let sessions: Vec<Arc<WorkspaceSession>> = sessions.lock().await.values().cloned().collect();
+    let mut infos = Vec::with_capacity(sessions.len());
+    for session in sessions {
+        infos.push(session.info().await);
+    }
+    infos.sort_by(|a, b| a.workspace_id.cmp(&b.workspace_id));
+    infos
+}
+
 pub(crate) async fn start_thread_core(
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
@@ -71,7 +90,10 @@ pub(crate) async fn start_thread_core(
         "cwd": session.entry.path,
         "approvalPolicy": "on-request"
     });
-    session.send_request("thread/start", params).await
+    session
+        .send_request("thread/start", params)
+        .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn resume_thread_core(
@@ -81,17 +103,42 @@ pub(crate) async fn resume_thread_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "threadId": thread_id });
-    session.send_request("thread/resume", params).await
+    session
+        .send_request("thread/resume", params)
+        .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn fork_thread_core(
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
     thread_id: String,
+    name: Option<String>,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    let mut params = json!({ "threadId": thread_id });
+    if let Some(name) = name.map(|name| name.trim().to_string()) {
+        if !name.is_empty() {
+            params["name"] = json!(name);
+        }
+    }
+    session
+        .send_request("thread/fork", params)
+        .await
+        .map_err(String::from)
+}
+
+pub(crate) async fn duplicate_thread_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    thread_id: String,
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "threadId": thread_id });
-    session.send_request("thread/fork", params).await
+    session
+        .send_request("thread/duplicate", params)
+        .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn list_threads_core(
@@ -103,7 +150,152 @@ pub(crate) async fn list_threads_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "cursor": cursor, "limit": limit, "sortKey": sort_key });
-    session.send_request("thread/list", params).await
+    session
+        .send_request("thread/list", params)
+        .await
+        .map_err(String::from)
+}
+
+/// Page size of `list_all_threads_core` when the caller gives none.
+const DEFAULT_ALL_THREADS_LIMIT: u32 = 50;
+
+/// A thread's `updatedAt` (falling back to `createdAt`) in milliseconds. Like
+/// the frontend, values below 10^12 are taken as seconds and strings may be
+/// numbers or RFC 3339 dates.
+fn thread_activity_ms(thread: &Value) -> i64 {
+    let raw = ["updatedAt", "updated_at", "createdAt", "created_at"]
+        .iter()
+        .find_map(|key| thread.get(*key).filter(|value| !value.is_null()));
+    let numeric = match raw {
+        Some(Value::Number(number)) => number.as_f64().unwrap_or(0.0),
+        Some(Value::String(text)) => text.trim().parse::<f64>().unwrap_or_else(|_| {
+            chrono::DateTime::parse_from_rfc3339(text.trim())
+                .map(|date| date.timestamp_millis() as f64)
+                .unwrap_or(0.0)
+        }),
+        _ => 0.0,
+    };
+    if !numeric.is_finite() || numeric <= 0.0 {
+        return 0;
+    }
+    if numeric < 1_000_000_000_000.0 {
+        (numeric * 1000.0) as i64
+    } else {
+        numeric as i64
+    }
+}
+
+fn same_workspace_path(left: &str, right: &str) -> bool {
+    let trim = |path: &str| path.trim_end_matches(['/', '\\']).to_string();
+    trim(left) == trim(right)
+}
+
+/// Up to `window` of one workspace's most recent threads, and whether it has
+/// more. A connected session is asked directly; codex lists every thread in
+/// its home, so those are narrowed to the workspace's `cwd`. A workspace
+/// without a session is read from its adapter thread store on disk.
+async fn recent_workspace_threads(
+    entry: &WorkspaceEntry,
+    session: Option<Arc<WorkspaceSession>>,
+    store_dir: Option<&Path>,
+    window: u32,
+) -> (Vec<Value>, bool) {
+    let Some(session) = session else {
+        let store_path = adapter_base::thread_store_path(store_dir, &entry.id);
+        let threads = adapter_base::ThreadStore::load(&store_path).list_entries();
+        return (threads, false);
+    };
+    let params = json!({ "cursor": null, "limit": window, "sortKey": "updated_at" });
+    let response = match session.send_request("thread/list", params).await {
+        Ok(response) => response,
+        Err(err) => {
+            eprintln!(
+                "list_all_threads: skipping workspace {}: {}",
+                entry.id,
+                String::from(err)
+            );
+            return (Vec::new(), false);
+        }
+    };
+    let result = response.get("result").unwrap_or(&response);
+    let threads = result
+        .get("data")
+        .or_else(|| result.get("threads"))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|thread| {
+            thread
+                .get("cwd")
+                .and_then(Value::as_str)
+                .is_none_or(|cwd| same_workspace_path(cwd, &entry.path))
+        })
+        .collect();
+    let has_more = result
+        .get("nextCursor")
+        .or_else(|| result.get("next_cursor"))
+        .is_some_and(|cursor| !cursor.is_null())
+        || result.get("hasMore").and_then(Value::as_bool) == Some(true);
+    (threads, has_more)
+}
+
+/// Threads from every workspace, most recently updated first, as
+/// `{ data: [{ workspaceId, thread }], nextCursor }`. The cursor is the number
+/// of entries already returned.
+pub(crate) async fn list_all_threads_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    app_settings: &Mutex<AppSettings>,
+    cursor: Option<String>,
+    limit: Option<u32>,
+) -> Result<Value, String> {
+    let offset = match cursor.as_deref().map(str::trim) {
+        None | Some("") => 0,
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid cursor: {cursor}"))?,
+    };
+    let limit = limit
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_ALL_THREADS_LIMIT) as usize;
+    let window = u32::try_from(offset + limit).unwrap_or(u32::MAX);
+
+    let mut entries: Vec<WorkspaceEntry> = workspaces.lock().await.values().cloned().collect();
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    let sessions = sessions.lock().await.clone();
+    let store_dir = app_settings.lock().await.thread_store_dir.clone();
+
+    let mut merged = Vec::new();
+    let mut any_truncated = false;
+    for entry in &entries {
+        let session = sessions.get(&entry.id).cloned();
+        let (threads, has_more) =
+            recent_workspace_threads(entry, session, store_dir.as_deref().map(Path::new), window)
+                .await;
+        any_truncated |= has_more;
+        merged.extend(threads.into_iter().map(|thread| (entry.id.clone(), thread)));
+    }
+    merged.sort_by(|(a_ws, a), (b_ws, b)| {
+        thread_activity_ms(b)
+            .cmp(&thread_activity_ms(a))
+            .then_with(|| a_ws.cmp(b_ws))
+            .then_with(|| {
+                let id =
+                    |thread: &Value| thread.get("id").and_then(Value::as_str).map(str::to_owned);
+                id(a).cmp(&id(b))
+            })
+    });
+
+    let has_more = merged.len() > offset + limit || any_truncated;
+    let data: Vec<Value> = merged
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(workspace_id, thread)| json!({ "workspaceId": workspace_id, "thread": thread }))
+        .collect();
+    let next_cursor = (has_more && !data.is_empty()).then(|| (offset + data.len()).to_string());
+    Ok(json!({ "data": data, "nextCursor": next_cursor }))
 }
 
 pub(crate) async fn list_mcp_server_status_core(
@@ -114,7 +306,10 @@ pub(crate) async fn list_mcp_server_status_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "cursor": cursor, "limit": limit });
-    session.send_request("mcpServerStatus/list", params).await
+    session
+        .send_request("mcpServerStatus/list", params)
+        .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn archive_thread_core(
@@ -124,9 +319,16 @@ pub(crate) async fn archive_thread_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "threadId": thread_id });
-    session.send_request("thread/archive", params).await
+    session
+        .send_request("thread/archive", params)
+        .await
+        .map_err(String::from)
 }
 
+/// Compacts the thread's context. Codex compacts inside the app-server and
+/// reports progress through its own events; adapter CLIs compact (or report
+/// `unsupported`) before answering, with the context size before and after.
+/// The answer is the session's result tagged with `threadId` and `cliType`.
 pub(crate) async fn compact_thread_core(
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
@@ -134,53 +336,154 @@ pub(crate) async fn compact_thread_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "threadId": thread_id });
-    session.send_request("thread/compact/start", params).await
+    let response = session
+        .send_request("thread/compact/start", params)
+        .await
+        .map_err(String::from)?;
+    let mut summary = match response.get("result") {
+        Some(Value::Object(result)) => result.clone(),
+        _ => Map::new(),
+    };
+    summary
+        .entry("threadId")
+        .or_insert_with(|| json!(thread_id));
+    summary.insert("cliType".to_string(), json!(session.cli_type()));
+    Ok(Value::Object(summary))
 }
 
-pub(crate) async fn set_thread_name_core(
+pub(crate) async fn get_thread_usage_core(
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
     thread_id: String,
-    name: String,
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
-    let params = json!({ "threadId": thread_id, "name": name });
-    session.send_request("thread/name/set", params).await
+    let params = json!({ "threadId": thread_id });
+    session
+        .send_request("thread/usage/read", params)
+        .await
+        .map_err(String::from)
+}
+
+pub(crate) async fn repair_thread_store_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    app_settings: &Mutex<AppSettings>,
+    workspace_id: String,
+) -> Result<Value, String> {
+    let session = sessions.lock().await.get(&workspace_id).cloned();
+    match session {
+        // A connected adapter owns the in-memory copy, so let it repair and reload.
+        Some(session) if session.is_adapter() => session
+            .send_request("thread/store/repair", json!({}))
+            .await
+            .map_err(String::from),
+        _ => {
+            let store_dir = app_settings.lock().await.thread_store_dir.clone();
+            let store_path =
+                adapter_base::thread_store_path(store_dir.as_deref().map(Path::new), &workspace_id);
+            let report = adapter_base::repair_thread_store(&store_path)?;
+            Ok(json!({ "result": report }))
+        }
+    }
 }
 
-pub(crate) async fn send_user_message_core(
+pub(crate) async fn set_thread_name_core(
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
     thread_id: String,
-    text: String,
-    model: Option<String>,
-    effort: Option<String>,
-    access_mode: Option<String>,
-    images: Option<Vec<String>>,
-    collaboration_mode: Option<Value>,
+    name: String,
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
-    let access_mode = access_mode.unwrap_or_else(|| "current".to_string());
-    let sandbox_policy = match access_mode.as_str() {
-        "full-access" => json!({ "type": "dangerFullAccess" }),
-        "read-only" => json!({ "type": "readOnly" }),
-        _ => json!({
-            "type": "workspaceWrite",
-            "writableRoots": [session.entry.path],
-            "networkAccess": true
-        }),
-    };
+    let params = json!({ "threadId": thread_id, "name": name });
+    session
+        .send_request("thread/name/set", params)
+        .await
+        .map_err(String::from)
+}
+
+/// Attachments above this size are rejected rather than inlined into the prompt.
+pub(crate) const MAX_INLINE_ATTACHMENT_BYTES: u64 = 64 * 1024;
 
-    let approval_policy = if access_mode == "full-access" {
-        "never"
+/// Cap on all of a message's attachments together.
+pub(crate) const MAX_INLINE_ATTACHMENTS_TOTAL_BYTES: u64 = 256 * 1024;
+
+/// Inlines `files` into the prompt. Paths resolve against the workspace and
+/// must stay inside it once symlinks are followed, so a message can't pull
+/// arbitrary files off the machine into the agent's context.
+fn read_file_attachments(
+    files: Option<Vec<String>>,
+    workspace_path: &str,
+) -> Result<Option<String>, String> {
+    let files: Vec<String> = files
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|file| !file.trim().is_empty())
+        .collect();
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let root = Path::new(workspace_path)
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve workspace path {workspace_path}: {e}"))?;
+    let mut blocks = Vec::new();
+    let mut total_bytes = 0;
+    for file in &files {
+        let trimmed = file.trim();
+        let path = root
+            .join(trimmed)
+            .canonicalize()
+            .map_err(|_| format!("Attached file not found: {trimmed}"))?;
+        if !path.starts_with(&root) {
+            return Err(format!(
+                "Attached file {trimmed} is outside the workspace {workspace_path}"
+            ));
+        }
+        let metadata =
+            std::fs::metadata(&path).map_err(|_| format!("Attached file not found: {trimmed}"))?;
+        if !metadata.is_file() {
+            return Err(format!("Attached path is not a file: {trimmed}"));
+        }
+        if metadata.len() > MAX_INLINE_ATTACHMENT_BYTES {
+            return Err(format!(
+                "Attached file {trimmed} is {} KB; the limit is {} KB.",
+                metadata.len().div_ceil(1024),
+                MAX_INLINE_ATTACHMENT_BYTES / 1024
+            ));
+        }
+        total_bytes += metadata.len();
+        if total_bytes > MAX_INLINE_ATTACHMENTS_TOTAL_BYTES {
+            return Err(format!(
+                "Attached files exceed {} KB together; attach fewer files.",
+                MAX_INLINE_ATTACHMENTS_TOTAL_BYTES / 1024
+            ));
+        }
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read attached file {trimmed}: {err}"))?;
+        blocks.push(format!(
+            "<file path=\"{trimmed}\">\n{}\n</file>",
+            contents.trim_end()
+        ));
+    }
+    if blocks.is_empty() {
+        Ok(None)
     } else {
-        "on-request"
-    };
+        Ok(Some(blocks.join("\n\n")))
+    }
+}
 
+fn build_user_input(
+    text: &str,
+    attachments: Option<&str>,
+    images: Option<Vec<String>>,
+) -> Result<Vec<Value>, String> {
     let trimmed_text = text.trim();
+    let combined_text = match attachments {
+        Some(attachments) if trimmed_text.is_empty() => attachments.to_string(),
+        Some(attachments) => format!("{trimmed_text}\n\n{attachments}"),
+        None => trimmed_text.to_string(),
+    };
     let mut input: Vec<Value> = Vec::new();
-    if !trimmed_text.is_empty() {
-        input.push(json!({ "type": "text", "text": trimmed_text }));
+    if !combined_text.is_empty() {
+        input.push(json!({ "type": "text", "text": combined_text }));
     }
     if let Some(paths) = images {
         for path in paths {
@@ -199,25 +502,276 @@ pub(crate) async fn send_user_message_core(
         }
     }
     if input.is_empty() {
-        return Err("empty user message".to_string());
+        return Err("Message text cannot be empty unless images are attached.".to_string());
+    }
+    Ok(input)
+}
+
+fn workspace_missing_event(session: &WorkspaceSession) -> AppServerEvent {
+    AppServerEvent {
+        workspace_id: session.entry.id.clone(),
+        message: json!({
+            "method": "workspace/missing",
+            "params": {
+                "workspaceId": session.entry.id,
+                "path": session.entry.path
+            }
+        }),
     }
+}
+
+fn long_message_warning(
+    workspace_id: &str,
+    thread_id: &str,
+    text: &str,
+    warn_chars: usize,
+) -> Option<AppServerEvent> {
+    let length = text.trim().chars().count();
+    if warn_chars == 0 || length <= warn_chars {
+        return None;
+    }
+    Some(AppServerEvent {
+        workspace_id: workspace_id.to_string(),
+        message: json!({
+            "method": "codex/messageWarning",
+            "params": {
+                "threadId": thread_id,
+                "reason": "longMessage",
+                "length": length,
+                "threshold": warn_chars
+            }
+        }),
+    })
+}
+
+/// The id to send the CLI for a model picked in the UI, per the user's
+/// `modelAliasMap` (UI id → CLI id). Unmapped ids pass through.
+pub(crate) fn model_for_cli(aliases: &HashMap<String, String>, model: &str) -> String {
+    aliases
+        .get(model.trim())
+        .map(|target| target.trim())
+        .filter(|target| !target.is_empty())
+        .unwrap_or(model)
+        .to_string()
+}
+
+/// Rewrites the CLI's model ids in a `model/list` response back to the UI ids
+/// aliased to them, so the picker shows the names the user mapped. When
+/// several aliases share a target, the alphabetically first one is shown.
+pub(crate) fn model_list_for_display(
+    aliases: &HashMap<String, String>,
+    mut response: Value,
+) -> Value {
+    let mut reverse: HashMap<&str, &str> = HashMap::new();
+    for (alias, target) in aliases {
+        let (alias, target) = (alias.trim(), target.trim());
+        if alias.is_empty() || target.is_empty() {
+            continue;
+        }
+        let shown = reverse.entry(target).or_insert(alias);
+        if alias < *shown {
+            *shown = alias;
+        }
+    }
+    if reverse.is_empty() {
+        return response;
+    }
+    let rename = |value: &mut Value| {
+        if let Some(alias) = value.as_str().and_then(|id| reverse.get(id)) {
+            *value = json!(alias);
+        }
+    };
+    let result = if response.get("result").is_some() {
+        &mut response["result"]
+    } else {
+        &mut response
+    };
+    for key in ["data", "models"] {
+        if let Some(models) = result.get_mut(key).and_then(Value::as_array_mut) {
+            for model in models {
+                for field in ["id", "model"] {
+                    if let Some(value) = model.get_mut(field) {
+                        rename(value);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(default_model) = result.get_mut("defaultModel") {
+        rename(default_model);
+    }
+    response
+}
+
+/// Checks `effort` against the selected model's `supportedReasoningEfforts` in
+/// a `model/list` response. Unsupported efforts fall back to the model's
+/// default; unknown models and models without an effort list pass through.
+fn resolve_supported_effort(
+    model_list: &Value,
+    model: Option<&str>,
+    effort: &str,
+) -> Result<String, String> {
+    let result = model_list.get("result").unwrap_or(model_list);
+    let Some(models) = result
+        .get("data")
+        .or_else(|| result.get("models"))
+        .and_then(|models| models.as_array())
+    else {
+        return Ok(effort.to_string());
+    };
+    let model_id = |entry: &Value| {
+        entry
+            .get("model")
+            .or_else(|| entry.get("id"))
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string())
+    };
+    let selected = match model.filter(|model| !model.trim().is_empty()) {
+        Some(model) => models.iter().find(|entry| {
+            entry.get("id").and_then(|id| id.as_str()) == Some(model)
+                || entry.get("model").and_then(|id| id.as_str()) == Some(model)
+        }),
+        None => {
+            let default_model = result.get("defaultModel").and_then(|id| id.as_str());
+            models.iter().find(|entry| {
+                entry.get("isDefault").and_then(|flag| flag.as_bool()) == Some(true)
+                    || (default_model.is_some() && model_id(entry).as_deref() == default_model)
+            })
+        }
+    };
+    let Some(selected) = selected else {
+        return Ok(effort.to_string());
+    };
+    let supported: Vec<String> = selected
+        .get("supportedReasoningEfforts")
+        .and_then(|efforts| efforts.as_array())
+        .map(|efforts| {
+            efforts
+                .iter()
+                .filter_map(|entry| {
+                    entry
+                        .get("reasoningEffort")
+                        .and_then(|value| value.as_str())
+                        .or_else(|| entry.as_str())
+                })
+                .map(|value| value.to_ascii_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    let requested = effort.trim().to_ascii_lowercase();
+    if supported.is_empty() || supported.contains(&requested) {
+        return Ok(effort.to_string());
+    }
+    let model_name = model_id(selected).unwrap_or_else(|| "the selected model".to_string());
+    selected
+        .get("defaultReasoningEffort")
+        .and_then(|value| value.as_str())
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| value.to_string())
+        .ok_or_else(|| {
+            format!(
+                "Reasoning effort `{effort}` is not supported by {model_name} (supported: {}).",
+                supported.join(", ")
+            )
+        })
+}
+
+pub(crate) async fn send_user_message_core<E: EventSink>(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    thread_id: String,
+    text: String,
+    model: Option<String>,
+    effort: Option<String>,
+    access_mode: Option<String>,
+    images: Option<Vec<String>>,
+    files: Option<Vec<String>>,
+    collaboration_mode: Option<Value>,
+    cwd: Option<String>,
+    default_approval_policy: Option<String>,
+    warn_chars: usize,
+    model_aliases: &HashMap<String, String>,
+    event_sink: &E,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    if let Err(error) = session.ensure_workspace_dir() {
+        event_sink.emit_app_server_event(workspace_missing_event(&session));
+        return Err(error.into());
+    }
+    let cwd = adapter_base::resolve_turn_cwd(&session.entry.path, cwd.as_deref())?;
+    let attachments = read_file_attachments(files, &session.entry.path)?;
+    let input = build_user_input(&text, attachments.as_deref(), images)?;
+    if let Some(event) = long_message_warning(&workspace_id, &thread_id, &text, warn_chars) {
+        event_sink.emit_app_server_event(event);
+    }
+    let model = match model.filter(|value| !value.trim().is_empty()) {
+        Some(model) => Some(model),
+        None => session.thread_model(&thread_id).await,
+    }
+    .map(|model| model_for_cli(model_aliases, &model));
+    let effort = match effort.filter(|value| !value.trim().is_empty()) {
+        Some(requested) => match session.send_request("model/list", json!({})).await {
+            Ok(model_list) => {
+                let resolved = resolve_supported_effort(&model_list, model.as_deref(), &requested)?;
+                if resolved != requested {
+                    event_sink.emit_app_server_event(AppServerEvent {
+                        workspace_id: workspace_id.clone(),
+                        message: json!({
+                            "method": "codex/messageWarning",
+                            "params": {
+                                "threadId": thread_id,
+                                "reason": "effortDowngraded",
+                                "requestedEffort": requested,
+                                "effort": resolved
+                            }
+                        }),
+                    });
+                }
+                Some(resolved)
+            }
+            // Older CLIs may not answer model/list; keep the caller's choice.
+            Err(_) => Some(requested),
+        },
+        None => None,
+    };
+    // The workspace default only applies when the caller leaves access unset.
+    let default_approval_policy = default_approval_policy.filter(|_| access_mode.is_none());
+    let access_mode = access_mode.unwrap_or_else(|| "current".to_string());
+    let sandbox_policy = match access_mode.as_str() {
+        "full-access" => json!({ "type": "dangerFullAccess" }),
+        "read-only" => json!({ "type": "readOnly" }),
+        _ => json!({
+            "type": "workspaceWrite",
+            "writableRoots": [session.entry.path],
+            "networkAccess": true
+        }),
+    };
+
+    let approval_policy = match default_approval_policy {
+        Some(policy) => policy,
+        None if access_mode == "full-access" => "never".to_string(),
+        None => "on-request".to_string(),
+    };
 
     let mut params = Map::new();
     params.insert("threadId".to_string(), json!(thread_id));
     params.insert("input".to_string(), json!(input));
-    params.insert("cwd".to_string(), json!(session.entry.path));
+    params.insert("cwd".to_string(), json!(cwd));
     params.insert("approvalPolicy".to_string(), json!(approval_policy));
     params.insert("sandboxPolicy".to_string(), json!(sandbox_policy));
     params.insert("model".to_string(), json!(model));
     params.insert("effort".to_string(), json!(effort));
+    let collaboration_mode = match collaboration_mode.filter(|mode| !mode.is_null()) {
+        Some(mode) => Some(mode),
+        None => session.collaboration_mode(&thread_id).await,
+    };
     if let Some(mode) = collaboration_mode {
-        if !mode.is_null() {
-            params.insert("collaborationMode".to_string(), mode);
-        }
+        params.insert("collaborationMode".to_string(), mode);
     }
     session
         .send_request("turn/start", Value::Object(params))
         .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn collaboration_mode_list_core(
@@ -228,6 +782,116 @@ pub(crate) async fn collaboration_mode_list_core(
     session
         .send_request("collaborationMode/list", json!({}))
         .await
+        .map_err(String::from)
+}
+
+/// Sets the thread's collaboration mode (`null` clears it) and saves it in the
+/// workspace settings so it outlives the session.
+pub(crate) async fn set_collaboration_mode_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    storage_path: &PathBuf,
+    workspace_id: String,
+    thread_id: String,
+    mode: Value,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    {
+        let mut workspaces = workspaces.lock().await;
+        let entry = workspaces
+            .get_mut(&workspace_id)
+            .ok_or_else(|| "workspace not found".to_string())?;
+        let modes = &mut entry.settings.thread_collaboration_modes;
+        if mode.is_null() {
+            modes.remove(&thread_id);
+        } else {
+            modes.insert(thread_id.clone(), mode.clone());
+        }
+        let list: Vec<_> = workspaces.values().cloned().collect();
+        write_workspaces(storage_path, &list)?;
+    }
+    session.set_collaboration_mode(&thread_id, mode).await;
+    get_collaboration_mode_core(sessions, workspace_id, thread_id).await
+}
+
+/// Sets the model used for the thread's turns when a message doesn't name one.
+/// Adapter sessions also persist it in the thread's metadata.
+pub(crate) async fn set_thread_model_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    thread_id: String,
+    model: Option<String>,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    let model = model
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+    if session.is_adapter() {
+        session
+            .send_request(
+                "thread/model/set",
+                json!({ "threadId": thread_id, "model": model }),
+            )
+            .await?;
+    }
+    session.set_thread_model(&thread_id, model.clone()).await;
+    Ok(json!({ "threadId": thread_id, "model": model }))
+}
+
+pub(crate) async fn get_collaboration_mode_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    thread_id: String,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    let mode = session.collaboration_mode(&thread_id).await;
+    Ok(json!({ "threadId": thread_id, "collaborationMode": mode }))
+}
+
+/// Starts appending the session's events to `path` as JSONL, replacing any
+/// recording already running for the workspace.
+pub(crate) async fn start_event_recording_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    path: String,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    session.event_recorder().start(Path::new(&path))?;
+    Ok(json!({ "workspaceId": workspace_id, "path": path, "recording": true }))
+}
+
+pub(crate) async fn stop_event_recording_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    let path = session.event_recorder().stop();
+    Ok(json!({ "workspaceId": workspace_id, "path": path, "recording": false }))
+}
+
+/// Longest delta coalescing window; beyond this streaming output looks stuck.
+const MAX_EVENT_THROTTLE_MS: u64 = 1000;
+
+/// Coalesces the session's streaming deltas over `min_interval_ms` before
+/// they are emitted; 0 turns coalescing off. Lasts until the session is
+/// respawned.
+pub(crate) async fn set_event_throttle_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    min_interval_ms: u64,
+) -> Result<Value, String> {
+    if min_interval_ms > MAX_EVENT_THROTTLE_MS {
+        return Err(format!(
+            "Event throttle must be at most {MAX_EVENT_THROTTLE_MS} ms"
+        ));
+    }
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    let throttle = session.event_recorder().delta_throttle();
+    throttle.set_interval(Duration::from_millis(min_interval_ms));
+    Ok(json!({
+        "workspaceId": workspace_id,
+        "minIntervalMs": throttle.interval().as_millis() as u64,
+    }))
 }
 
 pub(crate) async fn turn_interrupt_core(
@@ -238,7 +902,177 @@ pub(crate) async fn turn_interrupt_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "threadId": thread_id, "turnId": turn_id });
-    session.send_request("turn/interrupt", params).await
+    session
+        .send_request("turn/interrupt", params)
+        .await
+        .map_err(String::from)
+}
+
+/// Interrupts every foreground turn the workspace's session has in flight and
+/// emits `turn/interrupted` for each one that stopped. Turns whose interrupt
+/// fails are reported under `failed` and stay tracked.
+pub(crate) async fn interrupt_workspace_core<E: EventSink>(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    event_sink: &E,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    let mut interrupted = Vec::new();
+    let mut failed = Vec::new();
+    for (thread_id, turn_id) in session.active_turns().snapshot() {
+        let params = json!({ "threadId": thread_id, "turnId": turn_id });
+        match session.send_request("turn/interrupt", params.clone()).await {
+            Ok(_) => {
+                session.active_turns().finish(&turn_id);
+                event_sink.emit_app_server_event(AppServerEvent {
+                    workspace_id: workspace_id.clone(),
+                    message: json!({ "method": "turn/interrupted", "params": params }),
+                });
+                interrupted.push(params);
+            }
+            Err(err) => failed.push(json!({
+                "threadId": thread_id,
+                "turnId": turn_id,
+                "error": String::from(err),
+            })),
+        }
+    }
+    Ok(json!({ "interrupted": interrupted, "failed": failed }))
+}
+
+/// Tools a recent foreground turn used and how long it took. Only turns the
+/// session saw since it connected are known.
+pub(crate) async fn get_turn_summary_core(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    thread_id: String,
+    turn_id: String,
+) -> Result<Value, String> {
+    let session = get_session_clone(sessions, &workspace_id).await?;
+    session
+        .event_recorder()
+        .turn_summaries()
+        .summary(&thread_id, &turn_id)
+        .ok_or_else(|| format!("No summary for turn {turn_id} in thread {thread_id}"))
+}
+
+/// Why a `review/start` target was rejected before reaching the CLI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ReviewTargetError {
+    UnknownType(String),
+    MissingField(&'static str),
+    InvalidSha(String),
+    CommitNotFound(String),
+    BranchNotFound(String),
+    Git(String),
+}
+
+impl std::fmt::Display for ReviewTargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownType(kind) => write!(f, "Unknown review target type `{kind}`"),
+            Self::MissingField(field) => write!(f, "Review target is missing `{field}`"),
+            Self::InvalidSha(sha) => write!(f, "`{sha}` is not a valid commit sha"),
+            Self::CommitNotFound(sha) => {
+                write!(f, "Commit `{sha}` was not found in this repository")
+            }
+            Self::BranchNotFound(branch) => {
+                write!(f, "Branch `{branch}` was not found in this repository")
+            }
+            Self::Git(err) => write!(f, "Unable to verify review target: {err}"),
+        }
+    }
+}
+
+impl From<ReviewTargetError> for String {
+    fn from(error: ReviewTargetError) -> Self {
+        error.to_string()
+    }
+}
+
+fn review_target_field<'a>(
+    target: &'a Value,
+    field: &'static str,
+) -> Result<&'a str, ReviewTargetError> {
+    target
+        .get(field)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or(ReviewTargetError::MissingField(field))
+}
+
+/// Checks the target shapes the frontend sends (`uncommittedChanges`,
+/// `baseBranch`, `commit`, `custom`) and that referenced commits and branches
+/// exist, so a typo fails here instead of deep inside the review turn.
+pub(crate) async fn validate_review_target(
+    repo_root: &PathBuf,
+    target: &Value,
+) -> Result<(), ReviewTargetError> {
+    let kind = review_target_field(target, "type")?;
+    match kind {
+        "uncommittedChanges" => Ok(()),
+        "custom" => review_target_field(target, "instructions").map(|_| ()),
+        "commit" => {
+            let sha = review_target_field(target, "sha")?;
+            let is_hex = sha.chars().all(|ch| ch.is_ascii_hexdigit());
+            if !is_hex || !(4..=64).contains(&sha.len()) {
+                return Err(ReviewTargetError::InvalidSha(sha.to_string()));
+            }
+            match git_core::git_commit_exists(repo_root, sha).await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(ReviewTargetError::CommitNotFound(sha.to_string())),
+                Err(err) => Err(ReviewTargetError::Git(err)),
+            }
+        }
+        "baseBranch" => {
+            let branch = review_target_field(target, "branch")?;
+            if branch.starts_with('-') {
+                return Err(ReviewTargetError::BranchNotFound(branch.to_string()));
+            }
+            let exists = match git_core::git_branch_exists(repo_root, branch).await {
+                Ok(true) => Ok(true),
+                Ok(false) => {
+                    git_core::git_commit_exists(repo_root, &format!("refs/remotes/{branch}")).await
+                }
+                Err(err) => Err(err),
+            };
+            match exists {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(ReviewTargetError::BranchNotFound(branch.to_string())),
+                Err(err) => Err(ReviewTargetError::Git(err)),
+            }
+        }
+        other => Err(ReviewTargetError::UnknownType(other.to_string())),
+    }
+}
+
+pub(crate) const NO_REVIEW_CHANGES_MESSAGE: &str = "No uncommitted changes to review";
+
+/// Largest working-tree diff inlined into a `working` review; bigger diffs
+/// lose hunks the same way commit message diffs do.
+const WORKING_REVIEW_MAX_DIFF_BYTES: usize = 200_000;
+
+/// Expands a `{type: "working"}` target into a `custom` review of the current
+/// working-tree diff (staged, unstaged and untracked), so the frontend can ask
+/// for "my uncommitted changes" without collecting git state itself. Other
+/// targets pass through untouched.
+pub(crate) fn resolve_review_target(repo_root: &Path, target: Value) -> Result<Value, String> {
+    if target.get("type").and_then(Value::as_str) != Some("working") {
+        return Ok(target);
+    }
+    let diff = git_core::collect_workspace_diff_parts(repo_root)?.working_tree;
+    if diff.trim().is_empty() {
+        return Err(NO_REVIEW_CHANGES_MESSAGE.to_string());
+    }
+    let diff = codex_aux_core::cap_commit_diff(&diff, WORKING_REVIEW_MAX_DIFF_BYTES);
+    Ok(json!({
+        "type": "custom",
+        "instructions": format!(
+            "Review the following uncommitted changes in this repository. \
+             Report bugs, risky changes and missing tests, citing files and lines.\n\n{diff}"
+        ),
+    }))
 }
 
 pub(crate) async fn start_review_core(
@@ -249,6 +1083,9 @@ pub(crate) async fn start_review_core(
     delivery: Option<String>,
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
+    let repo_root = git_core::resolve_git_root(&session.entry)?;
+    let target = resolve_review_target(&repo_root, target)?;
+    validate_review_target(&repo_root, &target).await?;
     let mut params = Map::new();
     params.insert("threadId".to_string(), json!(thread_id));
     params.insert("target".to_string(), target);
@@ -258,14 +1095,25 @@ pub(crate) async fn start_review_core(
     session
         .send_request("review/start", Value::Object(params))
         .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn model_list_core(
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
+    model_aliases: &HashMap<String, String>,
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
-    session.send_request("model/list", json!({})).await
+    let response = match session.send_request("model/list", json!({})).await {
+        Ok(response) => response,
+        // An older or flaky Gemini CLI shouldn't leave the model picker empty.
+        Err(error) if session.cli_type() == "gemini" => {
+            eprintln!("model/list failed for {workspace_id}, using fallback models: {error}");
+            gemini_fallback_model_list()
+        }
+        Err(error) => return Err(error.into()),
+    };
+    Ok(model_list_for_display(model_aliases, response))
 }
 
 pub(crate) async fn account_rate_limits_core(
@@ -276,6 +1124,7 @@ pub(crate) async fn account_rate_limits_core(
     session
         .send_request("account/rateLimits/read", Value::Null)
         .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn account_read_core(
@@ -445,7 +1294,10 @@ pub(crate) async fn skills_list_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "cwd": session.entry.path });
-    session.send_request("skills/list", params).await
+    session
+        .send_request("skills/list", params)
+        .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn apps_list_core(
@@ -456,7 +1308,10 @@ pub(crate) async fn apps_list_core(
 ) -> Result<Value, String> {
     let session = get_session_clone(sessions, &workspace_id).await?;
     let params = json!({ "cursor": cursor, "limit": limit });
-    session.send_request("app/list", params).await
+    session
+        .send_request("app/list", params)
+        .await
+        .map_err(String::from)
 }
 
 pub(crate) async fn respond_to_server_request_core(
@@ -471,6 +1326,7 @@ pub(crate) async fn respond_to_server_request_core(
 
 pub(crate) async fn remember_approval_rule_core(
     workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
     workspace_id: String,
     command: Vec<String>,
 ) -> Result<Value, String> {
@@ -483,6 +1339,13 @@ pub(crate) async fn remember_approval_rule_core(
         return Err("empty command".to_string());
     }
 
+    let approval_rules_path = approval_rules::approval_rules_path(&workspace_id);
+    let mut store = approval_rules::ApprovalRuleStore::load(&approval_rules_path);
+    if store.add_rule(&command) {
+        store.save(&approval_rules_path)?;
+        invalidate_approval_rules(sessions, &workspace_id).await;
+    }
+
     let codex_home = resolve_codex_home_for_workspace_core(workspaces, &workspace_id).await?;
     let rules_path = rules::default_rules_path(&codex_home);
     rules::append_prefix_rule(&rules_path, &command)?;
@@ -490,14 +1353,1185 @@ pub(crate) async fn remember_approval_rule_core(
     Ok(json!({
         "ok": true,
         "rulesPath": rules_path,
+        "approvalRulesPath": approval_rules_path,
     }))
 }
 
+pub(crate) async fn list_approval_rules_core(workspace_id: String) -> Result<Value, String> {
+    let approval_rules_path = approval_rules::approval_rules_path(&workspace_id);
+    let store = approval_rules::ApprovalRuleStore::load(&approval_rules_path);
+    let rules = store
+        .list_rules()
+        .iter()
+        .map(|rule| json!({ "command": rule.prefix, "createdAt": rule.created_at }))
+        .collect::<Vec<_>>();
+    Ok(json!({ "rules": rules }))
+}
+
+/// Makes the workspace's connected session, if any, reload its remembered
+/// approval rules.
+async fn invalidate_approval_rules(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: &str,
+) {
+    let session = sessions.lock().await.get(workspace_id).cloned();
+    if let Some(session) = session {
+        session.invalidate_approval_rules().await;
+    }
+}
+
+/// Forgets the remembered rule for `command` (or every rule when `None`) in
+/// both stores `remember_approval_rule_core` writes to.
+async fn forget_approval_rules(
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: &str,
+    command: Option<&[String]>,
+) -> Result<Value, String> {
+    let approval_rules_path = approval_rules::approval_rules_path(workspace_id);
+    let codex_home = resolve_codex_home_for_workspace_core(workspaces, workspace_id).await?;
+    let rules_path = rules::default_rules_path(&codex_home);
+    let removed = approval_rules::forget_rules(&approval_rules_path, &rules_path, command)?;
+    if removed > 0 {
+        invalidate_approval_rules(sessions, workspace_id).await;
+    }
+    Ok(json!({ "ok": true, "removedCount": removed }))
+}
+
+pub(crate) async fn clear_approval_rule_core(
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+    command: Vec<String>,
+) -> Result<Value, String> {
+    let command = command
+        .into_iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect::<Vec<_>>();
+    if command.is_empty() {
+        return Err("empty command".to_string());
+    }
+    forget_approval_rules(workspaces, sessions, &workspace_id, Some(&command)).await
+}
+
+pub(crate) async fn clear_all_approval_rules_core(
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    workspace_id: String,
+) -> Result<Value, String> {
+    forget_approval_rules(workspaces, sessions, &workspace_id, None).await
+}
+
 pub(crate) async fn get_config_model_core(
     workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    app_settings: &Mutex<AppSettings>,
     workspace_id: String,
 ) -> Result<Value, String> {
+    let settings = app_settings.lock().await.clone();
+    if settings.cli_type == "gemini" {
+        let (entry, parent_entry) = resolve_workspace_and_parent(workspaces, &workspace_id).await?;
+        let gemini_home =
+            resolve_workspace_cli_home(&entry, parent_entry.as_ref(), Some(&settings))
+                .ok_or_else(|| "Unable to resolve GEMINI_HOME".to_string())?;
+        let (model, effort) = settings_core::read_gemini_model_defaults(&gemini_home)?;
+        return Ok(json!({ "model": model, "effort": effort }));
+    }
     let codex_home = resolve_codex_home_for_workspace_core(workspaces, &workspace_id).await?;
-    let model = codex_config::read_config_model(Some(codex_home))?;
-    Ok(json!({ "model": model }))
+    let model = codex_config::read_config_model(Some(codex_home.clone()))?;
+    let effort = codex_config::read_config_effort(Some(codex_home))?;
+    Ok(json!({ "model": model, "effort": effort }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apps_list_core, build_user_input, compact_thread_core, get_turn_summary_core,
+        interrupt_workspace_core, list_active_sessions_core, list_all_threads_core,
+        long_message_warning, model_for_cli, model_list_core, model_list_for_display,
+        read_file_attachments, resolve_review_target, resolve_supported_effort,
+        send_user_message_core, set_collaboration_mode_core, set_thread_model_core,
+        start_event_recording_core, validate_review_target, ReviewTargetError,
+        MAX_INLINE_ATTACHMENTS_TOTAL_BYTES, MAX_INLINE_ATTACHMENT_BYTES, NO_REVIEW_CHANGES_MESSAGE,
+    };
+    use crate::backend::adapter_base::GenericAdapterSession;
+    use crate::backend::app_server::{CliAdapter, CliSpawnConfig, WorkspaceSession};
+    use crate::backend::claude_adapter::ClaudeProfile;
+    use crate::backend::errors::AppServerError;
+    use crate::backend::events::{AppServerEvent, EventSink, TerminalExit, TerminalOutput};
+    use crate::backend::gemini_adapter::GeminiProfile;
+    use crate::backend::test_support::{test_workspace_entry, RecordedRequests, TestAdapter};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    #[derive(Clone)]
+    struct NoopEventSink;
+
+    impl EventSink for NoopEventSink {
+        fn emit_app_server_event(&self, _event: AppServerEvent) {}
+        fn emit_terminal_output(&self, _event: TerminalOutput) {}
+        fn emit_terminal_exit(&self, _event: TerminalExit) {}
+    }
+
+    fn fake_session(workspace_id: &str, cli_type: &'static str) -> Arc<WorkspaceSession> {
+        fake_session_recording(workspace_id, cli_type, RecordedRequests::default())
+    }
+
+    fn fake_session_recording(
+        workspace_id: &str,
+        cli_type: &'static str,
+        requests: RecordedRequests,
+    ) -> Arc<WorkspaceSession> {
+        let adapter = TestAdapter::new(cli_type)
+            .recording(requests)
+            .responding(|_, _| Ok(Value::Null));
+        Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry(workspace_id, "/tmp"),
+            Box::new(adapter),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn model_list_falls_back_to_known_gemini_models() {
+        let sessions = Mutex::new(HashMap::new());
+        for cli_type in ["gemini", "codex"] {
+            // Fails every request, like a CLI that doesn't answer `model/list`.
+            let adapter = TestAdapter::new(cli_type)
+                .responding(|method, _| Err(format!("unsupported method: {method}")));
+            let session = WorkspaceSession::new_with_adapter(
+                test_workspace_entry(cli_type, "/tmp"),
+                Box::new(adapter),
+                Arc::new(Mutex::new(HashMap::new())),
+                Arc::default(),
+            );
+            sessions
+                .lock()
+                .await
+                .insert(cli_type.to_string(), Arc::new(session));
+        }
+
+        let gemini = model_list_core(&sessions, "gemini".to_string(), &HashMap::new())
+            .await
+            .expect("gemini falls back");
+        assert_eq!(gemini["result"]["source"], "fallback");
+        let ids: Vec<&str> = gemini["result"]["models"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|model| model["id"].as_str())
+            .collect();
+        assert!(ids.contains(&"gemini-2.5-pro"));
+
+        let codex = model_list_core(&sessions, "codex".to_string(), &HashMap::new()).await;
+        assert_eq!(codex, Err("unsupported method: model/list".to_string()));
+    }
+
+    #[derive(Default)]
+    struct CollectingEventSink(std::sync::Mutex<Vec<AppServerEvent>>);
+
+    impl EventSink for CollectingEventSink {
+        fn emit_app_server_event(&self, event: AppServerEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+        fn emit_terminal_output(&self, _event: TerminalOutput) {}
+        fn emit_terminal_exit(&self, _event: TerminalExit) {}
+    }
+
+    /// Answers `turn/start` with sequential turn ids.
+    fn turn_adapter(requests: RecordedRequests) -> TestAdapter {
+        let started = std::sync::atomic::AtomicUsize::new(0);
+        TestAdapter::new("claude")
+            .recording(requests)
+            .responding(move |method, _| {
+                if method != "turn/start" {
+                    return Ok(json!({ "result": {} }));
+                }
+                let turn = started.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                Ok(json!({ "result": { "turn": { "id": format!("turn-{turn}") } } }))
+            })
+    }
+
+    #[tokio::test]
+    async fn interrupt_workspace_stops_every_active_turn() {
+        let requests = RecordedRequests::default();
+        let session = Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry("ws", &std::env::temp_dir().to_string_lossy()),
+            Box::new(turn_adapter(requests.clone())),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ));
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        session
+            .background_thread_callbacks
+            .lock()
+            .await
+            .insert("helper".to_string(), tx);
+        for thread_id in ["a", "b", "done", "helper"] {
+            session
+                .send_request("turn/start", json!({ "threadId": thread_id, "input": [] }))
+                .await
+                .expect("turn/start");
+        }
+        session.event_recorder().record(&AppServerEvent {
+            workspace_id: "ws".to_string(),
+            message: json!({
+                "method": "turn/completed",
+                "params": { "threadId": "done", "turn": { "id": "turn-3" } }
+            }),
+        });
+        let sessions = Mutex::new(HashMap::from([("ws".to_string(), session.clone())]));
+        let sink = CollectingEventSink::default();
+
+        let result = interrupt_workspace_core(&sessions, "ws".to_string(), &sink)
+            .await
+            .expect("interrupt workspace");
+
+        let expected = json!([
+            { "threadId": "a", "turnId": "turn-1" },
+            { "threadId": "b", "turnId": "turn-2" }
+        ]);
+        assert_eq!(result["interrupted"], expected);
+        assert_eq!(result["failed"], json!([]));
+        let interrupts: Vec<Value> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(method, _)| method == "turn/interrupt")
+            .map(|(_, params)| params.clone())
+            .collect();
+        assert_eq!(Value::from(interrupts), expected);
+        let events = sink.0.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        for (event, turn) in events.iter().zip(expected.as_array().unwrap()) {
+            assert_eq!(event.workspace_id, "ws");
+            assert_eq!(event.message["method"], "turn/interrupted");
+            assert_eq!(&event.message["params"], turn);
+        }
+        assert!(session.active_turns().snapshot().is_empty());
+    }
+
+    #[tokio::test]
+    async fn compact_thread_routes_by_cli_type() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("codex-core-compact-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        let entry = |id: &str| test_workspace_entry(id, &temp_dir.to_string_lossy());
+        let adapter_session = |id: &str, adapter: Box<dyn CliAdapter>| {
+            Arc::new(WorkspaceSession::new_with_adapter(
+                entry(id),
+                adapter,
+                Arc::new(Mutex::new(HashMap::new())),
+                Arc::default(),
+            ))
+        };
+        let claude = GenericAdapterSession::new(
+            ClaudeProfile::default(),
+            &entry("claude-ws"),
+            CliSpawnConfig {
+                cli_type: "claude".to_string(),
+                ..CliSpawnConfig::default()
+            },
+            Arc::new(|_| {}),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("claude.json"));
+        let started = claude
+            .send_request("thread/start", json!({}))
+            .await
+            .unwrap();
+        let claude_thread = started["result"]["threadId"].as_str().unwrap().to_string();
+        let gemini = GenericAdapterSession::new(
+            GeminiProfile,
+            &entry("gemini-ws"),
+            CliSpawnConfig {
+                cli_type: "gemini".to_string(),
+                ..CliSpawnConfig::default()
+            },
+            Arc::new(|_| {}),
+            Arc::new(Mutex::new(HashMap::new())),
+        )
+        .with_thread_store_path(temp_dir.join("gemini.json"));
+        let codex_requests = RecordedRequests::default();
+        let sessions = Mutex::new(HashMap::from([
+            (
+                "claude-ws".to_string(),
+                adapter_session("claude-ws", Box::new(claude)),
+            ),
+            (
+                "gemini-ws".to_string(),
+                adapter_session("gemini-ws", Box::new(gemini)),
+            ),
+            (
+                "codex-ws".to_string(),
+                fake_session_recording("codex-ws", "codex", codex_requests.clone()),
+            ),
+        ]));
+        let compact = |workspace_id: &str, thread_id: &str| {
+            compact_thread_core(&sessions, workspace_id.to_string(), thread_id.to_string())
+        };
+
+        // Claude compacts through the adapter; a thread without a CLI
+        // session has nothing to compact yet.
+        let result = compact("claude-ws", &claude_thread).await.unwrap();
+        assert_eq!(result["cliType"], "claude");
+        assert_eq!(result["threadId"], claude_thread.as_str());
+        assert_eq!(result["compacted"], false);
+        assert!(result.get("unsupported").is_none());
+        assert!(compact("claude-ws", "missing").await.is_err());
+
+        let result = compact("gemini-ws", "thread-1").await.unwrap();
+        assert_eq!(result["cliType"], "gemini");
+        assert_eq!(result["unsupported"], true);
+
+        let result = compact("codex-ws", "thread-1").await.unwrap();
+        assert_eq!(
+            result,
+            json!({ "threadId": "thread-1", "cliType": "codex" })
+        );
+        assert_eq!(
+            *codex_requests.lock().unwrap(),
+            vec![(
+                "thread/compact/start".to_string(),
+                json!({ "threadId": "thread-1" })
+            )]
+        );
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn turn_summary_counts_claude_tool_uses() {
+        let session = Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry("ws", &std::env::temp_dir().to_string_lossy()),
+            Box::new(turn_adapter(RecordedRequests::default())),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ));
+        let lines = [
+            r#"{"type":"system","subtype":"init","session_id":"s1","model":"claude-sonnet-4"}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"tu1","name":"Read"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"tool_result","tool_use_id":"tu1"}"#,
+            r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"tu2","name":"Bash"}}"#,
+            r#"{"type":"content_block_stop","index":1}"#,
+            r#"{"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"tu3","name":"Read"}}"#,
+            r#"{"type":"content_block_start","index":3,"content_block":{"type":"tool_use","id":"tu4","name":"mcp__docs__search"}}"#,
+            r#"{"type":"result","duration_ms":250}"#,
+        ];
+        for line in lines {
+            if let Some(message) =
+                crate::backend::claude_adapter::parse_stream_json_line(line, "thread-1", "turn-1")
+            {
+                session.event_recorder().record(&AppServerEvent {
+                    workspace_id: "ws".to_string(),
+                    message,
+                });
+            }
+        }
+        let sessions = Mutex::new(HashMap::from([("ws".to_string(), session)]));
+
+        let summary = get_turn_summary_core(
+            &sessions,
+            "ws".to_string(),
+            "thread-1".to_string(),
+            "turn-1".to_string(),
+        )
+        .await
+        .expect("turn summary");
+
+        assert_eq!(
+            summary["tools"],
+            json!([
+                { "name": "Read", "count": 2 },
+                { "name": "Bash", "count": 1 },
+                { "name": "mcp__docs__search", "count": 1 }
+            ])
+        );
+        assert_eq!(summary["durationMs"], 250);
+        assert_eq!(summary["completed"], true);
+        assert!(get_turn_summary_core(
+            &sessions,
+            "ws".to_string(),
+            "thread-1".to_string(),
+            "turn-2".to_string(),
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn turn_start_fails_when_workspace_directory_is_gone() {
+        let removed =
+            std::env::temp_dir().join(format!("codex-core-missing-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&removed).unwrap();
+        let requests = RecordedRequests::default();
+        let session = Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry("ws", &removed.to_string_lossy()),
+            Box::new(TestAdapter::new("claude").recording(requests.clone())),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ));
+        assert!(session.ensure_workspace_dir().is_ok());
+        std::fs::remove_dir_all(&removed).unwrap();
+
+        assert!(matches!(
+            session.send_request("turn/start", json!({})).await,
+            Err(AppServerError::WorkspaceMissing(_))
+        ));
+
+        let sessions = Mutex::new(HashMap::from([("ws".to_string(), session)]));
+        let sink = CollectingEventSink::default();
+        let err = send_user_message_core(
+            &sessions,
+            "ws".to_string(),
+            "thread-1".to_string(),
+            "hello".to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            0,
+            &HashMap::new(),
+            &sink,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.starts_with("workspace directory missing"), "{err}");
+        assert!(requests.lock().unwrap().is_empty());
+        let events = sink.0.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message["method"], "workspace/missing");
+        assert_eq!(
+            events[0].message["params"]["path"],
+            removed.to_string_lossy().as_ref()
+        );
+    }
+
+    #[tokio::test]
+    async fn lists_active_sessions_with_metadata() {
+        let sessions = Mutex::new(HashMap::new());
+        sessions
+            .lock()
+            .await
+            .insert("ws-b".to_string(), fake_session("ws-b", "gemini"));
+        sessions
+            .lock()
+            .await
+            .insert("ws-a".to_string(), fake_session("ws-a", "claude"));
+        let recording = std::env::temp_dir()
+            .join(format!("session-recording-{}.jsonl", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string();
+        start_event_recording_core(&sessions, "ws-a".to_string(), recording.clone())
+            .await
+            .expect("start recording");
+
+        let infos = list_active_sessions_core(&sessions).await;
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].workspace_id, "ws-a");
+        assert_eq!(infos[0].cli_type, "claude");
+        assert_eq!(infos[0].recording_path.as_deref(), Some(recording.as_str()));
+        assert_eq!(infos[1].workspace_id, "ws-b");
+        assert_eq!(infos[1].cli_type, "gemini");
+        assert!(infos[1].recording_path.is_none());
+        for info in &infos {
+            assert!(info.alive);
+            assert!(info.pid.is_none());
+            assert!(info.started_at > 0);
+        }
+        let _ = std::fs::remove_file(recording);
+    }
+
+    #[test]
+    fn rejects_whitespace_only_text_without_images() {
+        let err =
+            build_user_input("   \n\t", None, None).expect_err("empty text should be rejected");
+        assert!(err.contains("cannot be empty"));
+        let err = build_user_input("", None, Some(vec!["  ".to_string()]))
+            .expect_err("blank image paths should not count");
+        assert!(err.contains("cannot be empty"));
+    }
+
+    #[test]
+    fn allows_images_only_messages() {
+        let input = build_user_input(
+            "  ",
+            None,
+            Some(vec![
+                "/tmp/screenshot.png".to_string(),
+                "https://example.com/a.png".to_string(),
+            ]),
+        )
+        .expect("images-only message is valid");
+        assert_eq!(input.len(), 2);
+        assert_eq!(input[0]["type"], "localImage");
+        assert_eq!(input[1]["type"], "image");
+    }
+
+    #[test]
+    fn inlines_small_file_attachments_with_text() {
+        let dir = std::env::temp_dir().join(format!("attach-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        std::fs::write(dir.join("build.log"), "error: boom\n").expect("write log");
+
+        let attachments =
+            read_file_attachments(Some(vec!["build.log".to_string()]), &dir.to_string_lossy())
+                .expect("attachment under cap")
+                .expect("attachment text");
+        assert_eq!(
+            attachments,
+            "<file path=\"build.log\">\nerror: boom\n</file>"
+        );
+
+        let input = build_user_input("Why?", Some(&attachments), None).expect("input");
+        assert_eq!(input.len(), 1);
+        assert_eq!(
+            input[0]["text"],
+            "Why?\n\n<file path=\"build.log\">\nerror: boom\n</file>"
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rejects_missing_and_oversized_file_attachments() {
+        let dir = std::env::temp_dir().join(format!("attach-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let big = "x".repeat(MAX_INLINE_ATTACHMENT_BYTES as usize + 1);
+        std::fs::write(dir.join("huge.patch"), big).expect("write patch");
+        let workspace = dir.to_string_lossy().to_string();
+
+        let err = read_file_attachments(Some(vec!["huge.patch".to_string()]), &workspace)
+            .expect_err("oversized attachment should be rejected");
+        assert!(err.contains("limit is 64 KB"));
+
+        let err = read_file_attachments(Some(vec!["missing.log".to_string()]), &workspace)
+            .expect_err("missing attachment should be rejected");
+        assert!(err.contains("not found"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rejects_attachments_outside_the_workspace_or_over_the_total_cap() {
+        let dir = std::env::temp_dir().join(format!("attach-{}", uuid::Uuid::new_v4()));
+        let root = dir.join("repo");
+        std::fs::create_dir_all(&root).expect("create workspace");
+        std::fs::write(dir.join("secret.txt"), "token").expect("write outside file");
+        let part = "x".repeat(MAX_INLINE_ATTACHMENT_BYTES as usize);
+        let parts: Vec<String> = (0..=MAX_INLINE_ATTACHMENTS_TOTAL_BYTES
+            / MAX_INLINE_ATTACHMENT_BYTES)
+            .map(|index| {
+                let name = format!("part-{index}.txt");
+                std::fs::write(root.join(&name), &part).expect("write part");
+                name
+            })
+            .collect();
+        let workspace = root.to_string_lossy().to_string();
+
+        let err = read_file_attachments(Some(vec!["../secret.txt".to_string()]), &workspace)
+            .expect_err("relative escape should be rejected");
+        assert!(err.contains("outside the workspace"), "{err}");
+        let absolute = dir.join("secret.txt").to_string_lossy().to_string();
+        let err = read_file_attachments(Some(vec![absolute]), &workspace)
+            .expect_err("absolute path outside the workspace should be rejected");
+        assert!(err.contains("outside the workspace"), "{err}");
+
+        let within = read_file_attachments(Some(parts[1..].to_vec()), &workspace)
+            .expect("attachments at the total cap")
+            .expect("attachment text");
+        assert!(within.contains("part-1.txt"));
+        let err = read_file_attachments(Some(parts), &workspace)
+            .expect_err("attachments over the total cap should be rejected");
+        assert!(err.contains("256 KB together"), "{err}");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn warns_only_when_text_exceeds_threshold() {
+        assert!(long_message_warning("ws", "thread", "hello", 5).is_none());
+        assert!(long_message_warning("ws", "thread", "hello!", 0).is_none());
+        let event = long_message_warning("ws", "thread", "hello!", 5).expect("warning");
+        assert_eq!(event.workspace_id, "ws");
+        assert_eq!(event.message["method"], "codex/messageWarning");
+        assert_eq!(event.message["params"]["length"], 6);
+        assert_eq!(event.message["params"]["threshold"], 5);
+    }
+
+    fn claude_model_list() -> Value {
+        let standard = json!([
+            { "reasoningEffort": "low" },
+            { "reasoningEffort": "medium" },
+            { "reasoningEffort": "high" }
+        ]);
+        json!({
+            "result": {
+                "models": [
+                    {
+                        "id": "claude-sonnet-4-20250514",
+                        "supportedReasoningEfforts": standard,
+                        "defaultReasoningEffort": "high"
+                    },
+                    {
+                        "id": "claude-opus-4-20250514",
+                        "supportedReasoningEfforts": [
+                            { "reasoningEffort": "high" },
+                            { "reasoningEffort": "max" }
+                        ],
+                        "defaultReasoningEffort": "high"
+                    },
+                    { "id": "no-default", "supportedReasoningEfforts": standard }
+                ],
+                "defaultModel": "claude-sonnet-4-20250514"
+            }
+        })
+    }
+
+    #[test]
+    fn keeps_effort_supported_by_model() {
+        let list = claude_model_list();
+        assert_eq!(
+            resolve_supported_effort(&list, Some("claude-opus-4-20250514"), "max").unwrap(),
+            "max"
+        );
+        assert_eq!(
+            resolve_supported_effort(&list, Some("unknown-model"), "max").unwrap(),
+            "max"
+        );
+    }
+
+    #[test]
+    fn downgrades_unsupported_effort_to_model_default() {
+        let list = claude_model_list();
+        assert_eq!(
+            resolve_supported_effort(&list, Some("claude-sonnet-4-20250514"), "max").unwrap(),
+            "high"
+        );
+        assert_eq!(
+            resolve_supported_effort(&list, None, "max").unwrap(),
+            "high"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_effort_without_default() {
+        let err = resolve_supported_effort(&claude_model_list(), Some("no-default"), "max")
+            .expect_err("no default to fall back to");
+        assert!(err.contains("`max` is not supported by no-default"));
+        assert!(err.contains("low, medium, high"));
+    }
+
+    #[tokio::test]
+    async fn stored_collaboration_mode_applies_to_messages_without_one() {
+        let requests = RecordedRequests::default();
+        let session = fake_session_recording("ws", "codex", requests.clone());
+        let storage_path = std::env::temp_dir()
+            .join(format!("collaboration-mode-{}", uuid::Uuid::new_v4()))
+            .join("workspaces.json");
+        let workspaces = Mutex::new(HashMap::from([("ws".to_string(), session.entry.clone())]));
+        let sessions = Mutex::new(HashMap::from([("ws".to_string(), session)]));
+        let plan = json!({ "mode": "plan" });
+        let stored = set_collaboration_mode_core(
+            &sessions,
+            &workspaces,
+            &storage_path,
+            "ws".to_string(),
+            "thread-1".to_string(),
+            plan.clone(),
+        )
+        .await
+        .expect("set mode");
+        assert_eq!(stored["collaborationMode"], plan);
+
+        // The choice is saved with the workspace, so a session started after
+        // a restart picks it up again.
+        let saved = crate::storage::read_workspaces(&storage_path).expect("read workspaces");
+        let restored = WorkspaceSession::new_with_adapter(
+            saved["ws"].clone(),
+            Box::new(TestAdapter::new("codex")),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        );
+        assert_eq!(
+            restored.collaboration_mode("thread-1").await,
+            Some(plan.clone())
+        );
+        let _ = std::fs::remove_dir_all(storage_path.parent().unwrap());
+
+        for (thread_id, explicit) in [
+            ("thread-1", None),
+            ("thread-1", Some(json!({ "mode": "code" }))),
+            ("thread-2", None),
+        ] {
+            send_user_message_core(
+                &sessions,
+                "ws".to_string(),
+                thread_id.to_string(),
+                "hello".to_string(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                explicit,
+                None,
+                None,
+                0,
+                &HashMap::new(),
+                &NoopEventSink,
+            )
+            .await
+            .expect("send message");
+        }
+
+        let requests = requests.lock().unwrap();
+        let modes: Vec<Option<&Value>> = requests
+            .iter()
+            .filter(|(method, _)| method == "turn/start")
+            .map(|(_, params)| params.get("collaborationMode"))
+            .collect();
+        assert_eq!(
+            modes,
+            vec![Some(&plan), Some(&json!({ "mode": "code" })), None]
+        );
+    }
+
+    #[test]
+    fn model_aliases_map_forward_and_back() {
+        let aliases = HashMap::from([
+            (
+                "claude-sonnet-4".to_string(),
+                "gateway/sonnet-proxy".to_string(),
+            ),
+            ("sonnet".to_string(), "gateway/sonnet-proxy".to_string()),
+            ("gpt-5".to_string(), " ".to_string()),
+        ]);
+        assert_eq!(
+            model_for_cli(&aliases, "claude-sonnet-4"),
+            "gateway/sonnet-proxy"
+        );
+        assert_eq!(model_for_cli(&aliases, "claude-opus-4"), "claude-opus-4");
+        assert_eq!(model_for_cli(&aliases, "gpt-5"), "gpt-5");
+
+        let adapter_list = json!({
+            "result": {
+                "models": [
+                    { "id": "gateway/sonnet-proxy", "name": "Sonnet via gateway" },
+                    { "id": "claude-opus-4", "name": "Claude Opus 4" }
+                ],
+                "defaultModel": "gateway/sonnet-proxy"
+            }
+        });
+        let shown = model_list_for_display(&aliases, adapter_list);
+        assert_eq!(shown["result"]["models"][0]["id"], "claude-sonnet-4");
+        assert_eq!(shown["result"]["models"][1]["id"], "claude-opus-4");
+        assert_eq!(shown["result"]["defaultModel"], "claude-sonnet-4");
+
+        let codex_list = json!({
+            "result": {
+                "data": [{ "id": "gateway/sonnet-proxy", "model": "gateway/sonnet-proxy" }]
+            }
+        });
+        let shown = model_list_for_display(&aliases, codex_list);
+        assert_eq!(
+            shown["result"]["data"][0],
+            json!({ "id": "claude-sonnet-4", "model": "claude-sonnet-4" })
+        );
+    }
+
+    #[tokio::test]
+    async fn aliased_model_is_sent_to_the_cli() {
+        let requests = RecordedRequests::default();
+        let sessions = Mutex::new(HashMap::from([(
+            "ws".to_string(),
+            fake_session_recording("ws", "claude", requests.clone()),
+        )]));
+        let aliases = HashMap::from([(
+            "claude-sonnet-4".to_string(),
+            "gateway/sonnet-proxy".to_string(),
+        )]);
+        send_user_message_core(
+            &sessions,
+            "ws".to_string(),
+            "thread-1".to_string(),
+            "hello".to_string(),
+            Some("claude-sonnet-4".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            0,
+            &aliases,
+            &NoopEventSink,
+        )
+        .await
+        .expect("send message");
+
+        let requests = requests.lock().unwrap();
+        let (_, params) = requests
+            .iter()
+            .find(|(method, _)| method == "turn/start")
+            .expect("turn/start");
+        assert_eq!(params["model"], "gateway/sonnet-proxy");
+    }
+
+    #[tokio::test]
+    async fn stored_thread_model_applies_to_messages_without_one() {
+        let requests = RecordedRequests::default();
+        let sessions = Mutex::new(HashMap::new());
+        sessions.lock().await.insert(
+            "ws".to_string(),
+            fake_session_recording("ws", "claude", requests.clone()),
+        );
+        let stored = set_thread_model_core(
+            &sessions,
+            "ws".to_string(),
+            "thread-1".to_string(),
+            Some("claude-opus-4".to_string()),
+        )
+        .await
+        .expect("set model");
+        assert_eq!(stored["model"], "claude-opus-4");
+
+        for (thread_id, explicit) in [
+            ("thread-1", None),
+            ("thread-1", Some("claude-sonnet-4")),
+            ("thread-2", None),
+        ] {
+            send_user_message_core(
+                &sessions,
+                "ws".to_string(),
+                thread_id.to_string(),
+                "hello".to_string(),
+                explicit.map(str::to_string),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                0,
+                &HashMap::new(),
+                &NoopEventSink,
+            )
+            .await
+            .expect("send message");
+        }
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests.first(),
+            Some(&(
+                "thread/model/set".to_string(),
+                json!({ "threadId": "thread-1", "model": "claude-opus-4" })
+            ))
+        );
+        let models: Vec<&Value> = requests
+            .iter()
+            .filter(|(method, _)| method == "turn/start")
+            .map(|(_, params)| &params["model"])
+            .collect();
+        assert_eq!(
+            models,
+            vec![
+                &json!("claude-opus-4"),
+                &json!("claude-sonnet-4"),
+                &Value::Null
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn apps_list_forwards_paging_to_app_list() {
+        let requests = RecordedRequests::default();
+        let sessions = Mutex::new(HashMap::new());
+        sessions.lock().await.insert(
+            "ws".to_string(),
+            fake_session_recording("ws", "claude", requests.clone()),
+        );
+
+        apps_list_core(
+            &sessions,
+            "ws".to_string(),
+            Some("page-2".to_string()),
+            Some(50),
+        )
+        .await
+        .expect("list apps");
+        assert!(apps_list_core(&sessions, "missing".to_string(), None, None)
+            .await
+            .is_err());
+
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![(
+                "app/list".to_string(),
+                json!({ "cursor": "page-2", "limit": 50 })
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn default_approval_policy_applies_only_without_access_mode() {
+        let requests = RecordedRequests::default();
+        let sessions = Mutex::new(HashMap::new());
+        sessions.lock().await.insert(
+            "ws".to_string(),
+            fake_session_recording("ws", "codex", requests.clone()),
+        );
+
+        for (access_mode, default_policy) in [
+            (None, Some("untrusted")),
+            (Some("full-access"), Some("untrusted")),
+            (Some("current"), Some("untrusted")),
+            (None, None),
+        ] {
+            send_user_message_core(
+                &sessions,
+                "ws".to_string(),
+                "thread-1".to_string(),
+                "hello".to_string(),
+                None,
+                None,
+                access_mode.map(str::to_string),
+                None,
+                None,
+                None,
+                None,
+                default_policy.map(str::to_string),
+                0,
+                &HashMap::new(),
+                &NoopEventSink,
+            )
+            .await
+            .expect("send message");
+        }
+
+        let requests = requests.lock().unwrap();
+        let policies: Vec<&Value> = requests
+            .iter()
+            .filter(|(method, _)| method == "turn/start")
+            .map(|(_, params)| &params["approvalPolicy"])
+            .collect();
+        assert_eq!(
+            policies,
+            vec!["untrusted", "never", "on-request", "on-request"]
+        );
+    }
+
+    fn review_repo() -> (std::path::PathBuf, String) {
+        let root = std::env::temp_dir().join(format!("review-target-{}", uuid::Uuid::new_v4()));
+        let repo = git2::Repository::init(&root).expect("init repo");
+        std::fs::write(root.join("a.txt"), "a\n").expect("write file");
+        let mut index = repo.index().expect("index");
+        index
+            .add_path(std::path::Path::new("a.txt"))
+            .expect("add path");
+        index.write().expect("write index");
+        let tree = repo
+            .find_tree(index.write_tree().expect("tree"))
+            .expect("find tree");
+        let signature = git2::Signature::now("Test", "test@example.com").expect("signature");
+        let commit_id = repo
+            .commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .expect("commit");
+        let commit = repo.find_commit(commit_id).expect("find commit");
+        repo.branch("feature", &commit, false).expect("branch");
+        (root, commit_id.to_string())
+    }
+
+    #[tokio::test]
+    async fn validates_review_target_shapes() {
+        let (root, sha) = review_repo();
+        let valid = [
+            json!({ "type": "uncommittedChanges" }),
+            json!({ "type": "custom", "instructions": "Focus on error handling" }),
+            json!({ "type": "commit", "sha": sha, "title": "init" }),
+            json!({ "type": "commit", "sha": &sha[..8] }),
+            json!({ "type": "baseBranch", "branch": "feature" }),
+        ];
+        for target in valid {
+            validate_review_target(&root, &target)
+                .await
+                .unwrap_or_else(|err| panic!("{target} should be valid: {err}"));
+        }
+
+        let invalid = [
+            (
+                json!({ "type": "commit", "sha": "not-a-sha" }),
+                ReviewTargetError::InvalidSha("not-a-sha".to_string()),
+            ),
+            (
+                json!({ "type": "commit", "sha": "deadbeefdeadbeef" }),
+                ReviewTargetError::CommitNotFound("deadbeefdeadbeef".to_string()),
+            ),
+            (
+                json!({ "type": "baseBranch", "branch": "missing" }),
+                ReviewTargetError::BranchNotFound("missing".to_string()),
+            ),
+            (
+                json!({ "type": "baseBranch" }),
+                ReviewTargetError::MissingField("branch"),
+            ),
+            (
+                json!({ "type": "pullRequest", "number": 3 }),
+                ReviewTargetError::UnknownType("pullRequest".to_string()),
+            ),
+        ];
+        for (target, expected) in invalid {
+            assert_eq!(validate_review_target(&root, &target).await, Err(expected));
+        }
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn working_review_target_inlines_the_working_tree_diff() {
+        let (root, _) = review_repo();
+        assert_eq!(
+            resolve_review_target(&root, json!({ "type": "working" })),
+            Err(NO_REVIEW_CHANGES_MESSAGE.to_string())
+        );
+        let untouched = json!({ "type": "uncommittedChanges" });
+        assert_eq!(
+            resolve_review_target(&root, untouched.clone()),
+            Ok(untouched)
+        );
+
+        std::fs::write(root.join("a.txt"), "a\nchanged\n").expect("modify file");
+        std::fs::write(root.join("new.txt"), "fresh\n").expect("add file");
+        let target = resolve_review_target(&root, json!({ "type": "working" })).expect("target");
+        assert_eq!(target["type"], "custom");
+        let instructions = target["instructions"].as_str().expect("instructions");
+        assert!(instructions.contains("+changed"), "{instructions}");
+        assert!(instructions.contains("+fresh"), "{instructions}");
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    fn stored_thread(
+        updated_at: u64,
+        archived: bool,
+    ) -> crate::backend::adapter_base::ThreadMetadata {
+        crate::backend::adapter_base::ThreadMetadata {
+            cli_session_id: None,
+            name: None,
+            created_at: 1,
+            updated_at,
+            archived,
+            usage: Default::default(),
+            model: None,
+            reported_model: None,
+            first_prompt: None,
+            fork_pending: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn list_all_threads_merges_workspaces_by_recency() {
+        use crate::backend::adapter_base::{thread_store_path, ThreadStore};
+
+        let store_dir = std::env::temp_dir().join(format!("all-threads-{}", uuid::Uuid::new_v4()));
+        let store_a = ThreadStore {
+            threads: HashMap::from([
+                ("a1".to_string(), stored_thread(100, false)),
+                ("a2".to_string(), stored_thread(300, false)),
+                ("a-old".to_string(), stored_thread(999, true)),
+            ]),
+        };
+        store_a
+            .save(&thread_store_path(Some(store_dir.as_path()), "ws-a"))
+            .expect("save a");
+        let store_b = ThreadStore {
+            threads: HashMap::from([
+                ("b1".to_string(), stored_thread(200, false)),
+                ("b2".to_string(), stored_thread(400, false)),
+            ]),
+        };
+        store_b
+            .save(&thread_store_path(Some(store_dir.as_path()), "ws-b"))
+            .expect("save b");
+
+        let workspaces = Mutex::new(HashMap::from([
+            ("ws-a".to_string(), test_workspace_entry("ws-a", "/repo/a")),
+            ("ws-b".to_string(), test_workspace_entry("ws-b", "/repo/b")),
+            ("ws-c".to_string(), test_workspace_entry("ws-c", "/repo/c")),
+        ]));
+        let session_c = Arc::new(WorkspaceSession::new_with_adapter(
+            test_workspace_entry("ws-c", "/repo/c"),
+            // Answers `thread/list` the way codex does: threads from every cwd.
+            Box::new(TestAdapter::new("codex").responding(|_, _| {
+                Ok(json!({
+                    "result": {
+                        "data": [
+                            { "id": "c1", "cwd": "/repo/c/", "updatedAt": 250 },
+                            { "id": "elsewhere", "cwd": "/repo/other", "updatedAt": 900 }
+                        ],
+                        "nextCursor": null
+                    }
+                }))
+            })),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::default(),
+        ));
+        let sessions = Mutex::new(HashMap::from([("ws-c".to_string(), session_c)]));
+        let settings = Mutex::new(crate::types::AppSettings {
+            thread_store_dir: Some(store_dir.to_string_lossy().to_string()),
+            ..Default::default()
+        });
+        let ids = |page: &Value| {
+            page["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "{}/{}",
+                        entry["workspaceId"].as_str().unwrap(),
+                        entry["thread"]["id"].as_str().unwrap()
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let first = list_all_threads_core(&sessions, &workspaces, &settings, None, Some(3))
+            .await
+            .expect("first page");
+        assert_eq!(ids(&first), vec!["ws-b/b2", "ws-a/a2", "ws-c/c1"]);
+        assert_eq!(first["nextCursor"], json!("3"));
+
+        let second = list_all_threads_core(
+            &sessions,
+            &workspaces,
+            &settings,
+            Some("3".to_string()),
+            Some(3),
+        )
+        .await
+        .expect("second page");
+        assert_eq!(ids(&second), vec!["ws-b/b1", "ws-a/a1"]);
+        assert_eq!(second["nextCursor"], Value::Null);
+
+        let bad_cursor = list_all_threads_core(
+            &sessions,
+            &workspaces,
+            &settings,
+            Some("x".to_string()),
+            None,
+        )
+        .await;
+        assert!(bad_cursor.is_err());
+        let _ = std::fs::remove_dir_all(store_dir);
+    }
 }
@@ -1,8 +1,12 @@
 #![allow(dead_code)]
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use git2::{DiffOptions, Repository};
 
 use crate::shared::process_core::tokio_command;
+use crate::types::{GitFileStatus, WorkspaceDiffStructured, WorkspaceEntry};
 use crate::utils::{git_env_path, resolve_git_binary};
 
 fn format_git_error(stdout: &[u8], stderr: &[u8]) -> String {
@@ -79,6 +83,233 @@ pub(crate) async fn run_git_diff(repo_path: &PathBuf, args: &[&str]) -> Result<V
     Err(format_git_error(&output.stdout, &output.stderr))
 }
 
+pub(crate) fn resolve_git_root(entry: &WorkspaceEntry) -> Result<PathBuf, String> {
+    let base = PathBuf::from(&entry.path);
+    let root = entry
+        .settings
+        .git_root
+        .as_ref()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty());
+    let Some(root) = root else {
+        return Ok(base);
+    };
+    let root_path = if Path::new(root).is_absolute() {
+        PathBuf::from(root)
+    } else {
+        base.join(root)
+    };
+    if root_path.is_dir() {
+        Ok(root_path)
+    } else {
+        Err(format!("Git root not found: {root}"))
+    }
+}
+
+pub(crate) fn diff_patch_to_string(patch: &mut git2::Patch) -> Result<String, git2::Error> {
+    let buf = patch.to_buf()?;
+    Ok(buf
+        .as_str()
+        .map(|value| value.to_string())
+        .unwrap_or_else(|| String::from_utf8_lossy(&buf).to_string()))
+}
+
+fn build_combined_diff(diff: &git2::Diff) -> String {
+    let mut combined_diff = String::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let path = delta.new_file().path().or_else(|| delta.old_file().path());
+        let Some(path) = path else {
+            continue;
+        };
+        let patch = match git2::Patch::from_diff(diff, index) {
+            Ok(patch) => patch,
+            Err(_) => continue,
+        };
+        let Some(mut patch) = patch else {
+            continue;
+        };
+        let content = match diff_patch_to_string(&mut patch) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        if content.trim().is_empty() {
+            continue;
+        }
+        if !combined_diff.is_empty() {
+            combined_diff.push_str("\n\n");
+        }
+        combined_diff.push_str(&format!("=== {} ===\n", path.display()));
+        combined_diff.push_str(&content);
+    }
+    combined_diff
+}
+
+/// Raw staged and working-tree diffs of a repository, kept apart so callers
+/// can tell "nothing staged" from "nothing changed".
+#[derive(Debug, Default, Clone)]
+pub(crate) struct WorkspaceDiffParts {
+    pub(crate) staged: String,
+    /// Everything that differs from `HEAD`, staged or not, including
+    /// untracked files.
+    pub(crate) working_tree: String,
+}
+
+/// Diff used for commit message generation: staged changes when there are
+/// any, otherwise the working tree (including untracked files).
+pub(crate) fn collect_workspace_diff(repo_root: &Path) -> Result<String, String> {
+    let parts = collect_workspace_diff_parts(repo_root)?;
+    if parts.staged.trim().is_empty() {
+        Ok(parts.working_tree)
+    } else {
+        Ok(parts.staged)
+    }
+}
+
+pub(crate) fn collect_workspace_diff_parts(repo_root: &Path) -> Result<WorkspaceDiffParts, String> {
+    let repo = Repository::open(repo_root).map_err(|e| e.to_string())?;
+    let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+
+    let mut options = DiffOptions::new();
+    let index = repo.index().map_err(|e| e.to_string())?;
+    let diff = match head_tree.as_ref() {
+        Some(tree) => repo
+            .diff_tree_to_index(Some(tree), Some(&index), Some(&mut options))
+            .map_err(|e| e.to_string())?,
+        None => repo
+            .diff_tree_to_index(None, Some(&index), Some(&mut options))
+            .map_err(|e| e.to_string())?,
+    };
+    let staged = build_combined_diff(&diff);
+
+    let mut options = DiffOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    let diff = match head_tree.as_ref() {
+        Some(tree) => repo
+            .diff_tree_to_workdir_with_index(Some(tree), Some(&mut options))
+            .map_err(|e| e.to_string())?,
+        None => repo
+            .diff_tree_to_workdir_with_index(None, Some(&mut options))
+            .map_err(|e| e.to_string())?,
+    };
+    Ok(WorkspaceDiffParts {
+        staged,
+        working_tree: build_combined_diff(&diff),
+    })
+}
+
+/// Resolves the new path of a `--numstat` entry, expanding rename forms like
+/// `old => new` and `src/{old.rs => new.rs}`. Returns whether it was a rename.
+fn numstat_path(raw: &str) -> (String, bool) {
+    let Some((before, after)) = raw.split_once(" => ") else {
+        return (raw.to_string(), false);
+    };
+    let path = match (before.rfind('{'), after.find('}')) {
+        (Some(open), Some(close)) => {
+            format!(
+                "{}{}{}",
+                &before[..open],
+                &after[..close],
+                &after[close + 1..]
+            )
+        }
+        _ => after.to_string(),
+    };
+    (path.replace("//", "/"), true)
+}
+
+/// File status letters (`A`/`D`/`R`) from the extended headers of a patch,
+/// keyed by the file's new path. Files without one are modifications.
+fn patch_statuses(patch: &str) -> HashMap<String, &'static str> {
+    let mut statuses = HashMap::new();
+    let mut current: Option<String> = None;
+    for line in patch.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            current = rest.rsplit_once(" b/").map(|(_, path)| path.to_string());
+            continue;
+        }
+        if line.starts_with("@@") {
+            current = None;
+            continue;
+        }
+        let Some(path) = current.as_ref() else {
+            continue;
+        };
+        let status = if line.starts_with("new file mode") {
+            "A"
+        } else if line.starts_with("deleted file mode") {
+            "D"
+        } else if line.starts_with("rename to ") {
+            "R"
+        } else {
+            continue;
+        };
+        statuses.insert(path.clone(), status);
+    }
+    statuses
+}
+
+/// Builds the structured form of a diff from `git diff --numstat` output and
+/// the matching patch. Binary files (`-` counts) report zero lines changed.
+pub(crate) fn parse_structured_diff(numstat: &str, patch: &str) -> WorkspaceDiffStructured {
+    let statuses = patch_statuses(patch);
+    let files = numstat
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let additions = parts.next()?.trim().parse::<i64>().unwrap_or(0);
+            let deletions = parts.next()?.trim().parse::<i64>().unwrap_or(0);
+            let (path, renamed) = numstat_path(parts.next()?.trim());
+            let status = if renamed {
+                "R"
+            } else {
+                statuses.get(&path).copied().unwrap_or("M")
+            };
+            Some(GitFileStatus {
+                path,
+                status: status.to_string(),
+                additions,
+                deletions,
+            })
+        })
+        .collect();
+    WorkspaceDiffStructured {
+        files,
+        diff: patch.to_string(),
+    }
+}
+
+/// Structured counterpart of [`collect_workspace_diff`] built from the git
+/// CLI: staged changes when there are any, otherwise unstaged ones (unless
+/// `staged_only`). Untracked files aren't included, so callers fall back to
+/// the raw diff when it's empty.
+pub(crate) async fn collect_workspace_diff_structured(
+    repo_root: &PathBuf,
+    staged_only: bool,
+) -> Result<WorkspaceDiffStructured, String> {
+    let scopes: &[bool] = if staged_only { &[true] } else { &[true, false] };
+    for &staged in scopes {
+        let mut args = vec!["diff", "--no-color", "-M"];
+        if staged {
+            args.push("--cached");
+        }
+        let numstat_args = [args.as_slice(), &["--numstat"]].concat();
+        let numstat = run_git_diff(repo_root, &numstat_args).await?;
+        let numstat = String::from_utf8_lossy(&numstat);
+        if numstat.trim().is_empty() {
+            continue;
+        }
+        let patch = run_git_diff(repo_root, &args).await?;
+        return Ok(parse_structured_diff(
+            &numstat,
+            &String::from_utf8_lossy(&patch),
+        ));
+    }
+    Ok(WorkspaceDiffStructured::default())
+}
+
 pub(crate) fn is_missing_worktree_error(error: &str) -> bool {
     error.contains("is not a working tree")
 }
@@ -95,6 +326,25 @@ pub(crate) async fn git_branch_exists(repo_path: &PathBuf, branch: &str) -> Resu
     Ok(status.success())
 }
 
+/// Whether `rev` names a commit; trees, blobs and unknown names don't count.
+pub(crate) async fn git_commit_exists(repo_path: &PathBuf, rev: &str) -> Result<bool, String> {
+    let git_bin = resolve_git_binary().map_err(|err| format!("Failed to run git: {err}"))?;
+    let status = tokio_command(git_bin)
+        .args([
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ])
+        .current_dir(repo_path)
+        .env("PATH", git_env_path())
+        .stdout(std::process::Stdio::null())
+        .status()
+        .await
+        .map_err(|err| format!("Failed to run git: {err}"))?;
+    Ok(status.success())
+}
+
 pub(crate) async fn git_remote_exists(repo_path: &PathBuf, remote: &str) -> Result<bool, String> {
     let git_bin = resolve_git_binary().map_err(|err| format!("Failed to run git: {err}"))?;
     let status = tokio_command(git_bin)
@@ -239,3 +489,80 @@ pub(crate) async fn git_get_origin_url(repo_path: &PathBuf) -> Option<String> {
         .await
         .ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{numstat_path, parse_structured_diff};
+    use crate::types::GitFileStatus;
+
+    fn file(path: &str, status: &str, additions: i64, deletions: i64) -> GitFileStatus {
+        GitFileStatus {
+            path: path.to_string(),
+            status: status.to_string(),
+            additions,
+            deletions,
+        }
+    }
+
+    #[test]
+    fn parses_numstat_and_patch_into_structured_diff() {
+        let numstat = "3\t1\tsrc/lib.rs\n\
+2\t0\tdocs/new.md\n\
+0\t4\told.txt\n\
+-\t-\tassets/logo.png\n\
+1\t1\tsrc/{util.rs => helpers.rs}\n";
+        let patch = "diff --git a/src/lib.rs b/src/lib.rs\n\
+index 1111111..2222222 100644\n\
+--- a/src/lib.rs\n\
++++ b/src/lib.rs\n\
+@@ -1,2 +1,4 @@\n\
+-new file mode looks like a header but is content\n\
++fn a() {}\n\
+diff --git a/docs/new.md b/docs/new.md\n\
+new file mode 100644\n\
+--- /dev/null\n\
++++ b/docs/new.md\n\
+@@ -0,0 +1,2 @@\n\
++# New\n\
+diff --git a/old.txt b/old.txt\n\
+deleted file mode 100644\n\
+diff --git a/assets/logo.png b/assets/logo.png\n\
+Binary files a/assets/logo.png and b/assets/logo.png differ\n\
+diff --git a/src/util.rs b/src/helpers.rs\n\
+similarity index 90%\n\
+rename from src/util.rs\n\
+rename to src/helpers.rs\n";
+
+        let structured = parse_structured_diff(numstat, patch);
+
+        assert_eq!(
+            structured.files,
+            vec![
+                file("src/lib.rs", "M", 3, 1),
+                file("docs/new.md", "A", 2, 0),
+                file("old.txt", "D", 0, 4),
+                file("assets/logo.png", "M", 0, 0),
+                file("src/helpers.rs", "R", 1, 1),
+            ]
+        );
+        assert_eq!(structured.diff, patch);
+    }
+
+    #[test]
+    fn expands_numstat_rename_forms() {
+        assert_eq!(numstat_path("a.txt"), ("a.txt".to_string(), false));
+        assert_eq!(numstat_path("a.txt => b.txt"), ("b.txt".to_string(), true));
+        assert_eq!(
+            numstat_path("src/{a => b}/mod.rs"),
+            ("src/b/mod.rs".to_string(), true)
+        );
+        assert_eq!(
+            numstat_path("src/{ => nested}/mod.rs"),
+            ("src/nested/mod.rs".to_string(), true)
+        );
+        assert_eq!(
+            numstat_path("src/{nested => }/mod.rs"),
+            ("src/mod.rs".to_string(), true)
+        );
+    }
+}
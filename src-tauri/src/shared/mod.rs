@@ -8,5 +8,6 @@ pub(crate) mod git_core;
 pub(crate) mod process_core;
 pub(crate) mod sandbox_setup_core;
 pub(crate) mod settings_core;
+pub(crate) mod version_core;
 pub(crate) mod workspaces_core;
 pub(crate) mod worktree_core;
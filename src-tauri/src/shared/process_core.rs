@@ -3,10 +3,17 @@ use std::env;
 use std::ffi::OsStr;
 #[cfg(target_os = "windows")]
 use std::path::{Path, PathBuf};
-#[cfg(windows)]
 use std::process::Stdio;
 
-use tokio::process::{Child, Command};
+use serde_json::Value;
+use std::io;
+use std::process::Output;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, Command};
+use tokio::task::JoinHandle;
 
 /// On Windows, spawning a console app from a GUI subsystem app will open a new
 /// console window unless we explicitly disable it.
@@ -25,25 +32,273 @@ pub(crate) fn tokio_command(program: impl AsRef<OsStr>) -> Command {
     command
 }
 
+/// Runs prepared commands to completion. Installation checks and sandbox
+/// setup go through this so tests can swap in `MockCommandRunner` instead of
+/// needing real binaries on PATH. A missing binary surfaces as
+/// `ErrorKind::NotFound`, an expired limit as `ErrorKind::TimedOut`. Only
+/// stdout and stderr the command pipes end up in the `Output`.
+#[async_trait::async_trait]
+pub(crate) trait CommandRunner: Send + Sync {
+    async fn output(&self, command: Command, limit: Duration) -> io::Result<Output>;
+}
+
+pub(crate) struct SystemCommandRunner;
+
+#[async_trait::async_trait]
+impl CommandRunner for SystemCommandRunner {
+    async fn output(&self, mut command: Command, limit: Duration) -> io::Result<Output> {
+        command.stdin(Stdio::null()).kill_on_drop(true);
+        let mut child = command.spawn()?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let finished = tokio::time::timeout(limit, async {
+            let (stdout, stderr, status) =
+                tokio::join!(read_pipe(stdout), read_pipe(stderr), child.wait());
+            Ok::<_, io::Error>(Output {
+                status: status?,
+                stdout: stdout?,
+                stderr: stderr?,
+            })
+        })
+        .await;
+        match finished {
+            Ok(result) => result,
+            Err(_) => {
+                // A hung CLI would otherwise keep running after setup gives up.
+                kill_child_process_tree(&mut child).await;
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("command did not finish within {}s", limit.as_secs()),
+                ))
+            }
+        }
+    }
+}
+
+async fn read_pipe(pipe: Option<impl AsyncRead + Unpin>) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    if let Some(mut pipe) = pipe {
+        pipe.read_to_end(&mut buffer).await?;
+    }
+    Ok(buffer)
+}
+
+/// Starts a stdio JSON-RPC server, sends it one request (one JSON message per
+/// line) and returns the response with the same id, then kills the server.
+/// MCP server checks go through this so tests can fake the handshake. A
+/// server that exits before answering surfaces as `ErrorKind::UnexpectedEof`
+/// with the tail of its stderr in the message.
+#[async_trait::async_trait]
+pub(crate) trait StdioRequestRunner: Send + Sync {
+    async fn request(&self, command: Command, request: Value, limit: Duration)
+        -> io::Result<Value>;
+}
+
+pub(crate) struct SystemStdioRequestRunner;
+
+/// Stderr kept when a stdio server exits early.
+const STDIO_STDERR_TAIL_BYTES: usize = 2000;
+
+#[async_trait::async_trait]
+impl StdioRequestRunner for SystemStdioRequestRunner {
+    async fn request(
+        &self,
+        mut command: Command,
+        request: Value,
+        limit: Duration,
+    ) -> io::Result<Value> {
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let mut child = command.spawn()?;
+        // Drained while the request is in flight so a server that logs a lot
+        // can't block on a full stderr pipe before it answers.
+        let stderr_tail = StderrTail::default();
+        let mut drain = child
+            .stderr
+            .take()
+            .map(|stderr| tokio::spawn(stderr_tail.clone().drain(stderr)));
+        let result = match tokio::time::timeout(limit, exchange_request(&mut child, &request)).await
+        {
+            Ok(Err(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                let stderr = stderr_tail.finish(drain.take()).await;
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    if stderr.is_empty() {
+                        err.to_string()
+                    } else {
+                        format!("{err}: {stderr}")
+                    },
+                ))
+            }
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no response within {}s", limit.as_secs()),
+            )),
+        };
+        kill_child_process_tree(&mut child).await;
+        if let Some(drain) = drain {
+            drain.abort();
+        }
+        result
+    }
+}
+
+async fn exchange_request(child: &mut Child, request: &Value) -> io::Result<Value> {
+    let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "stdio pipe unavailable");
+    let mut stdin = child.stdin.take().ok_or_else(closed)?;
+    let stdout = child.stdout.take().ok_or_else(closed)?;
+    stdin.write_all(format!("{request}\n").as_bytes()).await?;
+    stdin.flush().await?;
+
+    let id = request.get("id");
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await? {
+        let Ok(message) = serde_json::from_str::<Value>(line.trim()) else {
+            continue;
+        };
+        let is_response = message.get("result").is_some() || message.get("error").is_some();
+        if is_response && message.get("id") == id {
+            return Ok(message);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "server exited before responding",
+    ))
+}
+
+/// The last `STDIO_STDERR_TAIL_BYTES` a stdio server wrote to stderr.
+#[derive(Clone, Default)]
+struct StderrTail(Arc<Mutex<Vec<u8>>>);
+
+impl StderrTail {
+    async fn drain(self, mut stderr: ChildStderr) {
+        let mut chunk = [0u8; 4096];
+        while let Ok(read @ 1..) = stderr.read(&mut chunk).await {
+            let mut tail = self.0.lock().unwrap_or_else(|err| err.into_inner());
+            tail.extend_from_slice(&chunk[..read]);
+            let excess = tail.len().saturating_sub(STDIO_STDERR_TAIL_BYTES);
+            tail.drain(..excess);
+        }
+    }
+
+    /// Gives the drain a moment to reach the end of stderr, then returns what
+    /// it kept.
+    async fn finish(&self, drain: Option<JoinHandle<()>>) -> String {
+        if let Some(drain) = drain {
+            let _ = tokio::time::timeout(Duration::from_secs(1), drain).await;
+        }
+        let tail = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        String::from_utf8_lossy(&tail).trim().to_string()
+    }
+}
+
+/// Replays queued results in order and records each command line it was
+/// asked to run.
+#[cfg(test)]
+pub(crate) struct MockCommandRunner {
+    responses: std::sync::Mutex<std::collections::VecDeque<io::Result<Output>>>,
+    calls: std::sync::Mutex<Vec<Vec<String>>>,
+}
+
+#[cfg(test)]
+impl MockCommandRunner {
+    pub(crate) fn new(responses: Vec<io::Result<Output>>) -> Self {
+        Self {
+            responses: std::sync::Mutex::new(responses.into()),
+            calls: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn exited(success: bool, stdout: &str, stderr: &str) -> io::Result<Output> {
+        #[cfg(unix)]
+        let status = {
+            use std::os::unix::process::ExitStatusExt;
+            std::process::ExitStatus::from_raw(if success { 0 } else { 1 << 8 })
+        };
+        #[cfg(windows)]
+        let status = {
+            use std::os::windows::process::ExitStatusExt;
+            std::process::ExitStatus::from_raw(if success { 0 } else { 1 })
+        };
+        Ok(Output {
+            status,
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        })
+    }
+
+    pub(crate) fn failed(kind: io::ErrorKind) -> io::Result<Output> {
+        Err(io::Error::new(kind, format!("mock {kind:?}")))
+    }
+
+    pub(crate) fn calls(&self) -> Vec<Vec<String>> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn next(&self, command: &std::process::Command) -> io::Result<Output> {
+        let mut line = vec![command.get_program().to_string_lossy().to_string()];
+        line.extend(
+            command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string()),
+        );
+        self.calls.lock().unwrap().push(line);
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| Self::failed(io::ErrorKind::Other))
+    }
+}
+
+#[cfg(test)]
+#[async_trait::async_trait]
+impl CommandRunner for MockCommandRunner {
+    async fn output(&self, command: Command, _limit: Duration) -> io::Result<Output> {
+        self.next(command.as_std())
+    }
+}
+
+/// Kills `child` and everything it spawned. On Windows agents are usually
+/// launched through `npx`/`cmd` shims, so killing only the direct child would
+/// leave the real `node` process running; `taskkill /T` walks the whole tree.
 pub(crate) async fn kill_child_process_tree(child: &mut Child) {
     #[cfg(windows)]
     {
         if let Some(pid) = child.id() {
-            let _ = tokio_command("taskkill")
-                .arg("/PID")
-                .arg(pid.to_string())
-                .arg("/T")
-                .arg("/F")
+            let killed_tree = tokio_command("taskkill")
+                .args(taskkill_tree_args(pid))
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .status()
-                .await;
+                .await
+                .map(|status| status.success())
+                .unwrap_or(false);
+            if killed_tree {
+                let _ = child.wait().await;
+                return;
+            }
         }
     }
 
     let _ = child.kill().await;
 }
 
+#[cfg(windows)]
+fn taskkill_tree_args(pid: u32) -> [String; 4] {
+    [
+        "/PID".to_string(),
+        pid.to_string(),
+        "/T".to_string(),
+        "/F".to_string(),
+    ]
+}
+
 #[cfg(target_os = "windows")]
 pub(crate) fn resolve_windows_executable(program: &str, path_env: Option<&str>) -> Option<PathBuf> {
     let trimmed = program.trim();
@@ -155,3 +410,151 @@ pub(crate) fn build_cmd_c_command(program: &Path, args: &[String]) -> Result<Str
     let inner = parts.join(" ");
     Ok(format!("\"{inner}\""))
 }
+
+#[cfg(all(test, windows))]
+mod tests {
+    use super::{kill_child_process_tree, taskkill_tree_args, tokio_command};
+    use std::process::Stdio;
+    use std::time::Duration;
+
+    async fn child_pids(parent: u32) -> Vec<u32> {
+        let output = tokio_command("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                &format!(
+                    "Get-CimInstance Win32_Process -Filter 'ParentProcessId={parent}' | ForEach-Object {{ $_.ProcessId }}"
+                ),
+            ])
+            .output()
+            .await
+            .expect("query child processes");
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .collect()
+    }
+
+    async fn is_running(pid: u32) -> bool {
+        let output = tokio_command("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+            .output()
+            .await
+            .expect("run tasklist");
+        String::from_utf8_lossy(&output.stdout).contains(&pid.to_string())
+    }
+
+    #[test]
+    fn taskkill_targets_whole_tree() {
+        assert_eq!(taskkill_tree_args(42), ["/PID", "42", "/T", "/F"]);
+    }
+
+    #[tokio::test]
+    async fn kill_child_process_tree_terminates_grandchildren() {
+        let mut child = tokio_command("cmd")
+            .args(["/C", "ping -n 60 127.0.0.1 > NUL"])
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("spawn cmd");
+        let parent = child.id().expect("cmd pid");
+
+        let mut grandchildren = Vec::new();
+        for _ in 0..50 {
+            grandchildren = child_pids(parent).await;
+            if !grandchildren.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(
+            !grandchildren.is_empty(),
+            "ping should be running under cmd"
+        );
+
+        kill_child_process_tree(&mut child).await;
+
+        assert!(!is_running(parent).await);
+        for pid in grandchildren {
+            assert!(!is_running(pid).await, "grandchild {pid} survived");
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod unix_tests {
+    use super::{
+        tokio_command, CommandRunner, StdioRequestRunner, SystemCommandRunner,
+        SystemStdioRequestRunner,
+    };
+    use serde_json::json;
+    use std::io;
+    use std::time::Duration;
+
+    fn sh(script: &str) -> tokio::process::Command {
+        let mut command = tokio_command("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[tokio::test]
+    async fn stdio_request_survives_a_server_that_floods_stderr() {
+        // Far more than a pipe buffer holds, written before the answer.
+        let command = sh(r#"head -c 300000 /dev/zero | tr '\0' x >&2
+read -r line
+echo '{"id":1,"result":{"ok":true}}'"#);
+        let response = SystemStdioRequestRunner
+            .request(command, json!({ "id": 1 }), Duration::from_secs(10))
+            .await
+            .expect("response despite stderr output");
+        assert_eq!(response["result"]["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn stdio_request_reports_the_stderr_tail_of_an_early_exit() {
+        let command = sh(r#"read -r line
+head -c 300000 /dev/zero | tr '\0' x >&2
+echo 'bad config' >&2"#);
+        let err = SystemStdioRequestRunner
+            .request(command, json!({ "id": 1 }), Duration::from_secs(10))
+            .await
+            .expect_err("server exits without answering");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let message = err.to_string();
+        assert!(message.ends_with("xbad config"), "{message}");
+        assert!(
+            message.len() < 2200,
+            "stderr is capped: {} bytes",
+            message.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn command_output_kills_a_command_that_times_out() {
+        let dir = std::env::temp_dir().join(format!("process-core-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("finished");
+        let command = sh(&format!("sleep 1 && touch '{}'", marker.display()));
+
+        let err = SystemCommandRunner
+            .output(command, Duration::from_millis(100))
+            .await
+            .expect_err("command outlives its limit");
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists(), "timed-out command kept running");
+
+        let mut command = sh("echo out; echo err >&2");
+        command
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        let output = SystemCommandRunner
+            .output(command, Duration::from_secs(5))
+            .await
+            .expect("command finishes");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
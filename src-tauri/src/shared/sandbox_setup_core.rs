@@ -1,53 +1,225 @@
 use serde_json::{json, Map, Value};
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::shared::process_core::{tokio_command, CommandRunner, SystemCommandRunner};
+use crate::types::AppSettings;
 
 const GONDOLIN_MCP_SERVER: &str = "gondolin";
 
-fn gondolin_command_spec() -> (String, Vec<String>) {
+/// The `npx` gondolin is launched with: `npx_path` (the `sandboxNpxPath`
+/// setting) when it names an existing file, otherwise `npx` from PATH. Pinning
+/// it keeps the managed server off whatever node an nvm shell put first.
+fn resolve_npx(npx_path: Option<&Path>) -> String {
+    npx_path
+        .filter(|path| !path.as_os_str().is_empty())
+        .filter(|path| {
+            let usable = path.is_file();
+            if !usable {
+                eprintln!(
+                    "sandbox setup: npx path {} not found, using npx from PATH",
+                    path.display()
+                );
+            }
+            usable
+        })
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "npx".to_string())
+}
+
+fn gondolin_command_spec(npx_path: Option<&Path>) -> (String, Vec<String>) {
     (
-        "npx".to_string(),
+        resolve_npx(npx_path),
         vec![
             "-y".to_string(),
-            "@earendil-works/gondolin".to_string(),
+            GONDOLIN_PACKAGE.to_string(),
             "mcp".to_string(),
         ],
     )
 }
 
-fn command_in_workspace(workspace_path: &Path, program: &str, args: &[&str]) -> bool {
-    Command::new(program)
+/// Steps reported while sandbox setup runs, in the order they happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SandboxSetupStep {
+    Checking,
+    Installing,
+    WritingSettings,
+    Done,
+}
+
+impl SandboxSetupStep {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Checking => "checking",
+            Self::Installing => "installing",
+            Self::WritingSettings => "writingSettings",
+            Self::Done => "done",
+        }
+    }
+}
+
+const MCP_ADD_ATTEMPTS: u32 = 3;
+const MCP_ADD_BACKOFF: Duration = Duration::from_secs(1);
+/// Upper bound for a single `mcp get`/`mcp add` call so a hung CLI fails the
+/// setup instead of stalling it.
+const MCP_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn workspace_command(workspace_path: &Path, program: &str, args: &[&str]) -> Command {
+    let mut command = tokio_command(program);
+    command
         .args(args)
         .current_dir(workspace_path)
-        .status()
-        .map(|status| status.success())
-        .unwrap_or(false)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    command
 }
 
-fn ensure_codex_mcp_server(workspace_path: &Path) {
-    if command_in_workspace(
-        workspace_path,
-        "codex",
-        &["mcp", "get", GONDOLIN_MCP_SERVER],
-    ) {
-        return;
+/// Runs `program` in the workspace. `Ok` tells whether it exited successfully
+/// without explaining itself; `Err` carries the reason it failed (stderr, a
+/// spawn error, or the timeout).
+async fn command_in_workspace(
+    runner: &dyn CommandRunner,
+    workspace_path: &Path,
+    program: &str,
+    args: &[&str],
+    limit: Duration,
+) -> Result<bool, String> {
+    let command = workspace_command(workspace_path, program, args);
+    let output = runner
+        .output(command, limit)
+        .await
+        .map_err(|err| match err.kind() {
+            ErrorKind::TimedOut => {
+                format!("`{program} {}` timed out after {limit:?}", args.join(" "))
+            }
+            _ => err.to_string(),
+        })?;
+    if output.status.success() {
+        return Ok(true);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if stderr.is_empty() {
+        Ok(false)
+    } else {
+        Err(stderr)
+    }
+}
+
+fn is_transient_npm_error(stderr: &str) -> bool {
+    let lower = stderr.to_ascii_lowercase();
+    [
+        "etimedout",
+        "econnreset",
+        "econnrefused",
+        "eai_again",
+        "enotfound",
+        "socket hang up",
+        "network",
+        "fetch failed",
+        "503",
+    ]
+    .iter()
+    .any(|needle| lower.contains(needle))
+}
+
+/// Runs a command, retrying with exponential backoff only while the failure
+/// looks like a flaky npm/network error.
+async fn run_with_retry(
+    runner: &dyn CommandRunner,
+    workspace_path: &Path,
+    program: &str,
+    args: &[&str],
+    attempts: u32,
+    backoff: Duration,
+) -> Result<(), String> {
+    let attempts = attempts.max(1);
+    let mut last_error = String::new();
+    for attempt in 0..attempts {
+        if attempt > 0 {
+            tokio::time::sleep(backoff.saturating_mul(2u32.saturating_pow(attempt - 1))).await;
+        }
+        match command_in_workspace(runner, workspace_path, program, args, MCP_COMMAND_TIMEOUT).await
+        {
+            Ok(true) => return Ok(()),
+            Ok(false) => last_error.clear(),
+            Err(err) => last_error = err,
+        }
+        if !is_transient_npm_error(&last_error) {
+            break;
+        }
+    }
+    let detail = if last_error.is_empty() {
+        "no error output".to_string()
+    } else {
+        last_error
+    };
+    Err(format!("`{program} {}` failed: {detail}", args.join(" ")))
+}
+
+/// Whether `program mcp get gondolin` reports the server as registered. A
+/// failed lookup falls through to registering it again, but a timeout is
+/// returned: a CLI that hangs on `get` would hang on `add` too.
+async fn gondolin_registered(
+    runner: &dyn CommandRunner,
+    workspace_path: &Path,
+    program: &str,
+) -> Result<bool, String> {
+    let args = ["mcp", "get", GONDOLIN_MCP_SERVER];
+    let command = workspace_command(workspace_path, program, &args);
+    match runner.output(command, MCP_COMMAND_TIMEOUT).await {
+        Ok(output) => Ok(output.status.success()),
+        Err(err) if err.kind() == ErrorKind::TimedOut => Err(format!(
+            "`{program} {}` timed out after {MCP_COMMAND_TIMEOUT:?}",
+            args.join(" ")
+        )),
+        Err(_) => Ok(false),
     }
-    let (command, args) = gondolin_command_spec();
+}
+
+async fn ensure_codex_mcp_server_with(
+    workspace_path: &Path,
+    runner: &dyn CommandRunner,
+    npx_path: Option<&Path>,
+    backoff: Duration,
+    report: &(dyn Fn(SandboxSetupStep) + Sync),
+) -> Result<(), String> {
+    report(SandboxSetupStep::Checking);
+    if gondolin_registered(runner, workspace_path, "codex").await? {
+        return Ok(());
+    }
+    report(SandboxSetupStep::Installing);
+    let (command, args) = gondolin_command_spec(npx_path);
     let mut cli_args: Vec<&str> = vec!["mcp", "add", GONDOLIN_MCP_SERVER, "--"];
     cli_args.push(command.as_str());
     cli_args.extend(args.iter().map(|value| value.as_str()));
-    let _ = command_in_workspace(workspace_path, "codex", &cli_args);
+    run_with_retry(
+        runner,
+        workspace_path,
+        "codex",
+        &cli_args,
+        MCP_ADD_ATTEMPTS,
+        backoff,
+    )
+    .await
+    .map_err(|err| format!("Failed to register {GONDOLIN_MCP_SERVER} MCP server: {err}"))
 }
 
-fn ensure_claude_mcp_server(workspace_path: &Path) {
-    if command_in_workspace(
-        workspace_path,
-        "claude",
-        &["mcp", "get", GONDOLIN_MCP_SERVER],
-    ) {
-        return;
+async fn ensure_claude_mcp_server_with(
+    workspace_path: &Path,
+    runner: &dyn CommandRunner,
+    npx_path: Option<&Path>,
+    backoff: Duration,
+    report: &(dyn Fn(SandboxSetupStep) + Sync),
+) -> Result<(), String> {
+    report(SandboxSetupStep::Checking);
+    if gondolin_registered(runner, workspace_path, "claude").await? {
+        return Ok(());
     }
-    let (command, args) = gondolin_command_spec();
+    report(SandboxSetupStep::Installing);
+    let (command, args) = gondolin_command_spec(npx_path);
     let mut cli_args: Vec<&str> = vec![
         "mcp",
         "add",
@@ -58,7 +230,16 @@ fn ensure_claude_mcp_server(workspace_path: &Path) {
     ];
     cli_args.push(command.as_str());
     cli_args.extend(args.iter().map(|value| value.as_str()));
-    let _ = command_in_workspace(workspace_path, "claude", &cli_args);
+    run_with_retry(
+        runner,
+        workspace_path,
+        "claude",
+        &cli_args,
+        MCP_ADD_ATTEMPTS,
+        backoff,
+    )
+    .await
+    .map_err(|err| format!("Failed to register {GONDOLIN_MCP_SERVER} MCP server: {err}"))
 }
 
 fn ensure_object(value: &mut Value) -> &mut Map<String, Value> {
@@ -70,8 +251,38 @@ fn ensure_object(value: &mut Value) -> &mut Map<String, Value> {
         .expect("value was initialized to an object")
 }
 
-fn upsert_gemini_mcp_config(root: &mut Value) {
-    let (command, args) = gondolin_command_spec();
+const GONDOLIN_PACKAGE: &str = "@earendil-works/gondolin";
+
+/// Whether a server entry launches gondolin, whatever name or launcher an
+/// older app version registered it under.
+pub(crate) fn is_gondolin_server(name: &str, server: &Value) -> bool {
+    if name.eq_ignore_ascii_case(GONDOLIN_MCP_SERVER) {
+        return true;
+    }
+    let command = server.get("command").and_then(Value::as_str).unwrap_or("");
+    let command_name = Path::new(command)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    let runs_package = server
+        .get("args")
+        .and_then(Value::as_array)
+        .is_some_and(|args| {
+            args.iter()
+                .filter_map(Value::as_str)
+                .any(|arg| arg.starts_with(GONDOLIN_PACKAGE))
+        });
+    command_name == GONDOLIN_MCP_SERVER || command.contains(GONDOLIN_PACKAGE) || runs_package
+}
+
+/// Drops every gondolin definition from `servers` and writes the canonical one.
+fn replace_gondolin_server(servers: &mut Map<String, Value>, server_payload: Value) {
+    servers.retain(|name, server| !is_gondolin_server(name, server));
+    servers.insert(GONDOLIN_MCP_SERVER.to_string(), server_payload);
+}
+
+fn upsert_gemini_mcp_config(root: &mut Value, npx_path: Option<&Path>) {
+    let (command, args) = gondolin_command_spec(npx_path);
     let server_payload = json!({
         "command": command,
         "args": args,
@@ -82,8 +293,7 @@ fn upsert_gemini_mcp_config(root: &mut Value) {
     let mcp_servers = root_object
         .entry("mcpServers".to_string())
         .or_insert_with(|| json!({}));
-    let mcp_servers_object = ensure_object(mcp_servers);
-    mcp_servers_object.insert(GONDOLIN_MCP_SERVER.to_string(), server_payload.clone());
+    replace_gondolin_server(ensure_object(mcp_servers), server_payload.clone());
 
     // Gemini configs vary across versions (`mcp.servers` vs `mcpServers`), so write both.
     let mcp = root_object
@@ -93,34 +303,112 @@ fn upsert_gemini_mcp_config(root: &mut Value) {
     let servers = mcp_object
         .entry("servers".to_string())
         .or_insert_with(|| json!({}));
-    let servers_object = ensure_object(servers);
-    servers_object.insert(GONDOLIN_MCP_SERVER.to_string(), server_payload);
+    replace_gondolin_server(ensure_object(servers), server_payload);
+}
+
+/// Removes `//` and `/* */` comments outside of string literals so hand-edited
+/// settings files can still be read.
+fn strip_json_comments(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    while let Some(ch) = chars.next() {
+        if in_string {
+            output.push(ch);
+            if ch == '\\' {
+                if let Some(escaped) = chars.next() {
+                    output.push(escaped);
+                }
+            } else if ch == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match (ch, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                output.push(ch);
+            }
+            ('/', Some('/')) => {
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = '\0';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    if next == '\n' {
+                        output.push('\n');
+                    }
+                    previous = next;
+                }
+            }
+            _ => output.push(ch),
+        }
+    }
+    output
+}
+
+pub(crate) fn parse_settings_json(contents: &str) -> Result<Value, serde_json::Error> {
+    serde_json::from_str::<Value>(contents).or_else(|strict_err| {
+        serde_json::from_str::<Value>(&strip_json_comments(contents)).map_err(|_| strict_err)
+    })
 }
 
-fn ensure_gemini_mcp_server(gemini_home: Option<PathBuf>) -> Result<(), String> {
+/// Whether `contents` only parses once JSONC comments are stripped.
+pub(crate) fn has_json_comments(contents: &str) -> bool {
+    serde_json::from_str::<Value>(contents).is_err() && parse_settings_json(contents).is_ok()
+}
+
+fn ensure_gemini_mcp_server(
+    gemini_home: Option<PathBuf>,
+    npx_path: Option<&Path>,
+    report: &dyn Fn(SandboxSetupStep),
+) -> Result<(), String> {
     let home = gemini_home
         .or_else(resolve_default_gemini_home_fallback)
         .ok_or_else(|| "Unable to resolve GEMINI_HOME for sandbox setup".to_string())?;
+    report(SandboxSetupStep::WritingSettings);
     let settings_path = home.join("settings.json");
     if let Some(parent) = settings_path.parent() {
         std::fs::create_dir_all(parent)
             .map_err(|err| format!("Failed to create {}: {err}", parent.display()))?;
     }
 
-    let mut value = if settings_path.exists() {
-        let contents = std::fs::read_to_string(&settings_path)
-            .map_err(|err| format!("Failed to read {}: {err}", settings_path.display()))?;
-        if contents.trim().is_empty() {
-            json!({})
-        } else {
-            serde_json::from_str::<Value>(&contents)
-                .map_err(|err| format!("Failed to parse {}: {err}", settings_path.display()))?
-        }
+    let contents = if settings_path.exists() {
+        std::fs::read_to_string(&settings_path)
+            .map_err(|err| format!("Failed to read {}: {err}", settings_path.display()))?
     } else {
+        String::new()
+    };
+    let original = if contents.trim().is_empty() {
         json!({})
+    } else {
+        parse_settings_json(&contents)
+            .map_err(|err| format!("Failed to parse {}: {err}", settings_path.display()))?
     };
 
-    upsert_gemini_mcp_config(&mut value);
+    let mut value = original.clone();
+    upsert_gemini_mcp_config(&mut value, npx_path);
+    if settings_path.exists() && value == original {
+        return Ok(());
+    }
+    // Writing the file back out would drop the user's comments.
+    if has_json_comments(&contents) {
+        let (command, args) = gondolin_command_spec(npx_path);
+        return Err(format!(
+            "{} has comments that rewriting it would drop; add {} under \"mcpServers\" as \"gondolin\" by hand, or remove the comments and retry",
+            settings_path.display(),
+            json!({ "command": command, "args": args })
+        ));
+    }
     let serialized = serde_json::to_string_pretty(&value)
         .map_err(|err| format!("Failed to serialize Gemini settings: {err}"))?;
     std::fs::write(&settings_path, format!("{serialized}\n"))
@@ -149,32 +437,99 @@ fn resolve_default_gemini_home_fallback() -> Option<PathBuf> {
     None
 }
 
-pub(crate) fn ensure_workspace_sandbox_setup(
+/// The app settings sandbox setup reads.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct SandboxSetupOptions<'a> {
+    /// `disableManagedSandbox`: setup does nothing at all.
+    pub(crate) disable_managed_sandbox: bool,
+    /// Pins the `npx` the gondolin server is registered with.
+    pub(crate) npx_path: Option<&'a Path>,
+}
+
+impl<'a> SandboxSetupOptions<'a> {
+    pub(crate) fn from_settings(settings: &'a AppSettings) -> Self {
+        Self {
+            disable_managed_sandbox: settings.disable_managed_sandbox,
+            npx_path: settings.sandbox_npx_path.as_deref().map(Path::new),
+        }
+    }
+}
+
+/// Runs the sandbox setup for `cli_type`. When `progress` is given it is
+/// called as each step starts so the UI can show what a slow setup is waiting on.
+pub(crate) async fn ensure_workspace_sandbox_setup(
+    options: SandboxSetupOptions<'_>,
+    cli_type: &str,
+    workspace_path: &Path,
+    cli_home: Option<PathBuf>,
+    progress: Option<&(dyn Fn(SandboxSetupStep) + Sync)>,
+) -> Result<(), String> {
+    ensure_workspace_sandbox_setup_with(
+        &SystemCommandRunner,
+        options,
+        cli_type,
+        workspace_path,
+        cli_home,
+        progress,
+    )
+    .await
+}
+
+pub(crate) async fn ensure_workspace_sandbox_setup_with(
+    runner: &dyn CommandRunner,
+    options: SandboxSetupOptions<'_>,
     cli_type: &str,
     workspace_path: &Path,
     cli_home: Option<PathBuf>,
+    progress: Option<&(dyn Fn(SandboxSetupStep) + Sync)>,
 ) -> Result<(), String> {
+    if options.disable_managed_sandbox {
+        return Ok(());
+    }
+    let npx_path = options.npx_path;
+    let report = |step: SandboxSetupStep| {
+        if let Some(progress) = progress {
+            progress(step);
+        }
+    };
     match cli_type {
         "claude" => {
-            ensure_claude_mcp_server(workspace_path);
-            Ok(())
+            ensure_claude_mcp_server_with(
+                workspace_path,
+                runner,
+                npx_path,
+                MCP_ADD_BACKOFF,
+                &report,
+            )
+            .await
         }
-        "gemini" => ensure_gemini_mcp_server(cli_home),
+        "gemini" => ensure_gemini_mcp_server(cli_home, npx_path, &report),
         "codex" => {
             // Keep Codex native sandboxing and also ensure Gondolin MCP is available.
-            ensure_codex_mcp_server(workspace_path);
-            Ok(())
+            ensure_codex_mcp_server_with(workspace_path, runner, npx_path, MCP_ADD_BACKOFF, &report)
+                .await
         }
-        _ => Ok(()),
-    }
+        _ => return Ok(()),
+    }?;
+    report(SandboxSetupStep::Done);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ensure_workspace_sandbox_setup, upsert_gemini_mcp_config};
+    use super::{
+        command_in_workspace, ensure_claude_mcp_server_with, ensure_codex_mcp_server_with,
+        ensure_workspace_sandbox_setup, ensure_workspace_sandbox_setup_with, parse_settings_json,
+        run_with_retry, strip_json_comments, upsert_gemini_mcp_config, SandboxSetupOptions,
+        SandboxSetupStep,
+    };
+    use crate::shared::process_core::{MockCommandRunner, SystemCommandRunner};
     use serde_json::json;
     use std::fs;
+    use std::io::ErrorKind;
+    use std::path::Path;
     use std::path::PathBuf;
+    use std::time::Duration;
     use std::time::{SystemTime, UNIX_EPOCH};
 
     fn temp_dir(prefix: &str) -> PathBuf {
@@ -190,7 +545,7 @@ mod tests {
     #[test]
     fn upsert_gemini_mcp_config_adds_both_supported_shapes() {
         let mut value = json!({});
-        upsert_gemini_mcp_config(&mut value);
+        upsert_gemini_mcp_config(&mut value, None);
 
         let server = value
             .get("mcpServers")
@@ -223,7 +578,7 @@ mod tests {
                 }
             }
         });
-        upsert_gemini_mcp_config(&mut value);
+        upsert_gemini_mcp_config(&mut value, None);
 
         assert_eq!(
             value.get("model").and_then(|item| item.as_str()),
@@ -240,12 +595,50 @@ mod tests {
     }
 
     #[test]
-    fn ensure_workspace_sandbox_setup_writes_gemini_settings_file() {
+    fn upsert_gemini_mcp_config_reconciles_divergent_gondolin_entries() {
+        let mut value = json!({
+            "mcpServers": {
+                "gondolin": { "command": "node", "args": ["old-gondolin.js"] },
+                "Gondolin": { "command": "npx", "args": ["@earendil-works/gondolin"] },
+                "sandbox": { "command": "npx", "args": ["-y", "@earendil-works/gondolin@0.1", "mcp"] },
+                "existing": { "command": "node", "args": ["example.js"] }
+            },
+            "mcp": {
+                "servers": {
+                    "gondolin-mcp": { "command": "/usr/local/bin/gondolin", "args": ["mcp"] }
+                }
+            }
+        });
+        upsert_gemini_mcp_config(&mut value, None);
+
+        let canonical = json!({
+            "command": "npx",
+            "args": ["-y", "@earendil-works/gondolin", "mcp"]
+        });
+        assert_eq!(
+            value["mcpServers"],
+            json!({
+                "gondolin": canonical,
+                "existing": { "command": "node", "args": ["example.js"] }
+            })
+        );
+        assert_eq!(value["mcp"]["servers"], json!({ "gondolin": canonical }));
+    }
+
+    #[tokio::test]
+    async fn ensure_workspace_sandbox_setup_writes_gemini_settings_file() {
         let workspace_dir = temp_dir("sandbox-workspace");
         let gemini_home = temp_dir("sandbox-gemini-home");
 
-        ensure_workspace_sandbox_setup("gemini", &workspace_dir, Some(gemini_home.clone()))
-            .expect("gemini sandbox setup should succeed");
+        ensure_workspace_sandbox_setup(
+            SandboxSetupOptions::default(),
+            "gemini",
+            &workspace_dir,
+            Some(gemini_home.clone()),
+            None,
+        )
+        .await
+        .expect("gemini sandbox setup should succeed");
 
         let settings_path = gemini_home.join("settings.json");
         let contents = fs::read_to_string(&settings_path).expect("settings.json should exist");
@@ -263,4 +656,374 @@ mod tests {
         let _ = fs::remove_dir_all(workspace_dir);
         let _ = fs::remove_dir_all(gemini_home);
     }
+
+    #[test]
+    fn strip_json_comments_keeps_comment_markers_inside_strings() {
+        let input = r#"{"url": "https://example.com/*x*/", // trailing
+  /* block
+     comment */ "path": "a//b"}"#;
+        let parsed: serde_json::Value =
+            serde_json::from_str(&strip_json_comments(input)).expect("stripped json parses");
+        assert_eq!(parsed["url"], "https://example.com/*x*/");
+        assert_eq!(parsed["path"], "a//b");
+        assert!(parse_settings_json("{ // nope").is_err());
+    }
+
+    #[tokio::test]
+    async fn ensure_workspace_sandbox_setup_leaves_commented_gemini_settings_alone() {
+        let workspace_dir = temp_dir("sandbox-workspace-comments");
+        let gemini_home = temp_dir("sandbox-gemini-home-comments");
+        let settings_path = gemini_home.join("settings.json");
+        let original = r#"{
+  // preferred model
+  "model": "gemini-2.5-pro",
+  /* servers added by hand */
+  "mcpServers": {
+    "existing": { "command": "node", "args": ["example.js"] }
+  }
+}
+"#;
+        fs::write(&settings_path, original).expect("settings.json should be written");
+
+        let error = ensure_workspace_sandbox_setup(
+            SandboxSetupOptions::default(),
+            "gemini",
+            &workspace_dir,
+            Some(gemini_home.clone()),
+            None,
+        )
+        .await
+        .expect_err("rewriting a commented settings.json should be refused");
+        assert!(error.contains("has comments"), "{error}");
+        assert!(error.contains("@earendil-works/gondolin"), "{error}");
+        assert_eq!(
+            fs::read_to_string(&settings_path).expect("settings.json should exist"),
+            original
+        );
+
+        let _ = fs::remove_dir_all(workspace_dir);
+        let _ = fs::remove_dir_all(gemini_home);
+    }
+
+    #[tokio::test]
+    async fn ensure_workspace_sandbox_setup_accepts_commented_settings_already_set_up() {
+        let workspace_dir = temp_dir("sandbox-workspace-comments-done");
+        let gemini_home = temp_dir("sandbox-gemini-home-comments-done");
+        let settings_path = gemini_home.join("settings.json");
+        let original = r#"{
+  // added for the sandbox
+  "mcpServers": {
+    "gondolin": { "command": "npx", "args": ["-y", "@earendil-works/gondolin", "mcp"] }
+  },
+  "mcp": {
+    "servers": {
+      "gondolin": { "command": "npx", "args": ["-y", "@earendil-works/gondolin", "mcp"] }
+    }
+  }
+}
+"#;
+        fs::write(&settings_path, original).expect("settings.json should be written");
+
+        ensure_workspace_sandbox_setup(
+            SandboxSetupOptions::default(),
+            "gemini",
+            &workspace_dir,
+            Some(gemini_home.clone()),
+            None,
+        )
+        .await
+        .expect("nothing needs rewriting");
+        assert_eq!(
+            fs::read_to_string(&settings_path).expect("settings.json should exist"),
+            original
+        );
+
+        let _ = fs::remove_dir_all(workspace_dir);
+        let _ = fs::remove_dir_all(gemini_home);
+    }
+
+    #[tokio::test]
+    async fn disabled_sandbox_setup_changes_nothing() {
+        let workspace_dir = temp_dir("sandbox-disabled-workspace");
+        let gemini_home = temp_dir("sandbox-disabled-gemini-home");
+        let steps = std::sync::Mutex::new(Vec::new());
+        let record = |step: SandboxSetupStep| steps.lock().unwrap().push(step);
+
+        for cli_type in ["codex", "claude", "gemini", "cursor"] {
+            let runner = MockCommandRunner::new(Vec::new());
+            ensure_workspace_sandbox_setup_with(
+                &runner,
+                SandboxSetupOptions {
+                    disable_managed_sandbox: true,
+                    ..SandboxSetupOptions::default()
+                },
+                cli_type,
+                &workspace_dir,
+                Some(gemini_home.clone()),
+                Some(&record),
+            )
+            .await
+            .expect("disabled setup is a no-op");
+            assert!(runner.calls().is_empty(), "{cli_type} ran a command");
+        }
+
+        assert!(steps.lock().unwrap().is_empty());
+        assert_eq!(fs::read_dir(&gemini_home).unwrap().count(), 0);
+        assert_eq!(fs::read_dir(&workspace_dir).unwrap().count(), 0);
+
+        let _ = fs::remove_dir_all(workspace_dir);
+        let _ = fs::remove_dir_all(gemini_home);
+    }
+
+    fn flaky_runner(failures: usize, stderr: &str) -> MockCommandRunner {
+        let mut responses: Vec<_> = (0..failures)
+            .map(|_| MockCommandRunner::exited(false, "", stderr))
+            .collect();
+        responses.push(MockCommandRunner::exited(true, "", ""));
+        MockCommandRunner::new(responses)
+    }
+
+    #[tokio::test]
+    async fn run_with_retry_recovers_after_transient_failures() {
+        let runner = flaky_runner(2, "npm ERR! code ETIMEDOUT");
+        run_with_retry(
+            &runner,
+            Path::new("."),
+            "claude",
+            &["mcp", "add"],
+            3,
+            Duration::ZERO,
+        )
+        .await
+        .expect("third attempt should succeed");
+        assert_eq!(runner.calls().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn run_with_retry_stops_on_non_transient_failure() {
+        let runner = flaky_runner(5, "error: unknown option --scope");
+        let err = run_with_retry(
+            &runner,
+            Path::new("."),
+            "claude",
+            &["mcp", "add"],
+            3,
+            Duration::ZERO,
+        )
+        .await
+        .expect_err("non-transient failure should not be retried");
+        assert_eq!(runner.calls().len(), 1);
+        assert!(err.contains("unknown option --scope"));
+        assert!(err.contains("claude mcp add"));
+    }
+
+    #[tokio::test]
+    async fn ensure_mcp_server_skips_add_when_already_registered() {
+        let runner = flaky_runner(0, "");
+        ensure_claude_mcp_server_with(Path::new("."), &runner, None, Duration::ZERO, &|_| {})
+            .await
+            .expect("registered server should be left alone");
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn ensure_mcp_server_surfaces_failed_add() {
+        let runner = flaky_runner(5, "error: permission denied");
+        let err =
+            ensure_codex_mcp_server_with(Path::new("."), &runner, None, Duration::ZERO, &|_| {})
+                .await
+                .expect_err("failed add should be reported");
+        assert!(err.contains("gondolin"));
+        assert!(err.contains("permission denied"));
+
+        let runner = flaky_runner(5, "error: permission denied");
+        let err =
+            ensure_claude_mcp_server_with(Path::new("."), &runner, None, Duration::ZERO, &|_| {})
+                .await
+                .expect_err("failed add should be reported");
+        assert!(err.contains("claude mcp add --scope project gondolin"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_in_workspace_times_out_hung_commands() {
+        let started = std::time::Instant::now();
+        let err = command_in_workspace(
+            &SystemCommandRunner,
+            Path::new("."),
+            "sleep",
+            &["5"],
+            Duration::from_millis(100),
+        )
+        .await
+        .expect_err("slow command should time out");
+        assert!(err.contains("`sleep 5` timed out"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let ok = command_in_workspace(
+            &SystemCommandRunner,
+            Path::new("."),
+            "true",
+            &[],
+            Duration::from_secs(5),
+        )
+        .await;
+        assert_eq!(ok, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn sandbox_setup_runs_through_command_runner() {
+        let runner = MockCommandRunner::new(vec![MockCommandRunner::exited(true, "", "")]);
+        ensure_workspace_sandbox_setup_with(
+            &runner,
+            SandboxSetupOptions::default(),
+            "codex",
+            Path::new("."),
+            None,
+            None,
+        )
+        .await
+        .expect("registered server needs no add");
+        assert_eq!(
+            runner.calls(),
+            vec![vec!["codex", "mcp", "get", "gondolin"]]
+        );
+
+        let runner = MockCommandRunner::new(vec![
+            MockCommandRunner::failed(ErrorKind::NotFound),
+            MockCommandRunner::failed(ErrorKind::NotFound),
+        ]);
+        let err = ensure_workspace_sandbox_setup_with(
+            &runner,
+            SandboxSetupOptions::default(),
+            "claude",
+            Path::new("."),
+            None,
+            None,
+        )
+        .await
+        .expect_err("missing CLI should fail setup");
+        assert!(err.contains("Failed to register gondolin MCP server"));
+        assert!(err.contains("NotFound"));
+        assert_eq!(runner.calls().len(), 2);
+        assert_eq!(runner.calls()[1][..3], ["claude", "mcp", "add"]);
+    }
+
+    #[tokio::test]
+    async fn sandbox_setup_stops_when_the_registration_check_times_out() {
+        let runner = MockCommandRunner::new(vec![MockCommandRunner::failed(ErrorKind::TimedOut)]);
+        let err = ensure_workspace_sandbox_setup_with(
+            &runner,
+            SandboxSetupOptions::default(),
+            "codex",
+            Path::new("."),
+            None,
+            None,
+        )
+        .await
+        .expect_err("a hung `mcp get` should fail setup");
+        assert!(err.contains("`codex mcp get gondolin` timed out"), "{err}");
+        assert_eq!(
+            runner.calls(),
+            vec![vec!["codex", "mcp", "get", "gondolin"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn sandbox_setup_registers_gondolin_with_configured_npx() {
+        let node_bin = temp_dir("sandbox-npx");
+        let npx = node_bin.join("npx");
+        fs::write(&npx, "").expect("fake npx should be written");
+
+        let runner = MockCommandRunner::new(vec![
+            MockCommandRunner::exited(false, "", "No MCP server named gondolin"),
+            MockCommandRunner::exited(true, "", ""),
+        ]);
+        ensure_workspace_sandbox_setup_with(
+            &runner,
+            SandboxSetupOptions {
+                npx_path: Some(&npx),
+                ..SandboxSetupOptions::default()
+            },
+            "codex",
+            Path::new("."),
+            None,
+            None,
+        )
+        .await
+        .expect("setup should succeed");
+        let npx_arg = npx.to_string_lossy().to_string();
+        assert_eq!(
+            runner.calls()[1],
+            vec![
+                "codex",
+                "mcp",
+                "add",
+                "gondolin",
+                "--",
+                npx_arg.as_str(),
+                "-y",
+                "@earendil-works/gondolin",
+                "mcp"
+            ]
+        );
+
+        let mut value = json!({});
+        upsert_gemini_mcp_config(&mut value, Some(&npx));
+        assert_eq!(value["mcpServers"]["gondolin"]["command"], npx_arg);
+
+        // A path that doesn't exist falls back to PATH discovery.
+        upsert_gemini_mcp_config(&mut value, Some(&node_bin.join("missing")));
+        assert_eq!(value["mcpServers"]["gondolin"]["command"], "npx");
+
+        let _ = fs::remove_dir_all(node_bin);
+    }
+
+    #[tokio::test]
+    async fn sandbox_setup_reports_progress_steps_in_order() {
+        let steps = std::sync::Mutex::new(Vec::new());
+        let record = |step: SandboxSetupStep| steps.lock().unwrap().push(step);
+
+        let runner = MockCommandRunner::new(vec![
+            MockCommandRunner::exited(false, "", "No MCP server named gondolin"),
+            MockCommandRunner::exited(true, "", ""),
+        ]);
+        ensure_workspace_sandbox_setup_with(
+            &runner,
+            SandboxSetupOptions::default(),
+            "claude",
+            Path::new("."),
+            None,
+            Some(&record),
+        )
+        .await
+        .expect("setup should succeed");
+        assert_eq!(
+            *steps.lock().unwrap(),
+            vec![
+                SandboxSetupStep::Checking,
+                SandboxSetupStep::Installing,
+                SandboxSetupStep::Done
+            ]
+        );
+
+        steps.lock().unwrap().clear();
+        let gemini_home = temp_dir("sandbox-progress-gemini");
+        let runner = MockCommandRunner::new(Vec::new());
+        ensure_workspace_sandbox_setup_with(
+            &runner,
+            SandboxSetupOptions::default(),
+            "gemini",
+            Path::new("."),
+            Some(gemini_home.clone()),
+            Some(&record),
+        )
+        .await
+        .expect("gemini setup should succeed");
+        assert_eq!(
+            *steps.lock().unwrap(),
+            vec![SandboxSetupStep::WritingSettings, SandboxSetupStep::Done]
+        );
+        let _ = fs::remove_dir_all(gemini_home);
+    }
 }
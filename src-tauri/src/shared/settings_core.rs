@@ -1,8 +1,16 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use serde_json::{json, Map, Value};
 use tokio::sync::Mutex;
 
+use crate::backend::adapter_base;
+use crate::backend::app_server::WorkspaceSession;
+use crate::codex::args::validate_adapter_args;
 use crate::codex::config as codex_config;
+use crate::codex::home::resolve_default_cli_home;
+use crate::shared::sandbox_setup_core::{has_json_comments, parse_settings_json};
 use crate::storage::write_settings;
 use crate::types::AppSettings;
 
@@ -47,6 +55,11 @@ pub(crate) async fn update_app_settings_core(
     app_settings: &Mutex<AppSettings>,
     settings_path: &PathBuf,
 ) -> Result<AppSettings, String> {
+    validate_adapter_args(
+        settings.gemini_args.as_deref(),
+        settings.claude_args.as_deref(),
+        settings.cursor_args.as_deref(),
+    )?;
     let _ = codex_config::write_collab_enabled(settings.experimental_collab_enabled);
     let _ = codex_config::write_collaboration_modes_enabled(
         settings.collaboration_modes_enabled,
@@ -70,3 +83,396 @@ pub(crate) fn get_codex_config_path_core() -> Result<String, String> {
                 .ok_or_else(|| "Unable to resolve CODEX_HOME".to_string())
         })
 }
+
+pub(crate) fn get_cli_home_path_core(cli_type: &str) -> Result<String, String> {
+    let path = resolve_default_cli_home(cli_type)?;
+    path.to_str()
+        .map(|value| value.to_string())
+        .ok_or_else(|| format!("{cli_type} home path is not valid UTF-8"))
+}
+
+fn gemini_settings_path() -> Result<PathBuf, String> {
+    Ok(resolve_default_cli_home("gemini")?.join("settings.json"))
+}
+
+fn validate_gemini_setting_key(key: &str) -> Result<&str, String> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("Gemini setting key is required.".to_string());
+    }
+    Ok(key)
+}
+
+/// Reads the top-level object of a Gemini `settings.json`. A missing or empty
+/// file reads as no settings.
+fn read_gemini_settings_object(path: &Path) -> Result<Map<String, Value>, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Map::new()),
+        Err(err) => return Err(format!("Failed to read {}: {err}", path.display())),
+    };
+    if contents.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match parse_settings_json(&contents)
+        .map_err(|err| format!("Failed to parse {}: {err}", path.display()))?
+    {
+        Value::Object(settings) => Ok(settings),
+        _ => Err(format!("{} is not a JSON object", path.display())),
+    }
+}
+
+fn read_gemini_setting_at(path: &Path, key: &str) -> Result<Value, String> {
+    let key = validate_gemini_setting_key(key)?;
+    let mut settings = read_gemini_settings_object(path)?;
+    Ok(settings.remove(key).unwrap_or(Value::Null))
+}
+
+fn non_empty_string(value: Option<&Value>) -> Option<String> {
+    value
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// Default model and reasoning effort from Gemini settings. The model is
+/// either a plain string or `model.name`; the effort is
+/// `model.reasoningEffort` or a top-level `reasoningEffort`.
+fn parse_gemini_model_defaults(settings: &Map<String, Value>) -> (Option<String>, Option<String>) {
+    let model_settings = settings.get("model");
+    let model = non_empty_string(model_settings)
+        .or_else(|| non_empty_string(model_settings.and_then(|model| model.get("name"))));
+    let effort = non_empty_string(model_settings.and_then(|model| model.get("reasoningEffort")))
+        .or_else(|| non_empty_string(settings.get("reasoningEffort")));
+    (model, effort)
+}
+
+/// Reads the default model and effort from `settings.json` in `gemini_home`.
+pub(crate) fn read_gemini_model_defaults(
+    gemini_home: &Path,
+) -> Result<(Option<String>, Option<String>), String> {
+    let settings = read_gemini_settings_object(&gemini_home.join("settings.json"))?;
+    Ok(parse_gemini_model_defaults(&settings))
+}
+
+/// Replaces one top-level key, leaving every other key as it was on disk.
+/// A file with comments is left alone, since rewriting it would drop them.
+fn write_gemini_setting_at(path: &Path, key: &str, value: Value) -> Result<(), String> {
+    let key = validate_gemini_setting_key(key)?;
+    if let Ok(contents) = std::fs::read_to_string(path) {
+        if has_json_comments(&contents) {
+            return Err(format!(
+                "{} has comments that rewriting it would drop; edit it by hand instead",
+                path.display()
+            ));
+        }
+    }
+    let mut settings = read_gemini_settings_object(path)?;
+    settings.insert(key.to_string(), value);
+    let serialized = serde_json::to_string_pretty(&Value::Object(settings))
+        .map_err(|err| format!("Failed to serialize Gemini settings: {err}"))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create {}: {err}", parent.display()))?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, format!("{serialized}\n"))
+        .map_err(|err| format!("Failed to write {}: {err}", temp_path.display()))?;
+    std::fs::rename(&temp_path, path).map_err(|err| {
+        let _ = std::fs::remove_file(&temp_path);
+        format!("Failed to write {}: {err}", path.display())
+    })
+}
+
+/// Reads one top-level key from the Gemini `settings.json`; `null` when unset.
+pub(crate) fn get_gemini_setting_core(key: &str) -> Result<Value, String> {
+    read_gemini_setting_at(&gemini_settings_path()?, key)
+}
+
+/// Sets one top-level key in the Gemini `settings.json`.
+pub(crate) fn set_gemini_setting_core(key: &str, value: Value) -> Result<(), String> {
+    write_gemini_setting_at(&gemini_settings_path()?, key, value)
+}
+
+/// Points adapter thread stores at `dir` (or back at the default when `None`)
+/// after copying the existing stores there. Refused while an adapter session
+/// is connected, since it would keep writing to the old location.
+pub(crate) async fn set_thread_store_dir_core(
+    dir: Option<String>,
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    app_settings: &Mutex<AppSettings>,
+    settings_path: &PathBuf,
+) -> Result<Value, String> {
+    let dir = dir
+        .map(|dir| dir.trim().to_string())
+        .filter(|dir| !dir.is_empty());
+    if let Some(dir) = dir.as_deref() {
+        if !Path::new(dir).is_absolute() {
+            return Err(format!(
+                "Thread store directory must be an absolute path: {dir}"
+            ));
+        }
+    }
+    // Held until the new location is saved so no session connects meanwhile.
+    let sessions = sessions.lock().await;
+    let mut connected = sessions
+        .iter()
+        .filter(|(_, session)| session.cli_type() != "codex")
+        .map(|(id, _)| id.as_str())
+        .collect::<Vec<_>>();
+    if !connected.is_empty() {
+        connected.sort_unstable();
+        return Err(format!(
+            "Disconnect these workspaces before moving the thread store: {}",
+            connected.join(", ")
+        ));
+    }
+    let mut settings = app_settings.lock().await.clone();
+    let from = adapter_base::thread_store_dir(settings.thread_store_dir.as_deref().map(Path::new));
+    let to = adapter_base::thread_store_dir(dir.as_deref().map(Path::new));
+    let migrated = if from == to {
+        adapter_base::ensure_dir_writable(&to)?;
+        0
+    } else {
+        adapter_base::migrate_thread_stores(&from, &to)?
+    };
+    settings.thread_store_dir = dir;
+    write_settings(settings_path, &settings)?;
+    *app_settings.lock().await = settings;
+    Ok(json!({
+        "path": to.display().to_string(),
+        "migratedCount": migrated,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_gemini_model_defaults, read_gemini_setting_at, set_thread_store_dir_core,
+        update_app_settings_core, write_gemini_setting_at,
+    };
+    use crate::backend::app_server::WorkspaceSession;
+    use crate::backend::test_support::{test_workspace_entry, TestAdapter};
+    use crate::shared::sandbox_setup_core::parse_settings_json;
+    use crate::types::AppSettings;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    fn temp_settings_path() -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("gemini-settings-{}", uuid::Uuid::new_v4()))
+            .join("settings.json")
+    }
+
+    #[tokio::test]
+    async fn update_app_settings_rejects_adapter_protocol_flags() {
+        let settings_path = temp_settings_path();
+        let app_settings = Mutex::new(AppSettings::default());
+        let mut settings = AppSettings::default();
+        settings.gemini_args = Some("--output-format json".to_string());
+
+        let err = update_app_settings_core(settings, &app_settings, &settings_path)
+            .await
+            .expect_err("reserved flag");
+        assert!(err.contains("--output-format"), "{err}");
+        assert!(!settings_path.exists());
+        assert!(app_settings.lock().await.gemini_args.is_none());
+    }
+
+    fn model_defaults(contents: &str) -> (Option<String>, Option<String>) {
+        match parse_settings_json(contents).unwrap() {
+            Value::Object(settings) => parse_gemini_model_defaults(&settings),
+            other => panic!("not an object: {other}"),
+        }
+    }
+
+    #[test]
+    fn parses_gemini_effort_when_present() {
+        let nested = "{\n  // pinned for this machine\n  \"model\": {\n    \"name\": \"gemini-2.5-pro\",\n    \"reasoningEffort\": \"high\"\n  }\n}\n";
+        assert_eq!(
+            model_defaults(nested),
+            (Some("gemini-2.5-pro".to_string()), Some("high".to_string()))
+        );
+        let top_level = r#"{ "model": "gemini-2.5-flash", "reasoningEffort": " low " }"#;
+        let (model, effort) = model_defaults(top_level);
+        assert_eq!(model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(effort.as_deref(), Some("low"));
+    }
+
+    #[test]
+    fn gemini_effort_is_none_when_absent_or_blank() {
+        assert_eq!(
+            model_defaults(r#"{ "model": { "name": "gemini-2.5-pro" } }"#),
+            (Some("gemini-2.5-pro".to_string()), None)
+        );
+        assert_eq!(
+            model_defaults(r#"{ "model": { "reasoningEffort": "" }, "theme": "dark" }"#),
+            (None, None)
+        );
+        assert_eq!(model_defaults("{}"), (None, None));
+    }
+
+    #[test]
+    fn reads_present_and_absent_gemini_settings() {
+        let path = temp_settings_path();
+        assert_eq!(read_gemini_setting_at(&path, "model"), Ok(Value::Null));
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            "{\n  // picked in the CLI\n  \"model\": \"gemini-2.5-pro\"\n}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_gemini_setting_at(&path, " model "),
+            Ok(json!("gemini-2.5-pro"))
+        );
+        assert_eq!(read_gemini_setting_at(&path, "theme"), Ok(Value::Null));
+        assert!(read_gemini_setting_at(&path, "  ").is_err());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn setting_one_gemini_key_preserves_siblings() {
+        let path = temp_settings_path();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            r#"{"model":"gemini-2.5-pro","mcpServers":{"gondolin":{"command":"npx"}}}"#,
+        )
+        .unwrap();
+
+        write_gemini_setting_at(&path, "theme", json!("Dracula")).unwrap();
+        write_gemini_setting_at(&path, "model", json!("gemini-2.5-flash")).unwrap();
+
+        let saved: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            saved,
+            json!({
+                "model": "gemini-2.5-flash",
+                "theme": "Dracula",
+                "mcpServers": { "gondolin": { "command": "npx" } }
+            })
+        );
+        assert!(write_gemini_setting_at(&path, "", json!(true)).is_err());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn setting_a_gemini_key_refuses_to_drop_comments() {
+        let path = temp_settings_path();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let original = "{\n  // picked by hand\n  \"model\": \"gemini-2.5-pro\"\n}\n";
+        std::fs::write(&path, original).unwrap();
+
+        let err = write_gemini_setting_at(&path, "theme", json!("Dracula")).unwrap_err();
+        assert!(err.contains("comments"), "{err}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+        assert_eq!(
+            read_gemini_setting_at(&path, "model"),
+            Ok(json!("gemini-2.5-pro"))
+        );
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn set_thread_store_dir_migrates_stores_and_persists_setting() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("thread-store-dir-{}", uuid::Uuid::new_v4()));
+        let old_dir = temp_dir.join("old");
+        let new_dir = temp_dir.join("new");
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::write(old_dir.join("ws-1.json"), r#"{"threads":{}}"#).unwrap();
+        let settings_path = temp_dir.join("settings.json");
+        let app_settings = Mutex::new(AppSettings {
+            thread_store_dir: Some(old_dir.to_string_lossy().to_string()),
+            ..AppSettings::default()
+        });
+
+        let sessions = Mutex::new(HashMap::new());
+
+        let result = set_thread_store_dir_core(
+            Some(new_dir.to_string_lossy().to_string()),
+            &sessions,
+            &app_settings,
+            &settings_path,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result["migratedCount"], 1);
+        assert!(new_dir.join("ws-1.json").exists());
+        let expected = Some(new_dir.to_string_lossy().to_string());
+        assert_eq!(app_settings.lock().await.thread_store_dir, expected);
+        let saved: Value =
+            serde_json::from_str(&std::fs::read_to_string(&settings_path).unwrap()).unwrap();
+        assert_eq!(saved["threadStoreDir"], json!(expected));
+
+        let blocker = temp_dir.join("blocker");
+        std::fs::write(&blocker, "").unwrap();
+        let unwritable = blocker.join("threads").to_string_lossy().to_string();
+        for dir in [unwritable, "relative".to_string()] {
+            let result =
+                set_thread_store_dir_core(Some(dir), &sessions, &app_settings, &settings_path)
+                    .await;
+            assert!(result.is_err());
+        }
+        assert_eq!(app_settings.lock().await.thread_store_dir, expected);
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn set_thread_store_dir_refuses_while_adapter_sessions_are_connected() {
+        let temp_dir =
+            std::env::temp_dir().join(format!("thread-store-dir-{}", uuid::Uuid::new_v4()));
+        let settings_path = temp_dir.join("settings.json");
+        let app_settings = Mutex::new(AppSettings::default());
+        let session = |id: &str, cli_type: &'static str| {
+            Arc::new(WorkspaceSession::new_with_adapter(
+                test_workspace_entry(id, &temp_dir.to_string_lossy()),
+                Box::new(TestAdapter::new(cli_type)),
+                Arc::new(Mutex::new(HashMap::new())),
+                Arc::default(),
+            ))
+        };
+        let sessions = Mutex::new(HashMap::from([
+            ("claude-ws".to_string(), session("claude-ws", "claude")),
+            ("codex-ws".to_string(), session("codex-ws", "codex")),
+        ]));
+        let new_dir = temp_dir.join("threads").to_string_lossy().to_string();
+
+        let err = set_thread_store_dir_core(
+            Some(new_dir.clone()),
+            &sessions,
+            &app_settings,
+            &settings_path,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            err.contains("claude-ws") && !err.contains("codex-ws"),
+            "{err}"
+        );
+        assert!(app_settings.lock().await.thread_store_dir.is_none());
+        assert!(!settings_path.exists());
+
+        sessions.lock().await.remove("claude-ws");
+        set_thread_store_dir_core(
+            Some(new_dir.clone()),
+            &sessions,
+            &app_settings,
+            &settings_path,
+        )
+        .await
+        .expect("only codex sessions are connected");
+        assert_eq!(app_settings.lock().await.thread_store_dir, Some(new_dir));
+
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+
+/// A `major.minor.patch` CLI version. Pre-release and build suffixes are
+/// dropped, so `1.2.0-beta.1` compares equal to `1.2.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Version {
+    pub(crate) major: u64,
+    pub(crate) minor: u64,
+    pub(crate) patch: u64,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Finds the first dotted version in `--version` output such as
+/// `codex-cli 0.46.0` or `1.0.33 (Claude Code)`. A missing minor or patch
+/// counts as 0.
+pub(crate) fn parse_version(text: &str) -> Option<Version> {
+    text.split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',')
+        .map(|token| token.trim_start_matches(['v', 'V']))
+        .find_map(|token| {
+            let core = token.split(['-', '+']).next()?;
+            let mut parts = core.split('.');
+            let major = parts.next()?.parse().ok()?;
+            let minor = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+            let patch = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+            if parts.next().is_some() || !core.contains('.') {
+                return None;
+            }
+            Some(Version {
+                major,
+                minor,
+                patch,
+            })
+        })
+}
+
+/// Oldest release of each adapter CLI that has the flags its adapter passes:
+/// Claude's `--output-format stream-json --verbose` with
+/// `--append-system-prompt`, and Gemini's `--output-format stream-json` with
+/// `--approval-mode`. CLIs without an entry are not gated.
+const MIN_CLI_VERSIONS: &[(&str, &str)] = &[("claude", "1.0.0"), ("gemini", "0.11.0")];
+
+pub(crate) fn min_cli_version(cli_type: &str) -> Option<Version> {
+    MIN_CLI_VERSIONS
+        .iter()
+        .find(|(name, _)| *name == cli_type)
+        .and_then(|(_, minimum)| parse_version(minimum))
+}
+
+/// Fails when `version` (raw `--version` output) is older than the minimum
+/// for `cli_type`. Output without a recognizable version is let through.
+pub(crate) fn ensure_min_cli_version(
+    cli_type: &str,
+    cli_name: &str,
+    version: Option<&str>,
+) -> Result<(), String> {
+    let (Some(minimum), Some(found)) = (min_cli_version(cli_type), version.and_then(parse_version))
+    else {
+        return Ok(());
+    };
+    if found >= minimum {
+        return Ok(());
+    }
+    Err(format!(
+        "{cli_name} CLI {found} is too old; Agent Monitor needs {minimum} or newer. Update {cli_name}, or turn on \"Skip CLI version check\" in Settings to try it anyway."
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ensure_min_cli_version, parse_version, Version};
+
+    #[test]
+    fn parses_versions_out_of_cli_output() {
+        let version = |major, minor, patch| {
+            Some(Version {
+                major,
+                minor,
+                patch,
+            })
+        };
+        assert_eq!(parse_version("codex-cli 0.46.0\n"), version(0, 46, 0));
+        assert_eq!(parse_version("1.0.33 (Claude Code)"), version(1, 0, 33));
+        assert_eq!(parse_version("v20.11"), version(20, 11, 0));
+        assert_eq!(parse_version("gemini 0.12.0-preview.3"), version(0, 12, 0));
+        assert_eq!(parse_version("build 42"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn compares_numerically_rather_than_lexically() {
+        let version = |text| parse_version(text).unwrap();
+        assert!(version("0.9.0") < version("0.10.0"));
+        assert_eq!(version("1.2.0-beta"), version("1.2.0"));
+        assert!(version("2.0") > version("1.99.99"));
+    }
+
+    #[test]
+    fn gates_only_known_too_old_versions() {
+        let err = ensure_min_cli_version("claude", "Claude", Some("0.2.9 (Claude Code)"))
+            .expect_err("old Claude should be refused");
+        assert!(err.contains("Claude CLI 0.2.9 is too old"), "{err}");
+        assert!(err.contains("1.0.0"), "{err}");
+        assert!(ensure_min_cli_version("claude", "Claude", Some("1.0.33 (Claude Code)")).is_ok());
+        assert!(ensure_min_cli_version("gemini", "Gemini", Some("0.10.1")).is_err());
+        assert!(ensure_min_cli_version("gemini", "Gemini", Some("0.11.0")).is_ok());
+        assert!(ensure_min_cli_version("gemini", "Gemini", Some("dev build")).is_ok());
+        assert!(ensure_min_cli_version("gemini", "Gemini", None).is_ok());
+        assert!(ensure_min_cli_version("cursor", "Cursor", Some("0.0.1")).is_ok());
+    }
+}
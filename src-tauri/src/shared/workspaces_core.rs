@@ -5,13 +5,17 @@ use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
-use crate::backend::app_server::{CliSpawnConfig, WorkspaceSession};
-use crate::codex::args::resolve_workspace_codex_args;
-use crate::codex::home::resolve_workspace_codex_home;
+use crate::backend::app_server::{
+    build_spawn_command, describe_spawn_command, CliSpawnConfig, WorkspaceSession,
+};
+use crate::codex::args::{resolve_workspace_codex_args, validate_adapter_args};
+use crate::codex::home::{
+    normalize_codex_home_with_base, resolve_default_gemini_home, resolve_workspace_codex_home,
+};
 use crate::storage::write_workspaces;
 use crate::types::{
-    AppSettings, WorkspaceEntry, WorkspaceInfo, WorkspaceKind, WorkspaceSettings, WorktreeInfo,
-    WorktreeSetupStatus,
+    AppSettings, WorkspaceEntry, WorkspaceInfo, WorkspaceKind, WorkspacePathValidation,
+    WorkspaceSettings, WorktreeInfo, WorktreeSetupStatus,
 };
 use uuid::Uuid;
 
@@ -37,8 +41,8 @@ pub(crate) fn resolve_default_cli_bin(settings: &AppSettings) -> Option<String>
     }
 }
 
-fn resolve_default_cli_args(settings: &AppSettings) -> Option<String> {
-    match settings.cli_type.as_str() {
+fn resolve_default_cli_args(settings: &AppSettings, cli_type: &str) -> Option<String> {
+    match cli_type {
         "gemini" => settings.gemini_args.clone(),
         "cursor" => settings.cursor_args.clone(),
         "claude" => settings.claude_args.clone(),
@@ -142,9 +146,29 @@ pub(crate) fn resolve_workspace_cli_args(
     app_settings: Option<&AppSettings>,
 ) -> Option<String> {
     let cli_type = app_settings.map(|settings| settings.cli_type.as_str()).unwrap_or("codex");
-    if cli_type == "codex" {
-        return resolve_workspace_codex_args(entry, parent_entry, app_settings);
+    match cli_type {
+        "codex" => resolve_workspace_codex_args(entry, parent_entry, app_settings),
+        "cursor" => resolve_workspace_cursor_args(entry, parent_entry, app_settings),
+        _ => resolve_workspace_args_for(cli_type, entry, parent_entry, app_settings),
     }
+}
+
+/// Cursor args with workspace -> parent (worktrees only) -> app precedence,
+/// regardless of which CLI is currently active.
+pub(crate) fn resolve_workspace_cursor_args(
+    entry: &WorkspaceEntry,
+    parent_entry: Option<&WorkspaceEntry>,
+    app_settings: Option<&AppSettings>,
+) -> Option<String> {
+    resolve_workspace_args_for("cursor", entry, parent_entry, app_settings)
+}
+
+fn resolve_workspace_args_for(
+    cli_type: &str,
+    entry: &WorkspaceEntry,
+    parent_entry: Option<&WorkspaceEntry>,
+    app_settings: Option<&AppSettings>,
+) -> Option<String> {
     workspace_cli_args_override(&entry.settings, cli_type)
         .or_else(|| {
             if entry.kind.is_worktree() {
@@ -154,7 +178,9 @@ pub(crate) fn resolve_workspace_cli_args(
             }
         })
         .or_else(|| {
-            app_settings.and_then(|settings| normalize_workspace_cli_value(resolve_default_cli_args(settings)))
+            app_settings.and_then(|settings| {
+                normalize_workspace_cli_value(resolve_default_cli_args(settings, cli_type))
+            })
         })
 }
 
@@ -171,9 +197,150 @@ pub(crate) fn resolve_workspace_cli_home(
         parent_clone.settings.codex_home = workspace_cli_home_override(&parent.settings, cli_type);
         parent_clone
     });
+    if cli_type == "gemini" {
+        let has_workspace_override = entry_with_override.settings.codex_home.is_some()
+            || (entry.kind.is_worktree()
+                && parent_with_override
+                    .as_ref()
+                    .is_some_and(|parent| parent.settings.codex_home.is_some()));
+        if !has_workspace_override {
+            let base = PathBuf::from(&entry.path);
+            return app_settings
+                .and_then(|settings| settings.gemini_home.as_deref())
+                .and_then(|value| normalize_codex_home_with_base(value, &base))
+                .or_else(resolve_default_gemini_home);
+        }
+    }
     resolve_workspace_codex_home(&entry_with_override, parent_with_override.as_ref())
 }
 
+/// Claude/Cursor profile dir from the workspace (or a worktree's parent)
+/// settings. There is deliberately no Codex home fallback: without an override
+/// the CLI keeps its own default profile.
+pub(crate) fn resolve_workspace_profile_home(
+    entry: &WorkspaceEntry,
+    parent_entry: Option<&WorkspaceEntry>,
+    cli_type: &str,
+) -> Option<PathBuf> {
+    let resolve = |workspace: &WorkspaceEntry| {
+        let value = match cli_type {
+            "claude" => workspace.settings.claude_home.as_deref(),
+            "cursor" => workspace.settings.cursor_home.as_deref(),
+            _ => None,
+        }?;
+        normalize_codex_home_with_base(value, &PathBuf::from(&workspace.path))
+    };
+    resolve(entry).or_else(|| {
+        if entry.kind.is_worktree() {
+            parent_entry.and_then(resolve)
+        } else {
+            None
+        }
+    })
+}
+
+const APPROVAL_POLICIES: &[&str] = &["untrusted", "on-failure", "on-request", "never"];
+
+/// Approval policy for turns that don't pick an access mode, from the
+/// workspace (or a worktree's parent) settings. Unknown values are ignored.
+pub(crate) fn resolve_workspace_approval_policy(
+    entry: &WorkspaceEntry,
+    parent_entry: Option<&WorkspaceEntry>,
+) -> Option<String> {
+    let resolve = |workspace: &WorkspaceEntry| {
+        normalize_workspace_cli_value(workspace.settings.default_approval_policy.clone())
+            .filter(|policy| APPROVAL_POLICIES.contains(&policy.as_str()))
+    };
+    resolve(entry).or_else(|| {
+        if entry.kind.is_worktree() {
+            parent_entry.and_then(resolve)
+        } else {
+            None
+        }
+    })
+}
+
+const SANDBOX_POLICIES: &[&str] = &["readOnly", "workspaceWrite", "full"];
+
+/// Sandbox policy passed to spawned CLIs, from the workspace (or a worktree's
+/// parent) settings. Unknown values are ignored.
+pub(crate) fn resolve_workspace_sandbox_policy(
+    entry: &WorkspaceEntry,
+    parent_entry: Option<&WorkspaceEntry>,
+) -> Option<String> {
+    let resolve = |workspace: &WorkspaceEntry| {
+        normalize_workspace_cli_value(workspace.settings.sandbox_policy.clone())
+            .filter(|policy| SANDBOX_POLICIES.contains(&policy.as_str()))
+    };
+    resolve(entry).or_else(|| {
+        if entry.kind.is_worktree() {
+            parent_entry.and_then(resolve)
+        } else {
+            None
+        }
+    })
+}
+
+/// Standing instruction for every turn, from the workspace, a worktree's
+/// parent, then the app settings. Blank values are ignored.
+pub(crate) fn resolve_workspace_system_prompt(
+    entry: &WorkspaceEntry,
+    parent_entry: Option<&WorkspaceEntry>,
+    app_settings: Option<&AppSettings>,
+) -> Option<String> {
+    let resolve = |workspace: &WorkspaceEntry| {
+        normalize_workspace_cli_value(workspace.settings.system_prompt.clone())
+    };
+    resolve(entry)
+        .or_else(|| {
+            if entry.kind.is_worktree() {
+                parent_entry.and_then(resolve)
+            } else {
+                None
+            }
+        })
+        .or_else(|| {
+            app_settings
+                .and_then(|settings| normalize_workspace_cli_value(settings.system_prompt.clone()))
+        })
+}
+
+/// Cursor mode for turns that don't pick one, from the workspace, a
+/// worktree's parent, then the app settings. Validated at spawn rather than
+/// here so a typo is reported instead of silently ignored.
+pub(crate) fn resolve_workspace_cursor_mode(
+    entry: &WorkspaceEntry,
+    parent_entry: Option<&WorkspaceEntry>,
+    app_settings: Option<&AppSettings>,
+) -> Option<String> {
+    let resolve = |workspace: &WorkspaceEntry| {
+        normalize_workspace_cli_value(workspace.settings.cursor_default_mode.clone())
+    };
+    resolve(entry)
+        .or_else(|| {
+            if entry.kind.is_worktree() {
+                parent_entry.and_then(resolve)
+            } else {
+                None
+            }
+        })
+        .or_else(|| {
+            app_settings.and_then(|settings| {
+                normalize_workspace_cli_value(Some(settings.cursor_default_mode.clone()))
+            })
+        })
+}
+
+pub(crate) async fn workspace_default_approval_policy(
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    workspace_id: &str,
+) -> Option<String> {
+    let (entry, parent_entry) = resolve_entry_and_parent(workspaces, workspace_id)
+        .await
+        .ok()?;
+    resolve_workspace_approval_policy(&entry, parent_entry.as_ref())
+}
+
 pub(crate) fn build_cli_spawn_config(
     entry: &WorkspaceEntry,
     parent_entry: Option<&WorkspaceEntry>,
@@ -184,6 +351,29 @@ pub(crate) fn build_cli_spawn_config(
         cli_bin: resolve_workspace_cli_bin(entry, app_settings),
         cli_args: resolve_workspace_cli_args(entry, parent_entry, Some(app_settings)),
         cli_home: resolve_workspace_cli_home(entry, parent_entry, Some(app_settings)),
+        claude_home: resolve_workspace_profile_home(entry, parent_entry, "claude"),
+        cursor_home: resolve_workspace_profile_home(entry, parent_entry, "cursor"),
+        sandbox_policy: resolve_workspace_sandbox_policy(entry, parent_entry),
+        cursor_default_mode: resolve_workspace_cursor_mode(entry, parent_entry, Some(app_settings)),
+        gemini_sandbox_disabled: !app_settings.gemini_use_sandbox,
+        retry_rate_limited_turns: app_settings.auto_retry_rate_limited_turns,
+        thread_store_dir: app_settings.thread_store_dir.as_ref().map(PathBuf::from),
+        system_prompt: resolve_workspace_system_prompt(entry, parent_entry, Some(app_settings)),
+        skip_min_version_check: app_settings.skip_cli_version_check,
+        stderr_deny_patterns: app_settings.stderr_deny_patterns.clone(),
+        stderr_allow_patterns: app_settings.stderr_allow_patterns.clone(),
+        approval_timeout_ms: app_settings
+            .approval_timeout_secs
+            .filter(|secs| *secs > 0)
+            .map(|secs| secs * 1000),
+        approval_timeout_decision: Some(app_settings.approval_timeout_decision.clone()),
+        initialize_attempts: app_settings.initialize_attempts,
+        initialize_timeout_ms: app_settings
+            .initialize_timeout_secs
+            .filter(|secs| *secs > 0)
+            .map(|secs| secs * 1000),
+        initialize_backoff_ms: app_settings.initialize_backoff_ms,
+        ..CliSpawnConfig::default()
     }
 }
 
@@ -242,6 +432,56 @@ pub(crate) fn is_workspace_path_dir_core(path: &str) -> bool {
     PathBuf::from(path).is_dir()
 }
 
+/// Checks a path before it becomes a workspace, so a missing directory or a
+/// broken worktree is reported up front instead of on the first turn.
+pub(crate) fn validate_workspace_path_core(path: &str) -> WorkspacePathValidation {
+    let path = PathBuf::from(path);
+    let exists = path.exists();
+    let is_dir = path.is_dir();
+    if !is_dir {
+        return WorkspacePathValidation {
+            exists,
+            ..WorkspacePathValidation::default()
+        };
+    }
+    let repo = git2::Repository::discover(&path).ok();
+    // A worktree's `.git` is a file pointing back at the main repository; it
+    // stays behind when that link breaks, so it also flags broken worktrees.
+    let is_worktree =
+        repo.as_ref().is_some_and(|repo| repo.is_worktree()) || path.join(".git").is_file();
+    let worktree_valid = is_worktree.then(|| {
+        repo.as_ref()
+            .filter(|repo| repo.is_worktree())
+            .and_then(|repo| git2::Worktree::open_from_repository(repo).ok())
+            .is_some_and(|worktree| worktree.validate().is_ok())
+    });
+    WorkspacePathValidation {
+        exists,
+        is_dir,
+        is_git_repo: repo.is_some(),
+        writable: is_dir_writable(&path),
+        is_worktree,
+        worktree_valid,
+    }
+}
+
+/// Probes with a throwaway file, since permission bits alone miss ACLs and
+/// read-only mounts.
+fn is_dir_writable(dir: &std::path::Path) -> bool {
+    let probe = dir.join(format!(".codex-monitor-write-check-{}", Uuid::new_v4()));
+    match std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
 pub(crate) async fn list_workspaces_core(
     workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
     sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
@@ -348,6 +588,63 @@ pub(crate) async fn worktree_setup_mark_ran_core(
     Ok(())
 }
 
+/// Rejects a new session once `max_concurrent_sessions` sessions are live.
+/// `replacing` is the workspace whose session the spawn would replace, so
+/// reconnecting never counts against the limit. A limit of 0 disables it.
+pub(crate) async fn ensure_session_capacity(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    app_settings: &Mutex<AppSettings>,
+    replacing: Option<&str>,
+) -> Result<(), String> {
+    let limit = app_settings.lock().await.max_concurrent_sessions;
+    check_session_capacity(&*sessions.lock().await, limit, replacing)
+}
+
+fn check_session_capacity(
+    sessions: &HashMap<String, Arc<WorkspaceSession>>,
+    limit: usize,
+    replacing: Option<&str>,
+) -> Result<(), String> {
+    if limit == 0 {
+        return Ok(());
+    }
+    let live = sessions
+        .keys()
+        .filter(|id| Some(id.as_str()) != replacing)
+        .count();
+    if live >= limit {
+        return Err(format!(
+            "Session limit reached: {live} of {limit} sessions are running. Disconnect a workspace or raise the limit in Settings."
+        ));
+    }
+    Ok(())
+}
+
+/// Inserts a freshly spawned session, checking the limit again under the
+/// same lock as the insert: concurrent spawns can all pass
+/// `ensure_session_capacity` before any of them lands. A session that no
+/// longer fits is killed.
+pub(crate) async fn insert_session_within_capacity(
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+    app_settings: &Mutex<AppSettings>,
+    workspace_id: &str,
+    session: Arc<WorkspaceSession>,
+) -> Result<(), String> {
+    let limit = app_settings.lock().await.max_concurrent_sessions;
+    let rejected = {
+        let mut sessions = sessions.lock().await;
+        match check_session_capacity(&sessions, limit, Some(workspace_id)) {
+            Ok(()) => {
+                sessions.insert(workspace_id.to_string(), session);
+                return Ok(());
+            }
+            Err(error) => error,
+        }
+    };
+    session.kill().await;
+    Err(rejected)
+}
+
 pub(crate) async fn add_workspace_core<F, Fut>(
     path: String,
     codex_bin: Option<String>,
@@ -364,6 +661,7 @@ where
     if !PathBuf::from(&path).is_dir() {
         return Err("Workspace path must be a folder.".to_string());
     }
+    ensure_session_capacity(sessions, app_settings, None).await?;
 
     let name = PathBuf::from(&path)
         .file_name()
@@ -385,6 +683,7 @@ where
 
     let config = build_cli_spawn_config(&entry, None, &settings_snapshot);
     let session = spawn_session(entry.clone(), config).await?;
+    insert_session_within_capacity(sessions, app_settings, &entry.id, session).await?;
 
     if let Err(error) = {
         let mut workspaces = workspaces.lock().await;
@@ -396,12 +695,10 @@ where
             let mut workspaces = workspaces.lock().await;
             workspaces.remove(&entry.id);
         }
-        session.kill().await;
+        kill_session_by_id(sessions, &entry.id).await;
         return Err(error);
     }
 
-    sessions.lock().await.insert(entry.id.clone(), session);
-
     Ok(WorkspaceInfo {
         id: entry.id,
         name: entry.name,
@@ -492,6 +789,7 @@ where
     if parent_entry.kind.is_worktree() {
         return Err("Cannot create a worktree from another worktree.".to_string());
     }
+    ensure_session_capacity(sessions, app_settings, None).await?;
 
     let worktree_root = data_dir.join("worktrees").join(&parent_entry.id);
     std::fs::create_dir_all(&worktree_root)
@@ -572,6 +870,7 @@ where
     let settings_snapshot = app_settings.lock().await.clone();
     let config = build_cli_spawn_config(&entry, Some(&parent_entry), &settings_snapshot);
     let session = spawn_session(entry.clone(), config).await?;
+    insert_session_within_capacity(sessions, app_settings, &entry.id, session).await?;
 
     {
         let mut workspaces = workspaces.lock().await;
@@ -580,8 +879,6 @@ where
         write_workspaces(storage_path, &list)?;
     }
 
-    sessions.lock().await.insert(entry.id.clone(), session);
-
     Ok(WorkspaceInfo {
         id: entry.id,
         name: entry.name,
@@ -607,11 +904,34 @@ where
     Fut: Future<Output = Result<Arc<WorkspaceSession>, String>>,
 {
     let (entry, parent_entry) = resolve_entry_and_parent(workspaces, &workspace_id).await?;
+    ensure_session_capacity(sessions, app_settings, Some(&entry.id)).await?;
     let settings_snapshot = app_settings.lock().await.clone();
     let config = build_cli_spawn_config(&entry, parent_entry.as_ref(), &settings_snapshot);
     let session = spawn_session(entry.clone(), config).await?;
-    sessions.lock().await.insert(entry.id, session);
-    Ok(())
+    insert_session_within_capacity(sessions, app_settings, &entry.id, session).await
+}
+
+/// The command connecting the workspace would run, from the same config and
+/// builder as `connect_workspace_core`, with env values left out.
+pub(crate) async fn preview_spawn_command_core(
+    workspace_id: String,
+    workspaces: &Mutex<HashMap<String, WorkspaceEntry>>,
+    app_settings: &Mutex<AppSettings>,
+) -> Result<serde_json::Value, String> {
+    let (entry, parent_entry) = resolve_entry_and_parent(workspaces, &workspace_id).await?;
+    let settings_snapshot = app_settings.lock().await.clone();
+    let config = build_cli_spawn_config(&entry, parent_entry.as_ref(), &settings_snapshot);
+    let command = build_spawn_command(&entry, &config)?;
+    Ok(describe_spawn_command(&command))
+}
+
+/// Stops the workspace's session but keeps the workspace, freeing its slot
+/// toward `max_concurrent_sessions`.
+pub(crate) async fn disconnect_workspace_core(
+    workspace_id: String,
+    sessions: &Mutex<HashMap<String, Arc<WorkspaceSession>>>,
+) {
+    kill_session_by_id(sessions, &workspace_id).await;
 }
 
 async fn kill_session_by_id(
@@ -1071,10 +1391,16 @@ where
     Ok(())
 }
 
+/// Saves a workspace's settings and respawns its session when the CLI home
+/// or args changed. Connected worktrees that inherit the change are respawned
+/// with `spawn_session_deferred`, so their handshakes run side by side and
+/// report through `codex/connected` / `codex/connectError`.
 pub(crate) async fn update_workspace_settings_core<
     FApplySettings,
     FSpawn,
     FutSpawn,
+    FSpawnDeferred,
+    FutSpawnDeferred,
 >(
     id: String,
     mut settings: WorkspaceSettings,
@@ -1084,13 +1410,21 @@ pub(crate) async fn update_workspace_settings_core<
     storage_path: &PathBuf,
     apply_settings_update: FApplySettings,
     spawn_session: FSpawn,
+    spawn_session_deferred: FSpawnDeferred,
 ) -> Result<WorkspaceInfo, String>
 where
     FApplySettings: Fn(&mut HashMap<String, WorkspaceEntry>, &str, WorkspaceSettings)
         -> Result<WorkspaceEntry, String>,
     FSpawn: Fn(WorkspaceEntry, CliSpawnConfig) -> FutSpawn,
     FutSpawn: Future<Output = Result<Arc<WorkspaceSession>, String>>,
+    FSpawnDeferred: Fn(WorkspaceEntry, CliSpawnConfig) -> FutSpawnDeferred,
+    FutSpawnDeferred: Future<Output = Result<Arc<WorkspaceSession>, String>>,
 {
+    validate_adapter_args(
+        settings.gemini_args.as_deref(),
+        settings.claude_args.as_deref(),
+        settings.cursor_args.as_deref(),
+    )?;
     settings.worktree_setup_script = normalize_setup_script(settings.worktree_setup_script);
     let app_settings_snapshot = app_settings.lock().await.clone();
 
@@ -1117,6 +1451,10 @@ where
             app_settings_snapshot.cli_type.as_str(),
         );
         let previous_worktree_setup_script = previous_entry.settings.worktree_setup_script.clone();
+        // Only `set_collaboration_mode` changes these; a settings form saved
+        // from an older snapshot must not drop them.
+        settings.thread_collaboration_modes =
+            previous_entry.settings.thread_collaboration_modes.clone();
         let entry_snapshot = apply_settings_update(&mut workspaces, &id, settings)?;
         let parent_entry = entry_snapshot
             .parent_id
@@ -1200,13 +1538,9 @@ where
             if previous_child_home == next_child_home && previous_child_args == next_child_args {
                 continue;
             }
-            let child_config = build_cli_spawn_config(child, Some(&entry_snapshot), &app_settings_snapshot);
-            let new_session = match spawn_session(
-                child.clone(),
-                child_config,
-            )
-            .await
-            {
+            let child_config =
+                build_cli_spawn_config(child, Some(&entry_snapshot), &app_settings_snapshot);
+            let new_session = match spawn_session_deferred(child.clone(), child_config).await {
                 Ok(session) => session,
                 Err(error) => {
                     eprintln!(
@@ -1338,11 +1672,18 @@ mod tests {
     use std::path::PathBuf;
 
     use super::copy_agents_md_from_parent_to_worktree;
+    use super::resolve_workspace_approval_policy;
     use super::resolve_workspace_cli_args;
     use super::resolve_workspace_cli_bin;
     use super::resolve_workspace_cli_home;
+    use super::resolve_workspace_cursor_args;
+    use super::resolve_workspace_profile_home;
+    use super::resolve_workspace_sandbox_policy;
+    use super::resolve_workspace_system_prompt;
     use super::resolve_default_cli_bin;
+    use super::validate_workspace_path_core;
     use super::AGENTS_MD_FILE_NAME;
+    use crate::backend::test_support::test_workspace_entry;
     use crate::types::{AppSettings, WorkspaceEntry, WorkspaceKind, WorkspaceSettings};
     use uuid::Uuid;
 
@@ -1352,6 +1693,72 @@ mod tests {
         dir
     }
 
+    #[test]
+    fn validates_git_repo_plain_dir_and_missing_path() {
+        let repo_dir = make_temp_dir();
+        git2::Repository::init(&repo_dir).expect("init repo");
+        std::fs::create_dir_all(repo_dir.join("src")).expect("create subdir");
+        for path in [repo_dir.clone(), repo_dir.join("src")] {
+            let result = validate_workspace_path_core(path.to_str().unwrap());
+            assert!(
+                result.exists && result.is_dir && result.is_git_repo,
+                "{path:?}"
+            );
+            assert!(result.writable);
+            assert!(!result.is_worktree);
+            assert_eq!(result.worktree_valid, None);
+        }
+
+        let plain = make_temp_dir();
+        let result = validate_workspace_path_core(plain.to_str().unwrap());
+        assert!(result.exists && result.is_dir && result.writable);
+        assert!(!result.is_git_repo);
+        assert_eq!(
+            std::fs::read_dir(&plain).unwrap().count(),
+            0,
+            "write probe is cleaned up"
+        );
+
+        let missing = plain.join("missing");
+        let result = validate_workspace_path_core(missing.to_str().unwrap());
+        assert_eq!(result, Default::default());
+
+        let file = plain.join("notes.txt");
+        std::fs::write(&file, "hi").expect("write file");
+        let result = validate_workspace_path_core(file.to_str().unwrap());
+        assert!(result.exists && !result.is_dir && !result.is_git_repo);
+
+        let _ = std::fs::remove_dir_all(repo_dir);
+        let _ = std::fs::remove_dir_all(plain);
+    }
+
+    #[test]
+    fn validates_linked_worktrees() {
+        let repo_dir = make_temp_dir();
+        let repo = git2::Repository::init(&repo_dir).expect("init repo");
+        let signature = git2::Signature::now("Test", "test@example.com").expect("signature");
+        let tree_id = repo.index().unwrap().write_tree().expect("write tree");
+        let tree = repo.find_tree(tree_id).expect("tree");
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .expect("commit");
+        let worktree_dir = make_temp_dir().join("feature");
+        repo.worktree("feature", &worktree_dir, None)
+            .expect("add worktree");
+
+        let result = validate_workspace_path_core(worktree_dir.to_str().unwrap());
+        assert!(result.is_git_repo && result.is_worktree);
+        assert_eq!(result.worktree_valid, Some(true));
+
+        std::fs::remove_dir_all(repo_dir.join(".git").join("worktrees"))
+            .expect("drop worktree admin dir");
+        let result = validate_workspace_path_core(worktree_dir.to_str().unwrap());
+        assert!(result.is_worktree);
+        assert_eq!(result.worktree_valid, Some(false));
+
+        let _ = std::fs::remove_dir_all(repo_dir);
+        let _ = std::fs::remove_dir_all(worktree_dir.parent().unwrap());
+    }
+
     #[test]
     fn copies_agents_md_when_missing_in_worktree() {
         let parent = make_temp_dir();
@@ -1507,29 +1914,18 @@ mod tests {
         settings.claude_args = Some("--app-claude".to_string());
 
         let parent = WorkspaceEntry {
-            id: "parent".to_string(),
-            name: "Parent".to_string(),
-            path: "/tmp/parent".to_string(),
-            codex_bin: None,
-            kind: WorkspaceKind::Main,
-            parent_id: None,
-            worktree: None,
             settings: WorkspaceSettings {
                 codex_args: Some("--parent-codex".to_string()),
                 claude_args: Some("--parent-claude".to_string()),
                 ..WorkspaceSettings::default()
             },
+            ..test_workspace_entry("parent", "/tmp/parent")
         };
 
         let child = WorkspaceEntry {
-            id: "child".to_string(),
-            name: "Child".to_string(),
-            path: "/tmp/child".to_string(),
-            codex_bin: None,
             kind: WorkspaceKind::Worktree,
             parent_id: Some(parent.id.clone()),
-            worktree: None,
-            settings: WorkspaceSettings::default(),
+            ..test_workspace_entry("child", "/tmp/child")
         };
 
         settings.cli_type = "claude".to_string();
@@ -1553,17 +1949,11 @@ mod tests {
         settings.claude_args = Some("--app-claude".to_string());
 
         let entry = WorkspaceEntry {
-            id: "w1".to_string(),
-            name: "Workspace".to_string(),
-            path: "/tmp/w1".to_string(),
-            codex_bin: None,
-            kind: WorkspaceKind::Main,
-            parent_id: None,
-            worktree: None,
             settings: WorkspaceSettings {
                 codex_args: Some("--legacy-shared".to_string()),
                 ..WorkspaceSettings::default()
             },
+            ..test_workspace_entry("w1", "/tmp/w1")
         };
 
         assert_eq!(
@@ -1572,33 +1962,259 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolves_workspace_cursor_args_with_workspace_parent_app_precedence() {
+        let mut settings = AppSettings::default();
+        settings.cursor_args = Some("--app-cursor".to_string());
+
+        let parent = WorkspaceEntry {
+            settings: WorkspaceSettings {
+                cursor_args: Some("--parent-cursor".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("parent", "/tmp/parent")
+        };
+
+        let child = WorkspaceEntry {
+            kind: WorkspaceKind::Worktree,
+            parent_id: Some(parent.id.clone()),
+            ..test_workspace_entry("child", "/tmp/child")
+        };
+
+        assert_eq!(
+            resolve_workspace_cursor_args(&child, Some(&parent), Some(&settings)).as_deref(),
+            Some("--parent-cursor")
+        );
+
+        let mut child_override = child.clone();
+        child_override.settings.cursor_args = Some("  --child-cursor  ".to_string());
+        assert_eq!(
+            resolve_workspace_cursor_args(&child_override, Some(&parent), Some(&settings))
+                .as_deref(),
+            Some("--child-cursor")
+        );
+
+        let main = test_workspace_entry("main", "/tmp/main");
+        assert_eq!(
+            resolve_workspace_cursor_args(&main, Some(&parent), Some(&settings)).as_deref(),
+            Some("--app-cursor")
+        );
+
+        // Cursor args resolve the same way whichever CLI is active, and the
+        // spawn path picks them up once Cursor is selected.
+        settings.cli_type = "codex".to_string();
+        assert_eq!(
+            resolve_workspace_cursor_args(&child, Some(&parent), Some(&settings)).as_deref(),
+            Some("--parent-cursor")
+        );
+        settings.cli_type = "cursor".to_string();
+        assert_eq!(
+            super::build_cli_spawn_config(&child, Some(&parent), &settings)
+                .cli_args
+                .as_deref(),
+            Some("--parent-cursor")
+        );
+    }
+
+    #[test]
+    fn resolves_claude_and_cursor_profile_homes_without_codex_fallback() {
+        let parent = WorkspaceEntry {
+            settings: WorkspaceSettings {
+                codex_home: Some(".codex-home".to_string()),
+                claude_home: Some(".claude-home".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("parent", "/tmp/parent")
+        };
+        let child = WorkspaceEntry {
+            kind: WorkspaceKind::Worktree,
+            parent_id: Some(parent.id.clone()),
+            settings: WorkspaceSettings {
+                cursor_home: Some("/profiles/cursor".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("child", "/tmp/child")
+        };
+
+        assert_eq!(
+            resolve_workspace_profile_home(&child, Some(&parent), "claude"),
+            Some(PathBuf::from("/tmp/parent/.claude-home"))
+        );
+        assert_eq!(resolve_workspace_profile_home(&parent, None, "cursor"), None);
+
+        let mut settings = AppSettings::default();
+        settings.cli_type = "cursor".to_string();
+        let config = super::build_cli_spawn_config(&child, Some(&parent), &settings);
+        assert_eq!(config.cursor_home, Some(PathBuf::from("/profiles/cursor")));
+        assert_eq!(config.claude_home, Some(PathBuf::from("/tmp/parent/.claude-home")));
+    }
+
+    #[test]
+    fn resolves_default_approval_policy_from_workspace_then_parent() {
+        let parent = WorkspaceEntry {
+            settings: WorkspaceSettings {
+                default_approval_policy: Some("untrusted".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("parent", "/tmp/parent")
+        };
+        let mut child = WorkspaceEntry {
+            kind: WorkspaceKind::Worktree,
+            parent_id: Some(parent.id.clone()),
+            ..test_workspace_entry("child", "/tmp/child")
+        };
+
+        assert_eq!(
+            resolve_workspace_approval_policy(&child, Some(&parent)).as_deref(),
+            Some("untrusted")
+        );
+        child.settings.default_approval_policy = Some(" on-request ".to_string());
+        assert_eq!(
+            resolve_workspace_approval_policy(&child, Some(&parent)).as_deref(),
+            Some("on-request")
+        );
+        child.settings.default_approval_policy = Some("sometimes".to_string());
+        assert_eq!(
+            resolve_workspace_approval_policy(&child, Some(&parent)).as_deref(),
+            Some("untrusted")
+        );
+        assert_eq!(
+            resolve_workspace_approval_policy(&parent, None).as_deref(),
+            Some("untrusted")
+        );
+    }
+
+    #[test]
+    fn spawn_config_resolves_cursor_mode_from_workspace_parent_then_settings() {
+        let parent = WorkspaceEntry {
+            settings: WorkspaceSettings {
+                cursor_default_mode: Some("ask".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("parent", "/tmp/parent")
+        };
+        let mut child = WorkspaceEntry {
+            kind: WorkspaceKind::Worktree,
+            parent_id: Some(parent.id.clone()),
+            ..test_workspace_entry("child", "/tmp/child")
+        };
+        let mut settings = AppSettings::default();
+        settings.cursor_default_mode = "plan".to_string();
+
+        let config = super::build_cli_spawn_config(&child, Some(&parent), &settings);
+        assert_eq!(config.cursor_default_mode.as_deref(), Some("ask"));
+        child.settings.cursor_default_mode = Some(" debug ".to_string());
+        let config = super::build_cli_spawn_config(&child, Some(&parent), &settings);
+        assert_eq!(config.cursor_default_mode.as_deref(), Some("debug"));
+        child.settings.cursor_default_mode = None;
+        let config = super::build_cli_spawn_config(&child, None, &settings);
+        assert_eq!(config.cursor_default_mode.as_deref(), Some("plan"));
+    }
+
+    #[test]
+    fn spawn_config_carries_sandbox_policy_from_workspace_then_parent() {
+        let parent = WorkspaceEntry {
+            settings: WorkspaceSettings {
+                sandbox_policy: Some("readOnly".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("parent", "/tmp/parent")
+        };
+        let mut child = WorkspaceEntry {
+            kind: WorkspaceKind::Worktree,
+            parent_id: Some(parent.id.clone()),
+            ..test_workspace_entry("child", "/tmp/child")
+        };
+        let settings = AppSettings::default();
+
+        let config = super::build_cli_spawn_config(&child, Some(&parent), &settings);
+        assert_eq!(config.sandbox_policy.as_deref(), Some("readOnly"));
+        child.settings.sandbox_policy = Some(" full ".to_string());
+        let config = super::build_cli_spawn_config(&child, Some(&parent), &settings);
+        assert_eq!(config.sandbox_policy.as_deref(), Some("full"));
+        child.settings.sandbox_policy = Some("loose".to_string());
+        assert_eq!(
+            resolve_workspace_sandbox_policy(&child, Some(&parent)).as_deref(),
+            Some("readOnly")
+        );
+        let config = super::build_cli_spawn_config(&child, None, &settings);
+        assert!(config.sandbox_policy.is_none());
+    }
+
+    #[test]
+    fn system_prompt_prefers_workspace_then_parent_then_app() {
+        let parent = WorkspaceEntry {
+            settings: WorkspaceSettings {
+                system_prompt: Some("Answer in French.".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("parent", "/tmp/parent")
+        };
+        let mut child = WorkspaceEntry {
+            kind: WorkspaceKind::Worktree,
+            parent_id: Some(parent.id.clone()),
+            settings: WorkspaceSettings {
+                system_prompt: Some("   ".to_string()),
+                ..WorkspaceSettings::default()
+            },
+            ..test_workspace_entry("child", "/tmp/child")
+        };
+        let settings = AppSettings {
+            system_prompt: Some("Use British spelling.".to_string()),
+            ..AppSettings::default()
+        };
+
+        let config = super::build_cli_spawn_config(&child, Some(&parent), &settings);
+        assert_eq!(config.system_prompt.as_deref(), Some("Answer in French."));
+        child.settings.system_prompt = Some(" Prefer small diffs. ".to_string());
+        let config = super::build_cli_spawn_config(&child, Some(&parent), &settings);
+        assert_eq!(config.system_prompt.as_deref(), Some("Prefer small diffs."));
+        let config = super::build_cli_spawn_config(&parent, None, &AppSettings::default());
+        assert_eq!(config.system_prompt.as_deref(), Some("Answer in French."));
+        child.settings.system_prompt = None;
+        assert_eq!(
+            resolve_workspace_system_prompt(&child, None, Some(&settings)).as_deref(),
+            Some("Use British spelling.")
+        );
+        assert!(resolve_workspace_system_prompt(&child, None, None).is_none());
+    }
+
+    #[test]
+    fn spawn_config_takes_initialize_limits_from_settings() {
+        let entry = test_workspace_entry("ws", "/tmp/ws");
+        let config = super::build_cli_spawn_config(&entry, None, &AppSettings::default());
+        assert_eq!(config.initialize_attempts, None);
+        assert_eq!(config.initialize_timeout_ms, None);
+        assert_eq!(config.initialize_backoff_ms, None);
+
+        let settings = AppSettings {
+            initialize_attempts: Some(5),
+            initialize_timeout_secs: Some(30),
+            initialize_backoff_ms: Some(250),
+            ..AppSettings::default()
+        };
+        let config = super::build_cli_spawn_config(&entry, None, &settings);
+        assert_eq!(config.initialize_attempts, Some(5));
+        assert_eq!(config.initialize_timeout_ms, Some(30_000));
+        assert_eq!(config.initialize_backoff_ms, Some(250));
+    }
+
     #[test]
     fn resolves_workspace_cli_home_from_active_workspace_override() {
         let mut settings = AppSettings::default();
         settings.cli_type = "claude".to_string();
 
         let parent = WorkspaceEntry {
-            id: "parent".to_string(),
-            name: "Parent".to_string(),
-            path: "/tmp/parent".to_string(),
-            codex_bin: None,
-            kind: WorkspaceKind::Main,
-            parent_id: None,
-            worktree: None,
             settings: WorkspaceSettings {
                 claude_home: Some(".claude-home".to_string()),
                 ..WorkspaceSettings::default()
             },
+            ..test_workspace_entry("parent", "/tmp/parent")
         };
         let child = WorkspaceEntry {
-            id: "child".to_string(),
-            name: "Child".to_string(),
-            path: "/tmp/child".to_string(),
-            codex_bin: None,
             kind: WorkspaceKind::Worktree,
             parent_id: Some(parent.id.clone()),
-            worktree: None,
-            settings: WorkspaceSettings::default(),
+            ..test_workspace_entry("child", "/tmp/child")
         };
 
         assert_eq!(
@@ -1606,4 +2222,37 @@ mod tests {
             Some(PathBuf::from("/tmp/parent/.claude-home"))
         );
     }
+
+    #[test]
+    fn resolves_gemini_home_with_workspace_parent_app_precedence() {
+        let mut settings = AppSettings::default();
+        settings.cli_type = "gemini".to_string();
+        settings.gemini_home = Some("/app/gemini-home".to_string());
+
+        let parent = test_workspace_entry("parent", "/tmp/parent");
+        let child = WorkspaceEntry {
+            kind: WorkspaceKind::Worktree,
+            parent_id: Some(parent.id.clone()),
+            ..test_workspace_entry("child", "/tmp/child")
+        };
+
+        assert_eq!(
+            resolve_workspace_cli_home(&child, Some(&parent), Some(&settings)),
+            Some(PathBuf::from("/app/gemini-home"))
+        );
+
+        let mut parent_override = parent.clone();
+        parent_override.settings.gemini_home = Some("/parent/gemini-home".to_string());
+        assert_eq!(
+            resolve_workspace_cli_home(&child, Some(&parent_override), Some(&settings)),
+            Some(PathBuf::from("/parent/gemini-home"))
+        );
+
+        let mut child_override = child.clone();
+        child_override.settings.gemini_home = Some(".gemini-child".to_string());
+        assert_eq!(
+            resolve_workspace_cli_home(&child_override, Some(&parent_override), Some(&settings)),
+            Some(PathBuf::from("/tmp/child/.gemini-child"))
+        );
+    }
 }
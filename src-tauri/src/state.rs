@@ -5,6 +5,7 @@ use tauri::{AppHandle, Manager};
 use tokio::sync::Mutex;
 
 use crate::dictation::DictationState;
+use crate::shared::codex_aux_core::BackgroundPromptCancels;
 use crate::shared::codex_core::CodexLoginCancelState;
 use crate::storage::{read_settings, read_workspaces};
 use crate::types::{AppSettings, WorkspaceEntry};
@@ -20,6 +21,7 @@ pub(crate) struct AppState {
     pub(crate) app_settings: Mutex<AppSettings>,
     pub(crate) dictation: Mutex<DictationState>,
     pub(crate) codex_login_cancels: Mutex<HashMap<String, CodexLoginCancelState>>,
+    pub(crate) run_metadata_cancels: BackgroundPromptCancels,
 }
 
 impl AppState {
@@ -28,8 +30,13 @@ impl AppState {
             .path()
             .app_data_dir()
             .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| ".".into()));
-        let storage_path = data_dir.join("workspaces.json");
-        let settings_path = data_dir.join("settings.json");
+        Self::from_paths(
+            data_dir.join("workspaces.json"),
+            data_dir.join("settings.json"),
+        )
+    }
+
+    pub(crate) fn from_paths(storage_path: PathBuf, settings_path: PathBuf) -> Self {
         let workspaces = read_workspaces(&storage_path).unwrap_or_default();
         let app_settings = read_settings(&settings_path).unwrap_or_default();
         Self {
@@ -42,6 +49,7 @@ impl AppState {
             app_settings: Mutex::new(app_settings),
             dictation: Mutex::new(DictationState::default()),
             codex_login_cancels: Mutex::new(HashMap::new()),
+            run_metadata_cancels: Mutex::new(HashMap::new()),
         }
     }
 }
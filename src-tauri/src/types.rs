@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub(crate) struct GitFileStatus {
     pub(crate) path: String,
     pub(crate) status: String,
@@ -8,6 +9,13 @@ pub(crate) struct GitFileStatus {
     pub(crate) deletions: i64,
 }
 
+/// Per-file change summary plus the patch text, used to build commit prompts.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub(crate) struct WorkspaceDiffStructured {
+    pub(crate) files: Vec<GitFileStatus>,
+    pub(crate) diff: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct GitFileDiff {
     pub(crate) path: String,
@@ -209,6 +217,26 @@ pub(crate) struct WorkspaceEntry {
     pub(crate) settings: WorkspaceSettings,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct SessionInfo {
+    #[serde(rename = "workspaceId")]
+    pub(crate) workspace_id: String,
+    #[serde(rename = "cliType")]
+    pub(crate) cli_type: String,
+    pub(crate) alive: bool,
+    pub(crate) pid: Option<u32>,
+    #[serde(rename = "startedAt")]
+    pub(crate) started_at: u64,
+    #[serde(default, rename = "spawnDurationMs")]
+    pub(crate) spawn_duration_ms: u64,
+    /// `None` while a deferred `initialize` is still running.
+    #[serde(default, rename = "initializeDurationMs")]
+    pub(crate) initialize_duration_ms: Option<u64>,
+    /// JSONL file the session's events are being recorded to, if any.
+    #[serde(default, rename = "recordingPath")]
+    pub(crate) recording_path: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct WorkspaceInfo {
     pub(crate) id: String,
@@ -300,6 +328,24 @@ pub(crate) struct WorkspaceSettings {
     pub(crate) launch_scripts: Option<Vec<LaunchScriptEntry>>,
     #[serde(default, rename = "worktreeSetupScript")]
     pub(crate) worktree_setup_script: Option<String>,
+    #[serde(default, rename = "defaultApprovalPolicy")]
+    pub(crate) default_approval_policy: Option<String>,
+    #[serde(default, rename = "sandboxPolicy")]
+    pub(crate) sandbox_policy: Option<String>,
+    /// Standing instruction sent with every turn in the workspace.
+    #[serde(default, rename = "systemPrompt")]
+    pub(crate) system_prompt: Option<String>,
+    /// Cursor mode for turns that don't pick one; overrides the app setting.
+    #[serde(default, rename = "cursorDefaultMode")]
+    pub(crate) cursor_default_mode: Option<String>,
+    /// Collaboration mode picked per thread with `set_collaboration_mode`,
+    /// used by turns that don't pass one.
+    #[serde(
+        default,
+        rename = "threadCollaborationModes",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub(crate) thread_collaboration_modes: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -318,6 +364,23 @@ pub(crate) struct WorktreeSetupStatus {
     pub(crate) script: Option<String>,
 }
 
+/// What `validate_workspace_path` found at a path the user is about to add.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub(crate) struct WorkspacePathValidation {
+    pub(crate) exists: bool,
+    #[serde(rename = "isDir")]
+    pub(crate) is_dir: bool,
+    #[serde(rename = "isGitRepo")]
+    pub(crate) is_git_repo: bool,
+    pub(crate) writable: bool,
+    #[serde(rename = "isWorktree")]
+    pub(crate) is_worktree: bool,
+    /// Whether a worktree still links to its main repository; `None` when
+    /// the path isn't a worktree.
+    #[serde(rename = "worktreeValid")]
+    pub(crate) worktree_valid: Option<bool>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) struct OpenAppTarget {
     pub(crate) id: String,
@@ -345,6 +408,8 @@ pub(crate) struct AppSettings {
     pub(crate) gemini_bin: Option<String>,
     #[serde(default, rename = "geminiArgs")]
     pub(crate) gemini_args: Option<String>,
+    #[serde(default, rename = "geminiHome")]
+    pub(crate) gemini_home: Option<String>,
     #[serde(default, rename = "cursorBin")]
     pub(crate) cursor_bin: Option<String>,
     #[serde(default, rename = "cursorArgs")]
@@ -387,6 +452,79 @@ pub(crate) struct AppSettings {
         rename = "reviewDeliveryMode"
     )]
     pub(crate) review_delivery_mode: String,
+    #[serde(default = "default_message_warn_chars", rename = "messageWarnChars")]
+    pub(crate) message_warn_chars: usize,
+    #[serde(
+        default = "default_commit_message_diff_max_bytes",
+        rename = "commitMessageDiffMaxBytes"
+    )]
+    pub(crate) commit_message_diff_max_bytes: usize,
+    /// Live CLI sessions allowed at once; 0 means unlimited.
+    #[serde(
+        default = "default_max_concurrent_sessions",
+        rename = "maxConcurrentSessions"
+    )]
+    pub(crate) max_concurrent_sessions: usize,
+    /// Retry a rate-limited adapter turn once the limit resets.
+    #[serde(default, rename = "autoRetryRateLimitedTurns")]
+    pub(crate) auto_retry_rate_limited_turns: bool,
+    /// Directory for adapter thread stores; unset uses the app data dir.
+    #[serde(default, rename = "threadStoreDir")]
+    pub(crate) thread_store_dir: Option<String>,
+    /// Standing instruction for workspaces that don't set their own.
+    #[serde(default, rename = "systemPrompt")]
+    pub(crate) system_prompt: Option<String>,
+    /// Spawn adapter CLIs even when they report a version below the
+    /// supported minimum.
+    #[serde(default, rename = "skipCliVersionCheck")]
+    pub(crate) skip_cli_version_check: bool,
+    /// Debug aid: leave the threads behind commit message and run metadata
+    /// generation visible and unarchived.
+    #[serde(default, rename = "keepBackgroundThreadsVisible")]
+    pub(crate) keep_background_threads_visible: bool,
+    /// Regexes for CLI stderr lines to keep out of the UI log; they still
+    /// reach the startup diagnostics.
+    #[serde(default, rename = "stderrDenyPatterns")]
+    pub(crate) stderr_deny_patterns: Vec<String>,
+    /// Regexes for stderr lines shown even when a deny pattern matches.
+    #[serde(default, rename = "stderrAllowPatterns")]
+    pub(crate) stderr_allow_patterns: Vec<String>,
+    /// Model ids picked in the UI mapped to the ids actually sent to the CLI,
+    /// for gateways that serve models under their own names.
+    #[serde(default, rename = "modelAliasMap")]
+    pub(crate) model_alias_map: HashMap<String, String>,
+    /// Seconds an approval request may go unanswered before it is resolved
+    /// with `approval_timeout_decision`; unset waits indefinitely.
+    #[serde(default, rename = "approvalTimeoutSecs")]
+    pub(crate) approval_timeout_secs: Option<u64>,
+    /// `decline` or `accept`, sent for approval requests that time out.
+    #[serde(
+        default = "default_approval_timeout_decision",
+        rename = "approvalTimeoutDecision"
+    )]
+    pub(crate) approval_timeout_decision: String,
+    /// Windows of `initializeTimeoutSecs` to wait for a CLI's `initialize`
+    /// response before the spawn is abandoned; unset uses the default.
+    #[serde(default, rename = "initializeAttempts")]
+    pub(crate) initialize_attempts: Option<u32>,
+    #[serde(default, rename = "initializeTimeoutSecs")]
+    pub(crate) initialize_timeout_secs: Option<u64>,
+    /// Extra wait added to each later window, doubled every window.
+    #[serde(default, rename = "initializeBackoffMs")]
+    pub(crate) initialize_backoff_ms: Option<u64>,
+    /// `npx` used to launch the gondolin MCP server during sandbox setup;
+    /// unset uses the first `npx` on PATH.
+    #[serde(default, rename = "sandboxNpxPath")]
+    pub(crate) sandbox_npx_path: Option<String>,
+    /// Skip gondolin sandbox setup entirely, for users who manage their own
+    /// sandboxing: no MCP server is registered and no CLI config is written.
+    #[serde(default, rename = "disableManagedSandbox")]
+    pub(crate) disable_managed_sandbox: bool,
+    /// Run Gemini with its own `--sandbox`. Off for environments (CI,
+    /// containers) where that sandbox can't start; gondolin, registered by
+    /// sandbox setup, is then the only sandbox.
+    #[serde(default = "default_gemini_use_sandbox", rename = "geminiUseSandbox")]
+    pub(crate) gemini_use_sandbox: bool,
     #[serde(
         default = "default_composer_model_shortcut",
         rename = "composerModelShortcut"
@@ -642,6 +780,26 @@ fn default_cursor_use_http1() -> bool {
     false
 }
 
+fn default_message_warn_chars() -> usize {
+    100_000
+}
+
+fn default_commit_message_diff_max_bytes() -> usize {
+    96 * 1024
+}
+
+fn default_gemini_use_sandbox() -> bool {
+    true
+}
+
+fn default_approval_timeout_decision() -> String {
+    "decline".to_string()
+}
+
+fn default_max_concurrent_sessions() -> usize {
+    32
+}
+
 fn default_review_delivery_mode() -> String {
     "inline".to_string()
 }
@@ -1047,6 +1205,7 @@ impl Default for AppSettings {
             codex_args: None,
             gemini_bin: None,
             gemini_args: None,
+            gemini_home: None,
             cursor_bin: None,
             cursor_args: None,
             claude_bin: None,
@@ -1062,6 +1221,25 @@ impl Default for AppSettings {
             remote_backend_token: None,
             default_access_mode: "current".to_string(),
             review_delivery_mode: default_review_delivery_mode(),
+            message_warn_chars: default_message_warn_chars(),
+            commit_message_diff_max_bytes: default_commit_message_diff_max_bytes(),
+            max_concurrent_sessions: default_max_concurrent_sessions(),
+            auto_retry_rate_limited_turns: false,
+            thread_store_dir: None,
+            system_prompt: None,
+            skip_cli_version_check: false,
+            keep_background_threads_visible: false,
+            stderr_deny_patterns: Vec::new(),
+            stderr_allow_patterns: Vec::new(),
+            model_alias_map: HashMap::new(),
+            approval_timeout_secs: None,
+            approval_timeout_decision: default_approval_timeout_decision(),
+            initialize_attempts: None,
+            initialize_timeout_secs: None,
+            initialize_backoff_ms: None,
+            sandbox_npx_path: None,
+            disable_managed_sandbox: false,
+            gemini_use_sandbox: true,
             composer_model_shortcut: default_composer_model_shortcut(),
             composer_access_shortcut: default_composer_access_shortcut(),
             composer_reasoning_shortcut: default_composer_reasoning_shortcut(),
@@ -1135,6 +1313,7 @@ mod tests {
         assert!(settings.codex_args.is_none());
         assert!(settings.gemini_bin.is_none());
         assert!(settings.gemini_args.is_none());
+        assert!(settings.gemini_home.is_none());
         assert!(settings.cursor_bin.is_none());
         assert!(settings.cursor_args.is_none());
         assert!(settings.claude_bin.is_none());
@@ -1150,6 +1329,25 @@ mod tests {
         assert!(settings.remote_backend_token.is_none());
         assert_eq!(settings.default_access_mode, "current");
         assert_eq!(settings.review_delivery_mode, "inline");
+        assert_eq!(settings.message_warn_chars, 100_000);
+        assert_eq!(settings.commit_message_diff_max_bytes, 96 * 1024);
+        assert_eq!(settings.max_concurrent_sessions, 32);
+        assert!(!settings.auto_retry_rate_limited_turns);
+        assert!(settings.thread_store_dir.is_none());
+        assert!(settings.system_prompt.is_none());
+        assert!(!settings.skip_cli_version_check);
+        assert!(!settings.keep_background_threads_visible);
+        assert!(settings.stderr_deny_patterns.is_empty());
+        assert!(settings.stderr_allow_patterns.is_empty());
+        assert!(settings.model_alias_map.is_empty());
+        assert!(settings.approval_timeout_secs.is_none());
+        assert_eq!(settings.approval_timeout_decision, "decline");
+        assert!(settings.initialize_attempts.is_none());
+        assert!(settings.initialize_timeout_secs.is_none());
+        assert!(settings.initialize_backoff_ms.is_none());
+        assert!(settings.sandbox_npx_path.is_none());
+        assert!(!settings.disable_managed_sandbox);
+        assert!(settings.gemini_use_sandbox);
         let expected_primary = if cfg!(target_os = "macos") {
             "cmd"
         } else {
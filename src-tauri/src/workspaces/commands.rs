@@ -1,7 +1,4 @@
-use std::path::PathBuf;
-
-#[cfg(target_os = "windows")]
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
 
@@ -25,7 +22,9 @@ use super::worktree::{
 };
 
 use crate::backend::app_server::{CliSpawnConfig, WorkspaceSession};
-use crate::codex::spawn_workspace_session;
+use crate::backend::events::{AppServerEvent, EventSink};
+use crate::codex::{spawn_workspace_session, spawn_workspace_session_deferred};
+use crate::event_sink::TauriEventSink;
 use crate::git_utils::resolve_git_root;
 use crate::remote_backend;
 #[cfg(target_os = "windows")]
@@ -36,7 +35,8 @@ use crate::shared::workspaces_core;
 use crate::state::AppState;
 use crate::storage::write_workspaces;
 use crate::types::{
-    WorkspaceEntry, WorkspaceInfo, WorkspaceKind, WorkspaceSettings, WorktreeSetupStatus,
+    WorkspaceEntry, WorkspaceInfo, WorkspaceKind, WorkspacePathValidation, WorkspaceSettings,
+    WorktreeSetupStatus,
 };
 use crate::utils::{git_env_path, resolve_git_binary};
 
@@ -48,9 +48,18 @@ fn spawn_with_app(
     spawn_workspace_session(entry, config, app.clone())
 }
 
+fn spawn_deferred_with_app(
+    app: &AppHandle,
+    entry: WorkspaceEntry,
+    config: CliSpawnConfig,
+) -> impl std::future::Future<Output = Result<Arc<WorkspaceSession>, String>> {
+    spawn_workspace_session_deferred(entry, config, app.clone())
+}
+
 async fn setup_workspace_sandbox_if_needed(
     workspace_id: &str,
     state: &AppState,
+    app: &AppHandle,
 ) -> Result<(), String> {
     let (entry, parent_entry, settings_snapshot) = {
         let workspaces = state.workspaces.lock().await;
@@ -79,11 +88,28 @@ async fn setup_workspace_sandbox_if_needed(
         Some(&settings_snapshot),
     );
 
-    tokio::task::spawn_blocking(move || {
-        sandbox_setup_core::ensure_workspace_sandbox_setup(&cli_type, &workspace_path, cli_home)
-    })
+    let event_sink = TauriEventSink::new(app.clone());
+    let progress = |step: sandbox_setup_core::SandboxSetupStep| {
+        event_sink.emit_app_server_event(AppServerEvent {
+            workspace_id: workspace_id.to_string(),
+            message: json!({
+                "method": "sandbox/setup/progress",
+                "params": {
+                    "workspaceId": workspace_id,
+                    "cliType": cli_type,
+                    "step": step.as_str(),
+                }
+            }),
+        });
+    };
+    sandbox_setup_core::ensure_workspace_sandbox_setup(
+        sandbox_setup_core::SandboxSetupOptions::from_settings(&settings_snapshot),
+        &cli_type,
+        &workspace_path,
+        cli_home,
+        Some(&progress),
+    )
     .await
-    .map_err(|err| err.to_string())?
 }
 
 #[tauri::command]
@@ -146,6 +172,25 @@ pub(crate) async fn is_workspace_path_dir(
     Ok(workspaces_core::is_workspace_path_dir_core(&path))
 }
 
+#[tauri::command]
+pub(crate) async fn validate_workspace_path(
+    path: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<WorkspacePathValidation, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        let response = remote_backend::call_remote(
+            &*state,
+            app,
+            "validate_workspace_path",
+            json!({ "path": path }),
+        )
+        .await?;
+        return serde_json::from_value(response).map_err(|err| err.to_string());
+    }
+    Ok(workspaces_core::validate_workspace_path_core(&path))
+}
+
 #[tauri::command]
 pub(crate) async fn add_workspace(
     path: String,
@@ -197,6 +242,7 @@ pub(crate) async fn add_clone(
     if copies_folder.is_empty() {
         return Err("Copies folder is required.".to_string());
     }
+    workspaces_core::ensure_session_capacity(&state.sessions, &state.app_settings, None).await?;
     let copies_folder_path = PathBuf::from(&copies_folder);
     std::fs::create_dir_all(&copies_folder_path)
         .map_err(|e| format!("Failed to create copies folder: {e}"))?;
@@ -276,6 +322,17 @@ pub(crate) async fn add_clone(
             return Err(error);
         }
     };
+    if let Err(error) = workspaces_core::insert_session_within_capacity(
+        &state.sessions,
+        &state.app_settings,
+        &entry.id,
+        Arc::clone(&session),
+    )
+    .await
+    {
+        let _ = tokio::fs::remove_dir_all(&destination_path).await;
+        return Err(error);
+    }
 
     if let Err(error) = {
         let mut workspaces = state.workspaces.lock().await;
@@ -287,17 +344,12 @@ pub(crate) async fn add_clone(
             let mut workspaces = state.workspaces.lock().await;
             workspaces.remove(&entry.id);
         }
+        state.sessions.lock().await.remove(&entry.id);
         session.kill().await;
         let _ = tokio::fs::remove_dir_all(&destination_path).await;
         return Err(error);
     }
 
-    state
-        .sessions
-        .lock()
-        .await
-        .insert(entry.id.clone(), session);
-
     Ok(WorkspaceInfo {
         id: entry.id,
         name: entry.name,
@@ -750,6 +802,7 @@ pub(crate) async fn update_workspace_settings(
         |entry, config| {
             spawn_with_app(&app, entry, config)
         },
+        |entry, config| spawn_deferred_with_app(&app, entry, config),
     )
     .await
 }
@@ -805,7 +858,7 @@ pub(crate) async fn connect_workspace(
         return Ok(());
     }
 
-    if let Err(error) = setup_workspace_sandbox_if_needed(&id, &state).await {
+    if let Err(error) = setup_workspace_sandbox_if_needed(&id, &state, &app).await {
         eprintln!("sandbox setup skipped for workspace {}: {}", id, error);
     }
 
@@ -821,6 +874,46 @@ pub(crate) async fn connect_workspace(
     .await
 }
 
+#[tauri::command]
+pub(crate) async fn disconnect_workspace(
+    id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<(), String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        remote_backend::call_remote(&*state, app, "disconnect_workspace", json!({ "id": id }))
+            .await?;
+        return Ok(());
+    }
+
+    workspaces_core::disconnect_workspace_core(id, &state.sessions).await;
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) async fn preview_spawn_command(
+    workspace_id: String,
+    state: State<'_, AppState>,
+    app: AppHandle,
+) -> Result<serde_json::Value, String> {
+    if remote_backend::is_remote_mode(&*state).await {
+        return remote_backend::call_remote(
+            &*state,
+            app,
+            "preview_spawn_command",
+            json!({ "workspaceId": workspace_id }),
+        )
+        .await;
+    }
+
+    workspaces_core::preview_spawn_command_core(
+        workspace_id,
+        &state.workspaces,
+        &state.app_settings,
+    )
+    .await
+}
+
 #[tauri::command]
 pub(crate) async fn list_workspace_files(
     workspace_id: String,
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::future::Future;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use super::settings::{apply_workspace_settings_update, sort_workspaces};
@@ -8,7 +9,10 @@ use super::worktree::{
     build_clone_destination_path, sanitize_clone_dir_name, sanitize_worktree_name,
 };
 use crate::backend::app_server::WorkspaceSession;
-use crate::shared::workspaces_core::rename_worktree_core;
+use crate::backend::test_support::{test_workspace_entry, TestAdapter};
+use crate::shared::workspaces_core::{
+    connect_workspace_core, disconnect_workspace_core, rename_worktree_core,
+};
 use crate::storage::{read_workspaces, write_workspaces};
 use crate::types::{
     AppSettings, WorktreeInfo, WorkspaceEntry, WorkspaceInfo, WorkspaceKind, WorkspaceSettings,
@@ -66,6 +70,11 @@ fn workspace_with_id_and_kind(
             launch_script: None,
             launch_scripts: None,
             worktree_setup_script: None,
+            default_approval_policy: None,
+            sandbox_policy: None,
+            system_prompt: None,
+            cursor_default_mode: None,
+            thread_collaboration_modes: HashMap::new(),
         },
     }
 }
@@ -389,3 +398,110 @@ fn rename_worktree_updates_name_when_unmodified() {
     assert_eq!(updated.name, "feature/new");
     });
 }
+
+#[test]
+fn connect_workspace_enforces_max_concurrent_sessions() {
+    run_async(async {
+        let entries = ["ws-1", "ws-2", "ws-3"].map(|id| test_workspace_entry(id, "/tmp"));
+        let workspaces = Mutex::new(
+            entries
+                .iter()
+                .map(|entry| (entry.id.clone(), entry.clone()))
+                .collect::<HashMap<_, _>>(),
+        );
+        let sessions: Mutex<HashMap<String, Arc<WorkspaceSession>>> = Mutex::new(HashMap::new());
+        let app_settings = Mutex::new(AppSettings {
+            max_concurrent_sessions: 2,
+            ..AppSettings::default()
+        });
+        let connect = |id: &str| {
+            connect_workspace_core(
+                id.to_string(),
+                &workspaces,
+                &sessions,
+                &app_settings,
+                |entry, _config| async move {
+                    Ok(Arc::new(WorkspaceSession::new_with_adapter(
+                        entry,
+                        Box::new(TestAdapter::new("claude")),
+                        Arc::default(),
+                        Arc::default(),
+                    )))
+                },
+            )
+        };
+
+        connect("ws-1").await.expect("first session");
+        connect("ws-2").await.expect("second session");
+        let error = connect("ws-3")
+            .await
+            .expect_err("third session over the limit");
+        assert!(error.starts_with("Session limit reached"), "{error}");
+        assert!(!sessions.lock().await.contains_key("ws-3"));
+
+        connect("ws-2")
+            .await
+            .expect("reconnecting does not take a new slot");
+
+        disconnect_workspace_core("ws-1".to_string(), &sessions).await;
+        connect("ws-3").await.expect("disconnect frees a slot");
+        let mut live: Vec<_> = sessions.lock().await.keys().cloned().collect();
+        live.sort();
+        assert_eq!(live, vec!["ws-2".to_string(), "ws-3".to_string()]);
+    });
+}
+
+#[test]
+fn concurrent_connects_do_not_exceed_max_concurrent_sessions() {
+    run_async(async {
+        let entries = ["ws-1", "ws-2", "ws-3"].map(|id| test_workspace_entry(id, "/tmp"));
+        let workspaces = Mutex::new(
+            entries
+                .iter()
+                .map(|entry| (entry.id.clone(), entry.clone()))
+                .collect::<HashMap<_, _>>(),
+        );
+        let sessions: Mutex<HashMap<String, Arc<WorkspaceSession>>> = Mutex::new(HashMap::new());
+        let app_settings = Mutex::new(AppSettings {
+            max_concurrent_sessions: 2,
+            ..AppSettings::default()
+        });
+        let killed = Arc::new(AtomicBool::new(false));
+        let connect = |id: &str| {
+            connect_workspace_core(
+                id.to_string(),
+                &workspaces,
+                &sessions,
+                &app_settings,
+                |entry, _config| {
+                    let adapter = TestAdapter::new("claude").killed_flag(Arc::clone(&killed));
+                    async move {
+                        // Every connect passes the up-front check before any
+                        // spawn finishes.
+                        tokio::task::yield_now().await;
+                        Ok(Arc::new(WorkspaceSession::new_with_adapter(
+                            entry,
+                            Box::new(adapter),
+                            Arc::default(),
+                            Arc::default(),
+                        )))
+                    }
+                },
+            )
+        };
+
+        let (first, second, third) =
+            tokio::join!(connect("ws-1"), connect("ws-2"), connect("ws-3"));
+
+        let connected = [first, second, third]
+            .iter()
+            .filter(|result| result.is_ok())
+            .count();
+        assert_eq!(connected, 2);
+        assert_eq!(sessions.lock().await.len(), 2);
+        assert!(
+            killed.load(Ordering::SeqCst),
+            "the session over the limit is killed"
+        );
+    });
+}
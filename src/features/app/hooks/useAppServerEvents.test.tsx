@@ -54,11 +54,15 @@ describe("useAppServerEvents", () => {
       onReasoningSummaryBoundary: vi.fn(),
       onPlanDelta: vi.fn(),
       onApprovalRequest: vi.fn(),
+      onApprovalTimedOut: vi.fn(),
+      onThreadCompacted: vi.fn(),
+      onWorkspaceUnhealthy: vi.fn(),
       onRequestUserInput: vi.fn(),
       onItemCompleted: vi.fn(),
       onAgentMessageCompleted: vi.fn(),
       onAccountUpdated: vi.fn(),
       onAccountLoginCompleted: vi.fn(),
+      onTurnCompleted: vi.fn(),
     };
     const { root } = await mount(handlers);
 
@@ -273,6 +277,66 @@ describe("useAppServerEvents", () => {
       error: null,
     });
 
+    act(() => {
+      listener?.({
+        workspace_id: "ws-1",
+        message: {
+          method: "approval/timedOut",
+          params: { requestId: 7, threadId: "thread-1", decision: "decline" },
+        },
+      });
+    });
+    expect(handlers.onApprovalTimedOut).toHaveBeenCalledWith("ws-1", 7);
+
+    act(() => {
+      listener?.({
+        workspace_id: "ws-1",
+        message: {
+          method: "thread/compacted",
+          params: {
+            threadId: "thread-1",
+            compacted: true,
+            tokensBefore: 48000,
+            tokensAfter: null,
+          },
+        },
+      });
+    });
+    expect(handlers.onThreadCompacted).toHaveBeenCalledWith("ws-1", {
+      threadId: "thread-1",
+      tokensBefore: 48000,
+      tokensAfter: null,
+    });
+
+    act(() => {
+      listener?.({
+        workspace_id: "ws-1",
+        message: {
+          method: "cli/unhealthy",
+          params: { parseErrors: 20, message: "Reconnect the workspace." },
+        },
+      });
+    });
+    expect(handlers.onWorkspaceUnhealthy).toHaveBeenCalledWith(
+      "ws-1",
+      "Reconnect the workspace.",
+    );
+
+    act(() => {
+      listener?.({
+        workspace_id: "ws-1",
+        message: {
+          method: "turn/interrupted",
+          params: { threadId: "thread-1", turnId: "turn-1" },
+        },
+      });
+    });
+    expect(handlers.onTurnCompleted).toHaveBeenCalledWith(
+      "ws-1",
+      "thread-1",
+      "turn-1",
+    );
+
     await act(async () => {
       root.unmount();
     });
@@ -30,17 +30,28 @@ type AgentCompleted = {
 
 type AppServerEventHandlers = {
   onWorkspaceConnected?: (workspaceId: string) => void;
+  onWorkspaceMissing?: (workspaceId: string, path: string) => void;
+  onWorkspaceUnhealthy?: (workspaceId: string, message: string) => void;
   onThreadStarted?: (workspaceId: string, thread: Record<string, unknown>) => void;
   onThreadNameUpdated?: (
     workspaceId: string,
     payload: { threadId: string; threadName: string | null },
   ) => void;
+  onThreadCompacted?: (
+    workspaceId: string,
+    payload: {
+      threadId: string;
+      tokensBefore: number | null;
+      tokensAfter: number | null;
+    },
+  ) => void;
   onBackgroundThreadAction?: (
     workspaceId: string,
     threadId: string,
     action: string,
   ) => void;
   onApprovalRequest?: (request: ApprovalRequest) => void;
+  onApprovalTimedOut?: (workspaceId: string, requestId: number | string) => void;
   onRequestUserInput?: (request: RequestUserInputRequest) => void;
   onAgentMessageDelta?: (event: AgentDelta) => void;
   onAgentMessageCompleted?: (event: AgentCompleted) => void;
@@ -59,6 +70,12 @@ type AppServerEventHandlers = {
     turnId: string,
     payload: { explanation: unknown; plan: unknown },
   ) => void;
+  onTurnRetrying?: (
+    workspaceId: string,
+    threadId: string,
+    turnId: string,
+    delayMs: number,
+  ) => void;
   onItemStarted?: (workspaceId: string, threadId: string, item: Record<string, unknown>) => void;
   onItemCompleted?: (workspaceId: string, threadId: string, item: Record<string, unknown>) => void;
   onReasoningSummaryDelta?: (workspaceId: string, threadId: string, itemId: string, delta: string) => void;
@@ -66,6 +83,7 @@ type AppServerEventHandlers = {
   onReasoningTextDelta?: (workspaceId: string, threadId: string, itemId: string, delta: string) => void;
   onPlanDelta?: (workspaceId: string, threadId: string, itemId: string, delta: string) => void;
   onCommandOutputDelta?: (workspaceId: string, threadId: string, itemId: string, delta: string) => void;
+  onToolInputDelta?: (workspaceId: string, threadId: string, itemId: string, delta: string) => void;
   onTerminalInteraction?: (
     workspaceId: string,
     threadId: string,
@@ -94,6 +112,8 @@ export const METHODS_ROUTED_IN_USE_APP_SERVER_EVENTS = [
   "account/login/completed",
   "account/rateLimits/updated",
   "account/updated",
+  "approval/timedOut",
+  "cli/unhealthy",
   "codex/backgroundThread",
   "codex/connected",
   "error",
@@ -108,13 +128,19 @@ export const METHODS_ROUTED_IN_USE_APP_SERVER_EVENTS = [
   "item/reasoning/textDelta",
   "item/started",
   "item/tool/requestUserInput",
+  "item/toolInput/delta",
+  "thread/compacted",
   "thread/name/updated",
   "thread/started",
   "thread/tokenUsage/updated",
   "turn/completed",
   "turn/diff/updated",
+  "turn/error",
+  "turn/interrupted",
   "turn/plan/updated",
+  "turn/retrying",
   "turn/started",
+  "workspace/missing",
 ] as const satisfies readonly SupportedAppServerMethod[];
 
 export function useAppServerEvents(handlers: AppServerEventHandlers) {
@@ -134,6 +160,16 @@ export function useAppServerEvents(handlers: AppServerEventHandlers) {
         return;
       }
 
+      if (method === "workspace/missing") {
+        handlers.onWorkspaceMissing?.(workspace_id, String(params.path ?? ""));
+        return;
+      }
+
+      if (method === "cli/unhealthy") {
+        handlers.onWorkspaceUnhealthy?.(workspace_id, String(params.message ?? ""));
+        return;
+      }
+
       const requestId = getAppServerRequestId(payload);
       const hasRequestId = requestId !== null;
 
@@ -239,6 +275,19 @@ export function useAppServerEvents(handlers: AppServerEventHandlers) {
         return;
       }
 
+      if (method === "thread/compacted") {
+        const threadId = String(params.threadId ?? params.thread_id ?? "").trim();
+        const tokens = (value: unknown) => (typeof value === "number" ? value : null);
+        if (threadId) {
+          handlers.onThreadCompacted?.(workspace_id, {
+            threadId,
+            tokensBefore: tokens(params.tokensBefore ?? params.tokens_before),
+            tokensAfter: tokens(params.tokensAfter ?? params.tokens_after),
+          });
+        }
+        return;
+      }
+
       if (method === "codex/backgroundThread") {
         const threadId = String(params.threadId ?? params.thread_id ?? "");
         const action = String(params.action ?? "hide");
@@ -248,7 +297,7 @@ export function useAppServerEvents(handlers: AppServerEventHandlers) {
         return;
       }
 
-      if (method === "error") {
+      if (method === "error" || method === "turn/error") {
         const threadId = String(params.threadId ?? params.thread_id ?? "");
         const turnId = String(params.turnId ?? params.turn_id ?? "");
         const error = (params.error as Record<string, unknown> | undefined) ?? {};
@@ -263,7 +312,8 @@ export function useAppServerEvents(handlers: AppServerEventHandlers) {
         return;
       }
 
-      if (method === "turn/completed") {
+      // An interrupted turn is over as far as the thread is concerned.
+      if (method === "turn/completed" || method === "turn/interrupted") {
         const turn = params.turn as Record<string, unknown> | undefined;
         const threadId = String(
           params.threadId ?? params.thread_id ?? turn?.threadId ?? turn?.thread_id ?? "",
@@ -287,6 +337,16 @@ export function useAppServerEvents(handlers: AppServerEventHandlers) {
         return;
       }
 
+      if (method === "turn/retrying") {
+        const threadId = String(params.threadId ?? params.thread_id ?? "");
+        const turnId = String(params.turnId ?? params.turn_id ?? "");
+        const delayMs = Number(params.delayMs ?? params.delay_ms ?? 0);
+        if (threadId) {
+          handlers.onTurnRetrying?.(workspace_id, threadId, turnId, delayMs);
+        }
+        return;
+      }
+
       if (method === "turn/diff/updated") {
         const threadId = String(params.threadId ?? params.thread_id ?? "");
         const diff = String(params.diff ?? "");
@@ -317,6 +377,14 @@ export function useAppServerEvents(handlers: AppServerEventHandlers) {
         return;
       }
 
+      if (method === "approval/timedOut") {
+        const requestId = params.requestId ?? params.request_id;
+        if (typeof requestId === "number" || typeof requestId === "string") {
+          handlers.onApprovalTimedOut?.(workspace_id, requestId);
+        }
+        return;
+      }
+
       if (method === "account/updated") {
         const authModeRaw = params.authMode ?? params.auth_mode ?? null;
         const authMode =
@@ -375,6 +443,16 @@ export function useAppServerEvents(handlers: AppServerEventHandlers) {
         return;
       }
 
+      if (method === "item/toolInput/delta") {
+        const threadId = String(params.threadId ?? params.thread_id ?? "");
+        const itemId = String(params.itemId ?? params.item_id ?? "");
+        const delta = String(params.delta ?? "");
+        if (threadId && itemId && delta) {
+          handlers.onToolInputDelta?.(workspace_id, threadId, itemId, delta);
+        }
+        return;
+      }
+
       if (method === "item/reasoning/summaryTextDelta") {
         const threadId = String(params.threadId ?? params.thread_id ?? "");
         const itemId = String(params.itemId ?? params.item_id ?? "");
@@ -446,6 +446,17 @@ export function SettingsCodexSection({
               </div>
               {doctorState.result.details && <div>{doctorState.result.details}</div>}
               {doctorState.result.nodeDetails && <div>{doctorState.result.nodeDetails}</div>}
+              {doctorState.result.nodePath && (
+                <div className="settings-doctor-path">Node path: {doctorState.result.nodePath}</div>
+              )}
+              {(doctorState.result.nodeConflicts?.length ?? 0) > 0 && (
+                <div>
+                  Multiple Node versions on PATH:{" "}
+                  {(doctorState.result.nodeConflicts ?? [])
+                    .map((node) => `${node.path} (${node.version ?? "unknown"})`)
+                    .join(", ")}
+                </div>
+              )}
               {doctorState.result.path && (
                 <div className="settings-doctor-path">PATH: {doctorState.result.path}</div>
               )}
@@ -140,6 +140,7 @@ function normalizeAppSettings(settings: AppSettings): AppSettings {
     codexArgs: settings.codexArgs?.trim() ? settings.codexArgs.trim() : null,
     geminiBin: settings.geminiBin?.trim() ? settings.geminiBin.trim() : null,
     geminiArgs: settings.geminiArgs?.trim() ? settings.geminiArgs.trim() : null,
+    geminiHome: settings.geminiHome?.trim() ? settings.geminiHome.trim() : null,
     cursorBin: settings.cursorBin?.trim() ? settings.cursorBin.trim() : null,
     cursorArgs: settings.cursorArgs?.trim() ? settings.cursorArgs.trim() : null,
     claudeBin: settings.claudeBin?.trim() ? settings.claudeBin.trim() : null,
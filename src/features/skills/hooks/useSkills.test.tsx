@@ -119,4 +119,18 @@ describe("useSkills", () => {
     await new Promise((resolve) => setTimeout(resolve, 0));
     expect(getSkillsList).toHaveBeenCalledTimes(1);
   });
+
+  it("reports CLIs without skills as unsupported", async () => {
+    vi.mocked(getSkillsList).mockResolvedValue({
+      result: { skills: [], unsupported: true, message: "cursor has no skills." },
+    });
+
+    const { result } = renderHook(() => useSkills({ activeWorkspace: workspace }));
+
+    expect(result.current.skillsSupported).toBe(true);
+    await waitFor(() => {
+      expect(result.current.skillsSupported).toBe(false);
+    });
+    expect(result.current.skills).toEqual([]);
+  });
 });
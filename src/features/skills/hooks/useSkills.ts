@@ -11,6 +11,7 @@ type UseSkillsOptions = {
 
 export function useSkills({ activeWorkspace, onDebug }: UseSkillsOptions) {
   const [skills, setSkills] = useState<SkillOption[]>([]);
+  const [skillsSupported, setSkillsSupported] = useState(true);
   const lastFetchedWorkspaceId = useRef<string | null>(null);
   const inFlight = useRef(false);
 
@@ -54,6 +55,7 @@ export function useSkills({ activeWorkspace, onDebug }: UseSkillsOptions) {
         description: item.description ? String(item.description) : undefined,
       }));
       setSkills(data);
+      setSkillsSupported(response.result?.unsupported !== true);
       lastFetchedWorkspaceId.current = workspaceId;
     } catch (error) {
       onDebug?.({
@@ -109,6 +111,7 @@ export function useSkills({ activeWorkspace, onDebug }: UseSkillsOptions) {
 
   return {
     skills: skillOptions,
+    skillsSupported,
     refreshSkills,
   };
 }
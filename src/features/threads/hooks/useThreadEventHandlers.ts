@@ -117,6 +117,23 @@ export function useThreadEventHandlers({
     [dispatch],
   );
 
+  const onApprovalTimedOut = useCallback(
+    (workspaceId: string, requestId: number | string) => {
+      dispatch({ type: "removeApproval", requestId, workspaceId });
+    },
+    [dispatch],
+  );
+
+  const onThreadCompacted = useCallback(
+    (workspaceId: string, { threadId }: { threadId: string }) => {
+      onItemCompleted(workspaceId, threadId, {
+        id: `compaction-${threadId}-${Date.now()}`,
+        type: "contextCompaction",
+      });
+    },
+    [onItemCompleted],
+  );
+
   const onAppServerEvent = useCallback(
     (event: AppServerEvent) => {
       const method = getAppServerRawMethod(event) ?? "";
@@ -136,6 +153,8 @@ export function useThreadEventHandlers({
     () => ({
       onWorkspaceConnected,
       onApprovalRequest,
+      onApprovalTimedOut,
+      onThreadCompacted,
       onRequestUserInput,
       onBackgroundThreadAction,
       onAppServerEvent,
@@ -162,6 +181,8 @@ export function useThreadEventHandlers({
     [
       onWorkspaceConnected,
       onApprovalRequest,
+      onApprovalTimedOut,
+      onThreadCompacted,
       onRequestUserInput,
       onBackgroundThreadAction,
       onAppServerEvent,
@@ -657,7 +657,10 @@ export function useThreadMessaging({
                     "status" in authStatus
                   ? String((authStatus as { status?: unknown }).status ?? "")
                   : "";
-            lines.push(`- ${name}${authLabel ? ` (auth: ${authLabel})` : ""}`);
+            const managedLabel = server.managed === true ? " (managed)" : "";
+            lines.push(
+              `- ${name}${managedLabel}${authLabel ? ` (auth: ${authLabel})` : ""}`,
+            );
 
             const toolsRecord =
               server.tools && typeof server.tools === "object"
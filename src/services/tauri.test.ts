@@ -137,6 +137,19 @@ describe("tauri invoke wrappers", () => {
     });
   });
 
+  it("passes a trimmed name for fork_thread when provided", async () => {
+    const invokeMock = vi.mocked(invoke);
+    invokeMock.mockResolvedValueOnce({});
+
+    await forkThread("ws-9", "thread-9", "  Experiment ");
+
+    expect(invokeMock).toHaveBeenCalledWith("fork_thread", {
+      workspaceId: "ws-9",
+      threadId: "thread-9",
+      name: "Experiment",
+    });
+  });
+
   it("uses update_workspace_cli_bin for workspace CLI overrides", async () => {
     const invokeMock = vi.mocked(invoke);
     invokeMock.mockResolvedValueOnce({ id: "ws-1" });
@@ -5,18 +5,23 @@ import type {
   AgentProfileApplyMode,
   AgentProfileApplyResponse,
   AgentProfileListResponse,
+  AgentDoctorAllResult,
   AgentDoctorResult,
   AppSettings,
+  CliType,
+  CursorModeList,
   DetectedClis,
   DictationModelStatus,
   DictationSessionState,
   LocalUsageCliFilter,
   LocalUsageSnapshot,
+  McpServerTestResult,
   OrbitConnectTestResult,
   OrbitDeviceCodeStart,
   OrbitRunnerStatus,
   OrbitSignInPollResult,
   OrbitSignOutResult,
+  SessionInfo,
   TailscaleDaemonCommandPreview,
   TailscaleStatus,
   WorkspaceInfo,
@@ -32,6 +37,7 @@ import type {
   GitHubPullRequestsResponse,
   GitLogResponse,
   ReviewTarget,
+  WorkspaceDiffStructured,
 } from "../types";
 
 function isMissingTauriInvokeError(error: unknown) {
@@ -84,6 +90,27 @@ export async function getCodexConfigPath(): Promise<string> {
   return invoke<string>("get_codex_config_path");
 }
 
+export async function getCliHomePath(cliType: string): Promise<string> {
+  return invoke<string>("get_cli_home_path", { cliType });
+}
+
+export async function getGeminiSetting(key: string): Promise<unknown> {
+  return invoke<unknown>("get_gemini_setting", { key });
+}
+
+export async function setGeminiSetting(key: string, value: unknown): Promise<void> {
+  return invoke("set_gemini_setting", { key, value });
+}
+
+export type ThreadStoreDirResult = {
+  path: string;
+  migratedCount: number;
+};
+
+export async function setThreadStoreDir(dir: string | null): Promise<ThreadStoreDirResult> {
+  return invoke<ThreadStoreDirResult>("set_thread_store_dir", { dir });
+}
+
 export type TextFileResponse = {
   exists: boolean;
   content: string;
@@ -130,6 +157,18 @@ export async function writeGlobalCodexConfigToml(content: string): Promise<void>
   return fileWrite("global", "config", content);
 }
 
+export async function getConfigEffort(workspaceId: string): Promise<string | null> {
+  const response = await invoke<{ effort?: string | null }>("get_config_model", {
+    workspaceId,
+  });
+  const effort = response?.effort;
+  if (typeof effort !== "string") {
+    return null;
+  }
+  const trimmed = effort.trim();
+  return trimmed.length > 0 ? trimmed : null;
+}
+
 export async function getConfigModel(workspaceId: string): Promise<string | null> {
   const response = await invoke<{ model?: string | null }>("get_config_model", {
     workspaceId,
@@ -153,6 +192,21 @@ export async function isWorkspacePathDir(path: string): Promise<boolean> {
   return invoke<boolean>("is_workspace_path_dir", { path });
 }
 
+export type WorkspacePathValidation = {
+  exists: boolean;
+  isDir: boolean;
+  isGitRepo: boolean;
+  writable: boolean;
+  isWorktree: boolean;
+  worktreeValid: boolean | null;
+};
+
+export async function validateWorkspacePath(
+  path: string,
+): Promise<WorkspacePathValidation> {
+  return invoke<WorkspacePathValidation>("validate_workspace_path", { path });
+}
+
 export async function addClone(
   sourceWorkspaceId: string,
   copiesFolder: string,
@@ -261,18 +315,55 @@ export async function connectWorkspace(id: string): Promise<void> {
   return invoke("connect_workspace", { id });
 }
 
+export async function disconnectWorkspace(id: string): Promise<void> {
+  return invoke("disconnect_workspace", { id });
+}
+
+export type SpawnCommandPreview = {
+  program: string;
+  args: string[];
+  envKeys: string[];
+  cwd: string | null;
+};
+
+export async function previewSpawnCommand(
+  workspaceId: string,
+): Promise<SpawnCommandPreview> {
+  return invoke<SpawnCommandPreview>("preview_spawn_command", { workspaceId });
+}
+
 export async function startThread(workspaceId: string) {
   return invoke<any>("start_thread", { workspaceId });
 }
 
-export async function forkThread(workspaceId: string, threadId: string) {
-  return invoke<any>("fork_thread", { workspaceId, threadId });
+export async function forkThread(
+  workspaceId: string,
+  threadId: string,
+  name?: string | null,
+) {
+  const trimmedName = name?.trim();
+  return invoke<any>(
+    "fork_thread",
+    trimmedName ? { workspaceId, threadId, name: trimmedName } : { workspaceId, threadId },
+  );
+}
+
+export async function duplicateThread(workspaceId: string, threadId: string) {
+  return invoke<any>("duplicate_thread", { workspaceId, threadId });
 }
 
 export async function compactThread(workspaceId: string, threadId: string) {
   return invoke<any>("compact_thread", { workspaceId, threadId });
 }
 
+export async function getThreadUsage(workspaceId: string, threadId: string) {
+  return invoke<any>("get_thread_usage", { workspaceId, threadId });
+}
+
+export async function repairThreadStore(workspaceId: string) {
+  return invoke<any>("repair_thread_store", { workspaceId });
+}
+
 export async function sendUserMessage(
   workspaceId: string,
   threadId: string,
@@ -282,7 +373,9 @@ export async function sendUserMessage(
     effort?: string | null;
     accessMode?: "read-only" | "current" | "full-access";
     images?: string[];
+    files?: string[];
     collaborationMode?: Record<string, unknown> | null;
+    cwd?: string | null;
   },
 ) {
   const payload: Record<string, unknown> = {
@@ -294,9 +387,15 @@ export async function sendUserMessage(
     accessMode: options?.accessMode ?? null,
     images: options?.images ?? null,
   };
+  if (options?.files?.length) {
+    payload.files = options.files;
+  }
   if (options?.collaborationMode) {
     payload.collaborationMode = options.collaborationMode;
   }
+  if (options?.cwd) {
+    payload.cwd = options.cwd;
+  }
   return invoke("send_user_message", payload);
 }
 
@@ -308,6 +407,39 @@ export async function interruptTurn(
   return invoke("turn_interrupt", { workspaceId, threadId, turnId });
 }
 
+export type WorkspaceInterruptResult = {
+  interrupted: { threadId: string; turnId: string }[];
+  failed: { threadId: string; turnId: string; error: string }[];
+};
+
+export async function interruptWorkspace(
+  workspaceId: string,
+): Promise<WorkspaceInterruptResult> {
+  return invoke<WorkspaceInterruptResult>("interrupt_workspace", {
+    workspaceId,
+  });
+}
+
+export type TurnSummary = {
+  threadId: string;
+  turnId: string;
+  tools: { name: string; count: number }[];
+  durationMs: number;
+  completed: boolean;
+};
+
+export async function getTurnSummary(
+  workspaceId: string,
+  threadId: string,
+  turnId: string,
+): Promise<TurnSummary> {
+  return invoke<TurnSummary>("get_turn_summary", {
+    workspaceId,
+    threadId,
+    turnId,
+  });
+}
+
 export async function startReview(
   workspaceId: string,
   threadId: string,
@@ -352,6 +484,30 @@ export async function rememberApprovalRule(
   return invoke("remember_approval_rule", { workspaceId, command });
 }
 
+export type RememberedApprovalRule = {
+  command: string[];
+  createdAt: number;
+};
+
+export async function listApprovalRules(
+  workspaceId: string,
+): Promise<{ rules: RememberedApprovalRule[] }> {
+  return invoke("list_approval_rules", { workspaceId });
+}
+
+export async function clearApprovalRule(workspaceId: string, command: string[]) {
+  return invoke<{ ok: boolean; removedCount: number }>("clear_approval_rule", {
+    workspaceId,
+    command,
+  });
+}
+
+export async function clearAllApprovalRules(workspaceId: string) {
+  return invoke<{ ok: boolean; removedCount: number }>("clear_all_approval_rules", {
+    workspaceId,
+  });
+}
+
 export async function getGitStatus(workspace_id: string): Promise<{
   branchName: string;
   files: GitFileStatus[];
@@ -499,10 +655,57 @@ export async function generateRunMetadata(workspaceId: string, prompt: string) {
   });
 }
 
+export async function generateRunMetadataCancel(workspaceId: string) {
+  return invoke<{ canceled: boolean }>("generate_run_metadata_cancel", { workspaceId });
+}
+
+export async function quickPrompt(cliType: CliType, cwd: string, prompt: string) {
+  return invoke<string>("quick_prompt", { cliType, cwd, prompt });
+}
+
+export async function testMcpServer(command: string, args: string[]) {
+  return invoke<McpServerTestResult>("test_mcp_server", { command, args });
+}
+
 export async function getCollaborationModes(workspaceId: string) {
   return invoke<any>("collaboration_mode_list", { workspaceId });
 }
 
+export async function setCollaborationMode(
+  workspaceId: string,
+  threadId: string,
+  mode: Record<string, unknown> | null,
+) {
+  return invoke<any>("set_collaboration_mode", { workspaceId, threadId, mode });
+}
+
+export async function setThreadModel(
+  workspaceId: string,
+  threadId: string,
+  model: string | null,
+) {
+  return invoke<any>("set_thread_model", { workspaceId, threadId, model });
+}
+
+export async function getCollaborationMode(workspaceId: string, threadId: string) {
+  return invoke<any>("get_collaboration_mode", { workspaceId, threadId });
+}
+
+export async function startEventRecording(workspaceId: string, path: string) {
+  return invoke<any>("start_event_recording", { workspaceId, path });
+}
+
+export async function stopEventRecording(workspaceId: string) {
+  return invoke<any>("stop_event_recording", { workspaceId });
+}
+
+export async function setEventThrottle(
+  workspaceId: string,
+  minIntervalMs: number,
+) {
+  return invoke<any>("set_event_throttle", { workspaceId, minIntervalMs });
+}
+
 export async function getAccountRateLimits(workspaceId: string) {
   return invoke<any>("account_rate_limits", { workspaceId });
 }
@@ -615,6 +818,17 @@ export async function detectInstalledClis(): Promise<DetectedClis> {
   return invoke<DetectedClis>("detect_installed_clis");
 }
 
+export async function validateCliArgs(
+  cliType: string,
+  args: string | null,
+): Promise<string[]> {
+  return invoke<string[]>("validate_cli_args", { cliType, args });
+}
+
+export async function getDefaultArgsPreset(cliType: string): Promise<string> {
+  return invoke<string>("get_default_args_preset", { cliType });
+}
+
 export async function orbitConnectTest(): Promise<OrbitConnectTestResult> {
   return invoke<OrbitConnectTestResult>("orbit_connect_test");
 }
@@ -676,6 +890,14 @@ export async function runCodexDoctor(
   return runAgentDoctor(codexBin, codexArgs);
 }
 
+export async function runDoctorAll(): Promise<AgentDoctorAllResult> {
+  return invoke<AgentDoctorAllResult>("doctor_all");
+}
+
+export async function listCursorModes(): Promise<CursorModeList> {
+  return invoke<CursorModeList>("cursor_mode_list");
+}
+
 export async function getWorkspaceFiles(workspaceId: string) {
   return invoke<string[]>("list_workspace_files", { workspaceId });
 }
@@ -819,6 +1041,10 @@ export async function closeTerminalSession(
   return invoke("terminal_close", { workspaceId, terminalId });
 }
 
+export async function listActiveSessions(): Promise<SessionInfo[]> {
+  return invoke<SessionInfo[]>("list_active_sessions");
+}
+
 export async function listThreads(
   workspaceId: string,
   cursor?: string | null,
@@ -828,6 +1054,18 @@ export async function listThreads(
   return invoke<any>("list_threads", { workspaceId, cursor, limit, sortKey });
 }
 
+export type WorkspaceThreadEntry = {
+  workspaceId: string;
+  thread: Record<string, unknown>;
+};
+
+export async function listAllThreads(
+  cursor?: string | null,
+  limit?: number | null,
+): Promise<{ data: WorkspaceThreadEntry[]; nextCursor: string | null }> {
+  return invoke("list_all_threads", { cursor, limit });
+}
+
 export async function listMcpServerStatus(
   workspaceId: string,
   cursor?: string | null,
@@ -852,16 +1090,30 @@ export async function setThreadName(
   return invoke<any>("set_thread_name", { workspaceId, threadId, name });
 }
 
+export async function getWorkspaceDiffStructured(
+  workspaceId: string,
+): Promise<WorkspaceDiffStructured> {
+  return invoke("get_workspace_diff_structured", { workspaceId });
+}
+
 export async function getCommitMessagePrompt(
   workspaceId: string,
+  stagedOnly?: boolean,
 ): Promise<string> {
-  return invoke("get_commit_message_prompt", { workspaceId });
+  return invoke("get_commit_message_prompt", {
+    workspaceId,
+    stagedOnly: stagedOnly ?? null,
+  });
 }
 
 export async function generateCommitMessage(
   workspaceId: string,
+  stagedOnly?: boolean,
 ): Promise<string> {
-  return invoke("generate_commit_message", { workspaceId });
+  return invoke("generate_commit_message", {
+    workspaceId,
+    stagedOnly: stagedOnly ?? null,
+  });
 }
 
 export async function sendNotification(
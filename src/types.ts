@@ -18,8 +18,15 @@ export type WorkspaceSettings = {
   launchScript?: string | null;
   launchScripts?: LaunchScriptEntry[] | null;
   worktreeSetupScript?: string | null;
+  defaultApprovalPolicy?: string | null;
+  sandboxPolicy?: WorkspaceSandboxPolicy | null;
+  systemPrompt?: string | null;
+  cursorDefaultMode?: CursorOperatingMode | null;
+  threadCollaborationModes?: Record<string, Record<string, unknown>>;
 };
 
+export type WorkspaceSandboxPolicy = "readOnly" | "workspaceWrite" | "full";
+
 export type LaunchScriptIconId =
   | "play"
   | "build"
@@ -57,6 +64,17 @@ export type WorktreeInfo = {
   branch: string;
 };
 
+export type SessionInfo = {
+  workspaceId: string;
+  cliType: string;
+  alive: boolean;
+  pid: number | null;
+  startedAt: number;
+  spawnDurationMs: number;
+  initializeDurationMs: number | null;
+  recordingPath: string | null;
+};
+
 export type WorkspaceInfo = {
   id: string;
   name: string;
@@ -143,6 +161,7 @@ export type ThreadListSortKey = "created_at" | "updated_at";
 
 export type ReviewTarget =
   | { type: "uncommittedChanges" }
+  | { type: "working" }
   | { type: "baseBranch"; branch: string }
   | { type: "commit"; sha: string; title?: string }
   | { type: "custom"; instructions: string };
@@ -157,6 +176,17 @@ export type LocalUsageCliFilter = "all" | CliType;
 export type CursorOperatingMode = "agent" | "plan" | "ask" | "debug";
 export type CursorOutputFormat = "text" | "json" | "stream-json";
 
+export type McpServerTestResult = {
+  ok: boolean;
+  details: string;
+  capabilities: Record<string, unknown> | null;
+};
+
+export type CursorModeList = {
+  modes: CursorOperatingMode[];
+  defaultMode: CursorOperatingMode;
+};
+
 
 export type ComposerEditorPreset = "default" | "helpful" | "smart";
 
@@ -194,6 +224,7 @@ export type AppSettings = {
   codexArgs: string | null;
   geminiBin: string | null;
   geminiArgs: string | null;
+  geminiHome?: string | null;
   cursorBin: string | null;
   cursorArgs: string | null;
   claudeBin: string | null;
@@ -217,6 +248,25 @@ export type AppSettings = {
   orbitAccessClientSecretRef: string | null;
   defaultAccessMode: AccessMode;
   reviewDeliveryMode: "inline" | "detached";
+  messageWarnChars?: number;
+  commitMessageDiffMaxBytes?: number;
+  maxConcurrentSessions?: number;
+  autoRetryRateLimitedTurns?: boolean;
+  threadStoreDir?: string | null;
+  systemPrompt?: string | null;
+  skipCliVersionCheck?: boolean;
+  keepBackgroundThreadsVisible?: boolean;
+  stderrDenyPatterns?: string[];
+  stderrAllowPatterns?: string[];
+  modelAliasMap?: Record<string, string>;
+  approvalTimeoutSecs?: number | null;
+  approvalTimeoutDecision?: "decline" | "accept";
+  initializeAttempts?: number | null;
+  initializeTimeoutSecs?: number | null;
+  initializeBackoffMs?: number | null;
+  sandboxNpxPath?: string | null;
+  disableManagedSandbox?: boolean;
+  geminiUseSandbox?: boolean;
   composerModelShortcut: string | null;
   composerAccessShortcut: string | null;
   composerReasoningShortcut: string | null;
@@ -350,10 +400,44 @@ export type AgentDoctorResult = {
   nodeOk: boolean;
   nodeVersion: string | null;
   nodeDetails: string | null;
+  nodePath?: string | null;
+  nodeConflicts?: NodeBinaryInfo[];
+  binDetails?: CliBinDetails | null;
+  streamJsonOk?: boolean | null;
+  streamJsonDetails?: string | null;
+  sandboxMode?: "gemini" | "gondolin";
+  sandboxDetails?: string | null;
+};
+
+export type NodeBinaryInfo = {
+  path: string;
+  version: string | null;
+};
+
+export type CliBinDetails = {
+  bin: string;
+  resolvedPath: string | null;
+  exists: boolean;
+  isFile: boolean;
+  executable: boolean;
+  isSymlink: boolean;
+  symlinkTarget: string | null;
 };
 
 export type CodexDoctorResult = AgentDoctorResult;
 
+export type AgentDoctorAllEntry =
+  | (AgentDoctorResult & { status: "ok" | "failed" })
+  | { status: "skipped"; ok: false }
+  | { status: "failed"; ok: false; error: string };
+
+export type AgentDoctorAllResult = {
+  ok: boolean;
+  gemini: AgentDoctorAllEntry;
+  claude: AgentDoctorAllEntry;
+  cursor: AgentDoctorAllEntry;
+};
+
 export type ApprovalRequest = {
   workspace_id: string;
   request_id: number | string;
@@ -402,6 +486,11 @@ export type GitFileStatus = {
   deletions: number;
 };
 
+export type WorkspaceDiffStructured = {
+  files: GitFileStatus[];
+  diff: string;
+};
+
 export type GitFileDiff = {
   path: string;
   diff: string;
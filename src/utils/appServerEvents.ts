@@ -4,6 +4,8 @@ export const SUPPORTED_APP_SERVER_METHODS = [
   "account/login/completed",
   "account/rateLimits/updated",
   "account/updated",
+  "approval/timedOut",
+  "cli/unhealthy",
   "codex/backgroundThread",
   "codex/connected",
   "codex/event/skills_update_available",
@@ -19,13 +21,19 @@ export const SUPPORTED_APP_SERVER_METHODS = [
   "item/reasoning/textDelta",
   "item/started",
   "item/tool/requestUserInput",
+  "item/toolInput/delta",
+  "thread/compacted",
   "thread/name/updated",
   "thread/started",
   "thread/tokenUsage/updated",
   "turn/completed",
   "turn/diff/updated",
+  "turn/error",
+  "turn/interrupted",
   "turn/plan/updated",
+  "turn/retrying",
   "turn/started",
+  "workspace/missing",
 ] as const;
 
 export type SupportedAppServerMethod = (typeof SUPPORTED_APP_SERVER_METHODS)[number];